@@ -0,0 +1,48 @@
+/* C interface to the Lox interpreter, built with the `ffi` feature. See src/ffi.rs for what
+ * each function does and the rules for the pointers passed to it. */
+#ifndef LOX_TREEWALK_H
+#define LOX_TREEWALK_H
+
+#include <stdbool.h>
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define LOX_NIL 0
+#define LOX_BOOL 1
+#define LOX_NUMBER 2
+#define LOX_STRING 3
+#define LOX_OBJECT 4
+
+typedef struct LoxInterpreter LoxInterpreter;
+typedef struct LoxValue LoxValue;
+
+typedef LoxValue *(*LoxNativeCallback)(void *user_data, const LoxValue *const *arguments,
+                                       size_t count);
+
+LoxInterpreter *lox_new_interpreter(void);
+void lox_free_interpreter(LoxInterpreter *interpreter);
+int lox_run(LoxInterpreter *interpreter, const char *source);
+const char *lox_last_error(const LoxInterpreter *interpreter);
+int lox_register_native(LoxInterpreter *interpreter, const char *name, size_t arity,
+                        LoxNativeCallback callback, void *user_data);
+LoxValue *lox_get_global(const LoxInterpreter *interpreter, const char *name);
+
+LoxValue *lox_value_nil(void);
+LoxValue *lox_value_bool(bool value);
+LoxValue *lox_value_number(double value);
+LoxValue *lox_value_string(const char *value);
+int lox_value_type(const LoxValue *value);
+bool lox_value_as_bool(const LoxValue *value);
+double lox_value_as_number(const LoxValue *value);
+char *lox_value_to_string(const LoxValue *value);
+void lox_value_free(LoxValue *value);
+void lox_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
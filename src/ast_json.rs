@@ -0,0 +1,780 @@
+use crate::{
+    diagnostic::{emit, has_errors},
+    expr::{
+        Assign, Binary, Call, Closure, Expr, ExprVisitor, Get, Grouping, Index, List,
+        ListComprehension, Literal, Logical, Set, SetIndex, Super, Ternary, This, Unary, Variable,
+    },
+    json::Json,
+    lox_object::LoxLiteral,
+    lox_string::LoxString,
+    parser::Parser,
+    scanner::Scanner,
+    span::Span,
+    stmt::{
+        Block, Break, Class, Destructure, Expression, For, ForIn, Function, If, Print, Return,
+        Stmt, StmtVisitor, Var, While,
+    },
+    token::Token,
+    token_type::TokenType,
+};
+use std::rc::Rc;
+
+const BINARY_OPERATORS: [TokenType; 11] = [
+    TokenType::Minus,
+    TokenType::Plus,
+    TokenType::Slash,
+    TokenType::Star,
+    TokenType::Greater,
+    TokenType::GreaterEqual,
+    TokenType::Less,
+    TokenType::LessEqual,
+    TokenType::BangEqual,
+    TokenType::EqualEqual,
+    TokenType::Comma,
+];
+
+/// Parses `source` and renders its AST as JSON: an array of statements, each node an object
+/// whose `type` names it, with its children and the `span` of source it was parsed from.
+/// Returns `None` if the source has scan or parse errors, which are reported as usual.
+pub fn ast_to_json(source: &str) -> Option<String> {
+    let mut scanner = Scanner::new(source);
+    scanner.scan_tokens();
+    emit(source, &scanner.diagnostics);
+    let mut parser = Parser::new(scanner.tokens);
+    let parse_result = parser.parse();
+    emit(source, &parser.diagnostics);
+    let statements = parse_result.ok()?;
+    if has_errors(&scanner.diagnostics) {
+        return None;
+    }
+    Some(format!("{}\n", statements_to_json(&statements)))
+}
+
+pub(crate) fn statements_to_json(statements: &[Stmt]) -> Json {
+    Json::Array(
+        statements
+            .iter()
+            .map(|statement| statement.accept(&mut JsonWriter))
+            .collect(),
+    )
+}
+
+/// Reads back the statements [`ast_to_json`] wrote, checking the tree is one the parser could
+/// have produced. Errors name the node at fault.
+pub(crate) fn statements_from_json(text: &str) -> Result<Vec<Stmt>, String> {
+    let json = Json::parse(text)?;
+    JsonReader { loop_depth: 0 }.statements(&json)
+}
+
+fn node(node_type: &str, span: Span, fields: Vec<(&str, Json)>) -> Json {
+    let mut members = vec![(String::from("type"), Json::String(node_type.to_owned()))];
+    members.extend(
+        fields
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), value)),
+    );
+    let span = Json::Object(vec![
+        (String::from("line"), Json::Number(span.line as f64)),
+        (String::from("end_line"), Json::Number(span.end_line as f64)),
+        (String::from("start"), Json::Number(span.start as f64)),
+        (String::from("end"), Json::Number(span.end as f64)),
+    ]);
+    members.push((String::from("span"), span));
+    Json::Object(members)
+}
+
+fn token(token: &Token) -> Json {
+    Json::Object(vec![
+        (
+            String::from("lexeme"),
+            Json::String(token.lexeme.to_string()),
+        ),
+        (String::from("line"), Json::Number(token.line as f64)),
+        (String::from("start"), Json::Number(token.start as f64)),
+        (String::from("column"), Json::Number(token.column as f64)),
+    ])
+}
+
+fn tokens(tokens: &[Token]) -> Json {
+    Json::Array(tokens.iter().map(token).collect())
+}
+
+fn doc_comment(doc: Option<&str>) -> Json {
+    doc.map_or(Json::Null, |doc| Json::String(doc.to_owned()))
+}
+
+struct JsonWriter;
+
+impl JsonWriter {
+    fn exprs(&mut self, exprs: &[Expr]) -> Json {
+        Json::Array(exprs.iter().map(|expr| expr.accept(self)).collect())
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) -> Json {
+        Json::Array(stmts.iter().map(|stmt| stmt.accept(self)).collect())
+    }
+
+    fn optional_expr(&mut self, expr: Option<&Expr>) -> Json {
+        expr.map_or(Json::Null, |expr| expr.accept(self))
+    }
+}
+
+impl ExprVisitor<Json> for JsonWriter {
+    fn visit_binary_expr(&mut self, expr: &Binary) -> Json {
+        let fields = vec![
+            ("left", expr.left.accept(self)),
+            ("operator", token(&expr.operator)),
+            ("right", expr.right.accept(self)),
+        ];
+        node("Binary", expr.span, fields)
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Grouping) -> Json {
+        let fields = vec![("expression", expr.expression.accept(self))];
+        node("Grouping", expr.span, fields)
+    }
+
+    fn visit_literal_expr(&mut self, expr: &Literal) -> Json {
+        let value = match expr.value {
+            // JSON has no infinities or NaN, so these are tagged strings: `{"number": "inf"}`.
+            LoxLiteral::Number(val) if !val.is_finite() => Json::Object(vec![(
+                String::from("number"),
+                Json::String(val.to_string()),
+            )]),
+            LoxLiteral::Number(val) => Json::Number(val),
+            LoxLiteral::String(ref val) => Json::String(val.flat().to_string()),
+            LoxLiteral::Boolean(val) => Json::Bool(val),
+            LoxLiteral::Nil => Json::Null,
+        };
+        node("Literal", expr.span, vec![("value", value)])
+    }
+
+    fn visit_unary_expr(&mut self, expr: &Unary) -> Json {
+        let fields = vec![
+            ("operator", token(&expr.operator)),
+            ("right", expr.right.accept(self)),
+        ];
+        node("Unary", expr.span, fields)
+    }
+
+    fn visit_ternary_expr(&mut self, expr: &Ternary) -> Json {
+        let fields = vec![
+            ("condition", expr.condition.accept(self)),
+            ("left", expr.left.accept(self)),
+            ("right", expr.right.accept(self)),
+        ];
+        node("Ternary", expr.span, fields)
+    }
+
+    fn visit_variable_expr(&mut self, expr: &Variable) -> Json {
+        node("Variable", expr.span, vec![("name", token(&expr.name))])
+    }
+
+    fn visit_assign_expr(&mut self, expr: &Assign) -> Json {
+        let fields = vec![
+            ("name", token(&expr.name)),
+            ("value", expr.value.accept(self)),
+        ];
+        node("Assign", expr.span, fields)
+    }
+
+    fn visit_logical_expr(&mut self, expr: &Logical) -> Json {
+        let fields = vec![
+            ("left", expr.left.accept(self)),
+            ("operator", token(&expr.operator)),
+            ("right", expr.right.accept(self)),
+        ];
+        node("Logical", expr.span, fields)
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) -> Json {
+        let fields = vec![
+            ("callee", expr.callee.accept(self)),
+            ("paren", token(&expr.paren)),
+            ("arguments", self.exprs(&expr.arguments)),
+        ];
+        node("Call", expr.span, fields)
+    }
+
+    fn visit_closure_expr(&mut self, expr: &Rc<Closure>) -> Json {
+        let fields = vec![
+            ("params", tokens(&expr.params)),
+            ("body", self.stmts(&expr.body)),
+            ("doc", doc_comment(expr.doc.as_deref())),
+        ];
+        node("Closure", expr.span, fields)
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) -> Json {
+        let fields = vec![
+            ("object", expr.object.accept(self)),
+            ("name", token(&expr.name)),
+        ];
+        node("Get", expr.span, fields)
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) -> Json {
+        let fields = vec![
+            ("object", expr.object.accept(self)),
+            ("name", token(&expr.name)),
+            ("value", expr.value.accept(self)),
+        ];
+        node("Set", expr.span, fields)
+    }
+
+    fn visit_this_expr(&mut self, expr: &This) -> Json {
+        node("This", expr.span, vec![("keyword", token(&expr.keyword))])
+    }
+
+    fn visit_super_expr(&mut self, expr: &Super) -> Json {
+        let fields = vec![
+            ("keyword", token(&expr.keyword)),
+            ("method", token(&expr.method)),
+        ];
+        node("Super", expr.span, fields)
+    }
+
+    fn visit_list_expr(&mut self, expr: &List) -> Json {
+        let fields = vec![("elements", self.exprs(&expr.elements))];
+        node("List", expr.span, fields)
+    }
+
+    fn visit_list_comprehension_expr(&mut self, expr: &ListComprehension) -> Json {
+        let fields = vec![
+            ("element", expr.element.accept(self)),
+            ("name", token(&expr.name)),
+            ("iterable", expr.iterable.accept(self)),
+            ("condition", self.optional_expr(expr.condition.as_deref())),
+        ];
+        node("ListComprehension", expr.span, fields)
+    }
+
+    fn visit_index_expr(&mut self, expr: &Index) -> Json {
+        let fields = vec![
+            ("object", expr.object.accept(self)),
+            ("index", expr.index.accept(self)),
+        ];
+        node("Index", expr.span, fields)
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &SetIndex) -> Json {
+        let fields = vec![
+            ("object", expr.object.accept(self)),
+            ("index", expr.index.accept(self)),
+            ("value", expr.value.accept(self)),
+        ];
+        node("SetIndex", expr.span, fields)
+    }
+}
+
+impl StmtVisitor<Json> for JsonWriter {
+    fn visit_expression_stmt(&mut self, stmt: &Expression) -> Json {
+        let fields = vec![("expression", stmt.expression.accept(self))];
+        node("Expression", stmt.span, fields)
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Print) -> Json {
+        let fields = vec![
+            ("expression", stmt.expression.accept(self)),
+            ("echo", Json::Bool(stmt.echo)),
+        ];
+        node("Print", stmt.span, fields)
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Var) -> Json {
+        let fields = vec![
+            ("name", token(&stmt.name)),
+            ("initializer", self.optional_expr(stmt.initializer.as_ref())),
+        ];
+        node("Var", stmt.span, fields)
+    }
+
+    fn visit_destructure_stmt(&mut self, stmt: &Destructure) -> Json {
+        let fields = vec![
+            ("names", tokens(&stmt.names)),
+            ("initializer", stmt.initializer.accept(self)),
+        ];
+        node("Destructure", stmt.span, fields)
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &Block) -> Json {
+        let fields = vec![("statements", self.stmts(&stmt.statements))];
+        node("Block", stmt.span, fields)
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &If) -> Json {
+        let else_branch = stmt.else_branch.as_ref();
+        let fields = vec![
+            ("condition", stmt.condition.accept(self)),
+            ("then_branch", stmt.then_branch.accept(self)),
+            (
+                "else_branch",
+                else_branch.map_or(Json::Null, |stmt| stmt.accept(self)),
+            ),
+        ];
+        node("If", stmt.span, fields)
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &While) -> Json {
+        let fields = vec![
+            ("condition", stmt.condition.accept(self)),
+            ("body", stmt.body.accept(self)),
+        ];
+        node("While", stmt.span, fields)
+    }
+
+    fn visit_for_stmt(&mut self, stmt: &For) -> Json {
+        let initializer = stmt.initializer.as_ref();
+        let fields = vec![
+            (
+                "initializer",
+                initializer.map_or(Json::Null, |stmt| stmt.accept(self)),
+            ),
+            ("condition", self.optional_expr(stmt.condition.as_ref())),
+            ("increment", self.optional_expr(stmt.increment.as_ref())),
+            ("body", stmt.body.accept(self)),
+        ];
+        node("For", stmt.span, fields)
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) -> Json {
+        let fields = vec![
+            ("name", token(&stmt.name)),
+            ("iterable", stmt.iterable.accept(self)),
+            ("body", stmt.body.accept(self)),
+        ];
+        node("ForIn", stmt.span, fields)
+    }
+
+    fn visit_break_stmt(&mut self, stmt: &Break) -> Json {
+        node("Break", stmt.span, Vec::new())
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Function) -> Json {
+        let fields = vec![
+            ("name", token(&stmt.name)),
+            ("closure", self.visit_closure_expr(&stmt.closure)),
+            ("decorators", self.exprs(&stmt.decorators)),
+        ];
+        node("Function", stmt.span, fields)
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Return) -> Json {
+        let fields = vec![
+            ("keyword", token(&stmt.keyword)),
+            ("value", stmt.value.accept(self)),
+        ];
+        node("Return", stmt.span, fields)
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &Class) -> Json {
+        let fields = vec![
+            ("name", token(&stmt.name)),
+            ("superclass", self.optional_expr(stmt.superclass.as_deref())),
+            ("methods", self.stmts(&stmt.methods)),
+            ("doc", doc_comment(stmt.doc.as_deref())),
+        ];
+        node("Class", stmt.span, fields)
+    }
+}
+
+/// Builds syntax trees from JSON, rejecting trees the rest of the interpreter assumes the
+/// parser never produces, such as a `break` outside a loop or an operator in the wrong node.
+struct JsonReader {
+    /// Loops the node being read is in, within the innermost function.
+    loop_depth: usize,
+}
+
+impl JsonReader {
+    fn statements(&mut self, json: &Json) -> Result<Vec<Stmt>, String> {
+        match json {
+            Json::Array(statements) => statements.iter().map(|stmt| self.stmt(stmt)).collect(),
+            _ => Err(String::from("Expected an array of statements.")),
+        }
+    }
+
+    fn exprs(&mut self, json: &Json) -> Result<Vec<Expr>, String> {
+        match json {
+            Json::Array(exprs) => exprs.iter().map(|expr| self.expr(expr)).collect(),
+            _ => Err(String::from("Expected an array of expressions.")),
+        }
+    }
+
+    fn boxed_expr(&mut self, json: &Json, name: &str) -> Result<Box<Expr>, String> {
+        self.expr(field(json, name)?).map(Box::new)
+    }
+
+    /// The expression `name`, which may be `null` or left out.
+    fn optional_expr(&mut self, json: &Json, name: &str) -> Result<Option<Expr>, String> {
+        match json.get(name) {
+            None | Some(Json::Null) => Ok(None),
+            Some(expr) => self.expr(expr).map(Some),
+        }
+    }
+
+    fn optional_stmt(&mut self, json: &Json, name: &str) -> Result<Option<Box<Stmt>>, String> {
+        match json.get(name) {
+            None | Some(Json::Null) => Ok(None),
+            Some(stmt) => self.stmt(stmt).map(|stmt| Some(Box::new(stmt))),
+        }
+    }
+
+    fn loop_body(&mut self, json: &Json) -> Result<Box<Stmt>, String> {
+        self.loop_depth += 1;
+        let body = self.stmt(field(json, "body")?);
+        self.loop_depth -= 1;
+        body.map(Box::new)
+    }
+
+    fn closure(&mut self, json: &Json) -> Result<Rc<Closure>, String> {
+        if node_type(json)? != "Closure" {
+            return Err(String::from("Expected a Closure node."));
+        }
+        let params = identifiers(field(json, "params")?)?;
+        let enclosing_loops = std::mem::replace(&mut self.loop_depth, 0);
+        let body = self.statements(field(json, "body")?);
+        self.loop_depth = enclosing_loops;
+        Ok(Rc::new(Closure::new(
+            params,
+            body?,
+            doc(json)?,
+            span(json)?,
+        )))
+    }
+
+    fn expr(&mut self, json: &Json) -> Result<Expr, String> {
+        let node_type = node_type(json)?;
+        let span = span(json)?;
+        let expr = match node_type {
+            "Binary" => Expr::Binary(Binary {
+                span,
+                ..Binary::new(
+                    self.boxed_expr(json, "left")?,
+                    token_of(json, "operator", &BINARY_OPERATORS)?,
+                    self.boxed_expr(json, "right")?,
+                )
+            }),
+            "Grouping" => Expr::Grouping(Grouping::new(self.boxed_expr(json, "expression")?, span)),
+            "Literal" => {
+                let value = match field(json, "value")? {
+                    Json::Number(val) => LoxLiteral::Number(*val),
+                    Json::String(val) => LoxLiteral::String(Rc::new(LoxString::new(val.as_str()))),
+                    Json::Bool(val) => LoxLiteral::Boolean(*val),
+                    Json::Null => LoxLiteral::Nil,
+                    value @ Json::Object(_) => match value.get("number") {
+                        Some(Json::String(text)) => match text.parse::<f64>() {
+                            Ok(val) if !val.is_finite() => LoxLiteral::Number(val),
+                            _ => return Err(format!("Invalid number '{text}' in Literal node.")),
+                        },
+                        _ => return Err(String::from("Expected a literal value in Literal node.")),
+                    },
+                    _ => return Err(String::from("Expected a literal value in Literal node.")),
+                };
+                Expr::Literal(Literal::new(value, span))
+            }
+            "Unary" => Expr::Unary(Unary {
+                span,
+                ..Unary::new(
+                    token_of(json, "operator", &[TokenType::Bang, TokenType::Minus])?,
+                    self.boxed_expr(json, "right")?,
+                )
+            }),
+            "Ternary" => Expr::Ternary(Ternary {
+                span,
+                ..Ternary::new(
+                    self.boxed_expr(json, "condition")?,
+                    self.boxed_expr(json, "left")?,
+                    self.boxed_expr(json, "right")?,
+                )
+            }),
+            "Variable" => Expr::Variable(Variable {
+                span,
+                ..Variable::new(token_of(json, "name", &[TokenType::Identifier])?)
+            }),
+            "Assign" => Expr::Assign(Assign {
+                span,
+                ..Assign::new(
+                    token_of(json, "name", &[TokenType::Identifier])?,
+                    self.boxed_expr(json, "value")?,
+                )
+            }),
+            "Logical" => Expr::Logical(Logical {
+                span,
+                ..Logical::new(
+                    self.boxed_expr(json, "left")?,
+                    token_of(json, "operator", &[TokenType::And, TokenType::Or])?,
+                    self.boxed_expr(json, "right")?,
+                )
+            }),
+            "Call" => Expr::Call(Call {
+                span,
+                ..Call::new(
+                    self.boxed_expr(json, "callee")?,
+                    token_of(json, "paren", &[TokenType::RightParen])?,
+                    self.exprs(field(json, "arguments")?)?,
+                )
+            }),
+            "Closure" => Expr::Closure(self.closure(json)?),
+            "Get" => Expr::Get(Get {
+                span,
+                ..Get::new(
+                    self.boxed_expr(json, "object")?,
+                    token_of(json, "name", &[TokenType::Identifier])?,
+                )
+            }),
+            "Set" => Expr::Set(Set {
+                span,
+                ..Set::new(
+                    self.boxed_expr(json, "object")?,
+                    token_of(json, "name", &[TokenType::Identifier])?,
+                    self.boxed_expr(json, "value")?,
+                )
+            }),
+            "This" => Expr::This(This {
+                span,
+                ..This::new(token_of(json, "keyword", &[TokenType::This])?)
+            }),
+            "Super" => Expr::Super(Super {
+                span,
+                ..Super::new(
+                    token_of(json, "keyword", &[TokenType::Super])?,
+                    token_of(json, "method", &[TokenType::Identifier])?,
+                )
+            }),
+            "List" => Expr::List(List::new(self.exprs(field(json, "elements")?)?, span)),
+            "ListComprehension" => Expr::ListComprehension(ListComprehension::new(
+                self.boxed_expr(json, "element")?,
+                token_of(json, "name", &[TokenType::Identifier])?,
+                self.boxed_expr(json, "iterable")?,
+                self.optional_expr(json, "condition")?.map(Box::new),
+                span,
+            )),
+            "Index" => Expr::Index(Index::new(
+                self.boxed_expr(json, "object")?,
+                self.boxed_expr(json, "index")?,
+                span,
+            )),
+            "SetIndex" => Expr::SetIndex(SetIndex {
+                span,
+                ..SetIndex::new(
+                    self.boxed_expr(json, "object")?,
+                    self.boxed_expr(json, "index")?,
+                    self.boxed_expr(json, "value")?,
+                )
+            }),
+            other => return Err(format!("Unknown expression type '{other}'.")),
+        };
+        Ok(expr)
+    }
+
+    fn stmt(&mut self, json: &Json) -> Result<Stmt, String> {
+        let node_type = node_type(json)?;
+        let span = span(json)?;
+        let stmt = match node_type {
+            "Expression" => Stmt::Expression(Expression::new(
+                self.expr(field(json, "expression")?)?,
+                span,
+            )),
+            "Print" => {
+                let expression = self.expr(field(json, "expression")?)?;
+                match json.get("echo") {
+                    Some(Json::Bool(true)) => Stmt::Print(Print::echo(expression, span)),
+                    None | Some(Json::Bool(false)) => Stmt::Print(Print::new(expression, span)),
+                    _ => return Err(String::from("Expected a boolean 'echo' in Print node.")),
+                }
+            }
+            "Var" => Stmt::Var(Var::new(
+                token_of(json, "name", &[TokenType::Identifier])?,
+                self.optional_expr(json, "initializer")?,
+                span,
+            )),
+            "Destructure" => Stmt::Destructure(Destructure::new(
+                identifiers(field(json, "names")?)?,
+                self.expr(field(json, "initializer")?)?,
+                span,
+            )),
+            "Block" => Stmt::Block(Block::new(
+                self.statements(field(json, "statements")?)?,
+                span,
+            )),
+            "If" => Stmt::If(If::new(
+                self.expr(field(json, "condition")?)?,
+                Box::new(self.stmt(field(json, "then_branch")?)?),
+                self.optional_stmt(json, "else_branch")?,
+                span,
+            )),
+            "While" => Stmt::While(While::new(
+                self.expr(field(json, "condition")?)?,
+                self.loop_body(json)?,
+                span,
+            )),
+            "For" => Stmt::For(For::new(
+                self.optional_stmt(json, "initializer")?,
+                self.optional_expr(json, "condition")?,
+                self.optional_expr(json, "increment")?,
+                self.loop_body(json)?,
+                span,
+            )),
+            "ForIn" => Stmt::ForIn(ForIn::new(
+                token_of(json, "name", &[TokenType::Identifier])?,
+                self.expr(field(json, "iterable")?)?,
+                self.loop_body(json)?,
+                span,
+            )),
+            "Break" if self.loop_depth == 0 => {
+                return Err(String::from("Break node outside of any loop."))
+            }
+            "Break" => Stmt::Break(Break::new(span)),
+            "Function" => Stmt::Function(Function::new(
+                token_of(json, "name", &[TokenType::Identifier])?,
+                self.closure(field(json, "closure")?)?,
+                match json.get("decorators") {
+                    Some(decorators) => self.exprs(decorators)?,
+                    None => Vec::new(),
+                },
+                span,
+            )),
+            "Return" => Stmt::Return(Return::new(
+                token_of(json, "keyword", &[TokenType::Return])?,
+                self.expr(field(json, "value")?)?,
+                span,
+            )),
+            "Class" => {
+                let superclass = self.optional_expr(json, "superclass")?;
+                if superclass
+                    .as_ref()
+                    .is_some_and(|superclass| !matches!(superclass, Expr::Variable(_)))
+                {
+                    return Err(String::from("Expected a Variable node as superclass."));
+                }
+                let methods = self.statements(field(json, "methods")?)?;
+                if !methods
+                    .iter()
+                    .all(|method| matches!(method, Stmt::Function(_)))
+                {
+                    return Err(String::from("Expected only Function nodes as methods."));
+                }
+                Stmt::Class(Class::new(
+                    token_of(json, "name", &[TokenType::Identifier])?,
+                    superclass.map(Box::new),
+                    methods,
+                    doc(json)?,
+                    span,
+                ))
+            }
+            other => return Err(format!("Unknown statement type '{other}'.")),
+        };
+        Ok(stmt)
+    }
+}
+
+fn node_type(json: &Json) -> Result<&str, String> {
+    match json.get("type") {
+        Some(Json::String(node_type)) => Ok(node_type),
+        _ => Err(String::from("Expected a node with a 'type'.")),
+    }
+}
+
+fn field<'a>(json: &'a Json, name: &str) -> Result<&'a Json, String> {
+    json.get(name).ok_or_else(|| {
+        let node_type = node_type(json).unwrap_or("?");
+        format!("Expected '{name}' in {node_type} node.")
+    })
+}
+
+/// The doc comment of the node `json`, which may be left out or `null` if it has none.
+fn doc(json: &Json) -> Result<Option<Rc<str>>, String> {
+    match json.get("doc") {
+        None | Some(Json::Null) => Ok(None),
+        Some(Json::String(doc)) => Ok(Some(Rc::from(doc.as_str()))),
+        Some(_) => Err(format!(
+            "Expected a string 'doc' in {} node.",
+            node_type(json).unwrap_or("?")
+        )),
+    }
+}
+
+fn position(json: &Json, name: &str) -> Result<usize, String> {
+    match json.get(name) {
+        Some(&Json::Number(val)) if val >= 0.0 && val.fract() == 0.0 => Ok(val as usize),
+        _ => Err(format!("Expected a whole number '{name}'.")),
+    }
+}
+
+fn span(json: &Json) -> Result<Span, String> {
+    let span = field(json, "span")?;
+    let (line, end_line) = (position(span, "line")?, position(span, "end_line")?);
+    let (start, end) = (position(span, "start")?, position(span, "end")?);
+    if line == 0 || end_line < line || end < start {
+        return Err(format!(
+            "Invalid span in {} node.",
+            node_type(json).unwrap_or("?")
+        ));
+    }
+    Ok(Span::new(line, end_line, start, end))
+}
+
+/// The token `name` of the node `json`, which must be one of the `expected` types. The type
+/// comes from scanning the lexeme, so a token is always one the scanner could have produced.
+fn token_of(json: &Json, name: &str, expected: &[TokenType]) -> Result<Token, String> {
+    let node_type = node_type(json)?;
+    let token = token_from_json(field(json, name)?)?;
+    if !expected.contains(&token.token_type) {
+        return Err(format!(
+            "Unexpected token '{}' as '{name}' in {node_type} node.",
+            token.lexeme
+        ));
+    }
+    Ok(token)
+}
+
+fn identifiers(json: &Json) -> Result<Vec<Token>, String> {
+    let Json::Array(tokens) = json else {
+        return Err(String::from("Expected an array of names."));
+    };
+    tokens
+        .iter()
+        .map(|json| {
+            let token = token_from_json(json)?;
+            match token.token_type {
+                TokenType::Identifier => Ok(token),
+                _ => Err(format!("Expected a name, not '{}'.", token.lexeme)),
+            }
+        })
+        .collect()
+}
+
+fn token_from_json(json: &Json) -> Result<Token, String> {
+    let Some(Json::String(lexeme)) = json.get("lexeme") else {
+        return Err(String::from("Expected a token with a 'lexeme'."));
+    };
+    let mut scanner = Scanner::new(lexeme);
+    scanner.scan_tokens();
+    let mut tokens = scanner.tokens;
+    let scanned_whole = tokens.len() == 2 && *tokens[0].lexeme == **lexeme;
+    if !scanned_whole || !scanner.diagnostics.is_empty() {
+        return Err(format!("Invalid token '{lexeme}'."));
+    }
+    let mut token = tokens.swap_remove(0);
+    token.line = position(json, "line")?;
+    token.start = position(json, "start")?;
+    token.column = position(json, "column")?;
+    if token.line == 0 || token.column == 0 {
+        return Err(format!("Invalid position for token '{lexeme}'."));
+    }
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infinite_literals_round_trip() {
+        let source = format!("print 1{zeros}; print -1{zeros};", zeros = "0".repeat(400));
+        let json = ast_to_json(&source).unwrap();
+        assert!(json.contains(r#""number": "inf""#), "{json}");
+        let statements = statements_from_json(&json).unwrap();
+        assert_eq!(format!("{}\n", statements_to_json(&statements)), json);
+    }
+}
@@ -0,0 +1,258 @@
+use crate::{
+    diagnostic::{emit, has_errors},
+    expr::{
+        Assign, Binary, Call, Closure, Expr, ExprVisitor, Get, Grouping, Index, List,
+        ListComprehension, Literal, Logical, Set, SetIndex, Super, Ternary, This, Unary, Variable,
+    },
+    lox_object::LoxLiteral,
+    parser::Parser,
+    scanner::Scanner,
+    stmt::{
+        Block, Break, Class, Destructure, Expression, For, ForIn, Function, If, Print, Return,
+        Stmt, StmtVisitor, Var, While,
+    },
+    token::Token,
+};
+use std::rc::Rc;
+
+/// Parses `source` and renders its AST as S-expressions, one top-level statement per line.
+/// Returns `None` if the source has scan or parse errors, which are reported as usual.
+pub fn print_ast(source: &str) -> Option<String> {
+    let mut scanner = Scanner::new(source);
+    scanner.scan_tokens();
+    emit(source, &scanner.diagnostics);
+    let mut parser = Parser::new(scanner.tokens);
+    let parse_result = parser.parse();
+    emit(source, &parser.diagnostics);
+    let statements = parse_result.ok()?;
+    if has_errors(&scanner.diagnostics) {
+        return None;
+    }
+
+    let mut printer = AstPrinter { depth: 0 };
+    let mut out = String::new();
+    for statement in statements.iter() {
+        out.push_str(&statement.accept(&mut printer));
+        out.push('\n');
+    }
+    Some(out)
+}
+
+/// The first line of `stmt`'s S-expression, with `...` marking an elided body.
+pub(crate) fn summarize(stmt: &Stmt) -> String {
+    let printed = stmt.accept(&mut AstPrinter { depth: 0 });
+    match printed.split_once('\n') {
+        Some((head, _)) => format!("{head} ...)"),
+        None => printed,
+    }
+}
+
+struct AstPrinter {
+    /// Nesting of the statement bodies being printed, used for indentation.
+    depth: usize,
+}
+
+impl AstPrinter {
+    fn parenthesize(&mut self, name: &str, exprs: &[&Expr]) -> String {
+        let mut out = format!("({name}");
+        for expr in exprs {
+            out.push(' ');
+            out.push_str(&expr.accept(self));
+        }
+        out.push(')');
+        out
+    }
+
+    /// Closes `head` with `body`, printing each statement on its own indented line.
+    fn with_body<'a>(&mut self, head: String, body: impl IntoIterator<Item = &'a Stmt>) -> String {
+        let mut out = head;
+        self.depth += 1;
+        for statement in body {
+            out.push('\n');
+            out.push_str(&"  ".repeat(self.depth));
+            out.push_str(&statement.accept(self));
+        }
+        self.depth -= 1;
+        out.push(')');
+        out
+    }
+
+    fn names(names: &[Token]) -> String {
+        let names: Vec<&str> = names.iter().map(|name| &*name.lexeme).collect();
+        format!("({})", names.join(" "))
+    }
+
+    fn closure(&mut self, head: String, closure: &Closure) -> String {
+        let head = format!("{head} {}", AstPrinter::names(&closure.params));
+        self.with_body(head, &closure.body)
+    }
+}
+
+impl ExprVisitor<String> for AstPrinter {
+    fn visit_binary_expr(&mut self, expr: &Binary) -> String {
+        self.parenthesize(&expr.operator.lexeme, &[&expr.left, &expr.right])
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Grouping) -> String {
+        self.parenthesize("group", &[&expr.expression])
+    }
+
+    fn visit_literal_expr(&mut self, expr: &Literal) -> String {
+        match expr.value {
+            LoxLiteral::String(ref val) => format!("{val:?}"),
+            ref literal => literal.to_string(),
+        }
+    }
+
+    fn visit_unary_expr(&mut self, expr: &Unary) -> String {
+        self.parenthesize(&expr.operator.lexeme, &[&expr.right])
+    }
+
+    fn visit_ternary_expr(&mut self, expr: &Ternary) -> String {
+        self.parenthesize("?:", &[&expr.condition, &expr.left, &expr.right])
+    }
+
+    fn visit_variable_expr(&mut self, expr: &Variable) -> String {
+        expr.name.lexeme.to_string()
+    }
+
+    fn visit_assign_expr(&mut self, expr: &Assign) -> String {
+        self.parenthesize(&format!("= {}", expr.name.lexeme), &[&expr.value])
+    }
+
+    fn visit_logical_expr(&mut self, expr: &Logical) -> String {
+        self.parenthesize(&expr.operator.lexeme, &[&expr.left, &expr.right])
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) -> String {
+        let mut exprs = vec![expr.callee.as_ref()];
+        exprs.extend(expr.arguments.iter());
+        self.parenthesize("call", &exprs)
+    }
+
+    fn visit_closure_expr(&mut self, expr: &Rc<Closure>) -> String {
+        self.closure(String::from("(fun"), expr)
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) -> String {
+        self.parenthesize(&format!(". {}", expr.name.lexeme), &[&expr.object])
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) -> String {
+        self.parenthesize(
+            &format!(".= {}", expr.name.lexeme),
+            &[&expr.object, &expr.value],
+        )
+    }
+
+    fn visit_this_expr(&mut self, _expr: &This) -> String {
+        String::from("this")
+    }
+
+    fn visit_super_expr(&mut self, expr: &Super) -> String {
+        format!("(super {})", expr.method.lexeme)
+    }
+
+    fn visit_list_expr(&mut self, expr: &List) -> String {
+        let exprs: Vec<&Expr> = expr.elements.iter().collect();
+        self.parenthesize("list", &exprs)
+    }
+
+    fn visit_list_comprehension_expr(&mut self, expr: &ListComprehension) -> String {
+        let mut exprs = vec![expr.element.as_ref(), expr.iterable.as_ref()];
+        exprs.extend(expr.condition.as_deref());
+        self.parenthesize(&format!("list-for {}", expr.name.lexeme), &exprs)
+    }
+
+    fn visit_index_expr(&mut self, expr: &Index) -> String {
+        self.parenthesize("[]", &[&expr.object, &expr.index])
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &SetIndex) -> String {
+        self.parenthesize("[]=", &[&expr.object, &expr.index, &expr.value])
+    }
+}
+
+impl StmtVisitor<String> for AstPrinter {
+    fn visit_expression_stmt(&mut self, stmt: &Expression) -> String {
+        self.parenthesize(";", &[&stmt.expression])
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Print) -> String {
+        self.parenthesize("print", &[&stmt.expression])
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Var) -> String {
+        let name = format!("var {}", stmt.name.lexeme);
+        match stmt.initializer {
+            Some(ref initializer) => self.parenthesize(&name, &[initializer]),
+            None => format!("({name})"),
+        }
+    }
+
+    fn visit_destructure_stmt(&mut self, stmt: &Destructure) -> String {
+        let names = format!("var {}", AstPrinter::names(&stmt.names));
+        self.parenthesize(&names, &[&stmt.initializer])
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &Block) -> String {
+        self.with_body(String::from("(block"), &stmt.statements)
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &If) -> String {
+        let head = format!("(if {}", stmt.condition.accept(self));
+        let branches =
+            std::iter::once(stmt.then_branch.as_ref()).chain(stmt.else_branch.as_deref());
+        self.with_body(head, branches)
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &While) -> String {
+        let head = format!("(while {}", stmt.condition.accept(self));
+        self.with_body(head, [stmt.body.as_ref()])
+    }
+
+    fn visit_for_stmt(&mut self, stmt: &For) -> String {
+        let initializer = match stmt.initializer {
+            Some(ref initializer) => initializer.accept(self),
+            None => String::from("nil"),
+        };
+        let mut head = format!("(for {initializer}");
+        for clause in [&stmt.condition, &stmt.increment] {
+            let clause = match clause {
+                Some(expr) => expr.accept(self),
+                None => String::from("nil"),
+            };
+            head.push_str(&format!(" {clause}"));
+        }
+        self.with_body(head, [stmt.body.as_ref()])
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) -> String {
+        let head = format!("(for {} {}", stmt.name.lexeme, stmt.iterable.accept(self));
+        self.with_body(head, [stmt.body.as_ref()])
+    }
+
+    fn visit_break_stmt(&mut self, _stmt: &Break) -> String {
+        String::from("(break)")
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Function) -> String {
+        let mut head = format!("(fun {}", stmt.name.lexeme);
+        for decorator in stmt.decorators.iter() {
+            head.push_str(&format!(" (@ {})", decorator.accept(self)));
+        }
+        self.closure(head, &stmt.closure)
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Return) -> String {
+        self.parenthesize("return", &[&stmt.value])
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &Class) -> String {
+        let mut head = format!("(class {}", stmt.name.lexeme);
+        if let Some(ref superclass) = stmt.superclass {
+            head.push_str(&format!(" (< {})", superclass.accept(self)));
+        }
+        self.with_body(head, &stmt.methods)
+    }
+}
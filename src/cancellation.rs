@@ -0,0 +1,37 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// A handle for stopping a script from another thread, such as a GUI's stop button or a server
+/// enforcing a deadline. Get one with [`Interpreter::cancellation_token`]; clones share the same
+/// state and can be sent anywhere.
+///
+/// Cancelling makes the interpreter abort before its next statement, or partway through a
+/// `sleep()`, with [`LoxException::Cancelled`], which scripts can't intercept. A read from
+/// `readLine()` or `input()` can't be abandoned, so the abort waits until it returns. The
+/// interpreter clears the token when it aborts, so it can run more code afterwards.
+///
+/// [`Interpreter::cancellation_token`]: crate::interpreter::Interpreter::cancellation_token
+/// [`LoxException::Cancelled`]: crate::lox_exception::LoxException::Cancelled
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Asks the interpreter to stop what it's running.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the token has been cancelled since the interpreter last aborted.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Clears the token, returning whether it was cancelled.
+    pub(crate) fn take(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed) && self.cancelled.swap(false, Ordering::Relaxed)
+    }
+}
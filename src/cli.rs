@@ -0,0 +1,941 @@
+use crate::{
+    coverage::CoverageFormat,
+    diagnostic::DiagnosticFormat,
+    error_code::ErrorCode,
+    interpreter::{max_call_depth_for, Backend},
+    interpreter_thread::STACK_SIZE,
+};
+use std::fmt;
+
+/// Default for `--max-call-depth`. Scripts run on a thread with [`STACK_SIZE`] bytes of stack,
+/// so this is well above the library's default.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 10_000;
+
+/// Largest `--max-call-depth` that [`STACK_SIZE`] can hold.
+pub const MAX_CALL_DEPTH: usize = max_call_depth_for(STACK_SIZE);
+
+/// What the binary should do once arguments are parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Prompt,
+    Run(String),
+    /// Source code given on the command line.
+    Eval(String),
+    Check(String),
+    Fmt(String),
+    Minify(String),
+    Js(String),
+    Ast(String),
+    Tokens(String),
+    Highlight(String),
+    Report(String),
+    Doc(String),
+    Lint(String),
+    Debug(String),
+    /// A directory of test scripts, or a single one.
+    Test(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    pub command: Command,
+    pub help: bool,
+    pub version: bool,
+    pub stats: bool,
+    pub html: bool,
+    /// Print the syntax tree as JSON from `ast`.
+    pub json: bool,
+    /// Read the script as a JSON syntax tree rather than source code.
+    pub from_json: bool,
+    /// Give local variables shorter names in `minify`.
+    pub rename_locals: bool,
+    /// Log each statement before executing it.
+    pub trace: bool,
+    /// Log each variable definition and assignment.
+    pub trace_env: bool,
+    pub backend: Backend,
+    /// Report timings and execution counts after running the script.
+    pub bench: bool,
+    /// Report calls and time spent per function after running the script.
+    pub profile: bool,
+    /// Report which lines ran, in this format, after running the script.
+    pub coverage: Option<CoverageFormat>,
+    /// Re-run the script whenever it changes.
+    pub watch: bool,
+    /// Never color diagnostics, even when stderr is a terminal.
+    pub no_color: bool,
+    /// Warning codes not to print.
+    pub allow: Vec<ErrorCode>,
+    /// Warning codes to report as errors.
+    pub deny: Vec<ErrorCode>,
+    /// Treat warnings as errors.
+    pub deny_warnings: bool,
+    pub diagnostics: DiagnosticFormat,
+    pub max_line_length: usize,
+    /// Most calls a script may have in progress at once.
+    pub max_call_depth: usize,
+    /// Most statements a script may execute, or `None` for no limit.
+    pub max_ops: Option<usize>,
+    /// Most bytes of memory a script may use, or `None` for no limit.
+    pub max_memory: Option<usize>,
+    /// Script run before the first prompt, replacing the default `~/.loxrc`.
+    pub init: Option<String>,
+    pub eval: Option<String>,
+    /// Arguments following the script path, exposed to Lox as `args`.
+    pub script_args: Vec<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            command: Command::Prompt,
+            help: false,
+            version: false,
+            stats: false,
+            html: false,
+            json: false,
+            from_json: false,
+            rename_locals: false,
+            trace: false,
+            trace_env: false,
+            backend: Backend::TreeWalk,
+            bench: false,
+            profile: false,
+            coverage: None,
+            watch: false,
+            no_color: false,
+            allow: Vec::new(),
+            deny: Vec::new(),
+            deny_warnings: false,
+            diagnostics: DiagnosticFormat::Human,
+            max_line_length: 64 * 1024,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_ops: None,
+            max_memory: None,
+            init: None,
+            eval: None,
+            script_args: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageError(String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+struct Flag {
+    name: &'static str,
+    /// Single-letter alias, used as `-x`.
+    short: Option<char>,
+    /// Placeholder shown in help output for flags that take a value.
+    value: Option<&'static str>,
+    help: &'static str,
+    apply: fn(&mut Options, &str) -> Result<(), UsageError>,
+}
+
+/// Every flag the binary accepts. Parsing and `--help` output are both driven by this table.
+const FLAGS: &[Flag] = &[
+    Flag {
+        name: "eval",
+        short: Some('e'),
+        value: Some("code"),
+        help: "Run the given code instead of a script",
+        apply: |options, value| {
+            options.eval = Some(value.to_string());
+            Ok(())
+        },
+    },
+    Flag {
+        name: "help",
+        short: None,
+        value: None,
+        help: "Print this help and exit",
+        apply: |options, _| {
+            options.help = true;
+            Ok(())
+        },
+    },
+    Flag {
+        name: "version",
+        short: None,
+        value: None,
+        help: "Print the version and exit",
+        apply: |options, _| {
+            options.version = true;
+            Ok(())
+        },
+    },
+    Flag {
+        name: "stats",
+        short: None,
+        value: None,
+        help: "Print per-class instance counts to stderr after running a script",
+        apply: |options, _| {
+            options.stats = true;
+            Ok(())
+        },
+    },
+    Flag {
+        name: "html",
+        short: None,
+        value: None,
+        help: "Emit HTML instead of ANSI colors from `highlight`",
+        apply: |options, _| {
+            options.html = true;
+            Ok(())
+        },
+    },
+    Flag {
+        name: "json",
+        short: None,
+        value: None,
+        help: "Print the syntax tree from `ast` as JSON",
+        apply: |options, _| {
+            options.json = true;
+            Ok(())
+        },
+    },
+    Flag {
+        name: "from-json",
+        short: None,
+        value: None,
+        help: "Run a syntax tree written by `ast --json` instead of source code",
+        apply: |options, _| {
+            options.from_json = true;
+            Ok(())
+        },
+    },
+    Flag {
+        name: "rename-locals",
+        short: None,
+        value: None,
+        help: "Shorten the names of local variables and parameters in `minify`",
+        apply: |options, _| {
+            options.rename_locals = true;
+            Ok(())
+        },
+    },
+    Flag {
+        name: "trace",
+        short: None,
+        value: None,
+        help: "Log each statement to stderr before executing it",
+        apply: |options, _| {
+            options.trace = true;
+            Ok(())
+        },
+    },
+    Flag {
+        name: "trace-env",
+        short: None,
+        value: None,
+        help: "Log each variable definition and assignment to stderr",
+        apply: |options, _| {
+            options.trace_env = true;
+            Ok(())
+        },
+    },
+    Flag {
+        name: "bench",
+        short: None,
+        value: None,
+        help: "Print phase timings and execution counts to stderr after running a script",
+        apply: |options, _| {
+            options.bench = true;
+            Ok(())
+        },
+    },
+    Flag {
+        name: "profile",
+        short: None,
+        value: None,
+        help: "Print calls and time spent per function to stderr after running a script",
+        apply: |options, _| {
+            options.profile = true;
+            Ok(())
+        },
+    },
+    Flag {
+        name: "coverage",
+        short: None,
+        value: Some("format"),
+        help: "Print which lines ran to stderr after running a script or tests, as `lcov` or `annotated` source",
+        apply: |options, value| {
+            options.coverage = Some(match value {
+                "lcov" => CoverageFormat::Lcov,
+                "annotated" => CoverageFormat::Annotated,
+                _ => {
+                    return Err(UsageError(format!(
+                        "Invalid value '{value}' for --coverage; expected 'lcov' or 'annotated'."
+                    )))
+                }
+            });
+            Ok(())
+        },
+    },
+    Flag {
+        name: "watch",
+        short: None,
+        value: None,
+        help: "Re-run the script in a fresh interpreter whenever the file changes",
+        apply: |options, _| {
+            options.watch = true;
+            Ok(())
+        },
+    },
+    Flag {
+        name: "no-color",
+        short: None,
+        value: None,
+        help: "Print diagnostics without colors, even to a terminal",
+        apply: |options, _| {
+            options.no_color = true;
+            Ok(())
+        },
+    },
+    Flag {
+        name: "allow",
+        short: None,
+        value: Some("code"),
+        help: "Don't print warnings with this code, such as W0001 (may be repeated)",
+        apply: |options, value| {
+            match ErrorCode::parse(value) {
+                Some(code) if code.is_warning() => options.allow.push(code),
+                Some(_) => {
+                    return Err(UsageError(format!(
+                        "'{value}' is an error code; only warnings can be allowed."
+                    )))
+                }
+                None => return Err(UsageError(format!("Unknown warning code '{value}'."))),
+            }
+            Ok(())
+        },
+    },
+    Flag {
+        name: "deny",
+        short: None,
+        value: Some("code"),
+        help: "Report warnings with this code, such as W0004, as errors (may be repeated)",
+        apply: |options, value| {
+            match ErrorCode::parse(value) {
+                Some(code) if code.is_warning() => options.deny.push(code),
+                Some(_) => {
+                    return Err(UsageError(format!(
+                        "'{value}' is an error code; only warnings can be denied."
+                    )))
+                }
+                None => return Err(UsageError(format!("Unknown warning code '{value}'."))),
+            }
+            Ok(())
+        },
+    },
+    Flag {
+        name: "deny-warnings",
+        short: None,
+        value: None,
+        help: "Treat warnings as errors, refusing to run scripts that have any",
+        apply: |options, _| {
+            options.deny_warnings = true;
+            Ok(())
+        },
+    },
+    Flag {
+        name: "backend",
+        short: None,
+        value: Some("name"),
+        help: "Run scripts by walking their syntax tree (`tree`, the default) or on a bytecode VM (`vm`)",
+        apply: |options, value| {
+            options.backend = match value {
+                "tree" => Backend::TreeWalk,
+                "vm" => Backend::Vm,
+                _ => {
+                    return Err(UsageError(format!(
+                        "Invalid value '{value}' for --backend; expected 'tree' or 'vm'."
+                    )))
+                }
+            };
+            Ok(())
+        },
+    },
+    Flag {
+        name: "diagnostics",
+        short: None,
+        value: Some("format"),
+        help: "Print errors and warnings as `human` text (the default) or `json`, one per line",
+        apply: |options, value| {
+            options.diagnostics = match value {
+                "human" => DiagnosticFormat::Human,
+                "json" => DiagnosticFormat::Json,
+                _ => {
+                    return Err(UsageError(format!(
+                        "Invalid value '{value}' for --diagnostics; expected 'human' or 'json'."
+                    )))
+                }
+            };
+            Ok(())
+        },
+    },
+    Flag {
+        name: "max-line-length",
+        short: None,
+        value: Some("bytes"),
+        help: "Longest line the prompt or piped input accepts (default 65536)",
+        apply: |options, value| {
+            options.max_line_length = value.parse().map_err(|_| {
+                UsageError(format!("Invalid value '{value}' for --max-line-length."))
+            })?;
+            Ok(())
+        },
+    },
+    Flag {
+        name: "max-call-depth",
+        short: None,
+        value: Some("calls"),
+        help: "Most calls a script may nest before a stack overflow error (default 10000, at most 21845)",
+        apply: |options, value| {
+            options.max_call_depth = value.parse().map_err(|_| {
+                UsageError(format!("Invalid value '{value}' for --max-call-depth."))
+            })?;
+            if options.max_call_depth > MAX_CALL_DEPTH {
+                return Err(UsageError(format!(
+                    "Invalid value '{value}' for --max-call-depth; expected at most {MAX_CALL_DEPTH}."
+                )));
+            }
+            Ok(())
+        },
+    },
+    Flag {
+        name: "max-ops",
+        short: None,
+        value: Some("statements"),
+        help: "Most statements a script may execute before an error stops it (default unlimited)",
+        apply: |options, value| {
+            let max_ops = value
+                .parse()
+                .map_err(|_| UsageError(format!("Invalid value '{value}' for --max-ops.")))?;
+            options.max_ops = Some(max_ops);
+            Ok(())
+        },
+    },
+    Flag {
+        name: "max-memory",
+        short: None,
+        value: Some("size"),
+        help: "Most bytes of memory a script may use before an error stops it, such as 512M",
+        apply: |options, value| {
+            let max_memory = parse_size(value).ok_or_else(|| {
+                UsageError(format!("Invalid value '{value}' for --max-memory."))
+            })?;
+            options.max_memory = Some(max_memory);
+            Ok(())
+        },
+    },
+    Flag {
+        name: "init",
+        short: None,
+        value: Some("path"),
+        help: "Script to run before the first prompt (default ~/.loxrc)",
+        apply: |options, value| {
+            options.init = Some(value.to_string());
+            Ok(())
+        },
+    },
+];
+
+/// What a subcommand accepts after its name.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operands {
+    None,
+    Script,
+    ScriptAndArgs,
+    /// A file or directory.
+    Path,
+}
+
+struct Subcommand {
+    name: &'static str,
+    operands: Operands,
+    help: &'static str,
+    command: fn(String) -> Command,
+}
+
+/// Every subcommand. Like [`FLAGS`], this drives both parsing and `--help` output.
+const SUBCOMMANDS: &[Subcommand] = &[
+    Subcommand {
+        name: "run",
+        operands: Operands::ScriptAndArgs,
+        help: "Run a script (the default when the first argument is a script)",
+        command: Command::Run,
+    },
+    Subcommand {
+        name: "repl",
+        operands: Operands::None,
+        help: "Start the interactive prompt (the default with no arguments)",
+        command: |_| Command::Prompt,
+    },
+    Subcommand {
+        name: "check",
+        operands: Operands::Script,
+        help: "Report scan, parse and resolution errors without running the script",
+        command: Command::Check,
+    },
+    Subcommand {
+        name: "fmt",
+        operands: Operands::Script,
+        help: "Print the script with normalized indentation and spacing",
+        command: Command::Fmt,
+    },
+    Subcommand {
+        name: "minify",
+        operands: Operands::Script,
+        help: "Print the script on one line without comments or extra spaces (`--rename-locals` to shorten local names)",
+        command: Command::Minify,
+    },
+    Subcommand {
+        name: "js",
+        operands: Operands::Script,
+        help: "Print the script translated to JavaScript, for running without the interpreter",
+        command: Command::Js,
+    },
+    Subcommand {
+        name: "ast",
+        operands: Operands::Script,
+        help: "Print the script's syntax tree (`--json` for JSON)",
+        command: Command::Ast,
+    },
+    Subcommand {
+        name: "tokens",
+        operands: Operands::Script,
+        help: "Print the script's tokens",
+        command: Command::Tokens,
+    },
+    Subcommand {
+        name: "highlight",
+        operands: Operands::Script,
+        help: "Print the script with syntax highlighting (`--html` for HTML)",
+        command: Command::Highlight,
+    },
+    Subcommand {
+        name: "report",
+        operands: Operands::Script,
+        help: "Print metrics about the script",
+        command: Command::Report,
+    },
+    Subcommand {
+        name: "doc",
+        operands: Operands::Script,
+        help: "Print the `///` documentation of the script's functions and classes",
+        command: Command::Doc,
+    },
+    Subcommand {
+        name: "lint",
+        operands: Operands::Script,
+        help: "Report errors, plus warnings about code that's likely a mistake, without running the script",
+        command: Command::Lint,
+    },
+    Subcommand {
+        name: "debug",
+        operands: Operands::ScriptAndArgs,
+        help: "Run a script in a step debugger, paused before its first statement",
+        command: Command::Debug,
+    },
+    Subcommand {
+        name: "test",
+        operands: Operands::Path,
+        help: "Run every .lox file in a directory, checking its output against `// expect:` comments",
+        command: Command::Test,
+    },
+];
+
+impl Subcommand {
+    fn usage(&self) -> String {
+        match self.operands {
+            Operands::None => self.name.to_string(),
+            Operands::Script => format!("{} <script>", self.name),
+            Operands::ScriptAndArgs => format!("{} <script> [args...]", self.name),
+            Operands::Path => format!("{} <path>", self.name),
+        }
+    }
+}
+
+/// Picks the command from the positional arguments. A first argument that isn't a
+/// subcommand is a script to run, as with `run`.
+fn parse_command(
+    positionals: &[String],
+    script_args: &mut Vec<String>,
+) -> Result<Command, UsageError> {
+    let Some((first, rest)) = positionals.split_first() else {
+        return Ok(Command::Prompt);
+    };
+    let Some(subcommand) = SUBCOMMANDS
+        .iter()
+        .find(|subcommand| subcommand.name == first)
+    else {
+        script_args.splice(0..0, rest.iter().cloned());
+        return Ok(Command::Run(first.clone()));
+    };
+    match (subcommand.operands, rest) {
+        (Operands::None, []) => Ok((subcommand.command)(String::new())),
+        (Operands::Script | Operands::Path, [path]) => Ok((subcommand.command)(path.clone())),
+        (Operands::ScriptAndArgs, [script, args @ ..]) => {
+            script_args.splice(0..0, args.iter().cloned());
+            Ok((subcommand.command)(script.clone()))
+        }
+        _ => Err(UsageError(format!("Expected `{}`.", subcommand.usage()))),
+    }
+}
+
+/// The letter of a single-letter flag such as `-e`. A lone `-` is not a flag.
+fn short_flag(arg: &str) -> Option<char> {
+    let mut chars = arg.strip_prefix('-')?.chars();
+    match (chars.next(), chars.next()) {
+        (Some(short), None) => Some(short),
+        _ => None,
+    }
+}
+
+/// Parses the arguments following the program name.
+pub fn parse_args<I>(args: I) -> Result<Options, UsageError>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut options = Options::default();
+    let mut positionals = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let arg = arg.as_ref();
+        if arg == "--" {
+            options
+                .script_args
+                .extend(args.by_ref().map(|arg| arg.as_ref().to_string()));
+            break;
+        }
+        let (spec, inline_value) = if let Some(flag) = arg.strip_prefix("--") {
+            let (name, inline_value) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (flag, None),
+            };
+            let spec = FLAGS
+                .iter()
+                .find(|spec| spec.name == name)
+                .ok_or_else(|| UsageError(format!("Unknown flag '--{name}'.")))?;
+            (spec, inline_value)
+        } else if let Some(short) = short_flag(arg) {
+            let spec = FLAGS
+                .iter()
+                .find(|spec| spec.short == Some(short))
+                .ok_or_else(|| UsageError(format!("Unknown flag '-{short}'.")))?;
+            (spec, None)
+        } else {
+            positionals.push(arg.to_string());
+            continue;
+        };
+        let name = spec.name;
+        let value = match (spec.value, inline_value) {
+            (Some(_), Some(value)) => value,
+            (Some(_), None) => args
+                .next()
+                .map(|value| value.as_ref().to_string())
+                .ok_or_else(|| UsageError(format!("Flag '--{name}' expects a value.")))?,
+            (None, Some(_)) => {
+                return Err(UsageError(format!(
+                    "Flag '--{name}' does not take a value."
+                )))
+            }
+            (None, None) => String::new(),
+        };
+        (spec.apply)(&mut options, &value)?;
+    }
+
+    options.command = match (options.eval.take(), positionals.as_slice()) {
+        (Some(code), script_args) => {
+            options
+                .script_args
+                .splice(0..0, script_args.iter().cloned());
+            Command::Eval(code)
+        }
+        (None, positionals) => parse_command(positionals, &mut options.script_args)?,
+    };
+    if !options.script_args.is_empty()
+        && !matches!(
+            options.command,
+            Command::Run(_) | Command::Eval(_) | Command::Debug(_)
+        )
+    {
+        return Err(UsageError(String::from(
+            "Script arguments require a script to run.",
+        )));
+    }
+    if options.html && !matches!(options.command, Command::Highlight(_)) {
+        return Err(UsageError(String::from(
+            "--html can only be used with `highlight`.",
+        )));
+    }
+    if options.json && !matches!(options.command, Command::Ast(_)) {
+        return Err(UsageError(String::from(
+            "--json can only be used with `ast`.",
+        )));
+    }
+    if options.rename_locals && !matches!(options.command, Command::Minify(_)) {
+        return Err(UsageError(String::from(
+            "--rename-locals can only be used with `minify`.",
+        )));
+    }
+    if options.from_json && !matches!(options.command, Command::Run(_)) {
+        return Err(UsageError(String::from(
+            "--from-json requires a script to run.",
+        )));
+    }
+    if options.trace && !matches!(options.command, Command::Run(_) | Command::Eval(_)) {
+        return Err(UsageError(String::from("--trace requires a script or -e.")));
+    }
+    if options.coverage.is_some()
+        && !matches!(
+            options.command,
+            Command::Run(_) | Command::Eval(_) | Command::Test(_)
+        )
+    {
+        return Err(UsageError(String::from(
+            "--coverage requires a script, -e or `test`.",
+        )));
+    }
+    if options.coverage.is_some() && options.from_json {
+        return Err(UsageError(String::from(
+            "--coverage needs source code, so it can't be used with --from-json.",
+        )));
+    }
+    if options.trace_env && !matches!(options.command, Command::Run(_) | Command::Eval(_)) {
+        return Err(UsageError(String::from(
+            "--trace-env requires a script or -e.",
+        )));
+    }
+    if options.backend != Backend::TreeWalk
+        && !matches!(options.command, Command::Run(_) | Command::Eval(_))
+    {
+        return Err(UsageError(String::from(
+            "--backend requires a script or -e.",
+        )));
+    }
+    if options.backend == Backend::Vm && options.trace {
+        return Err(UsageError(String::from(
+            "--trace can only be used with the tree-walking backend.",
+        )));
+    }
+    if options.bench && !matches!(options.command, Command::Run(_) | Command::Eval(_)) {
+        return Err(UsageError(String::from("--bench requires a script or -e.")));
+    }
+    if options.profile && !matches!(options.command, Command::Run(_) | Command::Eval(_)) {
+        return Err(UsageError(String::from(
+            "--profile requires a script or -e.",
+        )));
+    }
+    if options.max_call_depth != DEFAULT_MAX_CALL_DEPTH
+        && !matches!(options.command, Command::Run(_) | Command::Eval(_))
+    {
+        return Err(UsageError(String::from(
+            "--max-call-depth requires a script or -e.",
+        )));
+    }
+    if options.max_ops.is_some() && !matches!(options.command, Command::Run(_) | Command::Eval(_)) {
+        return Err(UsageError(String::from(
+            "--max-ops requires a script or -e.",
+        )));
+    }
+    if options.max_memory.is_some()
+        && !matches!(options.command, Command::Run(_) | Command::Eval(_))
+    {
+        return Err(UsageError(String::from(
+            "--max-memory requires a script or -e.",
+        )));
+    }
+    if options.watch && !matches!(options.command, Command::Run(_)) {
+        return Err(UsageError(String::from("--watch requires a script.")));
+    }
+    if options.init.is_some() && options.command != Command::Prompt {
+        return Err(UsageError(String::from(
+            "--init can only be used with the interactive prompt.",
+        )));
+    }
+    Ok(options)
+}
+
+/// Parses a number of bytes, optionally followed by a `K`, `M` or `G` suffix for kibibytes,
+/// mebibytes or gibibytes.
+fn parse_size(value: &str) -> Option<usize> {
+    let (digits, multiplier) = match value.char_indices().last()? {
+        (idx, 'k' | 'K') => (&value[..idx], 1 << 10),
+        (idx, 'm' | 'M') => (&value[..idx], 1 << 20),
+        (idx, 'g' | 'G') => (&value[..idx], 1 << 30),
+        _ => (value, 1),
+    };
+    digits.parse::<usize>().ok()?.checked_mul(multiplier)
+}
+
+/// Help text listing every subcommand in [`SUBCOMMANDS`] and every flag in [`FLAGS`].
+pub fn usage() -> String {
+    let mut usage = String::from(
+        "Usage: lox_treewalk [options] <command> [-- args...]\n       lox_treewalk [options] <script> [args...]\n       lox_treewalk -e <code> [args...]\n\nCommands:\n",
+    );
+    let commands: Vec<(String, &str)> = SUBCOMMANDS
+        .iter()
+        .map(|subcommand| (subcommand.usage(), subcommand.help))
+        .collect();
+    push_table(&mut usage, commands);
+    usage.push_str("\nOptions:\n");
+    let flags: Vec<(String, &str)> = FLAGS
+        .iter()
+        .map(|flag| {
+            let short = match flag.short {
+                Some(short) => format!("-{short}, "),
+                None => String::new(),
+            };
+            match flag.value {
+                Some(value) => (format!("{short}--{} <{value}>", flag.name), flag.help),
+                None => (format!("{short}--{}", flag.name), flag.help),
+            }
+        })
+        .collect();
+    push_table(&mut usage, flags);
+    usage
+}
+
+/// Appends `rows` as two aligned columns.
+fn push_table(usage: &mut String, rows: Vec<(String, &str)>) {
+    let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, help) in rows {
+        usage.push_str(&format!("  {name:width$}  {help}\n"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, UsageError> {
+        parse_args(args)
+    }
+
+    fn error(args: &[&str]) -> String {
+        parse(args).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn options_may_come_before_or_after_the_command() {
+        let before = parse(&["--stats", "--max-ops", "100", "script.lox"]).unwrap();
+        let after = parse(&["script.lox", "--stats", "--max-ops", "100"]).unwrap();
+        assert_eq!(before, after);
+        assert_eq!(before.command, Command::Run(String::from("script.lox")));
+        assert!(before.stats);
+        assert_eq!(before.max_ops, Some(100));
+
+        let before = parse(&["--json", "ast", "script.lox"]).unwrap();
+        let after = parse(&["ast", "script.lox", "--json"]).unwrap();
+        assert_eq!(before, after);
+        assert_eq!(before.command, Command::Ast(String::from("script.lox")));
+    }
+
+    #[test]
+    fn eval_takes_the_remaining_positionals_as_args() {
+        let options = parse(&["-e", "print args;", "a", "b"]).unwrap();
+        assert_eq!(options.command, Command::Eval(String::from("print args;")));
+        assert_eq!(options.script_args, ["a", "b"]);
+
+        let options = parse(&["--eval=print 1;", "--", "-x"]).unwrap();
+        assert_eq!(options.command, Command::Eval(String::from("print 1;")));
+        assert_eq!(options.script_args, ["-x"]);
+    }
+
+    #[test]
+    fn arguments_after_a_double_dash_are_passed_through() {
+        let options = parse(&["script.lox", "a", "--", "--stats", "-e", "--"]).unwrap();
+        assert_eq!(options.command, Command::Run(String::from("script.lox")));
+        assert_eq!(options.script_args, ["a", "--stats", "-e", "--"]);
+        assert!(!options.stats);
+
+        let options = parse(&["debug", "script.lox", "--", "-v"]).unwrap();
+        assert_eq!(options.command, Command::Debug(String::from("script.lox")));
+        assert_eq!(options.script_args, ["-v"]);
+
+        assert_eq!(
+            error(&["check", "script.lox", "--", "a"]),
+            "Script arguments require a script to run."
+        );
+    }
+
+    #[test]
+    fn allow_and_deny_may_be_repeated() {
+        let options = parse(&[
+            "--allow",
+            "W0001",
+            "--allow=W0003",
+            "--deny",
+            "W0004",
+            "--deny",
+            "W0005",
+            "script.lox",
+        ])
+        .unwrap();
+        assert_eq!(
+            options.allow,
+            [ErrorCode::FLOAT_EQUALITY, ErrorCode::UNUSED_LOCAL]
+        );
+        assert_eq!(
+            options.deny,
+            [ErrorCode::SHADOWED_VARIABLE, ErrorCode::CONSTANT_CONDITION]
+        );
+
+        assert_eq!(
+            error(&["--allow", "E0304", "script.lox"]),
+            "'E0304' is an error code; only warnings can be allowed."
+        );
+        assert_eq!(
+            error(&["--deny", "W9999", "script.lox"]),
+            "Unknown warning code 'W9999'."
+        );
+    }
+
+    #[test]
+    fn unknown_flags_are_rejected() {
+        assert_eq!(error(&["--nope", "script.lox"]), "Unknown flag '--nope'.");
+        assert_eq!(error(&["script.lox", "-z"]), "Unknown flag '-z'.");
+        assert_eq!(
+            error(&["--stats=yes", "script.lox"]),
+            "Flag '--stats' does not take a value."
+        );
+    }
+
+    #[test]
+    fn flags_taking_a_value_require_one() {
+        assert_eq!(error(&["-e"]), "Flag '--eval' expects a value.");
+        assert_eq!(
+            error(&["script.lox", "--max-ops"]),
+            "Flag '--max-ops' expects a value."
+        );
+        assert_eq!(error(&["--allow"]), "Flag '--allow' expects a value.");
+    }
+
+    #[test]
+    fn max_call_depth_is_bounded_by_the_stack() {
+        let max = MAX_CALL_DEPTH.to_string();
+        let options = parse(&["--max-call-depth", &max, "script.lox"]).unwrap();
+        assert_eq!(options.max_call_depth, MAX_CALL_DEPTH);
+        let too_deep = (MAX_CALL_DEPTH + 1).to_string();
+        assert_eq!(
+            error(&["--max-call-depth", &too_deep, "script.lox"]),
+            format!("Invalid value '{too_deep}' for --max-call-depth; expected at most {max}.")
+        );
+        let flag = FLAGS
+            .iter()
+            .find(|flag| flag.name == "max-call-depth")
+            .unwrap();
+        assert!(flag.help.contains(&max));
+    }
+}
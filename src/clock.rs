@@ -0,0 +1,67 @@
+use std::{
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Where the time natives get the time from: `clock()`, `clockMillis()`, `now()` and
+/// `sleep()`. The standard library's clocks aren't available on every target, such as
+/// `wasm32-unknown-unknown`, so hosts there supply their own with [`Interpreter::set_clock`].
+///
+/// [`Interpreter::set_clock`]: crate::interpreter::Interpreter::set_clock
+pub trait Clock {
+    /// Time since the Unix epoch.
+    fn since_epoch(&self) -> Duration;
+
+    /// Time since a fixed point, from a clock that never goes backwards. Only differences
+    /// between two readings are meaningful.
+    fn monotonic(&self) -> Duration;
+
+    /// Blocks for `duration`.
+    fn sleep(&self, duration: Duration);
+}
+
+/// The standard library's system and monotonic clocks.
+#[derive(Debug, Clone)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    /// A clock whose monotonic readings count from now.
+    pub fn new() -> Self {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn since_epoch(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("SystemTime should be after UNIX EPOCH.")
+    }
+
+    fn monotonic(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// The clock an interpreter starts with: the browser's in WebAssembly builds with the `wasm`
+/// feature, the standard library's everywhere else.
+pub(crate) fn default_clock() -> Box<dyn Clock> {
+    #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+    return Box::new(crate::wasm::BrowserClock);
+    #[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+    Box::new(SystemClock::new())
+}
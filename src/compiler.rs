@@ -0,0 +1,923 @@
+use crate::{
+    expr::{
+        Assign, Binary, Call, Closure, Expr, ExprVisitor, Get, Grouping, Index, List,
+        ListComprehension, Literal, Logical, Set, SetIndex, Super, Ternary, This, Unary, Variable,
+    },
+    interpreter::Interpreter,
+    lox_object::{LoxLiteral, LoxObject},
+    span::Span,
+    stmt::{
+        Block, Break, Class, Destructure, Expression, For, ForIn, Function, If, Print, Return,
+        Stmt, StmtVisitor, Var, While,
+    },
+    token::Token,
+    token_type::TokenType,
+};
+use std::{fmt, rc::Rc};
+
+/// Bytecode for a script or function body, run by [`Interpreter::run`].
+pub struct Chunk {
+    pub code: Vec<Op>,
+    /// Whether a function's arguments are passed as its first stack slots rather than defined
+    /// in an environment, because nothing in its body can capture them.
+    pub params_on_stack: bool,
+}
+
+impl fmt::Debug for Chunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, op) in self.code.iter().enumerate() {
+            writeln!(f, "{idx:04} {op:?}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A single instruction. Operands are popped from the value stack and results pushed onto it.
+/// Jump targets are indexes into the chunk's code. Spans are those of the expression or
+/// statement the tree-walker would blame for the same error.
+#[derive(Debug)]
+pub enum Op {
+    Constant(LoxObject),
+    Pop,
+    /// Pushes the local kept in stack slot `slot`.
+    GetSlot(usize),
+    /// Writes the value on top of the stack to stack slot `slot`, leaving it there.
+    SetSlot {
+        slot: usize,
+        /// As for [`Op::SetLocal`].
+        name: Option<Token>,
+    },
+    /// Drops the stack slots from `len` up, ending the scope of the locals kept there.
+    Truncate(usize),
+    /// Reads a local from `slot` of the environment `depth` levels up.
+    GetLocal {
+        depth: usize,
+        slot: usize,
+    },
+    /// Writes the value on top of the stack to a local, leaving it there.
+    SetLocal {
+        depth: usize,
+        slot: usize,
+        /// The variable assigned, for the interpreter's hooks, or `None` for variables the
+        /// compiler writes itself.
+        name: Option<Token>,
+    },
+    /// Reads a global from `slot`, or by name if the resolver didn't number it.
+    GetGlobal {
+        name: Token,
+        slot: Option<usize>,
+    },
+    /// Writes the value on top of the stack to a global, leaving it there.
+    SetGlobal {
+        name: Token,
+        slot: Option<usize>,
+    },
+    /// Defines a variable in the current environment with the value popped.
+    Define(Rc<str>),
+    Binary {
+        operator: TokenType,
+        span: Span,
+        right: Span,
+    },
+    Unary {
+        operator: TokenType,
+        span: Span,
+    },
+    Jump(usize),
+    /// Jumps if the value on top of the stack is falsey, leaving it there.
+    JumpIfFalse(usize),
+    /// Jumps if the value on top of the stack is truthy, leaving it there.
+    JumpIfTrue(usize),
+    /// Starts a statement, counting it and checking for an interrupt.
+    Statement(Span),
+    Return,
+    Print {
+        echo: bool,
+        span: Span,
+    },
+    /// Calls the value below `argc` arguments.
+    Call {
+        argc: usize,
+        span: Span,
+        callee: Span,
+    },
+    Function(Rc<FunctionProto>),
+    /// Applies the decorator below the function on top of the stack.
+    Decorate(Span),
+    /// Builds a class, popping its superclass first if it has one.
+    Class(Rc<ClassProto>),
+    PushScope,
+    PopScope,
+    GetProperty {
+        name: Token,
+        object: Span,
+    },
+    /// Checks the value on top of the stack is an instance, before its field's value is
+    /// evaluated.
+    ExpectInstance(Span),
+    /// Pops a value and the instance below it, sets the field and pushes the value back.
+    SetProperty(Token),
+    Super {
+        depth: usize,
+        slot: usize,
+        method: Token,
+        span: Span,
+    },
+    /// Collects the top `n` values into a list.
+    List(usize),
+    /// Pops a value and pushes it onto the list below it.
+    Append,
+    Index {
+        object: Span,
+        index: Span,
+    },
+    /// Checks the value below the index on top of the stack is a list, before the element's
+    /// value is evaluated.
+    ExpectList(Span),
+    SetIndex {
+        index: Span,
+    },
+    Destructure {
+        names: Vec<Rc<str>>,
+        span: Span,
+    },
+    /// Like [`Op::Destructure`], pushing the `count` elements to become stack slots instead.
+    Unpack {
+        count: usize,
+        span: Span,
+    },
+    /// Pops an iterable and starts iterating over it.
+    IterStart(Span),
+    /// Pushes the innermost iterator's next value, or jumps to `exit` once it's done.
+    IterNext {
+        exit: usize,
+        span: Span,
+    },
+    IterEnd,
+}
+
+/// A function's compiled body, turned into a closure over the current environment each time
+/// its declaration runs.
+#[derive(Debug)]
+pub struct FunctionProto {
+    pub declaration: Rc<Closure>,
+    pub chunk: Rc<Chunk>,
+    pub name: Option<Rc<str>>,
+}
+
+#[derive(Debug)]
+pub struct ClassProto {
+    pub name: Rc<str>,
+    pub doc: Option<Rc<str>>,
+    /// Span of the superclass expression, whose value is on the stack, if there is one.
+    pub superclass: Option<Span>,
+    pub methods: Vec<(Rc<str>, Rc<Closure>, Rc<Chunk>)>,
+}
+
+/// A loop being compiled, for `break` to leave.
+struct Loop {
+    scope_depth: usize,
+    /// Stack slots in use when the loop started.
+    locals: usize,
+    /// Jumps to patch with the loop's exit.
+    breaks: Vec<usize>,
+}
+
+/// Where the variables of a scope live at runtime.
+#[derive(Debug, Clone, Copy)]
+enum Storage {
+    /// An environment of their own, which closures created in the scope can capture.
+    Environment,
+    /// The VM stack, from slot `base` up. Used for scopes that create no closures, so that
+    /// their locals don't need an environment allocated and reached through the chain.
+    Stack { base: usize },
+}
+
+/// Where a resolved local is found at runtime.
+enum Place {
+    Stack(usize),
+    Environment { depth: usize, slot: usize },
+}
+
+/// Lowers resolved statements to a [`Chunk`]. Locals are addressed by the depth and slot the
+/// resolver recorded on each use, and literals are taken from the constant pool of the
+/// interpreter the resolver filled in.
+pub struct Compiler<'a> {
+    interpreter: &'a Interpreter,
+    code: Vec<Op>,
+    /// Scopes entered since the start of the chunk, including a function's parameters.
+    scopes: Vec<Storage>,
+    /// Stack slots holding locals. Between statements, these are all the stack holds.
+    locals: usize,
+    /// Whether locals may be kept on the stack. Hooks and `--trace-env` look for them in
+    /// environments.
+    stack_locals: bool,
+    loops: Vec<Loop>,
+}
+
+impl<'a> Compiler<'a> {
+    pub fn new(interpreter: &'a Interpreter) -> Self {
+        Compiler {
+            interpreter,
+            code: Vec::new(),
+            scopes: Vec::new(),
+            locals: 0,
+            stack_locals: !interpreter.watches_variables(),
+            loops: Vec::new(),
+        }
+    }
+
+    pub fn compile(mut self, statements: &[Stmt]) -> Chunk {
+        for statement in statements {
+            self.statement(statement);
+        }
+        Chunk {
+            code: self.code,
+            params_on_stack: false,
+        }
+    }
+
+    fn compile_function(&self, declaration: &Rc<Closure>) -> Rc<Chunk> {
+        let mut compiler = Compiler::new(self.interpreter);
+        let params_on_stack = compiler.stack_locals && !creates_closure(&declaration.body);
+        if params_on_stack {
+            compiler.scopes.push(Storage::Stack { base: 0 });
+            compiler.locals = declaration.params.len();
+        } else {
+            compiler.scopes.push(Storage::Environment);
+        }
+        for statement in declaration.body.iter() {
+            compiler.statement(statement);
+        }
+        Rc::new(Chunk {
+            code: compiler.code,
+            params_on_stack,
+        })
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        self.emit(Op::Statement(stmt.span()));
+        stmt.accept(self);
+    }
+
+    fn expression(&mut self, expr: &Expr) {
+        expr.accept(self);
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.code.push(op);
+        self.code.len() - 1
+    }
+
+    /// Points the jump at `idx` to the next instruction.
+    fn patch(&mut self, idx: usize) {
+        let target = self.code.len();
+        match self.code[idx] {
+            Op::Jump(ref mut exit)
+            | Op::JumpIfFalse(ref mut exit)
+            | Op::JumpIfTrue(ref mut exit)
+            | Op::IterNext { ref mut exit, .. } => *exit = target,
+            _ => unreachable!("Only jumps are patched."),
+        }
+    }
+
+    /// Enters a scope whose locals can only be captured if `captured`.
+    fn push_scope(&mut self, captured: bool) {
+        if captured || !self.stack_locals {
+            self.emit(Op::PushScope);
+            self.scopes.push(Storage::Environment);
+        } else {
+            self.scopes.push(Storage::Stack { base: self.locals });
+        }
+    }
+
+    fn pop_scope(&mut self) {
+        match self.scopes.pop().expect("Expect a scope to end.") {
+            Storage::Environment => {
+                self.emit(Op::PopScope);
+            }
+            Storage::Stack { base } => {
+                if self.locals > base {
+                    self.emit(Op::Truncate(base));
+                }
+                self.locals = base;
+            }
+        }
+    }
+
+    /// Defines `name` in the current scope with the value on top of the stack.
+    fn define(&mut self, name: &Token) {
+        match self.scopes.last() {
+            Some(Storage::Stack { .. }) => self.locals += 1,
+            _ => {
+                self.emit(Op::Define(Rc::clone(&name.lexeme)));
+            }
+        }
+    }
+
+    /// Where the local the resolver found `depth` scopes out, in `slot`, is kept.
+    fn place(&self, depth: usize, slot: usize) -> Place {
+        let environments = |scopes: &[Storage]| {
+            scopes
+                .iter()
+                .filter(|storage| matches!(storage, Storage::Environment))
+                .count()
+        };
+        match self.scopes.len().checked_sub(depth + 1) {
+            Some(idx) => match self.scopes[idx] {
+                Storage::Stack { base } => Place::Stack(base + slot),
+                Storage::Environment => Place::Environment {
+                    depth: environments(&self.scopes[idx + 1..]),
+                    slot,
+                },
+            },
+            // Scopes enclosing the chunk are the environments a function closes over.
+            None => Place::Environment {
+                depth: environments(&self.scopes) + depth - self.scopes.len(),
+                slot,
+            },
+        }
+    }
+
+    fn end_loop(&mut self) {
+        let exit = self.loops.pop().expect("Expect a loop to end.");
+        for idx in exit.breaks {
+            self.patch(idx);
+        }
+    }
+
+    fn variable(&mut self, name: &Token, local: Option<(usize, usize)>, global: Option<usize>) {
+        let op = match local.map(|(depth, slot)| self.place(depth, slot)) {
+            Some(Place::Stack(slot)) => Op::GetSlot(slot),
+            Some(Place::Environment { depth, slot }) => Op::GetLocal { depth, slot },
+            None => Op::GetGlobal {
+                name: name.clone(),
+                slot: global,
+            },
+        };
+        self.emit(op);
+    }
+}
+
+impl ExprVisitor<()> for Compiler<'_> {
+    fn visit_binary_expr(&mut self, expr: &Binary) {
+        self.expression(&expr.left);
+        self.expression(&expr.right);
+        self.emit(Op::Binary {
+            operator: expr.operator.token_type,
+            span: expr.span,
+            right: expr.right.span(),
+        });
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Grouping) {
+        self.expression(&expr.expression);
+    }
+
+    fn visit_literal_expr(&mut self, expr: &Literal) {
+        let value = match expr.constant.get() {
+            Some(idx) => self.interpreter.constant(idx),
+            None => LoxObject::Literal(expr.value.clone()),
+        };
+        self.emit(Op::Constant(value));
+    }
+
+    fn visit_unary_expr(&mut self, expr: &Unary) {
+        self.expression(&expr.right);
+        self.emit(Op::Unary {
+            operator: expr.operator.token_type,
+            span: expr.span,
+        });
+    }
+
+    fn visit_ternary_expr(&mut self, expr: &Ternary) {
+        self.expression(&expr.condition);
+        let else_jump = self.emit(Op::JumpIfFalse(0));
+        self.emit(Op::Pop);
+        self.expression(&expr.left);
+        let end_jump = self.emit(Op::Jump(0));
+        self.patch(else_jump);
+        self.emit(Op::Pop);
+        self.expression(&expr.right);
+        self.patch(end_jump);
+    }
+
+    fn visit_variable_expr(&mut self, expr: &Variable) {
+        self.variable(&expr.name, expr.local.get(), expr.global.get());
+    }
+
+    fn visit_assign_expr(&mut self, expr: &Assign) {
+        self.expression(&expr.value);
+        let op = match expr
+            .local
+            .get()
+            .map(|(depth, slot)| self.place(depth, slot))
+        {
+            Some(Place::Stack(slot)) => Op::SetSlot {
+                slot,
+                name: Some(expr.name.clone()),
+            },
+            Some(Place::Environment { depth, slot }) => Op::SetLocal {
+                depth,
+                slot,
+                name: Some(expr.name.clone()),
+            },
+            None => Op::SetGlobal {
+                name: expr.name.clone(),
+                slot: expr.global.get(),
+            },
+        };
+        self.emit(op);
+    }
+
+    fn visit_logical_expr(&mut self, expr: &Logical) {
+        self.expression(&expr.left);
+        let end_jump = match expr.operator.token_type {
+            TokenType::Or => self.emit(Op::JumpIfTrue(0)),
+            _ => self.emit(Op::JumpIfFalse(0)),
+        };
+        self.emit(Op::Pop);
+        self.expression(&expr.right);
+        self.patch(end_jump);
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) {
+        self.expression(&expr.callee);
+        for argument in expr.arguments.iter() {
+            self.expression(argument);
+        }
+        self.emit(Op::Call {
+            argc: expr.arguments.len(),
+            span: expr.span,
+            callee: expr.callee.span(),
+        });
+    }
+
+    fn visit_closure_expr(&mut self, expr: &Rc<Closure>) {
+        let proto = FunctionProto {
+            declaration: Rc::clone(expr),
+            chunk: self.compile_function(expr),
+            name: None,
+        };
+        self.emit(Op::Function(Rc::new(proto)));
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) {
+        self.expression(&expr.object);
+        self.emit(Op::GetProperty {
+            name: expr.name.clone(),
+            object: expr.object.span(),
+        });
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) {
+        self.expression(&expr.object);
+        self.emit(Op::ExpectInstance(expr.object.span()));
+        self.expression(&expr.value);
+        self.emit(Op::SetProperty(expr.name.clone()));
+    }
+
+    fn visit_this_expr(&mut self, expr: &This) {
+        self.variable(&expr.keyword, expr.local.get(), None);
+    }
+
+    fn visit_super_expr(&mut self, expr: &Super) {
+        let (depth, slot) = expr.local.get().expect("Expected super local to resolve.");
+        let Place::Environment { depth, slot } = self.place(depth, slot) else {
+            unreachable!("Expect `super` to be in the environment a method closes over.");
+        };
+        self.emit(Op::Super {
+            depth,
+            slot,
+            method: expr.method.clone(),
+            span: expr.span,
+        });
+    }
+
+    fn visit_list_expr(&mut self, expr: &List) {
+        for element in expr.elements.iter() {
+            self.expression(element);
+        }
+        self.emit(Op::List(expr.elements.len()));
+    }
+
+    fn visit_list_comprehension_expr(&mut self, expr: &ListComprehension) {
+        let span = expr.iterable.span();
+        self.expression(&expr.iterable);
+        self.emit(Op::IterStart(span));
+
+        // A single environment holds the comprehension variable for every element. It's never
+        // kept on the stack, which holds the values of any enclosing expression.
+        self.push_scope(true);
+        self.emit(Op::Constant(LoxObject::Literal(LoxLiteral::Nil)));
+        self.emit(Op::Define(Rc::clone(&expr.name.lexeme)));
+        self.emit(Op::List(0));
+
+        let start = self.code.len();
+        let exit_jump = self.emit(Op::IterNext { exit: 0, span });
+        self.emit(Op::SetLocal {
+            depth: 0,
+            slot: 0,
+            name: None,
+        });
+        self.emit(Op::Pop);
+        if let Some(ref condition) = expr.condition {
+            self.expression(condition);
+            let skip_jump = self.emit(Op::JumpIfFalse(0));
+            self.emit(Op::Pop);
+            self.expression(&expr.element);
+            self.emit(Op::Append);
+            self.emit(Op::Jump(start));
+            self.patch(skip_jump);
+            self.emit(Op::Pop);
+        } else {
+            self.expression(&expr.element);
+            self.emit(Op::Append);
+        }
+        self.emit(Op::Jump(start));
+        self.patch(exit_jump);
+        self.emit(Op::IterEnd);
+        self.pop_scope();
+    }
+
+    fn visit_index_expr(&mut self, expr: &Index) {
+        self.expression(&expr.object);
+        self.expression(&expr.index);
+        self.emit(Op::Index {
+            object: expr.object.span(),
+            index: expr.index.span(),
+        });
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &SetIndex) {
+        self.expression(&expr.object);
+        self.expression(&expr.index);
+        self.emit(Op::ExpectList(expr.object.span()));
+        self.expression(&expr.value);
+        self.emit(Op::SetIndex {
+            index: expr.index.span(),
+        });
+    }
+}
+
+impl StmtVisitor<()> for Compiler<'_> {
+    fn visit_expression_stmt(&mut self, stmt: &Expression) {
+        self.expression(&stmt.expression);
+        self.emit(Op::Pop);
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Print) {
+        self.expression(&stmt.expression);
+        self.emit(Op::Print {
+            echo: stmt.echo,
+            span: stmt.span,
+        });
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Var) {
+        match stmt.initializer {
+            Some(ref expr) => self.expression(expr),
+            None => {
+                self.emit(Op::Constant(LoxObject::Literal(LoxLiteral::Nil)));
+            }
+        }
+        self.define(&stmt.name);
+    }
+
+    fn visit_destructure_stmt(&mut self, stmt: &Destructure) {
+        self.expression(&stmt.initializer);
+        let span = stmt.initializer.span();
+        if let Some(Storage::Stack { .. }) = self.scopes.last() {
+            let count = stmt.names.len();
+            self.emit(Op::Unpack { count, span });
+            self.locals += count;
+            return;
+        }
+        self.emit(Op::Destructure {
+            names: stmt
+                .names
+                .iter()
+                .map(|name| Rc::clone(&name.lexeme))
+                .collect(),
+            span,
+        });
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &Block) {
+        self.push_scope(creates_closure(&stmt.statements));
+        for statement in stmt.statements.iter() {
+            self.statement(statement);
+        }
+        self.pop_scope();
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &If) {
+        self.expression(&stmt.condition);
+        let else_jump = self.emit(Op::JumpIfFalse(0));
+        self.emit(Op::Pop);
+        self.statement(&stmt.then_branch);
+        let end_jump = self.emit(Op::Jump(0));
+        self.patch(else_jump);
+        self.emit(Op::Pop);
+        if let Some(ref else_branch) = stmt.else_branch {
+            self.statement(else_branch);
+        }
+        self.patch(end_jump);
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &While) {
+        let start = self.code.len();
+        self.expression(&stmt.condition);
+        let exit_jump = self.emit(Op::JumpIfFalse(0));
+        self.emit(Op::Pop);
+        self.loops.push(Loop {
+            scope_depth: self.scopes.len(),
+            locals: self.locals,
+            breaks: Vec::new(),
+        });
+        self.statement(&stmt.body);
+        self.emit(Op::Jump(start));
+        self.patch(exit_jump);
+        self.emit(Op::Pop);
+        self.end_loop();
+    }
+
+    fn visit_for_stmt(&mut self, stmt: &For) {
+        // The initializer's variable lives in one environment shared by every iteration.
+        if let Some(ref initializer) = stmt.initializer {
+            self.push_scope(ClosureFinder.visit_for_stmt(stmt));
+            self.statement(initializer);
+        }
+        let start = self.code.len();
+        let exit_jump = stmt.condition.as_ref().map(|condition| {
+            self.expression(condition);
+            let exit_jump = self.emit(Op::JumpIfFalse(0));
+            self.emit(Op::Pop);
+            exit_jump
+        });
+        self.loops.push(Loop {
+            scope_depth: self.scopes.len(),
+            locals: self.locals,
+            breaks: Vec::new(),
+        });
+        self.statement(&stmt.body);
+        if let Some(ref increment) = stmt.increment {
+            self.expression(increment);
+            self.emit(Op::Pop);
+        }
+        self.emit(Op::Jump(start));
+        if let Some(exit_jump) = exit_jump {
+            self.patch(exit_jump);
+            self.emit(Op::Pop);
+        }
+        self.end_loop();
+        if stmt.initializer.is_some() {
+            self.pop_scope();
+        }
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) {
+        let span = stmt.iterable.span();
+        self.expression(&stmt.iterable);
+        self.emit(Op::IterStart(span));
+
+        let start = self.code.len();
+        let exit_jump = self.emit(Op::IterNext { exit: 0, span });
+        self.loops.push(Loop {
+            scope_depth: self.scopes.len(),
+            locals: self.locals,
+            breaks: Vec::new(),
+        });
+        // Each iteration gets a fresh environment so closures capture that iteration's value.
+        self.push_scope(stmt.body.accept(&mut ClosureFinder));
+        self.define(&stmt.name);
+        self.statement(&stmt.body);
+        self.pop_scope();
+        self.emit(Op::Jump(start));
+        self.patch(exit_jump);
+        self.end_loop();
+        self.emit(Op::IterEnd);
+    }
+
+    fn visit_break_stmt(&mut self, _: &Break) {
+        let innermost = self
+            .loops
+            .last()
+            .expect("Expect break to be inside a loop due to parsing.");
+        let (loop_depth, locals) = (innermost.scope_depth, innermost.locals);
+        let environments = self.scopes[loop_depth..]
+            .iter()
+            .filter(|storage| matches!(storage, Storage::Environment))
+            .count();
+        for _ in 0..environments {
+            self.emit(Op::PopScope);
+        }
+        if self.locals > locals {
+            self.emit(Op::Truncate(locals));
+        }
+        let jump = self.emit(Op::Jump(0));
+        self.loops.last_mut().unwrap().breaks.push(jump);
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Function) {
+        for decorator in stmt.decorators.iter() {
+            self.expression(decorator);
+        }
+        let proto = FunctionProto {
+            declaration: Rc::clone(&stmt.closure),
+            chunk: self.compile_function(&stmt.closure),
+            name: Some(Rc::clone(&stmt.name.lexeme)),
+        };
+        self.emit(Op::Function(Rc::new(proto)));
+        // The decorator closest to the function is applied first.
+        for decorator in stmt.decorators.iter().rev() {
+            self.emit(Op::Decorate(decorator.span()));
+        }
+        self.define(&stmt.name);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Return) {
+        self.expression(&stmt.value);
+        self.emit(Op::Return);
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &Class) {
+        if let Some(ref superclass) = stmt.superclass {
+            self.expression(superclass);
+        }
+        let methods = stmt
+            .methods
+            .iter()
+            .filter_map(|method| match method {
+                Stmt::Function(function) => Some((
+                    Rc::clone(&function.name.lexeme),
+                    Rc::clone(&function.closure),
+                    self.compile_function(&function.closure),
+                )),
+                _ => None,
+            })
+            .collect();
+        let proto = ClassProto {
+            name: Rc::clone(&stmt.name.lexeme),
+            doc: stmt.doc.clone(),
+            superclass: stmt.superclass.as_ref().map(|superclass| superclass.span()),
+            methods,
+        };
+        self.emit(Op::Class(Rc::new(proto)));
+        self.define(&stmt.name);
+    }
+}
+
+/// Whether running `statements` can create a closure, which could capture the locals of the
+/// scopes around it.
+fn creates_closure(statements: &[Stmt]) -> bool {
+    statements
+        .iter()
+        .any(|statement| statement.accept(&mut ClosureFinder))
+}
+
+/// Finds functions, closures and classes, whose methods close over their surroundings.
+struct ClosureFinder;
+
+impl ClosureFinder {
+    fn any<'e>(&mut self, exprs: impl IntoIterator<Item = &'e Expr>) -> bool {
+        exprs.into_iter().any(|expr| expr.accept(self))
+    }
+}
+
+impl ExprVisitor<bool> for ClosureFinder {
+    fn visit_binary_expr(&mut self, expr: &Binary) -> bool {
+        self.any([&*expr.left, &*expr.right])
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Grouping) -> bool {
+        expr.expression.accept(self)
+    }
+
+    fn visit_literal_expr(&mut self, _: &Literal) -> bool {
+        false
+    }
+
+    fn visit_unary_expr(&mut self, expr: &Unary) -> bool {
+        expr.right.accept(self)
+    }
+
+    fn visit_ternary_expr(&mut self, expr: &Ternary) -> bool {
+        self.any([&*expr.condition, &*expr.left, &*expr.right])
+    }
+
+    fn visit_variable_expr(&mut self, _: &Variable) -> bool {
+        false
+    }
+
+    fn visit_assign_expr(&mut self, expr: &Assign) -> bool {
+        expr.value.accept(self)
+    }
+
+    fn visit_logical_expr(&mut self, expr: &Logical) -> bool {
+        self.any([&*expr.left, &*expr.right])
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) -> bool {
+        expr.callee.accept(self) || self.any(&expr.arguments)
+    }
+
+    fn visit_closure_expr(&mut self, _: &Rc<Closure>) -> bool {
+        true
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) -> bool {
+        expr.object.accept(self)
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) -> bool {
+        self.any([&*expr.object, &*expr.value])
+    }
+
+    fn visit_this_expr(&mut self, _: &This) -> bool {
+        false
+    }
+
+    fn visit_super_expr(&mut self, _: &Super) -> bool {
+        false
+    }
+
+    fn visit_list_expr(&mut self, expr: &List) -> bool {
+        self.any(&expr.elements)
+    }
+
+    fn visit_list_comprehension_expr(&mut self, expr: &ListComprehension) -> bool {
+        self.any([&*expr.element, &*expr.iterable]) || self.any(expr.condition.as_deref())
+    }
+
+    fn visit_index_expr(&mut self, expr: &Index) -> bool {
+        self.any([&*expr.object, &*expr.index])
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &SetIndex) -> bool {
+        self.any([&*expr.object, &*expr.index, &*expr.value])
+    }
+}
+
+impl StmtVisitor<bool> for ClosureFinder {
+    fn visit_expression_stmt(&mut self, stmt: &Expression) -> bool {
+        stmt.expression.accept(self)
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Print) -> bool {
+        stmt.expression.accept(self)
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Var) -> bool {
+        self.any(&stmt.initializer)
+    }
+
+    fn visit_destructure_stmt(&mut self, stmt: &Destructure) -> bool {
+        stmt.initializer.accept(self)
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &Block) -> bool {
+        creates_closure(&stmt.statements)
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &If) -> bool {
+        stmt.condition.accept(self)
+            || stmt.then_branch.accept(self)
+            || stmt
+                .else_branch
+                .as_ref()
+                .is_some_and(|else_branch| else_branch.accept(self))
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &While) -> bool {
+        stmt.condition.accept(self) || stmt.body.accept(self)
+    }
+
+    fn visit_for_stmt(&mut self, stmt: &For) -> bool {
+        stmt.initializer
+            .as_ref()
+            .is_some_and(|initializer| initializer.accept(self))
+            || self.any(stmt.condition.iter().chain(&stmt.increment))
+            || stmt.body.accept(self)
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) -> bool {
+        stmt.iterable.accept(self) || stmt.body.accept(self)
+    }
+
+    fn visit_break_stmt(&mut self, _: &Break) -> bool {
+        false
+    }
+
+    fn visit_function_stmt(&mut self, _: &Function) -> bool {
+        true
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Return) -> bool {
+        stmt.value.accept(self)
+    }
+
+    fn visit_class_stmt(&mut self, _: &Class) -> bool {
+        true
+    }
+}
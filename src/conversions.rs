@@ -0,0 +1,255 @@
+use crate::{
+    error_code::ErrorCode,
+    lox_callable::LoxCallable,
+    lox_class::LoxClass,
+    lox_exception::{LoxException, RuntimeError},
+    lox_foreign::LoxForeign,
+    lox_instance::LoxInstance,
+    lox_object::{LoxLiteral, LoxObject},
+    lox_string::LoxString,
+    span::Span,
+};
+use std::{any::Any, cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+/// Why a Lox value couldn't be converted to a Rust one.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConversionError {
+    /// The value was of another type than the one asked for.
+    WrongType {
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// The instance had no field with this name.
+    MissingField(String),
+}
+
+impl ConversionError {
+    /// The runtime error a native function raises when its arguments can't be converted.
+    pub fn into_exception(self, span: Span) -> LoxException {
+        LoxException::RuntimeError(RuntimeError::new(
+            ErrorCode::NATIVE_FUNCTION,
+            span,
+            self.to_string(),
+        ))
+    }
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::WrongType { expected, found } => {
+                write!(f, "Expected {expected} but found {found}.")
+            }
+            ConversionError::MissingField(name) => write!(f, "Expected a field named '{name}'."),
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// Describes the type of `object` for error messages.
+fn type_description(object: &LoxObject) -> &'static str {
+    match object {
+        LoxObject::Literal(LoxLiteral::Number(_)) => "a number",
+        LoxObject::Literal(LoxLiteral::String(_)) => "a string",
+        LoxObject::Literal(LoxLiteral::Boolean(_)) => "a boolean",
+        LoxObject::Literal(LoxLiteral::Nil) => "nil",
+        LoxObject::Callable(LoxCallable::Class(_)) => "a class",
+        LoxObject::Callable(_) => "a function",
+        LoxObject::List(_) => "a list",
+        LoxObject::Instance(_) => "an instance",
+        LoxObject::Foreign(_) => "a foreign value",
+    }
+}
+
+fn wrong_type(expected: &'static str, found: &LoxObject) -> ConversionError {
+    ConversionError::WrongType {
+        expected,
+        found: type_description(found),
+    }
+}
+
+impl LoxObject {
+    /// The value if this is a number.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            LoxObject::Literal(LoxLiteral::Number(val)) => Some(*val),
+            _ => None,
+        }
+    }
+
+    /// The value if this is a boolean. Unlike a condition, other values aren't treated as
+    /// true or false.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            LoxObject::Literal(LoxLiteral::Boolean(val)) => Some(*val),
+            _ => None,
+        }
+    }
+
+    /// The contents if this is a string.
+    pub fn as_str(&self) -> Option<Rc<str>> {
+        match self {
+            LoxObject::Literal(LoxLiteral::String(val)) => Some(val.flat()),
+            _ => None,
+        }
+    }
+
+    /// The Rust value a foreign value wraps, if this is one and it wraps a `T`.
+    pub fn downcast_foreign<T: Any>(&self) -> Option<&T> {
+        match self {
+            LoxObject::Foreign(foreign) => foreign.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn is_nil(&self) -> bool {
+        matches!(self, LoxObject::Literal(LoxLiteral::Nil))
+    }
+
+    /// A new instance of a class named `T::CLASS_NAME` with no methods, holding `value`'s fields.
+    pub fn from_struct<T: IntoInstance>(value: T) -> LoxObject {
+        let class = LoxClass::new(Rc::from(T::CLASS_NAME), None, HashMap::new());
+        let mut instance = LoxInstance::new(Rc::new(class));
+        for (name, field) in value.into_fields() {
+            instance.set_field(name, field);
+        }
+        LoxObject::Instance(Rc::new(RefCell::new(instance)))
+    }
+
+    /// Reads this instance's fields into a `T`.
+    pub fn to_struct<T: FromInstance>(&self) -> Result<T, ConversionError> {
+        match self {
+            LoxObject::Instance(instance) => T::from_instance(&instance.borrow()),
+            _ => Err(wrong_type("an instance", self)),
+        }
+    }
+}
+
+/// Rust types that become Lox instances with one field per struct field. Implementations only
+/// list the fields, so they can be written by hand or generated by a derive macro.
+pub trait IntoInstance {
+    /// Name of the class the instances belong to.
+    const CLASS_NAME: &'static str;
+
+    fn into_fields(self) -> Vec<(&'static str, LoxObject)>;
+}
+
+/// Rust types that can be read back from a Lox instance's fields, usually with
+/// [`LoxInstance::field`] once per struct field.
+pub trait FromInstance: Sized {
+    fn from_instance(instance: &LoxInstance) -> Result<Self, ConversionError>;
+}
+
+impl From<f64> for LoxObject {
+    fn from(value: f64) -> Self {
+        LoxObject::Literal(LoxLiteral::Number(value))
+    }
+}
+
+impl From<bool> for LoxObject {
+    fn from(value: bool) -> Self {
+        LoxObject::Literal(LoxLiteral::Boolean(value))
+    }
+}
+
+impl From<&str> for LoxObject {
+    fn from(value: &str) -> Self {
+        LoxObject::Literal(LoxLiteral::String(Rc::new(LoxString::from(value))))
+    }
+}
+
+impl From<String> for LoxObject {
+    fn from(value: String) -> Self {
+        LoxObject::Literal(LoxLiteral::String(Rc::new(LoxString::from(value))))
+    }
+}
+
+impl From<LoxForeign> for LoxObject {
+    fn from(value: LoxForeign) -> Self {
+        LoxObject::Foreign(Rc::new(value))
+    }
+}
+
+impl From<()> for LoxObject {
+    fn from(_: ()) -> Self {
+        LoxObject::Literal(LoxLiteral::Nil)
+    }
+}
+
+/// `None` becomes `nil`.
+impl<T: Into<LoxObject>> From<Option<T>> for LoxObject {
+    fn from(value: Option<T>) -> Self {
+        match value {
+            Some(value) => value.into(),
+            None => LoxObject::Literal(LoxLiteral::Nil),
+        }
+    }
+}
+
+/// Becomes a new list.
+impl<T: Into<LoxObject>> From<Vec<T>> for LoxObject {
+    fn from(value: Vec<T>) -> Self {
+        let elements = value.into_iter().map(Into::into).collect();
+        LoxObject::List(Rc::new(RefCell::new(elements)))
+    }
+}
+
+impl TryFrom<LoxObject> for f64 {
+    type Error = ConversionError;
+
+    fn try_from(value: LoxObject) -> Result<Self, Self::Error> {
+        value.as_f64().ok_or_else(|| wrong_type("a number", &value))
+    }
+}
+
+impl TryFrom<LoxObject> for bool {
+    type Error = ConversionError;
+
+    fn try_from(value: LoxObject) -> Result<Self, Self::Error> {
+        value
+            .as_bool()
+            .ok_or_else(|| wrong_type("a boolean", &value))
+    }
+}
+
+impl TryFrom<LoxObject> for String {
+    type Error = ConversionError;
+
+    fn try_from(value: LoxObject) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            Some(val) => Ok(String::from(&*val)),
+            None => Err(wrong_type("a string", &value)),
+        }
+    }
+}
+
+/// `nil` becomes `None`; anything else must convert to a `T`.
+impl<T> TryFrom<LoxObject> for Option<T>
+where
+    T: TryFrom<LoxObject, Error = ConversionError>,
+{
+    type Error = ConversionError;
+
+    fn try_from(value: LoxObject) -> Result<Self, Self::Error> {
+        match value {
+            LoxObject::Literal(LoxLiteral::Nil) => Ok(None),
+            value => T::try_from(value).map(Some),
+        }
+    }
+}
+
+/// Copies the list's elements, converting each to a `T`.
+impl<T> TryFrom<LoxObject> for Vec<T>
+where
+    T: TryFrom<LoxObject, Error = ConversionError>,
+{
+    type Error = ConversionError;
+
+    fn try_from(value: LoxObject) -> Result<Self, Self::Error> {
+        match value {
+            LoxObject::List(list) => list.borrow().iter().cloned().map(T::try_from).collect(),
+            value => Err(wrong_type("a list", &value)),
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use crate::{
+    expr::{
+        Assign, Binary, Call, Closure, ExprVisitor, Get, Grouping, Index, List, ListComprehension,
+        Literal, Logical, Set, SetIndex, Super, Ternary, This, Unary, Variable,
+    },
+    parser::Parser,
+    scanner::Scanner,
+    stmt::{
+        Block, Break, Class, Destructure, Expression, For, ForIn, Function, If, Print, Return,
+        Stmt, StmtVisitor, Var, While,
+    },
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    rc::Rc,
+};
+
+/// How `--coverage` reports which lines ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverageFormat {
+    /// An lcov tracefile, for coverage tools such as `genhtml`.
+    Lcov,
+    /// The source, each line prefixed with how many times it ran.
+    Annotated,
+}
+
+/// How many times each line of a script started running a statement, recorded while
+/// interpreting it with [`Interpreter::set_coverage`] on.
+///
+/// [`Interpreter::set_coverage`]: crate::interpreter::Interpreter::set_coverage
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineCoverage {
+    hits: BTreeMap<usize, usize>,
+}
+
+impl LineCoverage {
+    pub(crate) fn record(&mut self, line: usize) {
+        *self.hits.entry(line).or_insert(0) += 1;
+    }
+
+    /// Times a statement starting on `line` ran.
+    pub fn hits(&self, line: usize) -> usize {
+        self.hits.get(&line).copied().unwrap_or(0)
+    }
+
+    /// Each line of `source` that starts a statement, with the times it ran, in order. Lines
+    /// that ran but aren't in `source`, such as those of code run before it, are left out.
+    pub fn lines(&self, source: &str) -> Vec<(usize, usize)> {
+        executable_lines(source)
+            .into_iter()
+            .map(|line| (line, self.hits(line)))
+            .collect()
+    }
+
+    /// The report for `source` in `format`, naming it `path` where the format needs a name.
+    pub fn report(&self, source: &str, path: &str, format: CoverageFormat) -> String {
+        match format {
+            CoverageFormat::Lcov => self.lcov(source, path),
+            CoverageFormat::Annotated => self.annotate(source),
+        }
+    }
+
+    /// An lcov tracefile record for `source`, found at `path`.
+    pub fn lcov(&self, source: &str, path: &str) -> String {
+        let lines = self.lines(source);
+        let mut out = format!("TN:\nSF:{path}\n");
+        for &(line, hits) in lines.iter() {
+            out.push_str(&format!("DA:{line},{hits}\n"));
+        }
+        let hit = lines.iter().filter(|&&(_, hits)| hits > 0).count();
+        out.push_str(&format!("LF:{}\nLH:{hit}\nend_of_record\n", lines.len()));
+        out
+    }
+
+    /// `source` with the times each line ran in a column on the left, `#####` marking lines
+    /// that never ran and `-` those with no statements, followed by a summary.
+    pub fn annotate(&self, source: &str) -> String {
+        let lines: BTreeMap<usize, usize> = self.lines(source).into_iter().collect();
+        let mut out = String::new();
+        for (idx, text) in source.lines().enumerate() {
+            let count = match lines.get(&(idx + 1)) {
+                Some(0) => String::from("#####"),
+                Some(hits) => hits.to_string(),
+                None => String::from("-"),
+            };
+            out.push_str(&format!("{count:>9} | {text}\n"));
+        }
+        let hit = lines.values().filter(|&&hits| hits > 0).count();
+        let percent = match lines.len() {
+            0 => 100.0,
+            len => hit as f64 * 100.0 / len as f64,
+        };
+        out.push_str(&format!(
+            "Covered {hit} of {} lines ({percent:.1}%).\n",
+            lines.len()
+        ));
+        out
+    }
+}
+
+/// The lines of `source` where a statement starts, including those in function bodies. Empty
+/// if `source` doesn't parse.
+fn executable_lines(source: &str) -> BTreeSet<usize> {
+    let mut scanner = Scanner::new(source);
+    scanner.scan_tokens();
+    let mut parser = Parser::new(scanner.tokens);
+    let mut collector = LineCollector {
+        lines: BTreeSet::new(),
+    };
+    if let Ok(statements) = parser.parse() {
+        collector.visit_statements(&statements);
+    }
+    collector.lines
+}
+
+struct LineCollector {
+    lines: BTreeSet<usize>,
+}
+
+impl LineCollector {
+    fn visit_statements(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            statement.accept(self);
+        }
+    }
+}
+
+impl ExprVisitor<()> for LineCollector {
+    fn visit_binary_expr(&mut self, expr: &Binary) {
+        expr.left.accept(self);
+        expr.right.accept(self);
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Grouping) {
+        expr.expression.accept(self);
+    }
+
+    fn visit_literal_expr(&mut self, _expr: &Literal) {}
+
+    fn visit_unary_expr(&mut self, expr: &Unary) {
+        expr.right.accept(self);
+    }
+
+    fn visit_ternary_expr(&mut self, expr: &Ternary) {
+        expr.condition.accept(self);
+        expr.left.accept(self);
+        expr.right.accept(self);
+    }
+
+    fn visit_variable_expr(&mut self, _expr: &Variable) {}
+
+    fn visit_assign_expr(&mut self, expr: &Assign) {
+        expr.value.accept(self);
+    }
+
+    fn visit_logical_expr(&mut self, expr: &Logical) {
+        expr.left.accept(self);
+        expr.right.accept(self);
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) {
+        expr.callee.accept(self);
+        for argument in expr.arguments.iter() {
+            argument.accept(self);
+        }
+    }
+
+    fn visit_closure_expr(&mut self, expr: &Rc<Closure>) {
+        self.visit_statements(&expr.body);
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) {
+        expr.object.accept(self);
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) {
+        expr.object.accept(self);
+        expr.value.accept(self);
+    }
+
+    fn visit_this_expr(&mut self, _expr: &This) {}
+
+    fn visit_super_expr(&mut self, _expr: &Super) {}
+
+    fn visit_list_expr(&mut self, expr: &List) {
+        for element in expr.elements.iter() {
+            element.accept(self);
+        }
+    }
+
+    fn visit_list_comprehension_expr(&mut self, expr: &ListComprehension) {
+        expr.element.accept(self);
+        expr.iterable.accept(self);
+        if let Some(ref condition) = expr.condition {
+            condition.accept(self);
+        }
+    }
+
+    fn visit_index_expr(&mut self, expr: &Index) {
+        expr.object.accept(self);
+        expr.index.accept(self);
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &SetIndex) {
+        expr.object.accept(self);
+        expr.index.accept(self);
+        expr.value.accept(self);
+    }
+}
+
+impl StmtVisitor<()> for LineCollector {
+    fn visit_expression_stmt(&mut self, stmt: &Expression) {
+        self.lines.insert(stmt.span.line);
+        stmt.expression.accept(self);
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Print) {
+        self.lines.insert(stmt.span.line);
+        stmt.expression.accept(self);
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Var) {
+        self.lines.insert(stmt.span.line);
+        if let Some(ref initializer) = stmt.initializer {
+            initializer.accept(self);
+        }
+    }
+
+    fn visit_destructure_stmt(&mut self, stmt: &Destructure) {
+        self.lines.insert(stmt.span.line);
+        stmt.initializer.accept(self);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &Block) {
+        self.lines.insert(stmt.span.line);
+        self.visit_statements(&stmt.statements);
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &If) {
+        self.lines.insert(stmt.span.line);
+        stmt.condition.accept(self);
+        stmt.then_branch.accept(self);
+        if let Some(ref else_branch) = stmt.else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &While) {
+        self.lines.insert(stmt.span.line);
+        stmt.condition.accept(self);
+        stmt.body.accept(self);
+    }
+
+    fn visit_for_stmt(&mut self, stmt: &For) {
+        self.lines.insert(stmt.span.line);
+        if let Some(ref initializer) = stmt.initializer {
+            initializer.accept(self);
+        }
+        if let Some(ref condition) = stmt.condition {
+            condition.accept(self);
+        }
+        if let Some(ref increment) = stmt.increment {
+            increment.accept(self);
+        }
+        stmt.body.accept(self);
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) {
+        self.lines.insert(stmt.span.line);
+        stmt.iterable.accept(self);
+        stmt.body.accept(self);
+    }
+
+    fn visit_break_stmt(&mut self, stmt: &Break) {
+        self.lines.insert(stmt.span.line);
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Function) {
+        self.lines.insert(stmt.span.line);
+        for decorator in stmt.decorators.iter() {
+            decorator.accept(self);
+        }
+        self.visit_statements(&stmt.closure.body);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Return) {
+        self.lines.insert(stmt.span.line);
+        stmt.value.accept(self);
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &Class) {
+        self.lines.insert(stmt.span.line);
+        if let Some(ref superclass) = stmt.superclass {
+            superclass.accept(self);
+        }
+        // Methods only run when called, so only their bodies count.
+        for method in stmt.methods.iter() {
+            if let Stmt::Function(ref method) = method {
+                self.visit_statements(&method.closure.body);
+            }
+        }
+    }
+}
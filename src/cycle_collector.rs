@@ -0,0 +1,267 @@
+use crate::{
+    environment::Environment, lox_callable::LoxCallable, lox_class::LoxClass,
+    lox_function::LoxFunction, lox_instance::LoxInstance, lox_object::LoxObject,
+    memoized_function::MemoizedFunction,
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::{Rc, Weak},
+};
+
+/// Fewest candidates worth starting a collection for.
+const MIN_THRESHOLD: usize = 10_000;
+
+thread_local! {
+    /// Values that a reference cycle may pass through: environments closed over by functions,
+    /// and instances and lists that have been written to after being created.
+    static CANDIDATES: RefCell<Vec<Candidate>> = const { RefCell::new(Vec::new()) };
+    static THRESHOLD: Cell<usize> = const { Cell::new(MIN_THRESHOLD) };
+}
+
+enum Candidate {
+    Environment(Weak<RefCell<Environment>>),
+    Instance(Weak<RefCell<LoxInstance>>),
+    List(Weak<RefCell<Vec<LoxObject>>>),
+}
+
+fn track(candidate: Candidate) {
+    CANDIDATES.with(|candidates| candidates.borrow_mut().push(candidate));
+}
+
+/// Records that functions close over `environment`, so it may end up in a cycle with them.
+pub(crate) fn track_environment(environment: &Rc<RefCell<Environment>>) {
+    track(Candidate::Environment(Rc::downgrade(environment)));
+}
+
+/// Records that a field of `instance` was set, which may have closed a cycle through it.
+pub(crate) fn track_instance(instance: &Rc<RefCell<LoxInstance>>) {
+    track(Candidate::Instance(Rc::downgrade(instance)));
+}
+
+/// Records that an element of `list` was set, which may have closed a cycle through it.
+pub(crate) fn track_list(list: &Rc<RefCell<Vec<LoxObject>>>) {
+    track(Candidate::List(Rc::downgrade(list)));
+}
+
+/// Runs [`collect`] if enough candidates have been tracked since the last collection.
+pub(crate) fn collect_if_due() {
+    let due = CANDIDATES.with(|candidates| candidates.borrow().len()) >= THRESHOLD.with(Cell::get);
+    if due {
+        collect();
+    }
+}
+
+/// A value that holds references to other values.
+enum Node {
+    Environment(Rc<RefCell<Environment>>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    List(Rc<RefCell<Vec<LoxObject>>>),
+    Function(Rc<LoxFunction>),
+    Class(Rc<LoxClass>),
+    Memoized(Rc<MemoizedFunction>),
+}
+
+impl Node {
+    fn key(&self) -> usize {
+        match self {
+            Node::Environment(rc) => Rc::as_ptr(rc) as *const () as usize,
+            Node::Instance(rc) => Rc::as_ptr(rc) as *const () as usize,
+            Node::List(rc) => Rc::as_ptr(rc) as *const () as usize,
+            Node::Function(rc) => Rc::as_ptr(rc) as *const () as usize,
+            Node::Class(rc) => Rc::as_ptr(rc) as *const () as usize,
+            Node::Memoized(rc) => Rc::as_ptr(rc) as *const () as usize,
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Node::Environment(rc) => Rc::strong_count(rc),
+            Node::Instance(rc) => Rc::strong_count(rc),
+            Node::List(rc) => Rc::strong_count(rc),
+            Node::Function(rc) => Rc::strong_count(rc),
+            Node::Class(rc) => Rc::strong_count(rc),
+            Node::Memoized(rc) => Rc::strong_count(rc),
+        }
+    }
+
+    /// Adds every value this one holds a strong reference to, once per reference, to `children`.
+    /// Returns `None` if the value is borrowed for writing and can't be inspected.
+    fn children(&self, children: &mut Vec<Node>) -> Option<()> {
+        match self {
+            Node::Environment(environment) => {
+                let environment = environment.try_borrow().ok()?;
+                if let Some(ref enclosing) = environment.enclosing {
+                    children.push(Node::Environment(Rc::clone(enclosing)));
+                }
+                for value in environment.values() {
+                    object_children(value, children);
+                }
+            }
+            Node::Instance(instance) => {
+                let instance = instance.try_borrow().ok()?;
+                children.push(Node::Class(Rc::clone(instance.class())));
+                for (_, value) in instance.fields() {
+                    object_children(value, children);
+                }
+            }
+            Node::List(list) => {
+                for value in list.try_borrow().ok()?.iter() {
+                    object_children(value, children);
+                }
+            }
+            Node::Function(function) => function_children(function, children),
+            Node::Class(class) => class_children(class, children),
+            Node::Memoized(memoized) => {
+                callable_children(memoized.function(), children);
+                for value in memoized.cache().try_borrow().ok()?.values() {
+                    object_children(value, children);
+                }
+            }
+        }
+        Some(())
+    }
+
+    /// Drops the references this value holds, breaking any cycle through it. Functions and
+    /// classes are immutable, but every cycle passes through one of the other kinds.
+    fn clear(&self) {
+        match self {
+            Node::Environment(environment) => environment.borrow_mut().clear(),
+            Node::Instance(instance) => instance.borrow_mut().clear_fields(),
+            Node::List(list) => list.borrow_mut().clear(),
+            Node::Memoized(memoized) => memoized.cache().borrow_mut().clear(),
+            Node::Function(_) | Node::Class(_) => {}
+        }
+    }
+}
+
+fn object_children(object: &LoxObject, children: &mut Vec<Node>) {
+    match object {
+        LoxObject::Literal(_) | LoxObject::Foreign(_) => {}
+        LoxObject::Callable(callable) => callable_children(callable, children),
+        LoxObject::Instance(instance) => children.push(Node::Instance(Rc::clone(instance))),
+        LoxObject::List(list) => children.push(Node::List(Rc::clone(list))),
+    }
+}
+
+fn callable_children(callable: &LoxCallable, children: &mut Vec<Node>) {
+    match callable {
+        LoxCallable::Function(function) => children.push(Node::Function(Rc::clone(function))),
+        LoxCallable::Class(class) => children.push(Node::Class(Rc::clone(class))),
+        LoxCallable::Memoized(memoized) => children.push(Node::Memoized(Rc::clone(memoized))),
+        LoxCallable::NativeFun(_) => {}
+    }
+}
+
+/// The references held by `function`, which may be stored inline rather than behind an `Rc`.
+fn function_children(function: &LoxFunction, children: &mut Vec<Node>) {
+    children.push(Node::Environment(Rc::clone(function.context())));
+    if let Some(unbound) = function.unbind() {
+        function_children(unbound, children);
+    }
+}
+
+fn class_children(class: &LoxClass, children: &mut Vec<Node>) {
+    if let Some(ref superclass) = class.superclass {
+        children.push(Node::Class(Rc::clone(superclass)));
+    }
+    for method in class.methods.values() {
+        function_children(method, children);
+    }
+}
+
+/// Frees values that only keep each other alive, returning how many values were cleared.
+///
+/// Starting from the tracked candidates, finds every value they reach and subtracts the
+/// references those values hold to each other from their reference counts. Anything left with
+/// references is held from outside, by the interpreter or a Rust caller, and stays alive along
+/// with everything it reaches. The rest can't be reached and is cleared. It's safe to call at
+/// any point where no value is borrowed for writing; if one is, nothing is collected.
+pub(crate) fn collect() -> usize {
+    let candidates = CANDIDATES.with(|candidates| std::mem::take(&mut *candidates.borrow_mut()));
+
+    // Exactly one strong reference to each node is held here while collecting. Each node's
+    // children are the range of `edges` between its offset and the next node's.
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut indices: HashMap<usize, usize> = HashMap::new();
+    for candidate in candidates.iter() {
+        let node = match candidate {
+            Candidate::Environment(weak) => weak.upgrade().map(Node::Environment),
+            Candidate::Instance(weak) => weak.upgrade().map(Node::Instance),
+            Candidate::List(weak) => weak.upgrade().map(Node::List),
+        };
+        if let Some(node) = node {
+            indices.entry(node.key()).or_insert_with(|| {
+                nodes.push(node);
+                nodes.len() - 1
+            });
+        }
+    }
+
+    let mut offsets = Vec::with_capacity(nodes.len() + 1);
+    let mut edges = Vec::new();
+    let mut children = Vec::new();
+    let mut idx = 0;
+    while idx < nodes.len() {
+        offsets.push(edges.len());
+        if nodes[idx].children(&mut children).is_none() {
+            // Something is being modified; try again once it's done.
+            drop(nodes);
+            restore(candidates);
+            return 0;
+        }
+        for child in children.drain(..) {
+            let child_idx = *indices.entry(child.key()).or_insert_with(|| {
+                nodes.push(child);
+                nodes.len() - 1
+            });
+            edges.push(child_idx);
+        }
+        idx += 1;
+    }
+    offsets.push(edges.len());
+
+    let mut external: Vec<usize> = nodes.iter().map(|node| node.strong_count() - 1).collect();
+    for &child_idx in edges.iter() {
+        external[child_idx] -= 1;
+    }
+
+    let mut reachable = vec![false; nodes.len()];
+    let mut pending: Vec<usize> = (0..nodes.len()).filter(|&idx| external[idx] > 0).collect();
+    while let Some(idx) = pending.pop() {
+        if !reachable[idx] {
+            reachable[idx] = true;
+            pending.extend_from_slice(&edges[offsets[idx]..offsets[idx + 1]]);
+        }
+    }
+
+    let mut cleared = 0;
+    for (node, reachable) in nodes.iter().zip(reachable) {
+        if !reachable {
+            node.clear();
+            cleared += 1;
+        }
+    }
+    drop(nodes);
+
+    let survivors: Vec<Candidate> = candidates
+        .into_iter()
+        .filter(|candidate| match candidate {
+            Candidate::Environment(weak) => weak.strong_count() > 0,
+            Candidate::Instance(weak) => weak.strong_count() > 0,
+            Candidate::List(weak) => weak.strong_count() > 0,
+        })
+        .collect();
+    THRESHOLD.with(|threshold| threshold.set(MIN_THRESHOLD.max(2 * survivors.len())));
+    restore(survivors);
+    cleared
+}
+
+/// Puts `candidates` back, ahead of any tracked while they were taken.
+fn restore(mut candidates: Vec<Candidate>) {
+    CANDIDATES.with(|tracked| {
+        let mut tracked = tracked.borrow_mut();
+        candidates.append(&mut tracked);
+        *tracked = candidates;
+    });
+}
@@ -0,0 +1,225 @@
+use crate::{
+    cancellation::CancellationToken,
+    hooks::{ExecutionHooks, Scope},
+    lox_callable::LoxCallable,
+    lox_object::LoxObject,
+    span::Span,
+};
+use std::{
+    collections::BTreeSet,
+    ffi::OsStr,
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+const HELP: &str = "\
+Commands:
+  step, s                 Run the next statement, stepping into calls
+  next, n                 Run the next statement, stepping over calls
+  continue, c             Run until the next breakpoint
+  break, b [file:]<line>  Pause before statements on this line
+  delete, d [file:]<line> Remove the breakpoint on this line
+  locals                  Print the local variables, innermost scope first
+  globals                 Print the global variables, leaving out natives
+  print, p <name>         Print the variable as the paused code would see it
+  quit, q                 Stop the script
+  help, h                 Print this help";
+
+/// How far to run before pausing again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Pause before the next statement.
+    Step,
+    /// Pause before the next statement at most this many calls deep.
+    Next(usize),
+    /// Pause only at breakpoints.
+    Continue,
+}
+
+/// An interactive debugger for a single script, driven by [`ExecutionHooks`]. Before a
+/// statement runs it checks whether to pause, and while paused it reads commands from stdin
+/// and writes to stderr, so the script's own output stays separate. It starts paused before
+/// the first statement.
+pub struct Debugger {
+    source: String,
+    /// Path of the script, which breakpoints may name.
+    file: String,
+    breakpoints: BTreeSet<usize>,
+    mode: Mode,
+    /// Calls in progress.
+    depth: usize,
+    /// The statement last paused at, so the statements inside it on the same line don't pause
+    /// on the same breakpoint.
+    paused_at: Option<Span>,
+    /// Stops the script when the user quits.
+    cancellation: CancellationToken,
+}
+
+impl Debugger {
+    pub fn new(source: &str, file: &str, cancellation: CancellationToken) -> Self {
+        Debugger {
+            source: String::from(source),
+            file: String::from(file),
+            breakpoints: BTreeSet::new(),
+            mode: Mode::Step,
+            depth: 0,
+            paused_at: None,
+            cancellation,
+        }
+    }
+
+    fn should_pause(&self, span: Span) -> bool {
+        match self.mode {
+            Mode::Step => true,
+            Mode::Next(depth) => self.depth <= depth,
+            Mode::Continue => {
+                let nested = self.paused_at.is_some_and(|paused| {
+                    paused != span
+                        && paused.line == span.line
+                        && paused.start <= span.start
+                        && span.end <= paused.end
+                });
+                self.breakpoints.contains(&span.line) && !nested
+            }
+        }
+    }
+
+    /// Shows where execution is paused and runs commands until one resumes it.
+    fn pause(&mut self, span: Span, scope: &Scope) {
+        self.paused_at = Some(span);
+        let text = self.source.lines().nth(span.line - 1).unwrap_or("").trim();
+        eprintln!("[line {}] {text}", span.line);
+        let mut stdin = io::stdin().lock();
+        loop {
+            eprint!("(debug) ");
+            let _ = io::stderr().flush();
+            let mut line = String::new();
+            // Without more commands, let the script finish.
+            if stdin.read_line(&mut line).unwrap_or(0) == 0 {
+                self.breakpoints.clear();
+                self.mode = Mode::Continue;
+                eprintln!();
+                return;
+            }
+            let (command, argument) = match line.trim().split_once(char::is_whitespace) {
+                Some((command, argument)) => (command, argument.trim()),
+                None => (line.trim(), ""),
+            };
+            let resume = match command {
+                "step" | "s" => {
+                    self.mode = Mode::Step;
+                    true
+                }
+                "next" | "n" => {
+                    self.mode = Mode::Next(self.depth);
+                    true
+                }
+                "continue" | "c" => {
+                    self.mode = Mode::Continue;
+                    true
+                }
+                "quit" | "q" => {
+                    self.cancellation.cancel();
+                    self.breakpoints.clear();
+                    self.mode = Mode::Continue;
+                    true
+                }
+                _ => {
+                    self.run_command(command, argument, scope);
+                    false
+                }
+            };
+            if resume {
+                return;
+            }
+        }
+    }
+
+    /// Runs a command that doesn't resume execution.
+    fn run_command(&mut self, command: &str, argument: &str, scope: &Scope) {
+        match command {
+            "break" | "b" => match self.breakpoint_line(argument) {
+                Ok(line) => {
+                    self.breakpoints.insert(line);
+                    eprintln!("Breakpoint at line {line}.");
+                }
+                Err(message) => eprintln!("{message}"),
+            },
+            "delete" | "d" => match self.breakpoint_line(argument) {
+                Ok(line) if self.breakpoints.remove(&line) => {
+                    eprintln!("Removed the breakpoint at line {line}.")
+                }
+                Ok(line) => eprintln!("No breakpoint at line {line}."),
+                Err(message) => eprintln!("{message}"),
+            },
+            "locals" => print_locals(scope),
+            "globals" => {
+                for (name, value) in scope.globals() {
+                    if !matches!(value, LoxObject::Callable(LoxCallable::NativeFun(_))) {
+                        eprintln!("{name} = {value}");
+                    }
+                }
+            }
+            "print" | "p" if argument.is_empty() => eprintln!("Usage: print <name>"),
+            "print" | "p" => match scope.get(argument) {
+                Some(value) => eprintln!("{argument} = {value}"),
+                None => eprintln!("No variable named '{argument}'."),
+            },
+            "help" | "h" => eprintln!("{HELP}"),
+            "" => (),
+            _ => eprintln!("Unknown command '{command}'. Enter 'help' for a list."),
+        }
+    }
+
+    /// The line a `break` or `delete` argument names, as `line` or `file:line`.
+    fn breakpoint_line(&self, argument: &str) -> Result<usize, String> {
+        let line = match argument.rsplit_once(':') {
+            Some((file, line)) if self.names_script(file) => line,
+            Some((file, _)) => return Err(format!("No script named '{file}' is being debugged.")),
+            None => argument,
+        };
+        let Ok(line) = line.parse::<usize>() else {
+            return Err(format!("Expected a line number, not '{line}'."));
+        };
+        match line {
+            0 => Err(String::from("Lines are numbered from 1.")),
+            _ if line > self.source.lines().count() => {
+                Err(format!("Line {line} is past the end of the script."))
+            }
+            _ => Ok(line),
+        }
+    }
+
+    /// Whether `file` is the script's path or its file name.
+    fn names_script(&self, file: &str) -> bool {
+        file == self.file || Path::new(&self.file).file_name() == Some(OsStr::new(file))
+    }
+}
+
+fn print_locals(scope: &Scope) {
+    let locals = scope.locals();
+    if locals.iter().all(Vec::is_empty) {
+        eprintln!("No local variables.");
+    }
+    for (depth, variables) in locals.iter().enumerate() {
+        for (name, value) in variables {
+            eprintln!("{:indent$}{name} = {value}", "", indent = depth * 2);
+        }
+    }
+}
+
+impl ExecutionHooks for Debugger {
+    fn statement(&mut self, span: Span, scope: &Scope) {
+        if self.should_pause(span) {
+            self.pause(span, scope);
+        }
+    }
+
+    fn call(&mut self, _name: Option<&str>, _span: Span) {
+        self.depth += 1;
+    }
+
+    fn return_from(&mut self, _name: Option<&str>, _span: Span) {
+        self.depth -= 1;
+    }
+}
@@ -0,0 +1,336 @@
+use crate::{error_code::ErrorCode, token::Token};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+/// Whether [`emit`] styles diagnostics with ANSI colors.
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+/// Warning codes [`emit`] leaves out.
+static ALLOWED: Mutex<Vec<ErrorCode>> = Mutex::new(Vec::new());
+
+/// Warning codes reported as errors.
+static DENIED: Mutex<Vec<ErrorCode>> = Mutex::new(Vec::new());
+
+/// Whether warnings are treated as errors.
+static DENY_WARNINGS: AtomicBool = AtomicBool::new(false);
+
+/// Whether [`emit`] prints JSON rather than [`DiagnosticFormat::Human`] output.
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Path of the script diagnostics are found in, named in JSON output.
+static FILE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Turns ANSI colors in diagnostics printed by [`emit`] on or off. They're off by default.
+pub fn set_color(color: bool) {
+    COLOR.store(color, Ordering::Relaxed);
+}
+
+/// Stops [`emit`] from printing warnings with `code`. Errors are always printed.
+pub fn allow(code: ErrorCode) {
+    ALLOWED.lock().unwrap().push(code);
+}
+
+/// Makes warnings with `code` print as errors and count towards [`is_fatal`], unless they're
+/// also allowed.
+pub fn deny(code: ErrorCode) {
+    DENIED.lock().unwrap().push(code);
+}
+
+/// Makes warnings that aren't allowed print as errors and count towards [`is_fatal`].
+pub fn deny_warnings(deny: bool) {
+    DENY_WARNINGS.store(deny, Ordering::Relaxed);
+}
+
+/// Chooses how [`emit`] prints diagnostics. They're printed for people by default.
+pub fn set_format(format: DiagnosticFormat) {
+    JSON.store(format == DiagnosticFormat::Json, Ordering::Relaxed);
+}
+
+pub fn format() -> DiagnosticFormat {
+    match JSON.load(Ordering::Relaxed) {
+        true => DiagnosticFormat::Json,
+        false => DiagnosticFormat::Human,
+    }
+}
+
+/// Sets the path JSON diagnostics name as their file, or `None` for code that didn't come from
+/// a file.
+pub fn set_file(path: Option<&str>) {
+    *FILE.lock().unwrap() = path.map(str::to_string);
+}
+
+/// The severity `diagnostic` is reported with once [`allow`], [`deny`] and [`deny_warnings`] are
+/// applied, or `None` if it isn't reported at all.
+fn reported_severity(diagnostic: &Diagnostic) -> Option<Severity> {
+    match diagnostic.severity {
+        Severity::Error => Some(Severity::Error),
+        Severity::Warning if ALLOWED.lock().unwrap().contains(&diagnostic.code) => None,
+        Severity::Warning if DENIED.lock().unwrap().contains(&diagnostic.code) => {
+            Some(Severity::Error)
+        }
+        Severity::Warning if DENY_WARNINGS.load(Ordering::Relaxed) => Some(Severity::Error),
+        Severity::Warning => Some(Severity::Warning),
+    }
+}
+
+/// How [`emit`] prints diagnostics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiagnosticFormat {
+    /// Summary lines quoting the source they point at, for people to read.
+    #[default]
+    Human,
+    /// One JSON object per line, for editors and CI to parse.
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    /// ANSI color code for the severity's label and carets.
+    fn color(self) -> &'static str {
+        match self {
+            Severity::Error => "31",
+            Severity::Warning => "33",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "Error"),
+            Severity::Warning => write!(f, "Warning"),
+        }
+    }
+}
+
+/// The text a diagnostic points at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceSpan {
+    /// Byte offset of the text within the source.
+    pub offset: usize,
+    /// Length of the text in bytes.
+    pub len: usize,
+    /// One-based position of the text's first character within its line.
+    pub column: usize,
+}
+
+/// A problem found while scanning, parsing or resolving a script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub line: usize,
+    pub span: Option<SourceSpan>,
+    /// Where the problem was found, such as `at 'x'` or `at end`. Empty for scan errors.
+    pub location: String,
+    pub message: String,
+    pub code: ErrorCode,
+}
+
+impl Diagnostic {
+    /// A diagnostic for `code`, which decides whether it's an error or a warning.
+    pub fn new(
+        code: ErrorCode,
+        line: usize,
+        span: Option<SourceSpan>,
+        location: &str,
+        message: &str,
+    ) -> Self {
+        let severity = match code.is_warning() {
+            true => Severity::Warning,
+            false => Severity::Error,
+        };
+        Diagnostic {
+            severity,
+            line,
+            span,
+            location: location.to_string(),
+            message: message.to_string(),
+            code,
+        }
+    }
+
+    /// A diagnostic pointing at `token`.
+    pub(crate) fn at_token(code: ErrorCode, token: &Token, location: &str, message: &str) -> Self {
+        let span = SourceSpan {
+            offset: token.start,
+            len: token.lexeme.len(),
+            column: token.column,
+        };
+        Diagnostic::new(code, token.line, Some(span), location, message)
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// The diagnostic as printed to users: its summary line, then the line of `source` it
+    /// points at with carets under the offending text. With `color`, the position is cyan,
+    /// the severity and carets red or yellow, and the message bold.
+    pub fn render(&self, source: &str, color: bool) -> String {
+        let mut out = match color {
+            true => {
+                let severity = self.severity.color();
+                let mut summary = format!(
+                    "\x1b[36m{}\x1b[0m \x1b[1;{severity}m{}[{}]\x1b[0m",
+                    self.position(),
+                    self.severity,
+                    self.code
+                );
+                if !self.location.is_empty() {
+                    summary.push_str(&format!(" {}", self.location));
+                }
+                summary.push_str(&format!(": \x1b[1m{}\x1b[0m", self.message));
+                summary
+            }
+            false => self.to_string(),
+        };
+        out.push('\n');
+        let marker = self
+            .span
+            .and_then(|span| caret_marker(source, span.offset, span.len));
+        if let Some((text, carets)) = marker {
+            let carets = match color {
+                true => format!("\x1b[{}m{carets}\x1b[0m", self.severity.color()),
+                false => carets,
+            };
+            out.push_str(&format!("    {text}\n    {carets}\n"));
+        }
+        out
+    }
+
+    /// The diagnostic as a single-line JSON object. `file` is `null` when `None`, as are
+    /// `column` and `span` when the diagnostic has no span.
+    pub fn to_json(&self, file: Option<&str>) -> String {
+        let (column, span) = match self.span {
+            Some(span) => (
+                span.column.to_string(),
+                format!(
+                    "{{\"start\":{},\"end\":{}}}",
+                    span.offset,
+                    span.offset + span.len
+                ),
+            ),
+            None => (String::from("null"), String::from("null")),
+        };
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        format!(
+            "{{\"file\":{},\"severity\":\"{severity}\",\"code\":\"{}\",\"line\":{},\"column\":{column},\"span\":{span},\"location\":{},\"message\":{}}}",
+            file.map_or(String::from("null"), json_string),
+            self.code,
+            self.line,
+            json_string(&self.location),
+            json_string(&self.message)
+        )
+    }
+
+    /// `[line L:C]`, or `[line L]` when there is no span.
+    fn position(&self) -> String {
+        match self.span {
+            Some(span) => format!("[line {}:{}]", self.line, span.column),
+            None => format!("[line {}]", self.line),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}[{}]", self.position(), self.severity, self.code)?;
+        if !self.location.is_empty() {
+            write!(f, " {}", self.location)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Whether any of `diagnostics` is an error rather than a warning.
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(Diagnostic::is_error)
+}
+
+/// Whether `diagnostics` should stop the script from running: any error does, and so does
+/// any warning when [`deny_warnings`] is on.
+pub fn is_fatal(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics
+        .iter()
+        .any(|diagnostic| reported_severity(diagnostic) == Some(Severity::Error))
+}
+
+/// Prints each of `diagnostics` to stderr, quoting the lines of `source` they point at, in
+/// color if [`set_color`] turned it on, or as JSON if [`set_format`] chose it. Warnings passed
+/// to [`allow`] are skipped, and warnings are printed as errors if [`deny_warnings`] is on.
+pub fn emit(source: &str, diagnostics: &[Diagnostic]) {
+    let color = COLOR.load(Ordering::Relaxed);
+    let json = JSON.load(Ordering::Relaxed);
+    for diagnostic in diagnostics {
+        let Some(severity) = reported_severity(diagnostic) else {
+            continue;
+        };
+        let diagnostic = Diagnostic {
+            severity,
+            ..diagnostic.clone()
+        };
+        match json {
+            true => eprintln!("{}", diagnostic.to_json(FILE.lock().unwrap().as_deref())),
+            false => eprint!("{}", diagnostic.render(source, color)),
+        }
+    }
+}
+
+/// `text` as a quoted JSON string.
+pub(crate) fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// One-based column of the character at byte `offset` of `source`.
+pub(crate) fn column_at(source: &str, offset: usize) -> usize {
+    let line_start = source[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+    source[line_start..offset].chars().count() + 1
+}
+
+/// The line of `source` containing the `len` bytes at `offset`, and carets under them padded
+/// to line up beneath. Text running past the end of the line is underlined up to it.
+pub(crate) fn caret_marker(source: &str, offset: usize, len: usize) -> Option<(&str, String)> {
+    source.get(offset..)?;
+    let line_start = source[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+    let line_end = source[offset..]
+        .find('\n')
+        .map_or(source.len(), |idx| offset + idx);
+    let text = source[line_start..line_end].trim_end();
+    if text.is_empty() {
+        return None;
+    }
+    // Keep tabs so the carets line up however the terminal renders them.
+    let padding: String = source[line_start..offset]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let end = (offset + len).min(line_end);
+    let carets = "^".repeat(source[offset..end].chars().count().max(1));
+    Some((text, padding + &carets))
+}
@@ -0,0 +1,147 @@
+use crate::{
+    diagnostic::{emit, has_errors},
+    expr::{Closure, Expr},
+    lox_callable::LoxCallable,
+    lox_class::LoxClass,
+    parser::Parser,
+    scanner::Scanner,
+    stmt::{Class, Stmt},
+};
+use std::rc::Rc;
+
+/// Parses `source` and renders the documentation of the functions and classes it declares at
+/// the top level, in order. Each is its signature with its `///` doc comment indented beneath,
+/// and a class is followed by its methods, indented one level further. Returns `None` if the
+/// source has scan or parse errors, which are reported as usual.
+pub fn document(source: &str) -> Option<String> {
+    let mut scanner = Scanner::new(source);
+    scanner.scan_tokens();
+    emit(source, &scanner.diagnostics);
+    let mut parser = Parser::new(scanner.tokens);
+    let parse_result = parser.parse();
+    emit(source, &parser.diagnostics);
+    let statements = parse_result.ok()?;
+    if has_errors(&scanner.diagnostics) {
+        return None;
+    }
+
+    let items: Vec<String> = statements
+        .iter()
+        .filter_map(|statement| match statement {
+            Stmt::Function(function) => {
+                let mut out = String::new();
+                let signature = signature(Some(&function.name.lexeme), &function.closure);
+                push_item(
+                    &mut out,
+                    "",
+                    &format!("fun {signature}"),
+                    doc(&function.closure),
+                );
+                Some(out)
+            }
+            Stmt::Class(class) => Some(document_class(class)),
+            _ => None,
+        })
+        .collect();
+    Some(items.join("\n"))
+}
+
+fn document_class(class: &Class) -> String {
+    let superclass = match class.superclass.as_deref() {
+        Some(Expr::Variable(superclass)) => Some(&*superclass.name.lexeme),
+        _ => None,
+    };
+    let mut out = String::new();
+    push_item(
+        &mut out,
+        "",
+        &class_header(&class.name.lexeme, superclass),
+        class.doc.as_deref(),
+    );
+    for method in class.methods.iter() {
+        if let Stmt::Function(method) = method {
+            out.push('\n');
+            let signature = signature(Some(&method.name.lexeme), &method.closure);
+            push_item(&mut out, "    ", &signature, doc(&method.closure));
+        }
+    }
+    out
+}
+
+/// What `help()` shows for `callable`: its signature and doc comment, as [`document`]
+/// renders them. A class also lists the methods it declares rather than inherits.
+pub(crate) fn help(callable: &LoxCallable) -> String {
+    let mut out = String::new();
+    match callable {
+        LoxCallable::Function(function) => {
+            let signature = signature(function.name(), function.declaration());
+            push_item(&mut out, "", &format!("fun {signature}"), function.doc());
+        }
+        LoxCallable::NativeFun(native_fun) => {
+            out.push_str(&format!("native fun {}", native_fun.name()));
+        }
+        LoxCallable::Class(class) => help_class(class, &mut out),
+        LoxCallable::Memoized(memoized) => return help(memoized.function()),
+    }
+    out.trim_end().to_owned()
+}
+
+fn help_class(class: &LoxClass, out: &mut String) {
+    let superclass = class
+        .superclass
+        .as_ref()
+        .map(|superclass| &*superclass.name);
+    push_item(
+        out,
+        "",
+        &class_header(&class.name, superclass),
+        class.doc.as_deref(),
+    );
+    let mut methods: Vec<_> = class
+        .methods
+        .iter()
+        .filter(|(name, method)| {
+            let inherited = class
+                .superclass
+                .as_ref()
+                .and_then(|superclass| superclass.find_method(name));
+            !inherited
+                .is_some_and(|inherited| Rc::ptr_eq(inherited.declaration(), method.declaration()))
+        })
+        .collect();
+    methods.sort_by_key(|(_, method)| method.declaration().span.start);
+    for (name, method) in methods {
+        out.push('\n');
+        let signature = signature(Some(name), method.declaration());
+        push_item(out, "    ", &signature, method.doc());
+    }
+}
+
+fn class_header(name: &str, superclass: Option<&str>) -> String {
+    match superclass {
+        Some(superclass) => format!("class {name} < {superclass}"),
+        None => format!("class {name}"),
+    }
+}
+
+/// The name, if any, and parameter list of a function.
+fn signature(name: Option<&str>, closure: &Closure) -> String {
+    let params: Vec<&str> = closure.params.iter().map(|param| &*param.lexeme).collect();
+    format!("{}({})", name.unwrap_or(""), params.join(", "))
+}
+
+fn doc(closure: &Closure) -> Option<&str> {
+    closure.doc.as_deref()
+}
+
+/// Adds `signature` on a line of its own after `indent`, and each line of `doc` beneath it,
+/// indented one level further.
+fn push_item(out: &mut String, indent: &str, signature: &str, doc: Option<&str>) {
+    out.push_str(&format!("{indent}{signature}\n"));
+    for line in doc.unwrap_or("").lines() {
+        match line.is_empty() {
+            true => out.push('\n'),
+            false => out.push_str(&format!("{indent}    {line}\n")),
+        }
+    }
+}
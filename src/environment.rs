@@ -1,58 +1,317 @@
 use crate::{
+    error_code::ErrorCode,
+    live_count::{LiveCount, LiveKind},
     lox_exception::{LoxException, RuntimeError},
     lox_object::LoxObject,
     token::Token,
 };
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
+/// Variables are stored in slots, numbered in the order they're defined. The resolver numbers
+/// each local variable the same way, so locals are read and written by slot without hashing
+/// their names.
 #[derive(Debug, PartialEq)]
-pub struct Environment<'src> {
-    values: HashMap<&'src str, LoxObject<'src>>,
-    pub enclosing: Option<Rc<RefCell<Environment<'src>>>>,
+pub struct Environment {
+    values: Vec<LoxObject>,
+    /// Name of the variable in each slot. The global environment lists its names in the order
+    /// they were defined instead, since its slots are numbered differently.
+    names: Vec<Rc<str>>,
+    /// Where the global environment keeps its variables, in place of `values`.
+    globals: Option<Globals>,
+    pub enclosing: Option<Rc<RefCell<Environment>>>,
+    /// Log each definition and assignment to stderr. Environments inherit this from the one
+    /// they're nested in.
+    trace: bool,
+    live: LiveCount,
 }
 
-impl<'src> Environment<'src> {
-    pub fn new(enclosing: Option<Rc<RefCell<Environment<'src>>>>) -> Self {
+/// Global variables, in slots numbered by name the first time a name is resolved or defined.
+/// The resolver numbers the globals it sees, so they're read and written by slot like locals,
+/// but a slot has no value until its global is defined.
+#[derive(Debug, Default, PartialEq)]
+struct Globals {
+    slots: HashMap<Rc<str>, usize>,
+    values: Vec<Option<LoxObject>>,
+}
+
+impl Globals {
+    fn slot(&mut self, name: &Rc<str>) -> usize {
+        if let Some(&slot) = self.slots.get(name) {
+            return slot;
+        }
+        self.slots.insert(Rc::clone(name), self.values.len());
+        self.values.push(None);
+        self.values.len() - 1
+    }
+
+    fn get(&self, name: &str) -> Option<LoxObject> {
+        let slot = *self.slots.get(name)?;
+        self.values[slot].clone()
+    }
+
+    /// Assigns to the global in `slot`, returning `false` if it isn't defined.
+    fn assign(&mut self, slot: usize, value: LoxObject) -> bool {
+        match self.values[slot] {
+            Some(ref mut current) => {
+                *current = value;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// The global variables defined at one point in time, from [`Environment::snapshot_globals`].
+/// Lists and instances are shared with the live globals rather than copied, so it records
+/// which value each global holds but not what's inside those values.
+#[derive(Debug, Clone)]
+pub struct GlobalsSnapshot {
+    names: Vec<Rc<str>>,
+    values: Vec<Option<LoxObject>>,
+}
+
+impl Environment {
+    /// An environment nested in `enclosing`, or the global environment if there's none.
+    pub fn new(enclosing: Option<Rc<RefCell<Environment>>>) -> Self {
+        let globals = match enclosing {
+            Some(_) => None,
+            None => Some(Globals::default()),
+        };
+        let trace = enclosing
+            .as_ref()
+            .is_some_and(|enclosing| enclosing.borrow().trace);
         Environment {
-            values: HashMap::new(),
+            values: Vec::new(),
+            names: Vec::new(),
+            globals,
             enclosing,
+            trace,
+            live: LiveCount::new(LiveKind::Environment),
         }
     }
 
-    pub fn define(&mut self, name: &'src str, value: LoxObject<'src>) {
-        self.values.insert(name, value);
+    /// Number of names defined directly in this environment.
+    pub fn len(&self) -> usize {
+        self.names.len()
     }
 
-    pub fn get(&self, name: &Token) -> Result<LoxObject<'src>, LoxException<'src>> {
-        match self.values.contains_key(name.lexeme) {
-            true => Ok(self.values.get(name.lexeme).unwrap().clone()),
-            false if self.enclosing.is_some() => {
-                self.enclosing.as_deref().unwrap().borrow().get(name)
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(|name| name.as_ref())
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &LoxObject> {
+        let globals = self
+            .globals
+            .iter()
+            .flat_map(|globals| globals.values.iter().flatten());
+        self.values.iter().chain(globals)
+    }
+
+    /// Each variable defined directly in this environment with its value, in the order they
+    /// were defined.
+    pub fn variables(&self) -> Vec<(Rc<str>, LoxObject)> {
+        self.names
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, name)| {
+                let value = match self.globals {
+                    Some(ref globals) => globals.get(name)?,
+                    None => self.values.get(slot)?.clone(),
+                };
+                Some((Rc::clone(name), value))
+            })
+            .collect()
+    }
+
+    pub fn is_global(&self) -> bool {
+        self.globals.is_some()
+    }
+
+    /// Drops every variable and the enclosing environment, for the cycle collector to break
+    /// cycles through environments nothing can reach anymore.
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.names.clear();
+        if let Some(ref mut globals) = self.globals {
+            globals.values.clear();
+            globals.slots.clear();
+        }
+        self.enclosing = None;
+    }
+
+    /// Logs definitions and assignments in this environment, and those nested in it from now
+    /// on, to stderr.
+    pub fn is_traced(&self) -> bool {
+        self.trace
+    }
+
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    /// Logs `action` on the variable `name` if tracing is on, with how many environments this
+    /// one is nested in.
+    fn trace(&self, action: &str, name: &str, value: &LoxObject) {
+        if !self.trace {
+            return;
+        }
+        let mut depth = 0;
+        let mut enclosing = self.enclosing.clone();
+        while let Some(environment) = enclosing {
+            depth += 1;
+            enclosing = environment.borrow().enclosing.clone();
+        }
+        eprintln!("[env] {action} {name} = {value} (depth {depth})");
+    }
+
+    /// Defines a variable in the next slot. Redefining a global replaces its value instead.
+    pub fn define(&mut self, name: impl Into<Rc<str>>, value: LoxObject) {
+        let name = name.into();
+        self.trace("define", &name, &value);
+        if let Some(ref mut globals) = self.globals {
+            let slot = globals.slot(&name);
+            if globals.values[slot].is_none() {
+                self.names.push(name);
             }
-            false => Err(LoxException::RuntimeError(RuntimeError::new(
-                name.line,
-                format!("Undefined variable '{}'.", name.lexeme),
-            ))),
+            globals.values[slot] = Some(value);
+            return;
         }
+        self.values.push(value);
+        self.names.push(name);
     }
 
-    pub fn get_at(&self, distance: usize, name: &str) -> LoxObject<'src> {
-        let expect_msg = format!(
-            "Expect to find variable '{name}' at distance {distance} due to semantic analysis in Resolver."
-        );
+    /// The global variables defined now, to roll back to with [`Environment::restore_globals`].
+    pub fn snapshot_globals(&self) -> GlobalsSnapshot {
+        let globals = self.globals.as_ref();
+        let globals = globals.expect("Expect only the global environment to snapshot globals.");
+        GlobalsSnapshot {
+            names: self.names.clone(),
+            values: globals.values.clone(),
+        }
+    }
+
+    /// Sets every global back to the value it had when `snapshot` was taken, undefining those
+    /// defined since. Slots keep their numbers, so code already resolved stays valid.
+    pub fn restore_globals(&mut self, snapshot: &GlobalsSnapshot) {
+        let globals = self.globals.as_mut();
+        let globals = globals.expect("Expect only the global environment to restore globals.");
+        for (slot, value) in globals.values.iter_mut().enumerate() {
+            *value = snapshot.values.get(slot).cloned().flatten();
+        }
+        self.names = snapshot.names.clone();
+    }
+
+    /// Slot of the global named `name`, numbering it now if it's never been seen. Only the
+    /// global environment has these slots.
+    pub fn global_slot(&mut self, name: &Rc<str>) -> usize {
+        self.globals
+            .as_mut()
+            .expect("Expect only the global environment to number globals.")
+            .slot(name)
+    }
+
+    /// Reads the global in `slot`, or `None` if it isn't defined yet.
+    pub fn get_global(&self, slot: usize) -> Option<LoxObject> {
+        let globals = self.globals.as_ref();
+        globals
+            .expect("Expect only the global environment to number globals.")
+            .values[slot]
+            .clone()
+    }
+
+    /// Writes the global in `slot`, or returns `None` if it isn't defined yet.
+    pub fn assign_global(&mut self, slot: usize, value: LoxObject) -> Option<LoxObject> {
+        let globals = self.globals.as_mut();
+        let globals = globals.expect("Expect only the global environment to number globals.");
+        if !globals.assign(slot, value.clone()) {
+            return None;
+        }
+        if self.trace {
+            // Globals are numbered by name, so find the name this slot was given.
+            let slots = self.globals.iter().flat_map(|globals| globals.slots.iter());
+            if let Some((name, _)) = slots.into_iter().find(|(_, &defined)| defined == slot) {
+                self.trace("assign", name, &value);
+            }
+        }
+        Some(value)
+    }
+
+    /// The value of the variable named `name` in this environment, if it has one.
+    pub fn lookup(&self, name: &str) -> Option<LoxObject> {
+        match self.globals {
+            Some(ref globals) => globals.get(name),
+            None => {
+                let slot = self.names.iter().rposition(|defined| &**defined == name)?;
+                Some(self.values[slot].clone())
+            }
+        }
+    }
+
+    /// Looks up `name` in this environment and then each enclosing one. Only variables the
+    /// resolver left unresolved, which are late-bound globals, need looking up by name.
+    pub fn get(&self, name: &Token) -> Result<LoxObject, LoxException> {
+        if let Some(value) = self.lookup(&name.lexeme) {
+            return Ok(value);
+        }
+        let mut enclosing = self.enclosing.clone();
+        while let Some(environment) = enclosing {
+            if let Some(value) = environment.borrow().lookup(&name.lexeme) {
+                return Ok(value);
+            }
+            enclosing = environment.borrow().enclosing.clone();
+        }
+        Err(self.undefined_variable(name))
+    }
+
+    /// The error for using `name` when it isn't defined, suggesting a similarly spelled name
+    /// from this environment or an enclosing one.
+    pub fn undefined_variable(&self, name: &Token) -> LoxException {
+        let mut message = format!("Undefined variable '{}'.", name.lexeme);
+        if let Some(suggestion) = self.similar_name(&name.lexeme) {
+            message.push_str(&format!(" Did you mean '{suggestion}'?"));
+        }
+        LoxException::RuntimeError(RuntimeError::new(
+            ErrorCode::UNDEFINED_VARIABLE,
+            name.span(),
+            message,
+        ))
+    }
+
+    /// The visible name closest to `name` by edit distance, if any is close enough to be a
+    /// likely typo. A name that would have to be rewritten entirely doesn't count.
+    fn similar_name(&self, name: &str) -> Option<Rc<str>> {
+        let mut names = self.names.clone();
+        let mut enclosing = self.enclosing.clone();
+        while let Some(environment) = enclosing {
+            names.extend(environment.borrow().names.iter().cloned());
+            enclosing = environment.borrow().enclosing.clone();
+        }
+        let len = name.chars().count();
+        let max_distance = (len / 3).max(1).min(len - 1);
+        names
+            .into_iter()
+            .map(|candidate| (edit_distance(name, &candidate), candidate))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min()
+            .map(|(_, candidate)| candidate)
+    }
+
+    /// Reads the variable the resolver placed in `slot` of the environment `distance` levels up.
+    pub fn get_at(&self, distance: usize, slot: usize) -> LoxObject {
+        let expect_msg = "Expect variable slot to be defined due to semantic analysis in Resolver.";
         if distance == 0 {
-            self.values.get(name).expect(&expect_msg).clone()
+            self.values.get(slot).expect(expect_msg).clone()
         } else {
             self.ancestor(distance)
                 .borrow()
                 .values
-                .get(name)
-                .expect(&expect_msg)
+                .get(slot)
+                .expect(expect_msg)
                 .clone()
         }
     }
 
-    fn ancestor(&self, distance: usize) -> Rc<RefCell<Environment<'src>>> {
+    fn ancestor(&self, distance: usize) -> Rc<RefCell<Environment>> {
         let expect_msg = "Expect number of enclosing environments to match value from Resolver.";
         let mut environment = Rc::clone(self.enclosing.as_ref().expect(expect_msg));
         for _ in 1..distance {
@@ -62,46 +321,72 @@ impl<'src> Environment<'src> {
         environment
     }
 
-    pub fn assign(
-        &mut self,
-        name: &Token<'src>,
-        value: LoxObject<'src>,
-    ) -> Result<LoxObject<'src>, LoxException<'src>> {
-        match self.values.contains_key(&name.lexeme) {
-            true => {
-                self.values.insert(name.lexeme, value.clone());
-            }
-            false if self.enclosing.is_some() => {
-                self.enclosing
-                    .as_deref()
-                    .unwrap()
-                    .borrow_mut()
-                    .assign(name, value.clone())?;
-            }
-            false => {
-                return Err(LoxException::RuntimeError(RuntimeError::new(
-                    name.line,
-                    format!("Undefined variable '{}'.", &name.lexeme),
-                )))
+    /// Assigns to `name` in this environment or the nearest enclosing one defining it.
+    pub fn assign(&mut self, name: &Token, value: LoxObject) -> Result<LoxObject, LoxException> {
+        let assigned = match self.globals {
+            Some(ref mut globals) => match globals.slots.get(&name.lexeme) {
+                Some(&slot) => globals.assign(slot, value.clone()),
+                None => false,
+            },
+            None => match self
+                .names
+                .iter()
+                .rposition(|defined| *defined == name.lexeme)
+            {
+                Some(slot) => {
+                    self.values[slot] = value.clone();
+                    true
+                }
+                None => false,
+            },
+        };
+        if assigned {
+            self.trace("assign", &name.lexeme, &value);
+        } else {
+            match self.enclosing {
+                Some(ref enclosing) => {
+                    enclosing.borrow_mut().assign(name, value.clone())?;
+                }
+                None => return Err(self.undefined_variable(name)),
             }
         }
         Ok(value)
     }
 
-    pub fn assign_at(
-        &mut self,
-        distance: usize,
-        name: &Token<'src>,
-        value: LoxObject<'src>,
-    ) -> LoxObject<'src> {
+    /// Writes the variable the resolver placed in `slot` of the environment `distance` levels up.
+    pub fn assign_at(&mut self, distance: usize, slot: usize, value: LoxObject) -> LoxObject {
         if distance == 0 {
-            self.values.insert(name.lexeme, value.clone());
+            self.values[slot] = value.clone();
+            self.trace("assign", &self.names[slot], &value);
         } else {
-            self.ancestor(distance)
-                .borrow_mut()
-                .values
-                .insert(name.lexeme, value.clone());
+            let ancestor = self.ancestor(distance);
+            let mut ancestor = ancestor.borrow_mut();
+            ancestor.values[slot] = value.clone();
+            ancestor.trace("assign", &ancestor.names[slot], &value);
         }
         value
     }
 }
+
+/// Optimal string alignment distance between `a` and `b`, counted in characters: Levenshtein
+/// distance, except that swapping two adjacent characters counts as one edit, not two.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut before_previous: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, &a_char) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            let mut distance = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+            if i > 0 && j > 0 && a_char == b[j - 1] && a[i - 1] == b_char {
+                distance = distance.min(before_previous[j - 1] + 1);
+            }
+            current.push(distance);
+        }
+        before_previous = previous;
+        previous = current;
+    }
+    previous[b.len()]
+}
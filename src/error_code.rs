@@ -0,0 +1,151 @@
+use std::fmt;
+
+/// A stable identifier for a kind of error or warning, such as `E0102`. Codes are printed
+/// with every diagnostic and runtime error so they can be looked up, filtered and
+/// suppressed. Once assigned, a code never changes meaning.
+///
+/// Scan errors are numbered from `E0001`, parse errors from `E0101`, resolution errors
+/// from `E0201` and runtime errors from `E0301`. Warnings are numbered from `W0001`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ErrorCode(&'static str);
+
+impl ErrorCode {
+    pub const UNEXPECTED_CHARACTER: ErrorCode = ErrorCode("E0001");
+    pub const UNTERMINATED_STRING: ErrorCode = ErrorCode("E0002");
+    pub const INVALID_NUMBER: ErrorCode = ErrorCode("E0003");
+
+    pub const EXPECTED_TOKEN: ErrorCode = ErrorCode("E0101");
+    pub const EXPECTED_EXPRESSION: ErrorCode = ErrorCode("E0102");
+    pub const TRAILING_TOKENS: ErrorCode = ErrorCode("E0103");
+    pub const TOO_MANY_PARAMETERS: ErrorCode = ErrorCode("E0104");
+    pub const TOO_MANY_ARGUMENTS: ErrorCode = ErrorCode("E0105");
+    pub const INVALID_ASSIGNMENT_TARGET: ErrorCode = ErrorCode("E0106");
+    pub const MISSING_LEFT_OPERAND: ErrorCode = ErrorCode("E0107");
+    pub const BREAK_OUTSIDE_LOOP: ErrorCode = ErrorCode("E0108");
+    pub const TOO_DEEPLY_NESTED: ErrorCode = ErrorCode("E0109");
+
+    pub const ALREADY_DECLARED: ErrorCode = ErrorCode("E0201");
+    pub const READ_IN_OWN_INITIALIZER: ErrorCode = ErrorCode("E0202");
+    pub const THIS_OUTSIDE_CLASS: ErrorCode = ErrorCode("E0203");
+    pub const SUPER_OUTSIDE_CLASS: ErrorCode = ErrorCode("E0204");
+    pub const SUPER_WITHOUT_SUPERCLASS: ErrorCode = ErrorCode("E0205");
+    pub const RETURN_FROM_TOP_LEVEL: ErrorCode = ErrorCode("E0206");
+    pub const RETURN_VALUE_FROM_INITIALIZER: ErrorCode = ErrorCode("E0207");
+    pub const INHERITS_FROM_ITSELF: ErrorCode = ErrorCode("E0208");
+
+    pub const OPERAND_NOT_NUMBER: ErrorCode = ErrorCode("E0301");
+    pub const OPERANDS_NOT_NUMBERS: ErrorCode = ErrorCode("E0302");
+    pub const INVALID_ADDITION: ErrorCode = ErrorCode("E0303");
+    pub const DIVISION_BY_ZERO: ErrorCode = ErrorCode("E0304");
+    pub const UNDEFINED_VARIABLE: ErrorCode = ErrorCode("E0305");
+    pub const UNDEFINED_PROPERTY: ErrorCode = ErrorCode("E0306");
+    pub const PROPERTY_ON_NON_INSTANCE: ErrorCode = ErrorCode("E0307");
+    pub const FIELD_ON_NON_INSTANCE: ErrorCode = ErrorCode("E0308");
+    pub const NOT_CALLABLE: ErrorCode = ErrorCode("E0309");
+    pub const WRONG_ARGUMENT_COUNT: ErrorCode = ErrorCode("E0310");
+    pub const SUPERCLASS_NOT_CLASS: ErrorCode = ErrorCode("E0311");
+    pub const INDEX_ON_NON_LIST: ErrorCode = ErrorCode("E0312");
+    pub const INDEX_NOT_INTEGER: ErrorCode = ErrorCode("E0313");
+    pub const INDEX_OUT_OF_RANGE: ErrorCode = ErrorCode("E0314");
+    pub const DESTRUCTURE_NON_LIST: ErrorCode = ErrorCode("E0315");
+    pub const DESTRUCTURE_COUNT_MISMATCH: ErrorCode = ErrorCode("E0316");
+    pub const NOT_ITERABLE: ErrorCode = ErrorCode("E0317");
+    pub const INVALID_ITERATOR: ErrorCode = ErrorCode("E0318");
+    pub const INVALID_DECORATOR: ErrorCode = ErrorCode("E0319");
+    pub const INTERRUPTED: ErrorCode = ErrorCode("E0320");
+    pub const NATIVE_FUNCTION: ErrorCode = ErrorCode("E0321");
+    pub const INVALID_EXPRESSION: ErrorCode = ErrorCode("E0322");
+    pub const STACK_OVERFLOW: ErrorCode = ErrorCode("E0323");
+    pub const BUDGET_EXHAUSTED: ErrorCode = ErrorCode("E0324");
+    pub const OUT_OF_MEMORY: ErrorCode = ErrorCode("E0325");
+    pub const OUTPUT_FAILED: ErrorCode = ErrorCode("E0326");
+    pub const STRING_TOO_LONG: ErrorCode = ErrorCode("E0327");
+
+    pub const FLOAT_EQUALITY: ErrorCode = ErrorCode("W0001");
+    pub const ASSIGNMENT_AS_CONDITION: ErrorCode = ErrorCode("W0002");
+    pub const UNUSED_LOCAL: ErrorCode = ErrorCode("W0003");
+    pub const SHADOWED_VARIABLE: ErrorCode = ErrorCode("W0004");
+    pub const CONSTANT_CONDITION: ErrorCode = ErrorCode("W0005");
+    pub const EMPTY_BLOCK: ErrorCode = ErrorCode("W0006");
+    pub const SELF_ASSIGNMENT: ErrorCode = ErrorCode("W0007");
+
+    /// Every assigned code.
+    pub const ALL: &'static [ErrorCode] = &[
+        ErrorCode::UNEXPECTED_CHARACTER,
+        ErrorCode::UNTERMINATED_STRING,
+        ErrorCode::INVALID_NUMBER,
+        ErrorCode::EXPECTED_TOKEN,
+        ErrorCode::EXPECTED_EXPRESSION,
+        ErrorCode::TRAILING_TOKENS,
+        ErrorCode::TOO_MANY_PARAMETERS,
+        ErrorCode::TOO_MANY_ARGUMENTS,
+        ErrorCode::INVALID_ASSIGNMENT_TARGET,
+        ErrorCode::MISSING_LEFT_OPERAND,
+        ErrorCode::BREAK_OUTSIDE_LOOP,
+        ErrorCode::TOO_DEEPLY_NESTED,
+        ErrorCode::ALREADY_DECLARED,
+        ErrorCode::READ_IN_OWN_INITIALIZER,
+        ErrorCode::THIS_OUTSIDE_CLASS,
+        ErrorCode::SUPER_OUTSIDE_CLASS,
+        ErrorCode::SUPER_WITHOUT_SUPERCLASS,
+        ErrorCode::RETURN_FROM_TOP_LEVEL,
+        ErrorCode::RETURN_VALUE_FROM_INITIALIZER,
+        ErrorCode::INHERITS_FROM_ITSELF,
+        ErrorCode::OPERAND_NOT_NUMBER,
+        ErrorCode::OPERANDS_NOT_NUMBERS,
+        ErrorCode::INVALID_ADDITION,
+        ErrorCode::DIVISION_BY_ZERO,
+        ErrorCode::UNDEFINED_VARIABLE,
+        ErrorCode::UNDEFINED_PROPERTY,
+        ErrorCode::PROPERTY_ON_NON_INSTANCE,
+        ErrorCode::FIELD_ON_NON_INSTANCE,
+        ErrorCode::NOT_CALLABLE,
+        ErrorCode::WRONG_ARGUMENT_COUNT,
+        ErrorCode::SUPERCLASS_NOT_CLASS,
+        ErrorCode::INDEX_ON_NON_LIST,
+        ErrorCode::INDEX_NOT_INTEGER,
+        ErrorCode::INDEX_OUT_OF_RANGE,
+        ErrorCode::DESTRUCTURE_NON_LIST,
+        ErrorCode::DESTRUCTURE_COUNT_MISMATCH,
+        ErrorCode::NOT_ITERABLE,
+        ErrorCode::INVALID_ITERATOR,
+        ErrorCode::INVALID_DECORATOR,
+        ErrorCode::INTERRUPTED,
+        ErrorCode::NATIVE_FUNCTION,
+        ErrorCode::INVALID_EXPRESSION,
+        ErrorCode::STACK_OVERFLOW,
+        ErrorCode::BUDGET_EXHAUSTED,
+        ErrorCode::OUT_OF_MEMORY,
+        ErrorCode::OUTPUT_FAILED,
+        ErrorCode::STRING_TOO_LONG,
+        ErrorCode::FLOAT_EQUALITY,
+        ErrorCode::ASSIGNMENT_AS_CONDITION,
+        ErrorCode::UNUSED_LOCAL,
+        ErrorCode::SHADOWED_VARIABLE,
+        ErrorCode::CONSTANT_CONDITION,
+        ErrorCode::EMPTY_BLOCK,
+        ErrorCode::SELF_ASSIGNMENT,
+    ];
+
+    /// Looks up an assigned code by its text, such as `"W0001"`.
+    pub fn parse(code: &str) -> Option<ErrorCode> {
+        ErrorCode::ALL
+            .iter()
+            .copied()
+            .find(|known| known.as_str() == code)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        self.0
+    }
+
+    pub fn is_warning(self) -> bool {
+        self.0.starts_with('W')
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
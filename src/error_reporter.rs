@@ -0,0 +1,56 @@
+use crate::{
+    diagnostic::{self, emit, Diagnostic, DiagnosticFormat},
+    lox_exception::RuntimeError,
+};
+use std::{cell::RefCell, rc::Rc, slice};
+
+/// Receives the errors and warnings found while scanning, parsing, resolving and running Lox
+/// code, as they are found. Embedders implement it to capture, translate or display them.
+pub trait ErrorReporter {
+    fn report(&mut self, diagnostic: &Diagnostic);
+
+    /// Called with the runtime error that stopped the statements being interpreted.
+    fn runtime_error(&mut self, error: &RuntimeError);
+}
+
+/// A reporter shared by the scanner, parser, resolver and interpreter working on the same code.
+pub type SharedReporter = Rc<RefCell<dyn ErrorReporter>>;
+
+/// The command line's reporter. Diagnostics go to stderr, printed by [`emit`], and runtime
+/// errors to stdout, unless JSON output is on, when they're emitted as diagnostics too.
+pub struct ConsoleReporter {
+    /// The code being compiled and run, which errors are quoted from.
+    source: Rc<str>,
+    quote_runtime_errors: bool,
+}
+
+impl ConsoleReporter {
+    pub fn new(source: &str) -> Self {
+        ConsoleReporter {
+            source: Rc::from(source),
+            quote_runtime_errors: true,
+        }
+    }
+
+    /// Whether runtime errors quote the line they occurred on. Turn this off when the code
+    /// being run may call functions defined in some other source.
+    pub fn set_quote_runtime_errors(&mut self, quote_runtime_errors: bool) {
+        self.quote_runtime_errors = quote_runtime_errors;
+    }
+}
+
+impl ErrorReporter for ConsoleReporter {
+    fn report(&mut self, diagnostic: &Diagnostic) {
+        emit(&self.source, slice::from_ref(diagnostic));
+    }
+
+    fn runtime_error(&mut self, error: &RuntimeError) {
+        match diagnostic::format() {
+            DiagnosticFormat::Json => emit(&self.source, &[error.diagnostic(&self.source)]),
+            DiagnosticFormat::Human if self.quote_runtime_errors => {
+                println!("{}", error.render(&self.source))
+            }
+            DiagnosticFormat::Human => println!("{error}"),
+        }
+    }
+}
@@ -1,42 +1,51 @@
-use crate::{lox_object::LoxLiteral, stmt::Stmt, token::Token};
+use crate::{lox_object::LoxLiteral, span::Span, stmt::Stmt, token::Token};
+use std::{cell::Cell, rc::Rc};
 
-pub trait ExprVisitor<'src, T> {
-    fn visit_binary_expr(&mut self, expr: &Binary<'src>) -> T;
-    fn visit_grouping_expr(&mut self, expr: &Grouping<'src>) -> T;
+pub trait ExprVisitor<T> {
+    fn visit_binary_expr(&mut self, expr: &Binary) -> T;
+    fn visit_grouping_expr(&mut self, expr: &Grouping) -> T;
     fn visit_literal_expr(&mut self, expr: &Literal) -> T;
-    fn visit_unary_expr(&mut self, expr: &Unary<'src>) -> T;
-    fn visit_ternary_expr(&mut self, expr: &Ternary<'src>) -> T;
-    fn visit_variable_expr(&mut self, expr: &Variable<'src>) -> T;
-    fn visit_assign_expr(&mut self, expr: &Assign<'src>) -> T;
-    fn visit_logical_expr(&mut self, expr: &Logical<'src>) -> T;
-    fn visit_call_expr(&mut self, expr: &Call<'src>) -> T;
-    fn visit_closure_expr(&mut self, expr: &Closure<'src>) -> T;
-    fn visit_get_expr(&mut self, expr: &Get<'src>) -> T;
-    fn visit_set_expr(&mut self, expr: &Set<'src>) -> T;
-    fn visit_this_expr(&mut self, expr: &This<'src>) -> T;
-    fn visit_super_expr(&mut self, expr: &Super<'src>) -> T;
-}
-
-#[derive(Debug, Clone)]
-pub enum Expr<'src> {
-    Binary(Binary<'src>),
-    Grouping(Grouping<'src>),
+    fn visit_unary_expr(&mut self, expr: &Unary) -> T;
+    fn visit_ternary_expr(&mut self, expr: &Ternary) -> T;
+    fn visit_variable_expr(&mut self, expr: &Variable) -> T;
+    fn visit_assign_expr(&mut self, expr: &Assign) -> T;
+    fn visit_logical_expr(&mut self, expr: &Logical) -> T;
+    fn visit_call_expr(&mut self, expr: &Call) -> T;
+    fn visit_closure_expr(&mut self, expr: &Rc<Closure>) -> T;
+    fn visit_get_expr(&mut self, expr: &Get) -> T;
+    fn visit_set_expr(&mut self, expr: &Set) -> T;
+    fn visit_this_expr(&mut self, expr: &This) -> T;
+    fn visit_super_expr(&mut self, expr: &Super) -> T;
+    fn visit_list_expr(&mut self, expr: &List) -> T;
+    fn visit_list_comprehension_expr(&mut self, expr: &ListComprehension) -> T;
+    fn visit_index_expr(&mut self, expr: &Index) -> T;
+    fn visit_set_index_expr(&mut self, expr: &SetIndex) -> T;
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Binary(Binary),
+    Grouping(Grouping),
     Literal(Literal),
-    Unary(Unary<'src>),
-    Ternary(Ternary<'src>),
-    Variable(Variable<'src>),
-    Assign(Assign<'src>),
-    Logical(Logical<'src>),
-    Call(Call<'src>),
-    Closure(Closure<'src>),
-    Get(Get<'src>),
-    Set(Set<'src>),
-    This(This<'src>),
-    Super(Super<'src>),
-}
-
-impl<'src> Expr<'src> {
-    pub fn accept<T>(&self, visitor: &mut dyn ExprVisitor<'src, T>) -> T {
+    Unary(Unary),
+    Ternary(Ternary),
+    Variable(Variable),
+    Assign(Assign),
+    Logical(Logical),
+    Call(Call),
+    Closure(Rc<Closure>),
+    Get(Get),
+    Set(Set),
+    This(This),
+    Super(Super),
+    List(List),
+    ListComprehension(ListComprehension),
+    Index(Index),
+    SetIndex(SetIndex),
+}
+
+impl Expr {
+    pub fn accept<T>(&self, visitor: &mut dyn ExprVisitor<T>) -> T {
         match self {
             Expr::Binary(binary) => visitor.visit_binary_expr(binary),
             Expr::Grouping(grouping) => visitor.visit_grouping_expr(grouping),
@@ -52,187 +61,371 @@ impl<'src> Expr<'src> {
             Expr::Set(set) => visitor.visit_set_expr(set),
             Expr::This(this) => visitor.visit_this_expr(this),
             Expr::Super(super_expr) => visitor.visit_super_expr(super_expr),
+            Expr::List(list) => visitor.visit_list_expr(list),
+            Expr::ListComprehension(comprehension) => {
+                visitor.visit_list_comprehension_expr(comprehension)
+            }
+            Expr::Index(index) => visitor.visit_index_expr(index),
+            Expr::SetIndex(set_index) => visitor.visit_set_index_expr(set_index),
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Binary(binary) => binary.span,
+            Expr::Grouping(grouping) => grouping.span,
+            Expr::Literal(literal) => literal.span,
+            Expr::Unary(unary) => unary.span,
+            Expr::Ternary(ternary) => ternary.span,
+            Expr::Variable(variable) => variable.span,
+            Expr::Assign(assign) => assign.span,
+            Expr::Logical(logical) => logical.span,
+            Expr::Call(call) => call.span,
+            Expr::Closure(closure) => closure.span,
+            Expr::Get(get) => get.span,
+            Expr::Set(set) => set.span,
+            Expr::This(this) => this.span,
+            Expr::Super(super_expr) => super_expr.span,
+            Expr::List(list) => list.span,
+            Expr::ListComprehension(comprehension) => comprehension.span,
+            Expr::Index(index) => index.span,
+            Expr::SetIndex(set_index) => set_index.span,
         }
     }
 }
 
 // Expression Types
 #[derive(Debug, Clone)]
-pub struct Binary<'src> {
-    pub left: Box<Expr<'src>>,
-    pub operator: Token<'src>,
-    pub right: Box<Expr<'src>>,
-}
-impl<'src> Binary<'src> {
-    pub fn new(left: Box<Expr<'src>>, operator: Token<'src>, right: Box<Expr<'src>>) -> Self {
+pub struct Binary {
+    pub left: Box<Expr>,
+    pub operator: Token,
+    pub right: Box<Expr>,
+    pub span: Span,
+}
+impl Binary {
+    pub fn new(left: Box<Expr>, operator: Token, right: Box<Expr>) -> Self {
+        let span = left.span().to(right.span());
         Binary {
             left,
             operator,
             right,
+            span,
         }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Grouping<'src> {
-    pub expression: Box<Expr<'src>>,
+pub struct Grouping {
+    pub expression: Box<Expr>,
+    pub span: Span,
 }
-impl<'src> Grouping<'src> {
-    pub fn new(expression: Box<Expr<'src>>) -> Self {
-        Grouping { expression }
+impl Grouping {
+    pub fn new(expression: Box<Expr>, span: Span) -> Self {
+        Grouping { expression, span }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Literal {
     pub value: LoxLiteral,
+    /// Index into the interpreter's constant pool, assigned during resolution.
+    pub constant: Cell<Option<usize>>,
+    pub span: Span,
 }
 impl Literal {
-    pub fn new(value: LoxLiteral) -> Self {
-        Literal { value }
+    pub fn new(value: LoxLiteral, span: Span) -> Self {
+        Literal {
+            value,
+            constant: Cell::new(None),
+            span,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Unary<'src> {
-    pub operator: Token<'src>,
-    pub right: Box<Expr<'src>>,
-}
-impl<'src> Unary<'src> {
-    pub fn new(operator: Token<'src>, right: Box<Expr<'src>>) -> Self {
-        Unary { operator, right }
+pub struct Unary {
+    pub operator: Token,
+    pub right: Box<Expr>,
+    pub span: Span,
+}
+impl Unary {
+    pub fn new(operator: Token, right: Box<Expr>) -> Self {
+        let span = operator.span().to(right.span());
+        Unary {
+            operator,
+            right,
+            span,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Ternary<'src> {
-    pub condition: Box<Expr<'src>>,
-    pub left: Box<Expr<'src>>,
-    pub right: Box<Expr<'src>>,
-}
-impl<'src> Ternary<'src> {
-    pub fn new(condition: Box<Expr<'src>>, left: Box<Expr<'src>>, right: Box<Expr<'src>>) -> Self {
+pub struct Ternary {
+    pub condition: Box<Expr>,
+    pub left: Box<Expr>,
+    pub right: Box<Expr>,
+    pub span: Span,
+}
+impl Ternary {
+    pub fn new(condition: Box<Expr>, left: Box<Expr>, right: Box<Expr>) -> Self {
+        let span = condition.span().to(right.span());
         Ternary {
             condition,
             left,
             right,
+            span,
         }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Variable<'src> {
-    pub name: Token<'src>,
-}
-impl<'src> Variable<'src> {
-    pub fn new(name: Token<'src>) -> Self {
-        Variable { name }
+pub struct Variable {
+    pub name: Token,
+    /// Depth and slot of the local variable used, assigned during resolution. `None` for
+    /// globals.
+    pub local: Cell<Option<(usize, usize)>>,
+    /// Slot of the global variable used, assigned during resolution. `None` for locals, and for
+    /// globals looked up by name because the resolver never saw them.
+    pub global: Cell<Option<usize>>,
+    pub span: Span,
+}
+impl Variable {
+    pub fn new(name: Token) -> Self {
+        let span = name.span();
+        Variable {
+            name,
+            local: Cell::new(None),
+            global: Cell::new(None),
+            span,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Assign<'src> {
-    pub name: Token<'src>,
-    pub value: Box<Expr<'src>>,
-}
-impl<'src> Assign<'src> {
-    pub fn new(name: Token<'src>, value: Box<Expr<'src>>) -> Self {
-        Assign { name, value }
+pub struct Assign {
+    pub name: Token,
+    pub value: Box<Expr>,
+    /// Depth and slot of the local variable used, assigned during resolution. `None` for
+    /// globals.
+    pub local: Cell<Option<(usize, usize)>>,
+    /// Slot of the global variable used, assigned during resolution. `None` for locals, and for
+    /// globals looked up by name because the resolver never saw them.
+    pub global: Cell<Option<usize>>,
+    pub span: Span,
+}
+impl Assign {
+    pub fn new(name: Token, value: Box<Expr>) -> Self {
+        let span = name.span().to(value.span());
+        Assign {
+            name,
+            value,
+            local: Cell::new(None),
+            global: Cell::new(None),
+            span,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Logical<'src> {
-    pub left: Box<Expr<'src>>,
-    pub operator: Token<'src>,
-    pub right: Box<Expr<'src>>,
-}
-impl<'src> Logical<'src> {
-    pub fn new(left: Box<Expr<'src>>, operator: Token<'src>, right: Box<Expr<'src>>) -> Self {
+pub struct Logical {
+    pub left: Box<Expr>,
+    pub operator: Token,
+    pub right: Box<Expr>,
+    pub span: Span,
+}
+impl Logical {
+    pub fn new(left: Box<Expr>, operator: Token, right: Box<Expr>) -> Self {
+        let span = left.span().to(right.span());
         Logical {
             left,
             operator,
             right,
+            span,
         }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Call<'src> {
-    pub callee: Box<Expr<'src>>,
-    pub paren: Token<'src>,
-    pub arguments: Vec<Expr<'src>>,
-}
-impl<'src> Call<'src> {
-    pub fn new(callee: Box<Expr<'src>>, paren: Token<'src>, arguments: Vec<Expr<'src>>) -> Self {
+pub struct Call {
+    pub callee: Box<Expr>,
+    pub paren: Token,
+    pub arguments: Vec<Expr>,
+    pub span: Span,
+}
+impl Call {
+    pub fn new(callee: Box<Expr>, paren: Token, arguments: Vec<Expr>) -> Self {
+        let span = callee.span().to(paren.span());
         Call {
             callee,
             paren,
             arguments,
+            span,
         }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Closure<'src> {
-    pub params: Vec<Token<'src>>,
-    pub body: Vec<Stmt<'src>>,
-}
-impl<'src> Closure<'src> {
-    pub fn new(params: Vec<Token<'src>>, body: Vec<Stmt<'src>>) -> Self {
-        Closure { params, body }
+pub struct Closure {
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+    /// The doc comment of the function or method declaring the closure.
+    pub doc: Option<Rc<str>>,
+    pub span: Span,
+}
+impl Closure {
+    pub fn new(params: Vec<Token>, body: Vec<Stmt>, doc: Option<Rc<str>>, span: Span) -> Self {
+        Closure {
+            params,
+            body,
+            doc,
+            span,
+        }
     }
 }
-impl<'src> PartialEq for Closure<'src> {
+impl PartialEq for Closure {
     fn eq(&self, _: &Self) -> bool {
         false
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Get<'src> {
-    pub object: Box<Expr<'src>>,
-    pub name: Token<'src>,
-}
-impl<'src> Get<'src> {
-    pub fn new(object: Box<Expr<'src>>, name: Token<'src>) -> Self {
-        Get { object, name }
+pub struct Get {
+    pub object: Box<Expr>,
+    pub name: Token,
+    pub span: Span,
+}
+impl Get {
+    pub fn new(object: Box<Expr>, name: Token) -> Self {
+        let span = object.span().to(name.span());
+        Get { object, name, span }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Set<'src> {
-    pub object: Box<Expr<'src>>,
-    pub name: Token<'src>,
-    pub value: Box<Expr<'src>>,
-}
-impl<'src> Set<'src> {
-    pub fn new(object: Box<Expr<'src>>, name: Token<'src>, value: Box<Expr<'src>>) -> Self {
+pub struct Set {
+    pub object: Box<Expr>,
+    pub name: Token,
+    pub value: Box<Expr>,
+    pub span: Span,
+}
+impl Set {
+    pub fn new(object: Box<Expr>, name: Token, value: Box<Expr>) -> Self {
+        let span = object.span().to(value.span());
         Set {
             object,
             name,
             value,
+            span,
         }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct This<'src> {
-    pub keyword: Token<'src>,
+pub struct This {
+    pub keyword: Token,
+    /// Depth and slot of `this`, assigned during resolution.
+    pub local: Cell<Option<(usize, usize)>>,
+    pub span: Span,
+}
+impl This {
+    pub fn new(keyword: Token) -> Self {
+        let span = keyword.span();
+        This {
+            keyword,
+            local: Cell::new(None),
+            span,
+        }
+    }
 }
-impl<'src> This<'src> {
-    pub fn new(keyword: Token<'src>) -> Self {
-        This { keyword }
+
+#[derive(Debug, Clone)]
+pub struct Super {
+    pub keyword: Token,
+    pub method: Token,
+    /// Depth and slot of `super`, assigned during resolution.
+    pub local: Cell<Option<(usize, usize)>>,
+    pub span: Span,
+}
+impl Super {
+    pub fn new(keyword: Token, method: Token) -> Self {
+        let span = keyword.span().to(method.span());
+        Super {
+            keyword,
+            method,
+            local: Cell::new(None),
+            span,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Super<'src> {
-    pub keyword: Token<'src>,
-    pub method: Token<'src>,
+pub struct List {
+    pub elements: Vec<Expr>,
+    pub span: Span,
 }
-impl<'src> Super<'src> {
-    pub fn new(keyword: Token<'src>, method: Token<'src>) -> Self {
-        Super { keyword, method }
+impl List {
+    pub fn new(elements: Vec<Expr>, span: Span) -> Self {
+        List { elements, span }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ListComprehension {
+    pub element: Box<Expr>,
+    pub name: Token,
+    pub iterable: Box<Expr>,
+    pub condition: Option<Box<Expr>>,
+    pub span: Span,
+}
+impl ListComprehension {
+    pub fn new(
+        element: Box<Expr>,
+        name: Token,
+        iterable: Box<Expr>,
+        condition: Option<Box<Expr>>,
+        span: Span,
+    ) -> Self {
+        ListComprehension {
+            element,
+            name,
+            iterable,
+            condition,
+            span,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Index {
+    pub object: Box<Expr>,
+    pub index: Box<Expr>,
+    pub span: Span,
+}
+impl Index {
+    pub fn new(object: Box<Expr>, index: Box<Expr>, span: Span) -> Self {
+        Index {
+            object,
+            index,
+            span,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SetIndex {
+    pub object: Box<Expr>,
+    pub index: Box<Expr>,
+    pub value: Box<Expr>,
+    pub span: Span,
+}
+impl SetIndex {
+    pub fn new(object: Box<Expr>, index: Box<Expr>, value: Box<Expr>) -> Self {
+        let span = object.span().to(value.span());
+        SetIndex {
+            object,
+            index,
+            value,
+            span,
+        }
     }
 }
@@ -0,0 +1,264 @@
+use crate::{
+    error_code::ErrorCode,
+    interpreter::Interpreter,
+    lox_exception::{LoxException, RuntimeError},
+    lox_object::{LoxLiteral, LoxObject},
+    run::{run_source, LoxError},
+};
+use std::{
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    ptr,
+};
+
+// Types `lox_value_type` reports.
+pub const LOX_NIL: c_int = 0;
+pub const LOX_BOOL: c_int = 1;
+pub const LOX_NUMBER: c_int = 2;
+pub const LOX_STRING: c_int = 3;
+/// Lists, instances, functions, classes and foreign values.
+pub const LOX_OBJECT: c_int = 4;
+
+/// An interpreter owned by a C host, along with the errors from its last run.
+pub struct LoxInterpreter {
+    interpreter: Interpreter,
+    last_error: Option<CString>,
+}
+
+/// A Lox value owned by a C host. Values returned by these functions must be freed with
+/// `lox_value_free`.
+pub struct LoxValue(LoxObject);
+
+/// A native function implemented in C. It's given the `user_data` it was registered with and
+/// the call's arguments, which are only valid during the call. It returns the result, which the
+/// interpreter takes ownership of, or null to fail with a runtime error.
+pub type LoxNativeCallback = extern "C" fn(
+    user_data: *mut c_void,
+    arguments: *const *const LoxValue,
+    count: usize,
+) -> *mut LoxValue;
+
+/// Creates an interpreter with the built-in natives, to be freed with `lox_free_interpreter`.
+#[no_mangle]
+pub extern "C" fn lox_new_interpreter() -> *mut LoxInterpreter {
+    Box::into_raw(Box::new(LoxInterpreter {
+        interpreter: Interpreter::new(),
+        last_error: None,
+    }))
+}
+
+/// # Safety
+///
+/// `interpreter` must come from `lox_new_interpreter` and not have been freed, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn lox_free_interpreter(interpreter: *mut LoxInterpreter) {
+    if !interpreter.is_null() {
+        drop(Box::from_raw(interpreter));
+    }
+}
+
+/// Runs the NUL-terminated UTF-8 `source`, returning 0 on success, 65 if it has compile errors
+/// and 70 if it fails while running, like the command line's exit codes. The errors can then be
+/// read with `lox_last_error`.
+///
+/// # Safety
+///
+/// `interpreter` must be a live interpreter and `source` a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn lox_run(interpreter: *mut LoxInterpreter, source: *const c_char) -> c_int {
+    let host = &mut *interpreter;
+    let Ok(source) = CStr::from_ptr(source).to_str() else {
+        host.last_error = Some(c_string("Source is not valid UTF-8."));
+        return 65;
+    };
+    match run_source(source, &mut host.interpreter) {
+        Ok(()) => {
+            host.last_error = None;
+            0
+        }
+        Err(errors) => {
+            let status = match errors.first() {
+                Some(LoxError::Static(_)) => 65,
+                _ => 70,
+            };
+            let rendered: Vec<_> = errors.iter().map(|error| error.render(source)).collect();
+            host.last_error = Some(c_string(&rendered.join("\n")));
+            status
+        }
+    }
+}
+
+/// The errors from the last call to `lox_run`, one per line, or null if it succeeded. The
+/// string belongs to the interpreter and is only valid until the next run.
+///
+/// # Safety
+///
+/// `interpreter` must be a live interpreter.
+#[no_mangle]
+pub unsafe extern "C" fn lox_last_error(interpreter: *const LoxInterpreter) -> *const c_char {
+    match (*interpreter).last_error {
+        Some(ref error) => error.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Defines a global function `name` taking `arity` arguments that calls `callback`, returning
+/// 0, or -1 if `name` isn't valid UTF-8.
+///
+/// # Safety
+///
+/// `interpreter` must be a live interpreter and `name` a valid C string. `user_data` is passed
+/// to `callback` as is, and must stay valid as long as the interpreter does.
+#[no_mangle]
+pub unsafe extern "C" fn lox_register_native(
+    interpreter: *mut LoxInterpreter,
+    name: *const c_char,
+    arity: usize,
+    callback: LoxNativeCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    let Ok(name) = CStr::from_ptr(name).to_str() else {
+        return -1;
+    };
+    let message = format!("{name}() failed.");
+    (*interpreter)
+        .interpreter
+        .register_native(name, arity, move |_, arguments, span| {
+            let arguments: Vec<_> = arguments.into_iter().map(LoxValue).collect();
+            let pointers: Vec<*const LoxValue> =
+                arguments.iter().map(|arg| arg as *const _).collect();
+            let result = callback(user_data, pointers.as_ptr(), pointers.len());
+            if result.is_null() {
+                Err(LoxException::RuntimeError(RuntimeError::new(
+                    ErrorCode::NATIVE_FUNCTION,
+                    span,
+                    message.clone(),
+                )))
+            } else {
+                Ok(Box::from_raw(result).0)
+            }
+        });
+    0
+}
+
+/// The value of the global `name`, or null if it isn't defined.
+///
+/// # Safety
+///
+/// `interpreter` must be a live interpreter and `name` a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn lox_get_global(
+    interpreter: *const LoxInterpreter,
+    name: *const c_char,
+) -> *mut LoxValue {
+    let global = CStr::from_ptr(name)
+        .to_str()
+        .ok()
+        .and_then(|name| (*interpreter).interpreter.global(name));
+    match global {
+        Some(value) => new_value(value),
+        None => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn lox_value_nil() -> *mut LoxValue {
+    new_value(LoxObject::from(()))
+}
+
+#[no_mangle]
+pub extern "C" fn lox_value_bool(value: bool) -> *mut LoxValue {
+    new_value(LoxObject::from(value))
+}
+
+#[no_mangle]
+pub extern "C" fn lox_value_number(value: f64) -> *mut LoxValue {
+    new_value(LoxObject::from(value))
+}
+
+/// A string value copied from the NUL-terminated `value`, or null if it isn't valid UTF-8.
+///
+/// # Safety
+///
+/// `value` must be a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_string(value: *const c_char) -> *mut LoxValue {
+    match CStr::from_ptr(value).to_str() {
+        Ok(value) => new_value(LoxObject::from(value)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// One of the `LOX_` type constants.
+///
+/// # Safety
+///
+/// `value` must be a live value.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_type(value: *const LoxValue) -> c_int {
+    match (*value).0 {
+        LoxObject::Literal(LoxLiteral::Nil) => LOX_NIL,
+        LoxObject::Literal(LoxLiteral::Boolean(_)) => LOX_BOOL,
+        LoxObject::Literal(LoxLiteral::Number(_)) => LOX_NUMBER,
+        LoxObject::Literal(LoxLiteral::String(_)) => LOX_STRING,
+        _ => LOX_OBJECT,
+    }
+}
+
+/// The value if it's a boolean, or false.
+///
+/// # Safety
+///
+/// `value` must be a live value.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_as_bool(value: *const LoxValue) -> bool {
+    (*value).0.as_bool().unwrap_or(false)
+}
+
+/// The value if it's a number, or NaN.
+///
+/// # Safety
+///
+/// `value` must be a live value.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_as_number(value: *const LoxValue) -> f64 {
+    (*value).0.as_f64().unwrap_or(f64::NAN)
+}
+
+/// The value as `print` shows it, to be freed with `lox_string_free`.
+///
+/// # Safety
+///
+/// `value` must be a live value.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_to_string(value: *const LoxValue) -> *mut c_char {
+    c_string(&(*value).0.to_string()).into_raw()
+}
+
+/// # Safety
+///
+/// `value` must come from one of these functions and not have been freed, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn lox_value_free(value: *mut LoxValue) {
+    if !value.is_null() {
+        drop(Box::from_raw(value));
+    }
+}
+
+/// # Safety
+///
+/// `string` must come from `lox_value_to_string` and not have been freed, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn lox_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+fn new_value(value: LoxObject) -> *mut LoxValue {
+    Box::into_raw(Box::new(LoxValue(value)))
+}
+
+/// `text` as a C string, with any NUL bytes in it dropped.
+fn c_string(text: &str) -> CString {
+    CString::new(text.replace('\0', "")).expect("Expect NUL bytes to have been removed.")
+}
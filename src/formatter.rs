@@ -0,0 +1,204 @@
+use crate::{
+    diagnostic::{emit, has_errors},
+    parser::Parser,
+    scanner::Scanner,
+    token::Token,
+    token_type::TokenType,
+};
+
+const INDENT: &str = "  ";
+
+/// Reformats `source`: indentation follows bracket nesting, spacing within lines is
+/// normalized, and runs of blank lines collapse to one. Line breaks and comments are kept.
+/// Returns `None` if the source has scan or parse errors, which are reported as usual.
+pub fn format(source: &str) -> Option<String> {
+    let mut scanner = Scanner::new(source);
+    scanner.scan_tokens();
+    emit(source, &scanner.diagnostics);
+    let tokens = scanner.tokens;
+    let mut parser = Parser::new(tokens.clone());
+    let parse_result = parser.parse();
+    emit(source, &parser.diagnostics);
+    parse_result.ok()?;
+    if has_errors(&scanner.diagnostics) {
+        return None;
+    }
+
+    let mut formatter = Formatter::default();
+    let mut position = 0;
+    for token in tokens.iter() {
+        // Whitespace and comments never become tokens, so lay out the gap before each token.
+        formatter.gap(&source[position..token.start]);
+        if token.token_type != TokenType::Eof {
+            formatter.token(token);
+        }
+        position = token.start + token.lexeme.len();
+    }
+    formatter.gap(&source[position..]);
+    formatter.end_line();
+    Some(formatter.out)
+}
+
+#[derive(Default)]
+struct Formatter {
+    out: String,
+    /// Text of the line being built, without its indentation.
+    line: String,
+    /// Bracket nesting depth at the start of the current line.
+    indent: usize,
+    depth: usize,
+    /// The last token on the current line, and whether it was a unary operator.
+    previous: Option<(TokenType, bool)>,
+    /// The type of the last token written, on any line.
+    last: Option<TokenType>,
+}
+
+impl Formatter {
+    fn gap(&mut self, gap: &str) {
+        let mut newlines = 0;
+        let mut rest = gap;
+        while let Some(c) = rest.chars().next() {
+            if rest.starts_with("//") {
+                let end = rest.find('\n').unwrap_or(rest.len());
+                self.comment(&rest[..end], newlines);
+                newlines = 0;
+                rest = &rest[end..];
+            } else if rest.starts_with("/*") {
+                let end = block_comment_len(rest);
+                self.comment(&rest[..end], newlines);
+                newlines = 0;
+                rest = &rest[end..];
+            } else {
+                if c == '\n' {
+                    newlines += 1;
+                }
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        self.line_breaks(newlines);
+    }
+
+    /// Ends the current line after `newlines` line breaks, keeping at most one blank line.
+    fn line_breaks(&mut self, newlines: usize) {
+        if newlines == 0 {
+            return;
+        }
+        self.end_line();
+        if newlines > 1 && !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn comment(&mut self, comment: &str, newlines_before: usize) {
+        self.line_breaks(newlines_before);
+        self.push(comment, true);
+        self.previous = None;
+    }
+
+    fn token(&mut self, token: &Token) {
+        let token_type = token.token_type;
+        if is_closer(token_type) {
+            self.depth = self.depth.saturating_sub(1);
+        }
+        let space = match self.previous {
+            Some((previous, unary)) => needs_space(previous, unary, token_type),
+            None => true,
+        };
+        self.push(&token.lexeme, space);
+        let unary = match token_type {
+            TokenType::Bang => true,
+            TokenType::Minus => !self.last.is_some_and(ends_operand),
+            _ => false,
+        };
+        self.previous = Some((token_type, unary));
+        self.last = Some(token_type);
+        if is_opener(token_type) {
+            self.depth += 1;
+        }
+    }
+
+    fn push(&mut self, text: &str, space: bool) {
+        if self.line.is_empty() {
+            self.indent = self.depth;
+        } else if space {
+            self.line.push(' ');
+        }
+        self.line.push_str(text);
+    }
+
+    fn end_line(&mut self) {
+        if !self.line.is_empty() {
+            self.out.push_str(&INDENT.repeat(self.indent));
+            self.out.push_str(self.line.trim_end());
+            self.out.push('\n');
+            self.line.clear();
+        }
+        self.previous = None;
+    }
+}
+
+/// Length of the block comment at the start of `text`, which may contain nested comments.
+fn block_comment_len(text: &str) -> usize {
+    let mut depth = 0;
+    let mut idx = 0;
+    while idx < text.len() {
+        if text[idx..].starts_with("/*") {
+            depth += 1;
+            idx += 2;
+        } else if text[idx..].starts_with("*/") {
+            depth -= 1;
+            idx += 2;
+            if depth == 0 {
+                return idx;
+            }
+        } else {
+            idx += text[idx..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    text.len()
+}
+
+fn is_opener(token_type: TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace
+    )
+}
+
+fn is_closer(token_type: TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace
+    )
+}
+
+/// Whether a token of this type can end an operand, making a following `-` binary and a
+/// following `(` or `[` a call or index.
+fn ends_operand(token_type: TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::Identifier
+            | TokenType::String
+            | TokenType::Number
+            | TokenType::True
+            | TokenType::False
+            | TokenType::Nil
+            | TokenType::This
+            | TokenType::RightParen
+            | TokenType::RightBracket
+    )
+}
+
+fn needs_space(previous: TokenType, previous_unary: bool, next: TokenType) -> bool {
+    match (previous, next) {
+        (_, TokenType::RightParen | TokenType::RightBracket) => false,
+        (_, TokenType::Comma | TokenType::Semicolon | TokenType::Dot) => false,
+        (TokenType::LeftParen | TokenType::LeftBracket | TokenType::Dot | TokenType::At, _) => {
+            false
+        }
+        (TokenType::LeftBrace, TokenType::RightBrace) => false,
+        _ if previous_unary => false,
+        (_, TokenType::LeftParen | TokenType::LeftBracket) => !ends_operand(previous),
+        _ => true,
+    }
+}
@@ -0,0 +1,93 @@
+use crate::scanner::Scanner;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HighlightFormat {
+    /// A `<pre class="lox">` block where every token is wrapped in a
+    /// `<span class="lox-{category}">`, ready to be styled with CSS.
+    Html,
+    /// ANSI escape codes for printing to a terminal.
+    Ansi,
+}
+
+/// Renders `source` with syntax highlighting, preserving its whitespace and comments.
+pub fn highlight(source: &str, format: HighlightFormat) -> String {
+    let mut scanner = Scanner::new(source);
+    scanner.scan_tokens();
+
+    let mut output = String::with_capacity(source.len() * 2);
+    if format == HighlightFormat::Html {
+        output.push_str("<pre class=\"lox\"><code>");
+    }
+
+    let mut position = 0;
+    for token in scanner.tokens.iter() {
+        // Whitespace and comments never become tokens, so render the gap before each token.
+        write_gap(&mut output, &source[position..token.start], format);
+        write_styled(
+            &mut output,
+            &token.lexeme,
+            token.token_type.category().name(),
+            format,
+        );
+        position = token.start + token.lexeme.len();
+    }
+    write_gap(&mut output, &source[position..], format);
+
+    if format == HighlightFormat::Html {
+        output.push_str("</code></pre>\n");
+    }
+    output
+}
+
+fn write_gap(output: &mut String, gap: &str, format: HighlightFormat) {
+    let trimmed = gap.trim_start();
+    let leading = &gap[..gap.len() - trimmed.len()];
+    let text = trimmed.trim_end();
+    let trailing = &trimmed[text.len()..];
+
+    output.push_str(leading);
+    if !text.is_empty() {
+        // Anything left between tokens is a comment, or characters the scanner rejected.
+        let class = match text.starts_with('/') {
+            true => "comment",
+            false => "error",
+        };
+        write_styled(output, text, class, format);
+    }
+    output.push_str(trailing);
+}
+
+fn write_styled(output: &mut String, text: &str, class: &str, format: HighlightFormat) {
+    if text.is_empty() {
+        return;
+    }
+    match format {
+        HighlightFormat::Html => {
+            output.push_str(&format!("<span class=\"lox-{class}\">"));
+            for c in text.chars() {
+                match c {
+                    '&' => output.push_str("&amp;"),
+                    '<' => output.push_str("&lt;"),
+                    '>' => output.push_str("&gt;"),
+                    '"' => output.push_str("&quot;"),
+                    _ => output.push(c),
+                }
+            }
+            output.push_str("</span>");
+        }
+        HighlightFormat::Ansi => {
+            let color = match class {
+                "keyword" => "35",
+                "string" => "32",
+                "number" | "literal" => "33",
+                "comment" => "90",
+                "error" => "31",
+                _ => {
+                    output.push_str(text);
+                    return;
+                }
+            };
+            output.push_str(&format!("\x1b[{color}m{text}\x1b[0m"));
+        }
+    }
+}
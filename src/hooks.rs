@@ -0,0 +1,78 @@
+use crate::{environment::Environment, lox_object::LoxObject, span::Span};
+use std::{cell::RefCell, rc::Rc};
+
+/// Callbacks the interpreter makes as it runs a script, for debuggers, coverage tools and audit
+/// logs. Every method does nothing by default, so implementations only override the events
+/// they need. Install them with [`Interpreter::set_hooks`].
+///
+/// [`Interpreter::set_hooks`]: crate::interpreter::Interpreter::set_hooks
+pub trait ExecutionHooks {
+    /// A statement spanning `span` is about to run, with the variables in `scope` visible.
+    fn statement(&mut self, _span: Span, _scope: &Scope) {}
+
+    /// The function, native function or class `name` is about to be called from `span`.
+    /// Anonymous functions have no name.
+    fn call(&mut self, _name: Option<&str>, _span: Span) {}
+
+    /// The call [`ExecutionHooks::call`] reported has returned, whether with a value or an
+    /// error.
+    fn return_from(&mut self, _name: Option<&str>, _span: Span) {}
+
+    /// `value` was assigned to the variable `name`, whose name spans `span`. Definitions with
+    /// `var` aren't assignments.
+    fn assign(&mut self, _name: &str, _value: &LoxObject, _span: Span) {}
+}
+
+/// The variables visible where a statement is about to run, found by walking the chain of
+/// environments from the innermost block out to the globals.
+pub struct Scope<'a> {
+    environment: &'a Rc<RefCell<Environment>>,
+}
+
+impl<'a> Scope<'a> {
+    pub(crate) fn new(environment: &'a Rc<RefCell<Environment>>) -> Self {
+        Scope { environment }
+    }
+
+    /// The local variables of each enclosing scope, innermost first. Within a scope they're in
+    /// the order they were defined. Methods see `this`, and methods of subclasses `super`.
+    pub fn locals(&self) -> Vec<Vec<(Rc<str>, LoxObject)>> {
+        let mut locals = Vec::new();
+        let mut environment = Some(Rc::clone(self.environment));
+        while let Some(current) = environment {
+            let current = current.borrow();
+            if current.is_global() {
+                break;
+            }
+            locals.push(current.variables());
+            environment = current.enclosing.clone();
+        }
+        locals
+    }
+
+    /// Every global variable, including natives, in the order they were defined.
+    pub fn globals(&self) -> Vec<(Rc<str>, LoxObject)> {
+        let mut environment = Rc::clone(self.environment);
+        loop {
+            let enclosing = environment.borrow().enclosing.clone();
+            match enclosing {
+                Some(enclosing) => environment = enclosing,
+                None => return environment.borrow().variables(),
+            }
+        }
+    }
+
+    /// The variable `name` as code running here would see it, looking in the innermost scope
+    /// first.
+    pub fn get(&self, name: &str) -> Option<LoxObject> {
+        let mut environment = Some(Rc::clone(self.environment));
+        while let Some(current) = environment {
+            let current = current.borrow();
+            if let Some(value) = current.lookup(name) {
+                return Some(value);
+            }
+            environment = current.enclosing.clone();
+        }
+        None
+    }
+}
@@ -1,76 +1,744 @@
 use crate::{
+    ast_printer::summarize,
+    cancellation::CancellationToken,
+    clock::{default_clock, Clock},
+    compiler::{Chunk, Compiler},
+    coverage::LineCoverage,
+    cycle_collector,
+    diagnostic::{has_errors, Diagnostic},
     environment::Environment,
+    error_code::ErrorCode,
+    error_reporter::SharedReporter,
     expr::{
-        Assign, Binary, Call, Closure, Expr, ExprVisitor, Get, Grouping, Literal, Logical, Set,
-        Super, Ternary, This, Unary, Variable,
+        Assign, Binary, Call, Closure, Expr, ExprVisitor, Get, Grouping, Index, List,
+        ListComprehension, Literal, Logical, Set, SetIndex, Super, Ternary, This, Unary, Variable,
     },
+    heap,
+    hooks::{ExecutionHooks, Scope},
     lox_callable::LoxCallable,
     lox_class::LoxClass,
-    lox_exception::{LoxException, RuntimeError},
+    lox_exception::{CallFrame, LoxException, RuntimeError},
+    lox_foreign::LoxForeign,
     lox_function::LoxFunction,
-    lox_object::{LoxLiteral, LoxObject},
+    lox_instance::LoxInstance,
+    lox_object::{LiteralKey, LoxLiteral, LoxObject},
+    lox_string::LoxString,
     native_function::NativeFunction,
-    stmt::{Block, Class, Expression, Function, If, Print, Return, Stmt, StmtVisitor, Var, While},
+    natives,
+    parser::Parser,
+    profiler::{ProfileEntry, Profiler},
+    random::Random,
+    resolver::Resolver,
+    scanner::Scanner,
+    span::Span,
+    stmt::{
+        Block, Break, Class, Destructure, Expression, For, ForIn, Function, If, Print, Return,
+        Stmt, StmtVisitor, Var, While,
+    },
     token::Token,
     token_type::TokenType,
 };
-use std::{cell::RefCell, collections::HashMap, rc::Rc, time::SystemTime};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    io::{self, BufRead, Write},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+pub use crate::environment::GlobalsSnapshot;
+
+type ConstructionObserver = Box<dyn FnMut(&str)>;
+
+/// Native stack to allow for each Lox call. An unoptimized build uses about 16 KiB for a call
+/// a few expressions deep; an optimized one about a quarter of that.
+pub const STACK_PER_CALL: usize = 24 * 1024;
+
+/// Most calls that can be in progress at once on a stack of `stack_size` bytes.
+pub const fn max_call_depth_for(stack_size: usize) -> usize {
+    stack_size / STACK_PER_CALL
+}
+
+/// Default for [`Interpreter::set_max_call_depth`]: as many calls as fit in the 8 MiB stack of
+/// a main thread. Raise it with [`max_call_depth_for`] when running on a bigger stack.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = max_call_depth_for(8 * 1024 * 1024);
+
+/// Statements between checks for whether the cycle collector is due to run.
+const COLLECT_INTERVAL: usize = 1024;
+
+/// Most distinct literal values pooled by one interpreter, so that resolving new code
+/// forever, as a long REPL session does, can't grow the pool without bound.
+const MAX_CONSTANTS: usize = 1 << 16;
+
+/// How long `sleep()` blocks between checks for an interrupt or cancellation.
+const SLEEP_SLICE: Duration = Duration::from_millis(10);
+
+/// How [`Interpreter::interpret`] runs statements.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// Walks the syntax tree. The reference implementation, and the only one the REPL uses.
+    #[default]
+    TreeWalk,
+    /// Compiles the statements to bytecode first and runs that on a stack machine.
+    Vm,
+}
+
+/// Iteration state shared by for-in loops and list comprehensions.
+pub(crate) enum LoxIterator {
+    List(Rc<RefCell<Vec<LoxObject>>>, usize),
+    Protocol(Rc<RefCell<LoxInstance>>),
+}
 
-pub struct Interpreter<'src> {
-    pub globals: Rc<RefCell<Environment<'src>>>,
-    pub environment: Rc<RefCell<Environment<'src>>>,
-    locals: HashMap<Token<'src>, usize>,
+pub struct Interpreter {
+    pub globals: Rc<RefCell<Environment>>,
+    pub environment: Rc<RefCell<Environment>>,
     active_break: bool,
+    construction_counts: HashMap<Rc<str>, usize>,
+    construction_observer: Option<ConstructionObserver>,
+    random: Random,
+    /// Where the time natives get the time from.
+    clock: Box<dyn Clock>,
+    /// Literal values shared by every literal with the same value, indexed by
+    /// `Literal::constant`.
+    constants: Vec<LoxObject>,
+    /// Where each value in `constants` is, so each is pooled once.
+    constant_indices: HashMap<LiteralKey, usize>,
+    statements_executed: usize,
+    /// Value of `statements_executed` past which statements raise an error rather than run.
+    statement_limit: Option<usize>,
+    /// Most bytes the heap may hold before statements raise an error rather than run.
+    memory_limit: Option<usize>,
+    calls_made: usize,
+    /// Calls in progress, outermost first.
+    call_stack: Vec<CallFrame>,
+    max_call_depth: usize,
+    reporter: Option<SharedReporter>,
+    interrupted: Arc<AtomicBool>,
+    cancellation: CancellationToken,
+    trace: bool,
+    backend: Backend,
+    profiler: Option<Profiler>,
+    coverage: Option<LineCoverage>,
+    /// Where print statements write.
+    output: Box<dyn Write>,
+    hooks: Option<Box<dyn ExecutionHooks>>,
+    /// Where `readLine()` and `input()` read from, or stdin if unset. Stdin isn't held here
+    /// so the REPL can keep reading from it between evaluations.
+    input: Option<Box<dyn BufRead>>,
 }
 
-impl<'src> Interpreter<'src> {
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Configures an [`Interpreter`] before it's created, for programs embedding it. Anything left
+/// unset keeps the default [`Interpreter::new`] uses.
+#[derive(Default)]
+pub struct InterpreterBuilder {
+    script_args: Vec<String>,
+    max_call_depth: Option<usize>,
+    max_ops: Option<usize>,
+    max_memory: Option<usize>,
+    output: Option<Box<dyn Write>>,
+    input: Option<Box<dyn BufRead>>,
+    hooks: Option<Box<dyn ExecutionHooks>>,
+    clock: Option<Box<dyn Clock>>,
+    /// Leaves out every built-in native, not just those in `disabled_natives`.
+    no_natives: bool,
+    disabled_natives: HashSet<String>,
+    backend: Backend,
+    trace: bool,
+    trace_env: bool,
+    profiling: bool,
+    coverage: bool,
+}
+
+impl InterpreterBuilder {
     pub fn new() -> Self {
-        let globals = Rc::new(RefCell::new(Environment::new(None)));
-        // Implement global "clock" function.
-        let clock_function = |_: &mut Interpreter, _: Vec<LoxObject<'src>>| {
-            LoxObject::Literal(LoxLiteral::Number(
-                SystemTime::now()
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .expect("SystemTime should be after UNIX EPOCH in global clock function.")
-                    .as_secs_f64(),
-            ))
-        };
-        let global_clock = LoxObject::Callable(LoxCallable::NativeFun(Rc::new(
-            NativeFunction::new(clock_function, 0, String::from("<native fn>")),
-        )));
+        Self::default()
+    }
+
+    /// See [`Interpreter::set_script_args`].
+    pub fn script_args(mut self, args: Vec<String>) -> Self {
+        self.script_args = args;
+        self
+    }
+
+    /// See [`Interpreter::set_max_call_depth`].
+    pub fn max_call_depth(mut self, max_call_depth: usize) -> Self {
+        self.max_call_depth = Some(max_call_depth);
+        self
+    }
+
+    /// See [`Interpreter::set_max_ops`].
+    pub fn max_ops(mut self, max_ops: Option<usize>) -> Self {
+        self.max_ops = max_ops;
+        self
+    }
+
+    /// See [`Interpreter::set_max_memory`].
+    pub fn max_memory(mut self, max_memory: Option<usize>) -> Self {
+        self.max_memory = max_memory;
+        self
+    }
+
+    /// See [`Interpreter::set_output`].
+    pub fn output(mut self, output: impl Write + 'static) -> Self {
+        self.output = Some(Box::new(output));
+        self
+    }
+
+    /// See [`Interpreter::set_input`].
+    pub fn input(mut self, input: impl BufRead + 'static) -> Self {
+        self.input = Some(Box::new(input));
+        self
+    }
+
+    /// See [`Interpreter::set_hooks`].
+    pub fn hooks(mut self, hooks: impl ExecutionHooks + 'static) -> Self {
+        self.hooks = Some(Box::new(hooks));
+        self
+    }
+
+    /// See [`Interpreter::set_clock`].
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Some(Box::new(clock));
+        self
+    }
+
+    /// Leaves out every built-in native function, such as `clock()` and `readLine()`, so
+    /// scripts can only call the natives registered with [`Interpreter::register_native`].
+    pub fn without_natives(mut self) -> Self {
+        self.no_natives = true;
+        self
+    }
+
+    /// Leaves out the built-in native function `name`, such as `getenv`.
+    pub fn without_native(mut self, name: &str) -> Self {
+        self.disabled_natives.insert(String::from(name));
+        self
+    }
+
+    /// See [`Interpreter::set_backend`].
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// See [`Interpreter::set_trace`].
+    pub fn trace(mut self, trace: bool) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// See [`Interpreter::set_trace_env`].
+    pub fn trace_env(mut self, trace_env: bool) -> Self {
+        self.trace_env = trace_env;
+        self
+    }
+
+    /// See [`Interpreter::set_profiling`].
+    pub fn profiling(mut self, profiling: bool) -> Self {
+        self.profiling = profiling;
+        self
+    }
+
+    /// See [`Interpreter::set_coverage`].
+    pub fn coverage(mut self, coverage: bool) -> Self {
+        self.coverage = coverage;
+        self
+    }
+
+    pub fn build(self) -> Interpreter {
+        let mut interpreter = Interpreter::with_natives(|name| {
+            !self.no_natives && !self.disabled_natives.contains(name)
+        });
+        interpreter.set_script_args(self.script_args);
+        if let Some(max_call_depth) = self.max_call_depth {
+            interpreter.set_max_call_depth(max_call_depth);
+        }
+        interpreter.set_max_ops(self.max_ops);
+        interpreter.set_max_memory(self.max_memory);
+        if let Some(output) = self.output {
+            interpreter.output = output;
+        }
+        interpreter.input = self.input;
+        interpreter.hooks = self.hooks;
+        if let Some(clock) = self.clock {
+            interpreter.clock = clock;
+        }
+        interpreter.set_backend(self.backend);
+        interpreter.set_trace(self.trace);
+        interpreter.set_trace_env(self.trace_env);
+        interpreter.set_profiling(self.profiling);
+        interpreter.set_coverage(self.coverage);
+        interpreter
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter::with_natives(|_| true)
+    }
+
+    /// Starts configuring an interpreter; see [`InterpreterBuilder`].
+    pub fn builder() -> InterpreterBuilder {
+        InterpreterBuilder::new()
+    }
 
-        globals.borrow_mut().define("clock", global_clock);
+    /// An interpreter defining the built-in natives whose names `enabled` accepts.
+    fn with_natives(enabled: impl Fn(&str) -> bool) -> Self {
+        let globals = Rc::new(RefCell::new(Environment::new(None)));
+        natives::define_natives(&mut globals.borrow_mut(), enabled);
+        globals
+            .borrow_mut()
+            .define("args", LoxObject::List(Rc::new(RefCell::new(Vec::new()))));
 
         let environment = Rc::clone(&globals);
+        let clock = default_clock();
+        let seed = clock.since_epoch().as_nanos() as u64;
 
         Interpreter {
             globals,
             environment,
-            locals: HashMap::new(),
             active_break: false,
+            construction_counts: HashMap::new(),
+            construction_observer: None,
+            random: Random::new(seed),
+            clock,
+            constants: Vec::new(),
+            constant_indices: HashMap::new(),
+            statements_executed: 0,
+            statement_limit: None,
+            memory_limit: None,
+            calls_made: 0,
+            call_stack: Vec::new(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            reporter: None,
+            interrupted: Arc::new(AtomicBool::new(false)),
+            cancellation: CancellationToken::default(),
+            trace: false,
+            backend: Backend::TreeWalk,
+            profiler: None,
+            coverage: None,
+            output: Box::new(io::stdout()),
+            input: None,
+            hooks: None,
+        }
+    }
+
+    /// Names of every global, including natives.
+    pub fn global_names(&self) -> Vec<String> {
+        self.globals.borrow().names().map(String::from).collect()
+    }
+
+    /// The value of the global `name`, if it's defined.
+    pub fn global(&self, name: &str) -> Option<LoxObject> {
+        self.globals.borrow().lookup(name)
+    }
+
+    /// Replaces the `args` global with the given script arguments.
+    pub fn set_script_args(&mut self, args: Vec<String>) {
+        let args = args
+            .into_iter()
+            .map(|arg| LoxObject::Literal(LoxLiteral::String(Rc::new(LoxString::from(arg)))))
+            .collect();
+        self.globals
+            .borrow_mut()
+            .define("args", LoxObject::List(Rc::new(RefCell::new(args))));
+    }
+
+    /// Defines a global function `name` that runs `function`, so host applications can extend
+    /// the runtime. Calls with a number of arguments other than `arity` are rejected before
+    /// `function` runs. Registering a name that's already defined replaces it.
+    pub fn register_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&mut Interpreter, Vec<LoxObject>, Span) -> Result<LoxObject, LoxException>
+            + 'static,
+    ) {
+        let native = NativeFunction::named(name, arity, function);
+        self.globals.borrow_mut().define(
+            name,
+            LoxObject::Callable(LoxCallable::NativeFun(Rc::new(native))),
+        );
+    }
+
+    /// Defines a global `name` holding `functions` as properties, so a group of related natives
+    /// can be called as `name.function()` without each taking a global name of its own. The
+    /// module is an instance of a class called `name` with no methods. Registering a name
+    /// that's already defined replaces it.
+    pub fn register_module(
+        &mut self,
+        name: &str,
+        functions: impl IntoIterator<Item = NativeFunction>,
+    ) {
+        let class = LoxClass::new(Rc::from(name), None, HashMap::new());
+        let mut module = LoxInstance::new(Rc::new(class));
+        for function in functions {
+            let name: Rc<str> = Rc::from(function.name());
+            module.set_field(
+                name,
+                LoxObject::Callable(LoxCallable::NativeFun(Rc::new(function))),
+            );
+        }
+        self.globals
+            .borrow_mut()
+            .define(name, LoxObject::Instance(Rc::new(RefCell::new(module))));
+    }
+
+    /// Sends the output of print statements to `output` instead of stdout, so it can be
+    /// captured or written to a file.
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        self.output = Box::new(output);
+    }
+
+    /// Makes `readLine()` and `input()` read from `input` instead of stdin, so scripts can be
+    /// fed input from a string or a file.
+    pub fn set_input(&mut self, input: impl BufRead + 'static) {
+        self.input = Some(Box::new(input));
+    }
+
+    /// Reads a line for `readLine()` into `line`, returning how many bytes were read.
+    pub(crate) fn read_line(&mut self, line: &mut String) -> io::Result<usize> {
+        match self.input {
+            Some(ref mut input) => input.read_line(line),
+            None => io::stdin().lock().read_line(line),
+        }
+    }
+
+    /// The global variables as they are now, for [`Interpreter::restore`] to roll back to.
+    /// Snapshots are cheap: lists and instances are shared rather than copied, so changes to
+    /// their contents aren't rolled back.
+    pub fn snapshot(&self) -> GlobalsSnapshot {
+        self.globals.borrow().snapshot_globals()
+    }
+
+    /// Rolls the global variables back to `snapshot`, undoing definitions and assignments made
+    /// since it was taken.
+    pub fn restore(&mut self, snapshot: &GlobalsSnapshot) {
+        self.globals.borrow_mut().restore_globals(snapshot);
+    }
+
+    /// Calls `hooks` as statements run, functions are called and return, and variables are
+    /// assigned, replacing any hooks set before. On the VM backend, set them before running
+    /// the code whose locals they should see: functions compiled without hooks keep locals
+    /// that nothing can capture on the VM's stack, outside the scopes hooks are shown.
+    pub fn set_hooks(&mut self, hooks: impl ExecutionHooks + 'static) {
+        self.hooks = Some(Box::new(hooks));
+    }
+
+    /// Whether hooks or `--trace-env` look at variables, which they find in environments.
+    pub(crate) fn watches_variables(&self) -> bool {
+        self.hooks.is_some() || self.globals.borrow().is_traced()
+    }
+
+    /// Reports the assignment of `value` to `name` to the hooks, if any are set.
+    pub(crate) fn assigned(&mut self, name: &Token, value: &LoxObject) {
+        if let Some(ref mut hooks) = self.hooks {
+            hooks.assign(&name.lexeme, value, name.span());
+        }
+    }
+
+    /// Reseeds the generator behind `random()` and `randomInt()` so runs are reproducible.
+    pub fn seed_random(&mut self, seed: u64) {
+        self.random.seed(seed);
+    }
+
+    pub fn random(&mut self) -> &mut Random {
+        &mut self.random
+    }
+
+    pub(crate) fn clock(&self) -> &dyn Clock {
+        &*self.clock
+    }
+
+    /// Makes `clock()`, `clockMillis()`, `now()` and `sleep()` use `clock` instead of the
+    /// default, for targets without the standard library's clocks or for tests that need
+    /// fixed times.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock = Box::new(clock);
+    }
+
+    /// Flag that aborts the running evaluation with a runtime error once set. It is safe
+    /// to set from a signal handler; the interpreter clears it when it aborts. Like
+    /// cancellation, it can't stop a `readLine()` or `input()` that is waiting for input.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.interrupted)
+    }
+
+    /// A token that stops the running evaluation with [`LoxException::Cancelled`] once
+    /// cancelled, from any thread. A script blocked in `readLine()` or `input()` only stops
+    /// once the read returns; one in `sleep()` stops within a few milliseconds.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    /// Logs each statement to stderr, with its line, before executing it.
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    /// Logs each variable definition and assignment to stderr, with the variable's name, its
+    /// new value and how many scopes deep it lives, globals being depth 0.
+    pub fn set_trace_env(&mut self, trace_env: bool) {
+        self.globals.borrow_mut().set_trace(trace_env);
+    }
+
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    /// Counts and times calls to each function from now on, for [`Interpreter::profile`].
+    pub fn set_profiling(&mut self, profiling: bool) {
+        self.profiler = profiling.then(Profiler::default);
+    }
+
+    /// Calls and time spent per function while profiling, the one with the most time spent in
+    /// its own body first. Empty if profiling is off.
+    pub fn profile(&self) -> Vec<ProfileEntry> {
+        match self.profiler {
+            Some(ref profiler) => profiler.report(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Records which lines run statements from now on, for [`Interpreter::coverage`].
+    pub fn set_coverage(&mut self, coverage: bool) {
+        self.coverage = coverage.then(LineCoverage::default);
+    }
+
+    /// How many times each line ran a statement while recording coverage, or `None` if it's
+    /// off.
+    pub fn coverage(&self) -> Option<&LineCoverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Limits how many calls can be in progress at once. A call beyond the limit raises a
+    /// "Stack overflow." runtime error rather than overflowing the native stack, as long as the
+    /// limit is no more than [`max_call_depth_for`] the stack the interpreter runs on.
+    pub fn set_max_call_depth(&mut self, max_call_depth: usize) {
+        self.max_call_depth = max_call_depth;
+    }
+
+    /// Limits how many more statements the interpreter may execute, counting those in function
+    /// bodies. Once they've run, every statement raises an "Operation budget exhausted." runtime
+    /// error instead, so untrusted scripts can't run forever. `None` removes the limit.
+    pub fn set_max_ops(&mut self, max_ops: Option<usize>) {
+        self.statement_limit =
+            max_ops.map(|max_ops| self.statements_executed.saturating_add(max_ops));
+    }
+
+    /// Limits how many bytes may be allocated on the heap, by the interpreter and the values
+    /// scripts create alike. While more is allocated, every statement raises a "Memory limit
+    /// exceeded." runtime error instead of running. Usage is only known when the program
+    /// installs [`heap::CountingAllocator`]; without it, the limit is never reached.
+    pub fn set_max_memory(&mut self, max_memory: Option<usize>) {
+        self.memory_limit = max_memory;
+    }
+
+    /// Total number of statements executed so far, including those in function bodies.
+    pub fn statements_executed(&self) -> usize {
+        self.statements_executed
+    }
+
+    /// Total number of calls made so far, to functions, natives and classes alike.
+    pub fn calls_made(&self) -> usize {
+        self.calls_made
+    }
+
+    /// Registers a callback invoked with the class name every time a class is instantiated.
+    pub fn set_construction_observer(&mut self, observer: impl FnMut(&str) + 'static) {
+        self.construction_observer = Some(Box::new(observer));
+    }
+
+    pub fn record_construction(&mut self, class_name: Rc<str>) {
+        *self
+            .construction_counts
+            .entry(Rc::clone(&class_name))
+            .or_insert(0) += 1;
+        if let Some(ref mut observer) = self.construction_observer {
+            observer(&class_name);
         }
     }
 
-    pub fn interpret(&mut self, statements: &Vec<Stmt<'src>>) -> Result<(), LoxException<'src>> {
+    pub fn construction_count(&self, class_name: &str) -> usize {
+        self.construction_counts
+            .get(class_name)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Instantiation counts per class name, most frequently constructed first.
+    pub fn construction_counts(&self) -> Vec<(Rc<str>, usize)> {
+        let mut counts: Vec<_> = self
+            .construction_counts
+            .iter()
+            .map(|(name, &count)| (Rc::clone(name), count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts
+    }
+
+    /// Sets the reporter that the runtime error stopping [`Interpreter::interpret`] is passed to.
+    pub fn set_reporter(&mut self, reporter: SharedReporter) {
+        self.reporter = Some(reporter);
+    }
+
+    pub fn interpret(&mut self, statements: &Vec<Stmt>) -> Result<(), LoxException> {
+        if self.backend == Backend::Vm {
+            let chunk = Compiler::new(self).compile(statements);
+            let result = self.run(&chunk, Rc::clone(&self.environment), Vec::new());
+            if let (Err(LoxException::RuntimeError(error)), Some(reporter)) =
+                (&result, &self.reporter)
+            {
+                reporter.borrow_mut().runtime_error(error);
+            }
+            return result;
+        }
         for statement in statements {
-            self.execute(statement)?;
+            let result = self.execute(statement);
+            if let (Err(LoxException::RuntimeError(error)), Some(reporter)) =
+                (&result, &self.reporter)
+            {
+                reporter.borrow_mut().runtime_error(error);
+            }
+            result?;
         }
         Ok(())
     }
 
-    fn execute(&mut self, stmt: &Stmt<'src>) -> Result<(), LoxException<'src>> {
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), LoxException> {
+        if self.trace {
+            eprintln!("[line {}] {}", stmt.span().line, summarize(stmt));
+        }
+        self.begin_statement(stmt.span())?;
         stmt.accept(self)
     }
 
-    pub fn resolve(&mut self, token: Token<'src>, depth: usize) {
-        self.locals.insert(token, depth);
+    /// Blocks for `duration`, for `sleep()`, in slices so that an interrupt or cancellation
+    /// stops the wait rather than waiting for the next statement.
+    pub(crate) fn sleep(&mut self, duration: Duration, span: Span) -> Result<(), LoxException> {
+        let mut left = duration;
+        while !left.is_zero() {
+            self.check_stopped(span)?;
+            let slice = left.min(SLEEP_SLICE);
+            self.clock.sleep(slice);
+            left -= slice;
+        }
+        self.check_stopped(span)
+    }
+
+    /// Fails if the run has been interrupted or cancelled, clearing the request.
+    fn check_stopped(&self, span: Span) -> Result<(), LoxException> {
+        // Loading first keeps the common case free of a locked read-modify-write.
+        if self.interrupted.load(Ordering::Relaxed)
+            && self.interrupted.swap(false, Ordering::Relaxed)
+        {
+            return Err(LoxException::RuntimeError(RuntimeError::new(
+                ErrorCode::INTERRUPTED,
+                span,
+                String::from("Interrupted."),
+            )));
+        }
+        if self.cancellation.take() {
+            return Err(LoxException::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Counts a statement about to run, failing if the run has been interrupted or cancelled.
+    pub(crate) fn begin_statement(&mut self, span: Span) -> Result<(), LoxException> {
+        self.statements_executed += 1;
+        if self.statements_executed.is_multiple_of(COLLECT_INTERVAL) {
+            cycle_collector::collect_if_due();
+        }
+        self.check_stopped(span)?;
+        if self
+            .statement_limit
+            .is_some_and(|limit| self.statements_executed > limit)
+        {
+            return Err(LoxException::RuntimeError(RuntimeError::new(
+                ErrorCode::BUDGET_EXHAUSTED,
+                span,
+                String::from("Operation budget exhausted."),
+            )));
+        }
+        if let Some(limit) = self.memory_limit {
+            // Values only kept alive by reference cycles don't count against the limit.
+            if heap::allocated() > limit
+                && (cycle_collector::collect() == 0 || heap::allocated() > limit)
+            {
+                return Err(LoxException::RuntimeError(RuntimeError::new(
+                    ErrorCode::OUT_OF_MEMORY,
+                    span,
+                    String::from("Memory limit exceeded."),
+                )));
+            }
+        }
+        if let Some(ref mut coverage) = self.coverage {
+            coverage.record(span.line);
+        }
+        if let Some(ref mut hooks) = self.hooks {
+            hooks.statement(span, &Scope::new(&self.environment));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn constant(&self, idx: usize) -> LoxObject {
+        self.constants[idx].clone()
+    }
+
+    /// Adds `value` to the constant pool unless it is already there, returning its index for
+    /// `Literal::constant`. Returns `None` once the pool is full, leaving the literal to be
+    /// built from its node each time it is evaluated.
+    pub fn add_constant(&mut self, value: &LoxLiteral) -> Option<usize> {
+        let key = value.key();
+        if let Some(&idx) = self.constant_indices.get(&key) {
+            return Some(idx);
+        }
+        if self.constants.len() >= MAX_CONSTANTS {
+            return None;
+        }
+        let idx = self.constants.len();
+        self.constants.push(LoxObject::Literal(value.clone()));
+        self.constant_indices.insert(key, idx);
+        Some(idx)
+    }
+
+    /// Runs the iterations of a `for` loop whose initializer has already run.
+    fn run_for_loop(&mut self, stmt: &For) -> Result<(), LoxException> {
+        loop {
+            if let Some(ref condition) = stmt.condition {
+                let condition_value = self.evaluate(condition)?;
+                if !self.is_truthy(&condition_value) {
+                    break;
+                }
+            }
+            self.execute(&stmt.body)?;
+            if self.active_break {
+                break;
+            }
+            if let Some(ref increment) = stmt.increment {
+                self.evaluate(increment)?;
+            }
+        }
+        self.active_break = false;
+        Ok(())
     }
 
     pub fn execute_block(
         &mut self,
-        statements: &Vec<Stmt<'src>>,
-        environment: Rc<RefCell<Environment<'src>>>,
-    ) -> Result<(), LoxException<'src>> {
+        statements: &[Stmt],
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<(), LoxException> {
         let previous_env = Rc::clone(&self.environment);
         self.environment = environment;
 
@@ -91,11 +759,51 @@ impl<'src> Interpreter<'src> {
         Ok(())
     }
 
-    fn evaluate(&mut self, expr: &Expr<'src>) -> Result<LoxObject<'src>, LoxException<'src>> {
+    /// Evaluates a single expression such as `this.x + 1` with `this` bound to `instance`,
+    /// for watch expressions and conditional breakpoints. Syntax and resolution errors surface
+    /// as a `RuntimeError`.
+    pub fn eval_in_instance(
+        &mut self,
+        instance: Rc<RefCell<LoxInstance>>,
+        source: &str,
+    ) -> Result<LoxObject, LoxException> {
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+        let mut parser = Parser::new(scanner.tokens);
+        let parse_result = parser.parse_expression();
+        let expr = match parse_result {
+            Ok(expr) if !has_errors(&scanner.diagnostics) => expr,
+            _ => {
+                let mut diagnostics = scanner.diagnostics;
+                diagnostics.append(&mut parser.diagnostics);
+                return Err(invalid_expression(source, Span::default(), &diagnostics));
+            }
+        };
+
+        let mut resolver = Resolver::new(self);
+        resolver.resolve_instance_expr(&expr);
+        if resolver.had_error() {
+            return Err(invalid_expression(
+                source,
+                expr.span(),
+                &resolver.diagnostics,
+            ));
+        }
+
+        let mut environment = Environment::new(Some(Rc::clone(&self.globals)));
+        environment.define("this", LoxObject::Instance(instance));
+        let previous_env =
+            std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+        let result = self.evaluate(&expr);
+        self.environment = previous_env;
+        result
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> Result<LoxObject, LoxException> {
         expr.accept(self)
     }
 
-    fn is_truthy(&self, object: &LoxObject<'src>) -> bool {
+    pub(crate) fn is_truthy(&self, object: &LoxObject) -> bool {
         match &object {
             LoxObject::Literal(LoxLiteral::Nil) => false,
             LoxObject::Literal(LoxLiteral::Boolean(res)) => *res,
@@ -103,33 +811,144 @@ impl<'src> Interpreter<'src> {
         }
     }
 
-    fn look_up_variable(
+    /// Returns the iterator for a for-in loop or list comprehension. Instances use
+    /// the result of `iterate()` when they define it, otherwise the instance itself.
+    pub(crate) fn iterator_for(
         &mut self,
-        name: &Token<'src>,
-    ) -> Result<LoxObject<'src>, LoxException<'src>> {
-        match self.locals.get(name) {
-            Some(&distance) => Ok(self.environment.borrow().get_at(distance, name.lexeme)),
-            None => self.globals.borrow().get(name),
+        iterable: LoxObject,
+        span: Span,
+    ) -> Result<LoxIterator, LoxException> {
+        let instance = match iterable {
+            LoxObject::List(list) => return Ok(LoxIterator::List(list, 0)),
+            LoxObject::Instance(instance) => instance,
+            _ => return Err(LoxException::RuntimeError(RuntimeError::new(
+                ErrorCode::NOT_ITERABLE,
+                span,
+                String::from(
+                    "Can only iterate over lists and instances implementing the iterator protocol.",
+                ),
+            ))),
+        };
+
+        let iterate = instance
+            .borrow()
+            .get_property("iterate", Rc::clone(&instance));
+        if iterate.is_none() {
+            return Ok(LoxIterator::Protocol(instance));
+        }
+        match self.call_iterator_method(&instance, "iterate", span)? {
+            LoxObject::Instance(iterator) => Ok(LoxIterator::Protocol(iterator)),
+            _ => Err(LoxException::RuntimeError(RuntimeError::new(
+                ErrorCode::INVALID_ITERATOR,
+                span,
+                String::from("'iterate()' must return an instance."),
+            ))),
         }
     }
-}
 
-impl<'src> ExprVisitor<'src, Result<LoxObject<'src>, LoxException<'src>>> for Interpreter<'src> {
-    fn visit_binary_expr(
+    pub(crate) fn next_value(
         &mut self,
-        expr: &Binary<'src>,
-    ) -> Result<LoxObject<'src>, LoxException<'src>> {
-        let left = self.evaluate(&expr.left)?;
-        let right = self.evaluate(&expr.right)?;
+        iterator: &mut LoxIterator,
+        span: Span,
+    ) -> Result<Option<LoxObject>, LoxException> {
+        match iterator {
+            LoxIterator::List(list, index) => {
+                let value = list.borrow().get(*index).cloned();
+                *index += 1;
+                Ok(value)
+            }
+            LoxIterator::Protocol(instance) => {
+                let instance = Rc::clone(instance);
+                let done = self.call_iterator_method(&instance, "done", span)?;
+                if self.is_truthy(&done) {
+                    return Ok(None);
+                }
+                self.call_iterator_method(&instance, "next", span).map(Some)
+            }
+        }
+    }
 
-        match expr.operator.token_type {
+    /// Calls `callable`, keeping the call on the call stack while it runs so that a runtime
+    /// error raised inside carries a backtrace.
+    fn call(
+        &mut self,
+        callable: &LoxCallable,
+        arguments: Vec<LoxObject>,
+        span: Span,
+    ) -> Result<LoxObject, LoxException> {
+        if self.call_stack.len() >= self.max_call_depth {
+            return Err(LoxException::RuntimeError(RuntimeError::new(
+                ErrorCode::STACK_OVERFLOW,
+                span,
+                String::from("Stack overflow."),
+            )));
+        }
+        self.call_stack.push(CallFrame {
+            name: callable.name().map(String::from),
+            line: span.line,
+        });
+        if let Some(ref mut hooks) = self.hooks {
+            hooks.call(callable.name(), span);
+        }
+        let start = self
+            .profiler
+            .as_mut()
+            .map(|profiler| profiler.enter(callable));
+        let mut result = callable.call(self, arguments, span);
+        if let (Some(profiler), Some(start)) = (self.profiler.as_mut(), start) {
+            profiler.exit(callable, start);
+        }
+        if let Some(ref mut hooks) = self.hooks {
+            hooks.return_from(callable.name(), span);
+        }
+        // The innermost call sees the error first, while the whole stack is still in place.
+        if let Err(LoxException::RuntimeError(ref mut error)) = result {
+            if error.trace.is_empty() {
+                error.trace = self.call_stack.iter().rev().cloned().collect();
+            }
+        }
+        self.call_stack.pop();
+        result
+    }
+
+    fn call_iterator_method(
+        &mut self,
+        iterator: &Rc<RefCell<LoxInstance>>,
+        name: &str,
+        span: Span,
+    ) -> Result<LoxObject, LoxException> {
+        let method = iterator.borrow().get_property(name, Rc::clone(iterator));
+        match method {
+            Some(LoxObject::Callable(callable)) if callable.arity() == 0 => {
+                self.call(&callable, Vec::new(), span)
+            }
+            _ => Err(LoxException::RuntimeError(RuntimeError::new(
+                ErrorCode::INVALID_ITERATOR,
+                span,
+                format!("Iterator must implement '{name}()' taking no arguments."),
+            ))),
+        }
+    }
+
+    /// Applies a binary operator to evaluated operands. `span` covers the whole expression and
+    /// `right_span` the right operand.
+    pub(crate) fn binary(
+        &self,
+        operator: TokenType,
+        left: LoxObject,
+        right: LoxObject,
+        span: Span,
+        right_span: Span,
+    ) -> Result<LoxObject, LoxException> {
+        match operator {
             TokenType::Minus => match (left, right) {
                 (
                     LoxObject::Literal(LoxLiteral::Number(left_val)),
                     LoxObject::Literal(LoxLiteral::Number(right_val)),
                 ) => Ok(LoxObject::Literal(LoxLiteral::Number(left_val - right_val))),
                 _ => Err(LoxException::RuntimeError(RuntimeError::new(
-                    expr.operator.line,
+                    ErrorCode::OPERANDS_NOT_NUMBERS,
+                    span,
                     String::from("Operands must be numbers."),
                 ))),
             },
@@ -139,13 +958,15 @@ impl<'src> ExprVisitor<'src, Result<LoxObject<'src>, LoxException<'src>>> for In
                     LoxObject::Literal(LoxLiteral::Number(right_val)),
                 ) => match right_val == 0.0 {
                     true => Err(LoxException::RuntimeError(RuntimeError::new(
-                        expr.operator.line,
+                        ErrorCode::DIVISION_BY_ZERO,
+                        right_span,
                         String::from("Cannot divide by zero."),
                     ))),
                     false => Ok(LoxObject::Literal(LoxLiteral::Number(left_val / right_val))),
                 },
                 _ => Err(LoxException::RuntimeError(RuntimeError::new(
-                    expr.operator.line,
+                    ErrorCode::OPERANDS_NOT_NUMBERS,
+                    span,
                     String::from("Operands must be numbers."),
                 ))),
             },
@@ -155,7 +976,8 @@ impl<'src> ExprVisitor<'src, Result<LoxObject<'src>, LoxException<'src>>> for In
                     LoxObject::Literal(LoxLiteral::Number(right_val)),
                 ) => Ok(LoxObject::Literal(LoxLiteral::Number(left_val * right_val))),
                 _ => Err(LoxException::RuntimeError(RuntimeError::new(
-                    expr.operator.line,
+                    ErrorCode::OPERANDS_NOT_NUMBERS,
+                    span,
                     String::from("Operands must be numbers."),
                 ))),
             },
@@ -167,17 +989,18 @@ impl<'src> ExprVisitor<'src, Result<LoxObject<'src>, LoxException<'src>>> for In
                 (
                     LoxObject::Literal(LoxLiteral::String(left_val)),
                     LoxObject::Literal(LoxLiteral::String(right_val)),
-                ) => Ok(LoxObject::Literal(LoxLiteral::String(Rc::new(format!(
-                    "{left_val}{right_val}"
-                ))))),
-                (LoxObject::Literal(LoxLiteral::String(left_val)), right) => Ok(
-                    LoxObject::Literal(LoxLiteral::String(Rc::new(format!("{left_val}{right}",)))),
-                ),
-                (left, LoxObject::Literal(LoxLiteral::String(right_val))) => Ok(
-                    LoxObject::Literal(LoxLiteral::String(Rc::new(format!("{left}{right_val}",)))),
-                ),
+                ) => concat(&left_val, &right_val, span),
+                (LoxObject::Literal(LoxLiteral::String(left_val)), right) => {
+                    let right_val = Rc::new(LoxString::from(right.to_string()));
+                    concat(&left_val, &right_val, span)
+                }
+                (left, LoxObject::Literal(LoxLiteral::String(right_val))) => {
+                    let left_val = Rc::new(LoxString::from(left.to_string()));
+                    concat(&left_val, &right_val, span)
+                }
                 _ => Err(LoxException::RuntimeError(RuntimeError::new(
-                    expr.operator.line,
+                    ErrorCode::INVALID_ADDITION,
+                    span,
                     String::from("Operands must be two numbers or one must be a string."),
                 ))),
             },
@@ -189,7 +1012,8 @@ impl<'src> ExprVisitor<'src, Result<LoxObject<'src>, LoxException<'src>>> for In
                     left_val > right_val,
                 ))),
                 _ => Err(LoxException::RuntimeError(RuntimeError::new(
-                    expr.operator.line,
+                    ErrorCode::OPERANDS_NOT_NUMBERS,
+                    span,
                     String::from("Operands must be numbers."),
                 ))),
             },
@@ -201,7 +1025,8 @@ impl<'src> ExprVisitor<'src, Result<LoxObject<'src>, LoxException<'src>>> for In
                     left_val >= right_val,
                 ))),
                 _ => Err(LoxException::RuntimeError(RuntimeError::new(
-                    expr.operator.line,
+                    ErrorCode::OPERANDS_NOT_NUMBERS,
+                    span,
                     String::from("Operands must be numbers."),
                 ))),
             },
@@ -213,7 +1038,8 @@ impl<'src> ExprVisitor<'src, Result<LoxObject<'src>, LoxException<'src>>> for In
                     left_val < right_val,
                 ))),
                 _ => Err(LoxException::RuntimeError(RuntimeError::new(
-                    expr.operator.line,
+                    ErrorCode::OPERANDS_NOT_NUMBERS,
+                    span,
                     String::from("Operands must be numbers."),
                 ))),
             },
@@ -225,7 +1051,8 @@ impl<'src> ExprVisitor<'src, Result<LoxObject<'src>, LoxException<'src>>> for In
                     left_val <= right_val,
                 ))),
                 _ => Err(LoxException::RuntimeError(RuntimeError::new(
-                    expr.operator.line,
+                    ErrorCode::OPERANDS_NOT_NUMBERS,
+                    span,
                     String::from("Operands must be numbers."),
                 ))),
             },
@@ -236,33 +1063,20 @@ impl<'src> ExprVisitor<'src, Result<LoxObject<'src>, LoxException<'src>>> for In
         }
     }
 
-    fn visit_grouping_expr(
-        &mut self,
-        expr: &Grouping<'src>,
-    ) -> Result<LoxObject<'src>, LoxException<'src>> {
-        self.evaluate(&expr.expression)
-    }
-
-    fn visit_literal_expr(
-        &mut self,
-        expr: &Literal,
-    ) -> Result<LoxObject<'src>, LoxException<'src>> {
-        Ok(LoxObject::Literal(expr.value.clone()))
-    }
-
-    fn visit_unary_expr(
-        &mut self,
-        expr: &Unary<'src>,
-    ) -> Result<LoxObject<'src>, LoxException<'src>> {
-        let right = self.evaluate(&expr.right)?;
-
-        match expr.operator.token_type {
+    pub(crate) fn unary(
+        &self,
+        operator: TokenType,
+        right: LoxObject,
+        span: Span,
+    ) -> Result<LoxObject, LoxException> {
+        match operator {
             TokenType::Minus => match right {
                 LoxObject::Literal(LoxLiteral::Number(val)) => {
                     Ok(LoxObject::Literal(LoxLiteral::Number(-val)))
                 }
                 _ => Err(LoxException::RuntimeError(RuntimeError::new(
-                    expr.operator.line,
+                    ErrorCode::OPERAND_NOT_NUMBER,
+                    span,
                     String::from("Operand must be a number."),
                 ))),
             },
@@ -273,67 +1087,21 @@ impl<'src> ExprVisitor<'src, Result<LoxObject<'src>, LoxException<'src>>> for In
         }
     }
 
-    fn visit_ternary_expr(
-        &mut self,
-        expr: &Ternary<'src>,
-    ) -> Result<LoxObject<'src>, LoxException<'src>> {
-        let condition = self.evaluate(&expr.condition)?;
-        match self.is_truthy(&condition) {
-            true => self.evaluate(&expr.left),
-            false => self.evaluate(&expr.right),
-        }
-    }
-
-    fn visit_variable_expr(
+    /// Calls `callee` from a call expression spanning `span`, after checking it's callable
+    /// with that many arguments.
+    pub(crate) fn call_value(
         &mut self,
-        expr: &Variable<'src>,
-    ) -> Result<LoxObject<'src>, LoxException<'src>> {
-        self.look_up_variable(&expr.name)
-    }
-
-    fn visit_assign_expr(
-        &mut self,
-        expr: &Assign<'src>,
-    ) -> Result<LoxObject<'src>, LoxException<'src>> {
-        let value = self.evaluate(&expr.value)?;
-        match self.locals.get(&expr.name) {
-            Some(&distance) => Ok(self
-                .environment
-                .borrow_mut()
-                .assign_at(distance, &expr.name, value)),
-            None => self.globals.borrow_mut().assign(&expr.name, value),
-        }
-    }
-
-    fn visit_logical_expr(
-        &mut self,
-        expr: &Logical<'src>,
-    ) -> Result<LoxObject<'src>, LoxException<'src>> {
-        let left = self.evaluate(&expr.left)?;
-
-        match expr.operator.token_type {
-            TokenType::Or if self.is_truthy(&left) => Ok(left),
-            TokenType::And if !self.is_truthy(&left) => Ok(left),
-            _ => self.evaluate(&expr.right),
-        }
-    }
-
-    fn visit_call_expr(
-        &mut self,
-        expr: &Call<'src>,
-    ) -> Result<LoxObject<'src>, LoxException<'src>> {
-        let callee = self.evaluate(&expr.callee)?;
-
-        let mut arguments = Vec::new();
-        for argument in expr.arguments.iter() {
-            arguments.push(self.evaluate(argument)?);
-        }
-
+        callee: LoxObject,
+        arguments: Vec<LoxObject>,
+        span: Span,
+        callee_span: Span,
+    ) -> Result<LoxObject, LoxException> {
         match callee {
             LoxObject::Callable(callable) => {
                 if arguments.len() != callable.arity() {
                     return Err(LoxException::RuntimeError(RuntimeError::new(
-                        expr.paren.line,
+                        ErrorCode::WRONG_ARGUMENT_COUNT,
+                        span,
                         format!(
                             "Expected {} arguments but got {}.",
                             callable.arity(),
@@ -341,101 +1109,523 @@ impl<'src> ExprVisitor<'src, Result<LoxObject<'src>, LoxException<'src>>> for In
                         ),
                     )));
                 }
-                callable.call(self, arguments)
+                self.calls_made += 1;
+                self.call(&callable, arguments, span)
             }
             _ => Err(LoxException::RuntimeError(RuntimeError::new(
-                expr.paren.line,
+                ErrorCode::NOT_CALLABLE,
+                callee_span,
                 String::from("Can only call functions and classes."),
             ))),
         }
     }
 
-    fn visit_get_expr(&mut self, expr: &Get<'src>) -> Result<LoxObject<'src>, LoxException<'src>> {
-        let object = self.evaluate(&expr.object)?;
+    pub(crate) fn get_property(
+        &self,
+        object: LoxObject,
+        name: &Token,
+        object_span: Span,
+    ) -> Result<LoxObject, LoxException> {
         match object {
-            LoxObject::Instance(instance) => {
-                instance.borrow().get(&expr.name, Rc::clone(&instance))
-            }
+            LoxObject::Instance(instance) => instance.borrow().get(name, Rc::clone(&instance)),
+            LoxObject::Foreign(foreign) => LoxForeign::get(&foreign, name),
             _ => Err(LoxException::RuntimeError(RuntimeError::new(
-                expr.name.line,
+                ErrorCode::PROPERTY_ON_NON_INSTANCE,
+                object_span,
                 String::from("Only instances have properties."),
             ))),
         }
     }
 
-    fn visit_set_expr(&mut self, expr: &Set<'src>) -> Result<LoxObject<'src>, LoxException<'src>> {
-        let object = self.evaluate(&expr.object)?;
+    /// The instance a field is being set on, checked before the value is evaluated.
+    pub(crate) fn expect_instance(
+        &self,
+        object: LoxObject,
+        object_span: Span,
+    ) -> Result<Rc<RefCell<LoxInstance>>, LoxException> {
         match object {
-            LoxObject::Instance(instance) => {
-                let value = self.evaluate(&expr.value)?;
-                Ok(instance.borrow_mut().set(&expr.name, value.clone()))
-            }
+            LoxObject::Instance(instance) => Ok(instance),
             _ => Err(LoxException::RuntimeError(RuntimeError::new(
-                expr.name.line,
+                ErrorCode::FIELD_ON_NON_INSTANCE,
+                object_span,
                 String::from("Only instances have fields."),
             ))),
         }
     }
 
-    fn visit_this_expr(
-        &mut self,
-        expr: &This<'src>,
-    ) -> Result<LoxObject<'src>, LoxException<'src>> {
-        self.look_up_variable(&expr.keyword)
+    /// The list being indexed, checked before any value assigned to the element is evaluated.
+    pub(crate) fn expect_list(
+        &self,
+        object: LoxObject,
+        object_span: Span,
+    ) -> Result<Rc<RefCell<Vec<LoxObject>>>, LoxException> {
+        match object {
+            LoxObject::List(list) => Ok(list),
+            _ => Err(LoxException::RuntimeError(RuntimeError::new(
+                ErrorCode::INDEX_ON_NON_LIST,
+                object_span,
+                String::from("Only lists can be indexed."),
+            ))),
+        }
     }
 
-    fn visit_super_expr(&mut self, expr: &Super) -> Result<LoxObject<'src>, LoxException<'src>> {
-        let distance = self
-            .locals
-            .get(&expr.keyword)
-            .expect("Expected super local to resolve.");
-        let superclass = self.environment.borrow().get_at(*distance, "super");
+    pub(crate) fn index(
+        &self,
+        object: LoxObject,
+        index: LoxObject,
+        object_span: Span,
+        index_span: Span,
+    ) -> Result<LoxObject, LoxException> {
+        let list = self.expect_list(object, object_span)?;
+        let list = list.borrow();
+        let idx = self.list_index(list.len(), &index, index_span)?;
+        Ok(list[idx].clone())
+    }
 
-        let object = self.environment.borrow().get_at(*distance - 1, "this");
+    pub(crate) fn set_index(
+        &self,
+        list: Rc<RefCell<Vec<LoxObject>>>,
+        index: LoxObject,
+        value: LoxObject,
+        index_span: Span,
+    ) -> Result<LoxObject, LoxException> {
+        let len = list.borrow().len();
+        let idx = self.list_index(len, &index, index_span)?;
+        cycle_collector::track_list(&list);
+        list.borrow_mut()[idx] = value.clone();
+        Ok(value)
+    }
+
+    /// Looks up `method` on `superclass` and binds it to `object`, for a `super` expression.
+    pub(crate) fn super_method(
+        &self,
+        superclass: LoxObject,
+        object: LoxObject,
+        method: &Token,
+        span: Span,
+    ) -> Result<LoxObject, LoxException> {
         let instance = match object {
             LoxObject::Instance(instance) => instance,
             _ => unreachable!(),
         };
-
-        let method = match superclass {
-            LoxObject::Callable(LoxCallable::Class(ref class)) => {
-                class.find_method(expr.method.lexeme)
-            }
+        let method_fn = match superclass {
+            LoxObject::Callable(LoxCallable::Class(ref class)) => class.find_method(&method.lexeme),
             _ => unreachable!(),
         };
-        match method {
+        match method_fn {
             Some(function) => Ok(LoxObject::Callable(LoxCallable::Function(Rc::new(
                 function.bind(instance),
             )))),
             None => Err(LoxException::RuntimeError(RuntimeError::new(
-                expr.method.line,
-                format!("Undefined property '{}'.", expr.method.lexeme),
+                ErrorCode::UNDEFINED_PROPERTY,
+                span,
+                format!("Undefined property '{}'.", method.lexeme),
+            ))),
+        }
+    }
+
+    /// Applies `decorator`, written at `span`, to the function or decorated value `value`.
+    pub(crate) fn decorate(
+        &mut self,
+        decorator: LoxObject,
+        value: LoxObject,
+        span: Span,
+    ) -> Result<LoxObject, LoxException> {
+        match decorator {
+            LoxObject::Callable(callable) if callable.arity() == 1 => {
+                self.call(&callable, vec![value], span)
+            }
+            _ => Err(LoxException::RuntimeError(RuntimeError::new(
+                ErrorCode::INVALID_DECORATOR,
+                span,
+                String::from("Decorator must be a callable taking one argument."),
+            ))),
+        }
+    }
+
+    /// The values a destructuring declaration of `count` names binds, from the list `value`.
+    pub(crate) fn destructure(
+        &self,
+        value: LoxObject,
+        count: usize,
+        span: Span,
+    ) -> Result<Vec<LoxObject>, LoxException> {
+        let values = match value {
+            LoxObject::List(list) => list.borrow().clone(),
+            _ => {
+                return Err(LoxException::RuntimeError(RuntimeError::new(
+                    ErrorCode::DESTRUCTURE_NON_LIST,
+                    span,
+                    String::from("Can only destructure lists."),
+                )))
+            }
+        };
+        if values.len() != count {
+            return Err(LoxException::RuntimeError(RuntimeError::new(
+                ErrorCode::DESTRUCTURE_COUNT_MISMATCH,
+                span,
+                format!(
+                    "Expected {count} values to destructure but got {}.",
+                    values.len()
+                ),
+            )));
+        }
+        Ok(values)
+    }
+
+    /// Prints `value` for the print statement spanning `span`, pretty-printed if the REPL is
+    /// echoing it.
+    pub(crate) fn print(
+        &mut self,
+        value: &LoxObject,
+        echo: bool,
+        span: Span,
+    ) -> Result<(), LoxException> {
+        let written = if echo {
+            writeln!(self.output, "{}", value.pretty())
+        } else {
+            writeln!(self.output, "{value}")
+        };
+        written.map_err(|error| self.output_error(error, span))
+    }
+
+    /// Writes `text` to the output without a line ending and flushes it, for prompts.
+    pub(crate) fn write_prompt(&mut self, text: &str, span: Span) -> Result<(), LoxException> {
+        write!(self.output, "{text}")
+            .and_then(|()| self.output.flush())
+            .map_err(|error| self.output_error(error, span))
+    }
+
+    fn output_error(&self, error: io::Error, span: Span) -> LoxException {
+        LoxException::RuntimeError(RuntimeError::new(
+            ErrorCode::OUTPUT_FAILED,
+            span,
+            format!("Could not write output: {error}."),
+        ))
+    }
+
+    /// Checks that `value`, from the superclass expression spanning `span`, is a class.
+    pub(crate) fn superclass(
+        &self,
+        value: LoxObject,
+        span: Span,
+    ) -> Result<Rc<LoxClass>, LoxException> {
+        match value {
+            LoxObject::Callable(LoxCallable::Class(class)) => Ok(class),
+            _ => Err(LoxException::RuntimeError(RuntimeError::new(
+                ErrorCode::SUPERCLASS_NOT_CLASS,
+                span,
+                String::from("Superclass must be a class."),
             ))),
         }
     }
 
-    fn visit_closure_expr(
+    /// Builds a class whose methods close over the current environment, wrapped in one
+    /// binding `super` if there's a superclass. Methods with a chunk run on the VM.
+    pub(crate) fn class<'a>(
         &mut self,
-        expr: &Closure<'src>,
-    ) -> Result<LoxObject<'src>, LoxException<'src>> {
+        name: &Rc<str>,
+        doc: Option<&Rc<str>>,
+        superclass: Option<Rc<LoxClass>>,
+        methods: impl Iterator<Item = (&'a Rc<str>, &'a Rc<Closure>, Option<&'a Rc<Chunk>>)>,
+    ) -> LoxObject {
+        if let Some(ref superclass) = superclass {
+            self.environment = Rc::new(RefCell::new(Environment::new(Some(Rc::clone(
+                &self.environment,
+            )))));
+            self.environment.borrow_mut().define(
+                "super",
+                LoxObject::Callable(LoxCallable::Class(Rc::clone(superclass))),
+            );
+        }
+
+        let mut lox_methods = HashMap::new();
+        for (method_name, declaration, chunk) in methods {
+            let mut lox_fun = LoxFunction::new(
+                declaration,
+                Rc::clone(&self.environment),
+                Some(Rc::clone(method_name)),
+                &**method_name == "init",
+            );
+            if let Some(chunk) = chunk {
+                lox_fun = lox_fun.with_chunk(Rc::clone(chunk));
+            }
+            lox_methods.insert(Rc::clone(method_name), lox_fun);
+        }
+
+        if superclass.is_some() {
+            let enclosing = self.environment.borrow_mut().enclosing.take().unwrap();
+            self.environment = enclosing;
+        }
+
+        let mut klass = LoxClass::new(Rc::clone(name), superclass, lox_methods);
+        klass.doc = doc.cloned();
+        LoxObject::Callable(LoxCallable::Class(Rc::new(klass)))
+    }
+
+    fn list_index(&self, len: usize, index: &LoxObject, span: Span) -> Result<usize, LoxException> {
+        match index {
+            LoxObject::Literal(LoxLiteral::Number(val)) if val.fract() == 0.0 => {
+                if *val >= 0.0 && (*val as usize) < len {
+                    Ok(*val as usize)
+                } else {
+                    Err(LoxException::RuntimeError(RuntimeError::new(
+                        ErrorCode::INDEX_OUT_OF_RANGE,
+                        span,
+                        String::from("List index out of range."),
+                    )))
+                }
+            }
+            _ => Err(LoxException::RuntimeError(RuntimeError::new(
+                ErrorCode::INDEX_NOT_INTEGER,
+                span,
+                String::from("List index must be an integer."),
+            ))),
+        }
+    }
+
+    fn evaluate_comprehension(
+        &mut self,
+        expr: &ListComprehension,
+        iterator: &mut LoxIterator,
+    ) -> Result<Vec<LoxObject>, LoxException> {
+        let span = expr.iterable.span();
+        let mut elements = Vec::new();
+        while let Some(value) = self.next_value(iterator, span)? {
+            // The comprehension variable is the only one in its environment.
+            self.environment.borrow_mut().assign_at(0, 0, value);
+            if let Some(ref condition) = expr.condition {
+                let condition_value = self.evaluate(condition)?;
+                if !self.is_truthy(&condition_value) {
+                    continue;
+                }
+            }
+            elements.push(self.evaluate(&expr.element)?);
+        }
+        Ok(elements)
+    }
+
+    /// Reads the variable `name`, from the slot the resolver placed it in if it's `local`.
+    fn look_up_variable(
+        &mut self,
+        name: &Token,
+        local: Option<(usize, usize)>,
+        global: Option<usize>,
+    ) -> Result<LoxObject, LoxException> {
+        if let Some((distance, slot)) = local {
+            return Ok(self.environment.borrow().get_at(distance, slot));
+        }
+        // Names that aren't locals are globals, but suggest fixes from every scope in view.
+        let value = match global {
+            Some(slot) => self.globals.borrow().get_global(slot),
+            None => self.globals.borrow().get(name).ok(),
+        };
+        value.ok_or_else(|| self.environment.borrow().undefined_variable(name))
+    }
+}
+
+/// The string `+` makes of `left` and `right`.
+fn concat(
+    left: &Rc<LoxString>,
+    right: &Rc<LoxString>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    match LoxString::concat(left, right) {
+        Some(string) => Ok(LoxObject::Literal(LoxLiteral::String(Rc::new(string)))),
+        None => Err(LoxException::RuntimeError(RuntimeError::new(
+            ErrorCode::STRING_TOO_LONG,
+            span,
+            String::from("String too long."),
+        ))),
+    }
+}
+
+/// The error for a snippet passed to [`Interpreter::eval_in_instance`] that failed to compile,
+/// naming the first problem found.
+fn invalid_expression(source: &str, span: Span, diagnostics: &[Diagnostic]) -> LoxException {
+    let message = match diagnostics.iter().find(|diagnostic| diagnostic.is_error()) {
+        Some(diagnostic) => format!(
+            "Invalid expression '{source}': [{}] {}",
+            diagnostic.code, diagnostic.message
+        ),
+        None => format!("Invalid expression '{source}'."),
+    };
+    LoxException::RuntimeError(RuntimeError::new(
+        ErrorCode::INVALID_EXPRESSION,
+        span,
+        message,
+    ))
+}
+
+impl ExprVisitor<Result<LoxObject, LoxException>> for Interpreter {
+    fn visit_binary_expr(&mut self, expr: &Binary) -> Result<LoxObject, LoxException> {
+        let left = self.evaluate(&expr.left)?;
+        let right = self.evaluate(&expr.right)?;
+        self.binary(
+            expr.operator.token_type,
+            left,
+            right,
+            expr.span,
+            expr.right.span(),
+        )
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Grouping) -> Result<LoxObject, LoxException> {
+        self.evaluate(&expr.expression)
+    }
+
+    fn visit_literal_expr(&mut self, expr: &Literal) -> Result<LoxObject, LoxException> {
+        match expr.constant.get() {
+            Some(idx) => Ok(self.constants[idx].clone()),
+            None => Ok(LoxObject::Literal(expr.value.clone())),
+        }
+    }
+
+    fn visit_unary_expr(&mut self, expr: &Unary) -> Result<LoxObject, LoxException> {
+        let right = self.evaluate(&expr.right)?;
+        self.unary(expr.operator.token_type, right, expr.span)
+    }
+
+    fn visit_ternary_expr(&mut self, expr: &Ternary) -> Result<LoxObject, LoxException> {
+        let condition = self.evaluate(&expr.condition)?;
+        match self.is_truthy(&condition) {
+            true => self.evaluate(&expr.left),
+            false => self.evaluate(&expr.right),
+        }
+    }
+
+    fn visit_variable_expr(&mut self, expr: &Variable) -> Result<LoxObject, LoxException> {
+        self.look_up_variable(&expr.name, expr.local.get(), expr.global.get())
+    }
+
+    fn visit_assign_expr(&mut self, expr: &Assign) -> Result<LoxObject, LoxException> {
+        let value = self.evaluate(&expr.value)?;
+        let value = match expr.local.get() {
+            Some((distance, slot)) => self
+                .environment
+                .borrow_mut()
+                .assign_at(distance, slot, value),
+            None => {
+                let result = match expr.global.get() {
+                    Some(slot) => self.globals.borrow_mut().assign_global(slot, value),
+                    None => self.globals.borrow_mut().assign(&expr.name, value).ok(),
+                };
+                result.ok_or_else(|| self.environment.borrow().undefined_variable(&expr.name))?
+            }
+        };
+        self.assigned(&expr.name, &value);
+        Ok(value)
+    }
+
+    fn visit_logical_expr(&mut self, expr: &Logical) -> Result<LoxObject, LoxException> {
+        let left = self.evaluate(&expr.left)?;
+
+        match expr.operator.token_type {
+            TokenType::Or if self.is_truthy(&left) => Ok(left),
+            TokenType::And if !self.is_truthy(&left) => Ok(left),
+            _ => self.evaluate(&expr.right),
+        }
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) -> Result<LoxObject, LoxException> {
+        let callee = self.evaluate(&expr.callee)?;
+
+        let mut arguments = Vec::new();
+        for argument in expr.arguments.iter() {
+            arguments.push(self.evaluate(argument)?);
+        }
+        self.call_value(callee, arguments, expr.span, expr.callee.span())
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) -> Result<LoxObject, LoxException> {
+        let object = self.evaluate(&expr.object)?;
+        self.get_property(object, &expr.name, expr.object.span())
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) -> Result<LoxObject, LoxException> {
+        let object = self.evaluate(&expr.object)?;
+        let instance = self.expect_instance(object, expr.object.span())?;
+        let value = self.evaluate(&expr.value)?;
+        cycle_collector::track_instance(&instance);
+        let value = instance.borrow_mut().set(&expr.name, value);
+        Ok(value)
+    }
+
+    fn visit_this_expr(&mut self, expr: &This) -> Result<LoxObject, LoxException> {
+        self.look_up_variable(&expr.keyword, expr.local.get(), None)
+    }
+
+    fn visit_super_expr(&mut self, expr: &Super) -> Result<LoxObject, LoxException> {
+        let (distance, slot) = expr.local.get().expect("Expected super local to resolve.");
+        let superclass = self.environment.borrow().get_at(distance, slot);
+
+        // `this` is alone in the environment just inside the one holding `super`.
+        let object = self.environment.borrow().get_at(distance - 1, 0);
+        self.super_method(superclass, object, &expr.method, expr.span)
+    }
+
+    fn visit_closure_expr(&mut self, expr: &Rc<Closure>) -> Result<LoxObject, LoxException> {
         let closure = LoxFunction::new(expr, Rc::clone(&self.environment), None, false);
         Ok(LoxObject::Callable(LoxCallable::Function(Rc::new(closure))))
     }
+
+    fn visit_list_expr(&mut self, expr: &List) -> Result<LoxObject, LoxException> {
+        let mut elements = Vec::with_capacity(expr.elements.len());
+        for element in expr.elements.iter() {
+            elements.push(self.evaluate(element)?);
+        }
+        Ok(LoxObject::List(Rc::new(RefCell::new(elements))))
+    }
+
+    fn visit_list_comprehension_expr(
+        &mut self,
+        expr: &ListComprehension,
+    ) -> Result<LoxObject, LoxException> {
+        let iterable = self.evaluate(&expr.iterable)?;
+        let mut iterator = self.iterator_for(iterable, expr.iterable.span())?;
+
+        // A single environment holds the comprehension variable for every element.
+        let environment = Rc::new(RefCell::new(Environment::new(Some(Rc::clone(
+            &self.environment,
+        )))));
+        environment.borrow_mut().define(
+            Rc::clone(&expr.name.lexeme),
+            LoxObject::Literal(LoxLiteral::Nil),
+        );
+        let previous_env = std::mem::replace(&mut self.environment, environment);
+        let elements = self.evaluate_comprehension(expr, &mut iterator);
+        self.environment = previous_env;
+
+        Ok(LoxObject::List(Rc::new(RefCell::new(elements?))))
+    }
+
+    fn visit_index_expr(&mut self, expr: &Index) -> Result<LoxObject, LoxException> {
+        let object = self.evaluate(&expr.object)?;
+        let index = self.evaluate(&expr.index)?;
+        self.index(object, index, expr.object.span(), expr.index.span())
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &SetIndex) -> Result<LoxObject, LoxException> {
+        let object = self.evaluate(&expr.object)?;
+        let index = self.evaluate(&expr.index)?;
+        let list = self.expect_list(object, expr.object.span())?;
+        let value = self.evaluate(&expr.value)?;
+        self.set_index(list, index, value, expr.index.span())
+    }
 }
 
-impl<'src> StmtVisitor<'src, Result<(), LoxException<'src>>> for Interpreter<'src> {
-    fn visit_expression_stmt(&mut self, stmt: &Expression<'src>) -> Result<(), LoxException<'src>> {
+impl StmtVisitor<Result<(), LoxException>> for Interpreter {
+    fn visit_expression_stmt(&mut self, stmt: &Expression) -> Result<(), LoxException> {
         self.evaluate(&stmt.expression)?;
         Ok(())
     }
 
-    fn visit_print_stmt(&mut self, stmt: &Print<'src>) -> Result<(), LoxException<'src>> {
+    fn visit_print_stmt(&mut self, stmt: &Print) -> Result<(), LoxException> {
         let value = self.evaluate(&stmt.expression)?;
-        println!("{value}");
-        Ok(())
+        self.print(&value, stmt.echo, stmt.span)
     }
 
-    fn visit_var_stmt(&mut self, stmt: &Var<'src>) -> Result<(), LoxException<'src>> {
+    fn visit_var_stmt(&mut self, stmt: &Var) -> Result<(), LoxException> {
         let value = match stmt.initializer {
             Some(ref expr) => self.evaluate(expr)?,
             None => LoxObject::Literal(LoxLiteral::Nil),
@@ -443,18 +1633,29 @@ impl<'src> StmtVisitor<'src, Result<(), LoxException<'src>>> for Interpreter<'sr
 
         self.environment
             .borrow_mut()
-            .define(stmt.name.lexeme, value);
+            .define(Rc::clone(&stmt.name.lexeme), value);
         Ok(())
     }
 
-    fn visit_block_stmt(&mut self, stmt: &Block<'src>) -> Result<(), LoxException<'src>> {
+    fn visit_destructure_stmt(&mut self, stmt: &Destructure) -> Result<(), LoxException> {
+        let value = self.evaluate(&stmt.initializer)?;
+        let values = self.destructure(value, stmt.names.len(), stmt.initializer.span())?;
+
+        let mut environment = self.environment.borrow_mut();
+        for (name, value) in stmt.names.iter().zip(values) {
+            environment.define(Rc::clone(&name.lexeme), value);
+        }
+        Ok(())
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &Block) -> Result<(), LoxException> {
         let environment = Rc::new(RefCell::new(Environment::new(Some(Rc::clone(
             &self.environment,
         )))));
         self.execute_block(&stmt.statements, environment)
     }
 
-    fn visit_if_stmt(&mut self, stmt: &If<'src>) -> Result<(), LoxException<'src>> {
+    fn visit_if_stmt(&mut self, stmt: &If) -> Result<(), LoxException> {
         let condition_value = self.evaluate(&stmt.condition)?;
 
         if self.is_truthy(&condition_value) {
@@ -466,7 +1667,7 @@ impl<'src> StmtVisitor<'src, Result<(), LoxException<'src>>> for Interpreter<'sr
         Ok(())
     }
 
-    fn visit_while_stmt(&mut self, stmt: &While<'src>) -> Result<(), LoxException<'src>> {
+    fn visit_while_stmt(&mut self, stmt: &While) -> Result<(), LoxException> {
         loop {
             let condition_value = self.evaluate(&stmt.condition)?;
             if !self.is_truthy(&condition_value) {
@@ -481,94 +1682,275 @@ impl<'src> StmtVisitor<'src, Result<(), LoxException<'src>>> for Interpreter<'sr
         Ok(())
     }
 
-    fn visit_break_stmt(&mut self) -> Result<(), LoxException<'src>> {
+    fn visit_for_stmt(&mut self, stmt: &For) -> Result<(), LoxException> {
+        let Some(ref initializer) = stmt.initializer else {
+            return self.run_for_loop(stmt);
+        };
+        // The initializer's variable lives in one environment shared by every iteration.
+        let environment = Rc::new(RefCell::new(Environment::new(Some(Rc::clone(
+            &self.environment,
+        )))));
+        let previous_env = std::mem::replace(&mut self.environment, environment);
+        let result = self
+            .execute(initializer)
+            .and_then(|()| self.run_for_loop(stmt));
+        self.environment = previous_env;
+        result
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) -> Result<(), LoxException> {
+        let span = stmt.iterable.span();
+        let iterable = self.evaluate(&stmt.iterable)?;
+        let mut iterator = self.iterator_for(iterable, span)?;
+
+        while let Some(value) = self.next_value(&mut iterator, span)? {
+            // Each iteration gets a fresh environment so closures capture that iteration's value.
+            let environment = Rc::new(RefCell::new(Environment::new(Some(Rc::clone(
+                &self.environment,
+            )))));
+            environment
+                .borrow_mut()
+                .define(Rc::clone(&stmt.name.lexeme), value);
+            self.execute_block(std::slice::from_ref(stmt.body.as_ref()), environment)?;
+            if self.active_break {
+                break;
+            }
+        }
+        self.active_break = false;
+        Ok(())
+    }
+
+    fn visit_break_stmt(&mut self, _: &Break) -> Result<(), LoxException> {
         self.active_break = true;
         Ok(())
     }
 
-    fn visit_function_stmt(&mut self, stmt: &Function<'src>) -> Result<(), LoxException<'src>> {
-        let function_name = stmt.name.lexeme;
+    fn visit_function_stmt(&mut self, stmt: &Function) -> Result<(), LoxException> {
+        let mut decorators = Vec::with_capacity(stmt.decorators.len());
+        for decorator in stmt.decorators.iter() {
+            decorators.push((self.evaluate(decorator)?, decorator.span()));
+        }
+
+        let function_name = Rc::clone(&stmt.name.lexeme);
         let function = LoxFunction::new(
             &stmt.closure,
             Rc::clone(&self.environment),
-            Some(function_name),
+            Some(Rc::clone(&function_name)),
             false,
         );
-        self.environment.borrow_mut().define(
-            function_name,
-            LoxObject::Callable(LoxCallable::Function(Rc::new(function))),
-        );
+        let mut value = LoxObject::Callable(LoxCallable::Function(Rc::new(function)));
+
+        // The decorator closest to the function is applied first.
+        for (decorator, span) in decorators.into_iter().rev() {
+            value = self.decorate(decorator, value, span)?;
+        }
+
+        self.environment.borrow_mut().define(function_name, value);
         Ok(())
     }
 
-    fn visit_return_stmt(&mut self, stmt: &Return<'src>) -> Result<(), LoxException<'src>> {
+    fn visit_return_stmt(&mut self, stmt: &Return) -> Result<(), LoxException> {
         let value = self.evaluate(&stmt.value)?;
         Err(LoxException::Return(value))
     }
 
-    fn visit_class_stmt(&mut self, stmt: &Class<'src>) -> Result<(), LoxException<'src>> {
-        let mut superclass = None;
-        if let Some(ref superclass_expr) = stmt.superclass {
-            let superclass_err = LoxException::RuntimeError(RuntimeError::new(
-                stmt.name.line,
-                String::from("Superclass must be a class."),
-            ));
-
-            let superclass_obj = self.evaluate(superclass_expr)?;
-            if let LoxObject::Callable(ref callable) = superclass_obj {
-                match callable {
-                    LoxCallable::Class(class) => {
-                        superclass = Some(Rc::clone(class));
-                    }
-                    _ => {
-                        return Err(superclass_err);
-                    }
-                }
-            } else {
-                return Err(superclass_err);
+    fn visit_class_stmt(&mut self, stmt: &Class) -> Result<(), LoxException> {
+        let superclass = match stmt.superclass {
+            Some(ref superclass_expr) => {
+                let value = self.evaluate(superclass_expr)?;
+                Some(self.superclass(value, superclass_expr.span())?)
             }
-        }
+            None => None,
+        };
 
-        let class_name = stmt.name.lexeme;
+        let methods = stmt.methods.iter().filter_map(|method| match method {
+            Stmt::Function(function) => Some((&function.name.lexeme, &function.closure, None)),
+            _ => None,
+        });
+        let klass = self.class(&stmt.name.lexeme, stmt.doc.as_ref(), superclass, methods);
+
+        // Defined only now, but methods still see the class: they share this environment.
         self.environment
             .borrow_mut()
-            .define(class_name, LoxObject::Literal(LoxLiteral::Nil));
+            .define(Rc::clone(&stmt.name.lexeme), klass);
+        Ok(())
+    }
+}
 
-        if superclass.is_some() {
-            self.environment = Rc::new(RefCell::new(Environment::new(Some(Rc::clone(
-                &self.environment,
-            )))));
-            self.environment.borrow_mut().define(
-                "super",
-                LoxObject::Callable(LoxCallable::Class(Rc::clone(superclass.as_ref().unwrap()))),
-            );
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run::{run_source, LoxError, SharedBuffer};
+    use std::thread;
+
+    /// What `source` prints, failing the test if it doesn't run to the end.
+    fn output(source: &str) -> String {
+        output_on(Backend::TreeWalk, source)
+    }
+
+    fn output_on(backend: Backend, source: &str) -> String {
+        let buffer = SharedBuffer::default();
+        let mut interpreter = Interpreter::builder()
+            .backend(backend)
+            .output(buffer.clone())
+            .build();
+        if let Err(errors) = run_source(source, &mut interpreter) {
+            panic!("{errors:?}");
         }
+        let bytes = buffer.0.borrow().clone();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn lists_containing_themselves_print_as_ellipses() {
+        let source = "var a=[0, 1]; a[0]=a; print a; var b=[2]; print [b, b];";
+        assert_eq!(output(source), "[[...], 1]\n[[2], [2]]\n");
+    }
+
+    #[test]
+    fn lists_containing_themselves_compare_equal() {
+        let source = "var a=[0]; a[0]=a; var c=clone(a); print c==a;";
+        assert_eq!(output(source), "true\n");
+    }
 
-        let mut methods = HashMap::new();
-        for method in stmt.methods.iter() {
-            if let Stmt::Function(function) = method {
-                let method_name = function.name.lexeme;
-                let lox_fun = LoxFunction::new(
-                    &function.closure,
-                    Rc::clone(&self.environment),
-                    Some(method_name),
-                    method_name == "init",
-                );
-                methods.insert(method_name, lox_fun);
+    #[test]
+    fn doubling_a_string_forever_is_a_runtime_error() {
+        let source = r#"
+            var s = "0123456789012345678901234567890123456789012345678901234567890123456789";
+            while (true) s = s + s;
+        "#;
+        for backend in [Backend::TreeWalk, Backend::Vm] {
+            let mut interpreter = Interpreter::builder().backend(backend).build();
+            match run_source(source, &mut interpreter)
+                .as_ref()
+                .map_err(Vec::as_slice)
+            {
+                Err([LoxError::Runtime(error)]) => {
+                    assert_eq!(error.code, ErrorCode::STRING_TOO_LONG);
+                    assert_eq!(error.message, "String too long.");
+                }
+                result => panic!("{backend:?}: expected a runtime error, got {result:?}"),
             }
         }
+    }
 
-        if superclass.is_some() {
-            let enclosing = self.environment.borrow_mut().enclosing.take().unwrap();
-            self.environment = enclosing;
+    #[test]
+    fn the_default_call_depth_fits_in_a_main_thread_stack() {
+        let source = "fun f(n) { { return ((((f(n + 1)))) + 1); } } f(0);";
+        for backend in [Backend::TreeWalk, Backend::Vm] {
+            let result = thread::Builder::new()
+                .stack_size(8 * 1024 * 1024)
+                .spawn(move || {
+                    let mut interpreter = Interpreter::builder().backend(backend).build();
+                    match run_source(source, &mut interpreter) {
+                        Err(errors) => match errors.as_slice() {
+                            [LoxError::Runtime(error)] => error.message.clone(),
+                            errors => format!("{errors:?}"),
+                        },
+                        Ok(()) => String::from("no error"),
+                    }
+                })
+                .unwrap()
+                .join()
+                .unwrap();
+            assert_eq!(result, "Stack overflow.", "{backend:?}");
+        }
+    }
+
+    #[test]
+    fn functions_are_equal_to_themselves() {
+        let source = r#"
+            fun f() {}
+            var g = f;
+            print g == f;
+            fun make() { fun h() {} return h; }
+            print make() == make();
+            class A { m() {} }
+            var a = A();
+            print a.m == a.m;
+            print a.m == A().m;
+        "#;
+        for backend in [Backend::TreeWalk, Backend::Vm] {
+            assert_eq!(output_on(backend, source), "true\nfalse\ntrue\nfalse\n");
         }
+    }
 
-        let klass = LoxClass::new(class_name, superclass, methods);
+    #[test]
+    fn locals_kept_on_the_vm_stack_behave_as_in_environments() {
+        let source = r#"
+            fun total(n) {
+                var sum = 0;
+                for (var i = 0; i < n; i = i + 1) {
+                    var (a, b) = [i, i * 2];
+                    for (var j in [1, 2, 3]) {
+                        if (j == 3) break;
+                        sum = sum + a * j + b;
+                    }
+                    { var scaled = [x * i for x in [1, 2] if x != sum]; print scaled; }
+                }
+                return sum;
+            }
+            print total(4);
+            {
+                var kept = 1;
+                { var shadow = kept + 1; var kept = shadow * 10; print kept; }
+                var get = fun () { return kept; };
+                kept = kept + 1;
+                print get();
+            }
+            class A { init(x) { var y = x; { this.x = y; } } get() { var x = this.x; return x; } }
+            class B < A { get() { var base = super.get(); return base + 1; } }
+            print B(41).get();
+        "#;
+        let expected = output_on(Backend::TreeWalk, source);
+        assert_eq!(output_on(Backend::Vm, source), expected);
+        assert!(expected.ends_with("42\n"), "{expected}");
+    }
 
-        self.environment.borrow_mut().assign(
-            &stmt.name,
-            LoxObject::Callable(LoxCallable::Class(Rc::new(klass))),
-        )?;
-        Ok(())
+    #[test]
+    fn cancelling_stops_a_script_blocked_in_sleep() {
+        let mut interpreter = Interpreter::new();
+        let token = interpreter.cancellation_token();
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            token.cancel();
+        });
+        let start = std::time::Instant::now();
+        let result = run_source("sleep(1000000000); print 1;", &mut interpreter);
+        canceller.join().unwrap();
+        match result {
+            Err(errors) => assert!(matches!(errors[..], [LoxError::Cancelled]), "{errors:?}"),
+            Ok(()) => panic!("sleep() wasn't cancelled"),
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn swapped_letters_suggest_the_intended_name() {
+        let mut interpreter = Interpreter::new();
+        match run_source("var count = 1; print coutn;", &mut interpreter) {
+            Err(errors) => match errors.as_slice() {
+                [LoxError::Runtime(error)] => assert_eq!(
+                    error.message,
+                    "Undefined variable 'coutn'. Did you mean 'count'?"
+                ),
+                errors => panic!("{errors:?}"),
+            },
+            Ok(()) => panic!("coutn should be undefined"),
+        }
+    }
+
+    #[test]
+    fn resolving_the_same_literals_again_reuses_their_constants() {
+        let mut interpreter = Interpreter::builder()
+            .output(SharedBuffer::default())
+            .build();
+        let source = "fun f() { return 1.5 + 2; } print f(); print \"a\";";
+        run_source(source, &mut interpreter).unwrap();
+        let pooled = interpreter.constants.len();
+        for _ in 0..3 {
+            run_source(source, &mut interpreter).unwrap();
+        }
+        assert_eq!(interpreter.constants.len(), pooled);
     }
 }
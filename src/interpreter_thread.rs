@@ -0,0 +1,117 @@
+use crate::{cancellation::CancellationToken, interpreter::Interpreter};
+use std::{
+    panic,
+    sync::{
+        atomic::AtomicBool,
+        mpsc::{self, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+/// Stack size for threads that run an interpreter. Each Lox call recurses through several
+/// interpreter frames, so this holds [`max_call_depth_for`]`(STACK_SIZE)` calls where a main
+/// thread holds only [`DEFAULT_MAX_CALL_DEPTH`].
+///
+/// [`max_call_depth_for`]: crate::interpreter::max_call_depth_for
+/// [`DEFAULT_MAX_CALL_DEPTH`]: crate::interpreter::DEFAULT_MAX_CALL_DEPTH
+pub const STACK_SIZE: usize = 512 * 1024 * 1024;
+
+type Job = Box<dyn FnOnce(&mut Interpreter) + Send>;
+
+/// An interpreter running on a thread of its own, for multi-threaded and async hosts.
+///
+/// Lox values share state through `Rc` and `RefCell`, so an [`Interpreter`] can't leave the
+/// thread it was created on. This handle can: it's `Send` and `Sync`, and each call to
+/// [`InterpreterThread::with`] runs on the interpreter's thread, one at a time, in the order
+/// they arrive. Values passed in and out must be `Send`, so convert Lox values to Rust ones
+/// before returning them.
+pub struct InterpreterThread {
+    jobs: Option<Sender<Job>>,
+    thread: Option<JoinHandle<()>>,
+    interrupted: Arc<AtomicBool>,
+    cancellation: CancellationToken,
+}
+
+impl InterpreterThread {
+    /// Starts a thread and creates its interpreter there with `create`, such as
+    /// `Interpreter::new` or a closure calling [`Interpreter::builder`].
+    pub fn spawn(create: impl FnOnce() -> Interpreter + Send + 'static) -> Self {
+        let (jobs, received) = mpsc::channel::<Job>();
+        let (flag_sender, flag) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                let mut interpreter = create();
+                // The handle waits for these, so this can only fail if it was dropped.
+                let _ = flag_sender.send((
+                    interpreter.interrupt_flag(),
+                    interpreter.cancellation_token(),
+                ));
+                for job in received {
+                    job(&mut interpreter);
+                }
+            })
+            .expect("failed to spawn interpreter thread");
+        let (interrupted, cancellation) = match flag.recv() {
+            Ok(flags) => flags,
+            // `create` panicked; pass its panic on.
+            Err(_) => match thread.join() {
+                Err(panic) => panic::resume_unwind(panic),
+                Ok(()) => unreachable!("Expect the thread to send its flag before returning."),
+            },
+        };
+        InterpreterThread {
+            jobs: Some(jobs),
+            thread: Some(thread),
+            interrupted,
+            cancellation,
+        }
+    }
+
+    /// Runs `job` with the interpreter on its thread and returns what it returns, waiting for
+    /// calls made earlier to finish first. A panic in `job` is passed on to the caller, and
+    /// leaves the interpreter unusable.
+    pub fn with<R: Send + 'static>(
+        &self,
+        job: impl FnOnce(&mut Interpreter) -> R + Send + 'static,
+    ) -> R {
+        let (result_sender, result) = mpsc::sync_channel(1);
+        let job: Job = Box::new(move |interpreter| {
+            let _ = result_sender.send(job(interpreter));
+        });
+        let sent = self
+            .jobs
+            .as_ref()
+            .expect("Expect the job channel to stay open until drop.")
+            .send(job);
+        match sent.ok().and_then(|()| result.recv().ok()) {
+            Some(value) => value,
+            None => panic!("The interpreter thread panicked."),
+        }
+    }
+
+    /// The interpreter's [`Interpreter::interrupt_flag`], which can be set from any thread to
+    /// abort the running call to [`InterpreterThread::with`] with a runtime error.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.interrupted)
+    }
+
+    /// The interpreter's [`Interpreter::cancellation_token`], for stopping the running call to
+    /// [`InterpreterThread::with`] with [`LoxException::Cancelled`].
+    ///
+    /// [`LoxException::Cancelled`]: crate::lox_exception::LoxException::Cancelled
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+}
+
+impl Drop for InterpreterThread {
+    /// Waits for calls in progress to finish, then stops the thread.
+    fn drop(&mut self) {
+        drop(self.jobs.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
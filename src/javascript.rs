@@ -0,0 +1,585 @@
+use crate::{
+    diagnostic::{emit, has_errors, json_string},
+    expr::{
+        Assign, Binary, Call, Closure, Expr, ExprVisitor, Get, Grouping, Index, List,
+        ListComprehension, Literal, Logical, Set, SetIndex, Super, Ternary, This, Unary, Variable,
+    },
+    interpreter::Interpreter,
+    lox_object::LoxLiteral,
+    parser::Parser,
+    resolver::{ResolvedTo, Resolver},
+    scanner::Scanner,
+    stmt::{
+        Block, Break, Class, Destructure, Expression, For, ForIn, Function, If, Print, Return,
+        Stmt, StmtVisitor, Var, While,
+    },
+    token::Token,
+    token_type::TokenType,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+/// Helpers that give the translated program Lox's semantics, plus the string, time and
+/// introspection natives.
+const PRELUDE: &str = include_str!("javascript_prelude.js");
+
+const INDENT: &str = "  ";
+
+/// Identifiers Lox allows but strict-mode JavaScript reserves. They get a `$` appended, which
+/// no Lox identifier contains.
+const RESERVED: &[&str] = &[
+    "arguments",
+    "await",
+    "case",
+    "catch",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "enum",
+    "eval",
+    "export",
+    "extends",
+    "finally",
+    "function",
+    "implements",
+    "import",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "static",
+    "switch",
+    "throw",
+    "try",
+    "typeof",
+    "void",
+    "with",
+    "yield",
+];
+
+/// Translates `source` to a standalone JavaScript program that behaves the same, for running
+/// Lox in a browser or Node without the interpreter. Values map onto JavaScript's own, with a
+/// prelude of helpers for the semantics that differ, such as truthiness and binding methods
+/// when they're accessed; runtime errors are logged with `console.error`. Returns `None` if
+/// the source has scan, parse or resolution errors, which are reported as usual.
+pub fn transpile(source: &str) -> Option<String> {
+    let mut scanner = Scanner::new(source);
+    scanner.scan_tokens();
+    emit(source, &scanner.diagnostics);
+    let mut parser = Parser::new(scanner.tokens);
+    let parse_result = parser.parse();
+    emit(source, &parser.diagnostics);
+    let statements = parse_result.ok()?;
+    if has_errors(&scanner.diagnostics) {
+        return None;
+    }
+    let mut interpreter = Interpreter::new();
+    let mut resolver = Resolver::new(&mut interpreter);
+    resolver.resolve_statements(&statements);
+    if resolver.had_error() {
+        emit(source, &resolver.diagnostics);
+        return None;
+    }
+
+    let mut emitter = JsEmitter {
+        depth: 1,
+        scopes: Vec::new(),
+        initializer: false,
+        references: HashMap::new(),
+        globals: HashSet::new(),
+        local_names: HashMap::new(),
+        renamed: 0,
+    };
+    for resolution in resolver.resolutions {
+        let position = (resolution.line, resolution.column);
+        match (resolution.resolved_to, resolution.declared_at) {
+            (ResolvedTo::Global { .. }, _) => {
+                emitter.globals.insert(resolution.name);
+            }
+            (ResolvedTo::Local { .. }, Some(declared_at)) => {
+                emitter.references.insert(position, declared_at);
+            }
+            (ResolvedTo::Local { .. }, None) => {}
+        }
+    }
+
+    let mut out = String::from(PRELUDE);
+    out.push_str("\n$run(() => {\n");
+    for statement in statements.iter() {
+        out.push_str(&statement.accept(&mut emitter));
+    }
+    out.push_str("});\n");
+    Some(out)
+}
+
+fn mangle(name: &str) -> String {
+    match RESERVED.contains(&name) {
+        true => format!("{name}$"),
+        false => name.to_string(),
+    }
+}
+
+struct JsEmitter {
+    /// Nesting of the blocks being written, used for indentation.
+    depth: usize,
+    /// Names of the Lox locals in each scope enclosing the code being written, outermost
+    /// first. Empty at the top level, whose variables are globals.
+    scopes: Vec<Vec<Rc<str>>>,
+    /// Whether the code being written is the body of an `init` method.
+    initializer: bool,
+    /// The declaration each reference to a local resolved to, by line and column.
+    references: HashMap<(usize, usize), (usize, usize)>,
+    /// Names referenced as globals anywhere in the script.
+    globals: HashSet<String>,
+    /// The JavaScript name of each local, by the line and column of its declaration.
+    local_names: HashMap<(usize, usize), String>,
+    /// How many locals have been renamed so far, to number the next.
+    renamed: usize,
+}
+
+impl JsEmitter {
+    fn indent(&self) -> String {
+        INDENT.repeat(self.depth)
+    }
+
+    fn line(&self, text: &str) -> String {
+        format!("{}{text}\n", self.indent())
+    }
+
+    /// `let` for locals and `var` for globals, which Lox lets a script redeclare.
+    fn keyword(&self) -> &'static str {
+        match self.scopes.is_empty() {
+            true => "var",
+            false => "let",
+        }
+    }
+
+    /// The JavaScript name for a variable declared by `name`. JavaScript hoists a `let` to
+    /// the top of its block where Lox resolves names in order, so a local that shadows a
+    /// global or an enclosing local gets a name of its own, numbered after a `$`.
+    fn declare(&mut self, name: &Token) -> String {
+        if self.scopes.is_empty() {
+            return mangle(&name.lexeme);
+        }
+        let shadows = self.globals.contains(&*name.lexeme)
+            || self
+                .scopes
+                .iter()
+                .flatten()
+                .any(|local| *local == name.lexeme);
+        let js_name = match shadows {
+            true => {
+                self.renamed += 1;
+                format!("{}${}", name.lexeme, self.renamed)
+            }
+            false => mangle(&name.lexeme),
+        };
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(Rc::clone(&name.lexeme));
+        }
+        self.local_names
+            .insert((name.line, name.column), js_name.clone());
+        js_name
+    }
+
+    /// The JavaScript name for a reference to the variable `name`.
+    fn reference(&self, name: &Token) -> String {
+        self.references
+            .get(&(name.line, name.column))
+            .and_then(|declared_at| self.local_names.get(declared_at))
+            .cloned()
+            .unwrap_or_else(|| mangle(&name.lexeme))
+    }
+
+    /// Writes `statements` as a braced block, in a scope of their own.
+    fn block(&mut self, statements: &[Stmt]) -> String {
+        self.scopes.push(Vec::new());
+        let block = self.braced(statements, None);
+        self.scopes.pop();
+        block
+    }
+
+    /// Writes `statements`, then `last` if given, between braces.
+    fn braced(&mut self, statements: &[Stmt], last: Option<&str>) -> String {
+        let mut out = String::from("{\n");
+        self.depth += 1;
+        for statement in statements {
+            out.push_str(&statement.accept(self));
+        }
+        if let Some(last) = last {
+            out.push_str(&self.line(last));
+        }
+        self.depth -= 1;
+        out.push_str(&self.indent());
+        out.push('}');
+        out
+    }
+
+    /// The body of an `if` or loop, braced even if it's a single statement.
+    fn body(&mut self, stmt: &Stmt) -> String {
+        match stmt {
+            Stmt::Block(block) => self.block(&block.statements),
+            _ => self.block(std::slice::from_ref(stmt)),
+        }
+    }
+
+    /// The parameter list and body of a function, whose parameters share a scope with its
+    /// body as they do in Lox.
+    fn function(&mut self, closure: &Closure, initializer: bool) -> (String, String) {
+        let enclosing = self.initializer;
+        self.initializer = initializer;
+        self.scopes.push(Vec::new());
+        let params: Vec<String> = closure
+            .params
+            .iter()
+            .map(|param| self.declare(param))
+            .collect();
+        // An initializer returns its instance even when it runs off the end.
+        let last = initializer.then_some("return this;");
+        let body = self.braced(&closure.body, last);
+        self.scopes.pop();
+        self.initializer = enclosing;
+        (params.join(", "), body)
+    }
+
+    fn arguments(&mut self, exprs: &[Expr]) -> Vec<String> {
+        exprs.iter().map(|expr| expr.accept(self)).collect()
+    }
+}
+
+impl ExprVisitor<String> for JsEmitter {
+    fn visit_binary_expr(&mut self, expr: &Binary) -> String {
+        let left = expr.left.accept(self);
+        let right = expr.right.accept(self);
+        let helper = match expr.operator.token_type {
+            TokenType::Plus => "$add",
+            TokenType::Minus => "$sub",
+            TokenType::Star => "$mul",
+            TokenType::Slash => "$div",
+            TokenType::Greater => "$gt",
+            TokenType::GreaterEqual => "$ge",
+            TokenType::Less => "$lt",
+            TokenType::LessEqual => "$le",
+            TokenType::EqualEqual => "$eq",
+            TokenType::BangEqual => "!$eq",
+            // The comma operator is JavaScript's too.
+            _ => return format!("({left}, {right})"),
+        };
+        format!("{helper}({left}, {right})")
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Grouping) -> String {
+        format!("({})", expr.expression.accept(self))
+    }
+
+    fn visit_literal_expr(&mut self, expr: &Literal) -> String {
+        match expr.value {
+            LoxLiteral::Number(val) if val.is_nan() => String::from("NaN"),
+            LoxLiteral::Number(val) if val.is_infinite() => {
+                String::from(if val > 0.0 { "Infinity" } else { "-Infinity" })
+            }
+            LoxLiteral::Number(val) => format!("{val}"),
+            LoxLiteral::String(ref val) => json_string(&val.to_string()),
+            LoxLiteral::Boolean(val) => format!("{val}"),
+            LoxLiteral::Nil => String::from("null"),
+        }
+    }
+
+    fn visit_unary_expr(&mut self, expr: &Unary) -> String {
+        let right = expr.right.accept(self);
+        match expr.operator.token_type {
+            TokenType::Minus => format!("$neg({right})"),
+            _ => format!("!$truthy({right})"),
+        }
+    }
+
+    fn visit_ternary_expr(&mut self, expr: &Ternary) -> String {
+        format!(
+            "($truthy({}) ? {} : {})",
+            expr.condition.accept(self),
+            expr.left.accept(self),
+            expr.right.accept(self)
+        )
+    }
+
+    fn visit_variable_expr(&mut self, expr: &Variable) -> String {
+        self.reference(&expr.name)
+    }
+
+    fn visit_assign_expr(&mut self, expr: &Assign) -> String {
+        let value = expr.value.accept(self);
+        format!("({} = {value})", self.reference(&expr.name))
+    }
+
+    fn visit_logical_expr(&mut self, expr: &Logical) -> String {
+        let helper = match expr.operator.token_type {
+            TokenType::Or => "$or",
+            _ => "$and",
+        };
+        format!(
+            "{helper}({}, () => {})",
+            expr.left.accept(self),
+            expr.right.accept(self)
+        )
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) -> String {
+        let mut arguments = vec![expr.callee.accept(self)];
+        arguments.extend(self.arguments(&expr.arguments));
+        format!("$call({})", arguments.join(", "))
+    }
+
+    fn visit_closure_expr(&mut self, expr: &Rc<Closure>) -> String {
+        let (params, body) = self.function(expr, false);
+        format!("$fn(null, ({params}) => {body})")
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) -> String {
+        format!(
+            "$get({}, {})",
+            expr.object.accept(self),
+            json_string(&expr.name.lexeme)
+        )
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) -> String {
+        format!(
+            "$set($expectInstance({}), {}, {})",
+            expr.object.accept(self),
+            json_string(&expr.name.lexeme),
+            expr.value.accept(self)
+        )
+    }
+
+    fn visit_this_expr(&mut self, _: &This) -> String {
+        String::from("this")
+    }
+
+    fn visit_super_expr(&mut self, expr: &Super) -> String {
+        format!("$super(super$, this, {})", json_string(&expr.method.lexeme))
+    }
+
+    fn visit_list_expr(&mut self, expr: &List) -> String {
+        format!("[{}]", self.arguments(&expr.elements).join(", "))
+    }
+
+    fn visit_list_comprehension_expr(&mut self, expr: &ListComprehension) -> String {
+        // Evaluated outside the comprehension's scope, so it's passed in.
+        let iterable = expr.iterable.accept(self);
+        self.scopes.push(Vec::new());
+        let name = self.declare(&expr.name);
+        let element = expr.element.accept(self);
+        let push = match expr.condition {
+            Some(ref condition) => format!(
+                "if ($truthy({})) $list.push({element});",
+                condition.accept(self)
+            ),
+            None => format!("$list.push({element});"),
+        };
+        self.scopes.pop();
+        format!(
+            "(($iterable) => {{ let {name}; const $list = []; \
+             for ({name} of $iterate($iterable)) {push} return $list; }})({iterable})"
+        )
+    }
+
+    fn visit_index_expr(&mut self, expr: &Index) -> String {
+        format!(
+            "$index({}, {})",
+            expr.object.accept(self),
+            expr.index.accept(self)
+        )
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &SetIndex) -> String {
+        format!(
+            "$setIndex($indexTarget({}, {}), {})",
+            expr.object.accept(self),
+            expr.index.accept(self),
+            expr.value.accept(self)
+        )
+    }
+}
+
+impl StmtVisitor<String> for JsEmitter {
+    fn visit_expression_stmt(&mut self, stmt: &Expression) -> String {
+        let expression = stmt.expression.accept(self);
+        self.line(&format!("{expression};"))
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Print) -> String {
+        let expression = stmt.expression.accept(self);
+        self.line(&format!("$print({expression});"))
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Var) -> String {
+        let initializer = match stmt.initializer {
+            Some(ref initializer) => initializer.accept(self),
+            None => String::from("null"),
+        };
+        let keyword = self.keyword();
+        let name = self.declare(&stmt.name);
+        self.line(&format!("{keyword} {name} = {initializer};"))
+    }
+
+    fn visit_destructure_stmt(&mut self, stmt: &Destructure) -> String {
+        let initializer = stmt.initializer.accept(self);
+        let keyword = self.keyword();
+        let names: Vec<String> = stmt.names.iter().map(|name| self.declare(name)).collect();
+        self.line(&format!(
+            "{keyword} [{}] = $destructure({initializer}, {});",
+            names.join(", "),
+            names.len()
+        ))
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &Block) -> String {
+        let block = self.block(&stmt.statements);
+        self.line(&block)
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &If) -> String {
+        let condition = stmt.condition.accept(self);
+        let mut out = format!("if ($truthy({condition})) {}", self.body(&stmt.then_branch));
+        if let Some(ref else_branch) = stmt.else_branch {
+            out.push_str(&format!(" else {}", self.body(else_branch)));
+        }
+        self.line(&out)
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &While) -> String {
+        let condition = stmt.condition.accept(self);
+        let body = self.body(&stmt.body);
+        self.line(&format!("while ($truthy({condition})) {body}"))
+    }
+
+    fn visit_for_stmt(&mut self, stmt: &For) -> String {
+        // Lox shares one loop variable across iterations, unlike a `let` in a JavaScript
+        // `for`, so the initializer goes in a block around the loop.
+        let Some(ref initializer) = stmt.initializer else {
+            return self.for_loop(stmt);
+        };
+        self.scopes.push(Vec::new());
+        self.depth += 1;
+        let mut out = String::from("{\n");
+        out.push_str(&initializer.accept(self));
+        out.push_str(&self.for_loop(stmt));
+        self.depth -= 1;
+        self.scopes.pop();
+        out.push_str(&self.indent());
+        out.push('}');
+        self.line(&out)
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) -> String {
+        let iterable = stmt.iterable.accept(self);
+        self.scopes.push(Vec::new());
+        let name = self.declare(&stmt.name);
+        let body = self.body(&stmt.body);
+        self.scopes.pop();
+        self.line(&format!("for (let {name} of $iterate({iterable})) {body}"))
+    }
+
+    fn visit_break_stmt(&mut self, _: &Break) -> String {
+        self.line("break;")
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Function) -> String {
+        let decorators = self.arguments(&stmt.decorators);
+        let keyword = self.keyword();
+        let name = self.declare(&stmt.name);
+        let (params, body) = self.function(&stmt.closure, false);
+        let mut value = format!(
+            "$fn({}, ({params}) => {body})",
+            json_string(&stmt.name.lexeme)
+        );
+        // The decorator closest to the function is applied first.
+        for decorator in decorators.iter().rev() {
+            value = format!("$decorate({decorator}, {value})");
+        }
+        self.line(&format!("{keyword} {name} = {value};"))
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Return) -> String {
+        if self.initializer {
+            return self.line("return this;");
+        }
+        let value = stmt.value.accept(self);
+        self.line(&format!("return {value};"))
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &Class) -> String {
+        let superclass = stmt
+            .superclass
+            .as_ref()
+            .map(|superclass| superclass.accept(self));
+        let keyword = self.keyword();
+        let name = self.declare(&stmt.name);
+
+        let mut methods = String::new();
+        self.depth += 1;
+        for method in stmt.methods.iter() {
+            if let Stmt::Function(method) = method {
+                let key = match &*method.name.lexeme {
+                    // A method named `constructor` would be the JavaScript constructor.
+                    "constructor" => String::from("[\"constructor\"]"),
+                    key => key.to_string(),
+                };
+                let initializer = &*method.name.lexeme == "init";
+                let (params, body) = self.function(&method.closure, initializer);
+                methods.push_str(&self.line(&format!("{key}({params}) {body}")));
+            }
+        }
+        self.depth -= 1;
+
+        let class_name = json_string(&stmt.name.lexeme);
+        let value = match superclass {
+            Some(superclass) => format!(
+                "$class({class_name}, ((super$) => class extends super$ {{\n{methods}{}}})($superclass({superclass})))",
+                self.indent()
+            ),
+            None => format!(
+                "$class({class_name}, class extends $Instance {{\n{methods}{}}})",
+                self.indent()
+            ),
+        };
+        self.line(&format!("{keyword} {name} = {value};"))
+    }
+}
+
+impl JsEmitter {
+    fn for_loop(&mut self, stmt: &For) -> String {
+        let condition = match stmt.condition {
+            Some(ref condition) => format!("$truthy({})", condition.accept(self)),
+            None => String::new(),
+        };
+        let increment = match stmt.increment {
+            Some(ref increment) => format!(" {}", increment.accept(self)),
+            None => String::new(),
+        };
+        let body = self.body(&stmt.body);
+        self.line(&format!("for (; {condition};{increment}) {body}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literals_too_big_for_a_float_are_infinity() {
+        let source = format!("print 1{};", "0".repeat(400));
+        let js = transpile(&source).unwrap();
+        assert!(js.contains("$print(Infinity);"), "{js}");
+    }
+}
@@ -0,0 +1,401 @@
+"use strict";
+// Runtime support for Lox programs translated by `lox_treewalk js`. Lox values map onto
+// JavaScript ones: nil is null, lists are arrays, functions are functions and every class
+// extends $Instance. Names starting with `$` can't clash with Lox identifiers.
+
+class $RuntimeError extends Error {}
+
+function $error(message) {
+  return new $RuntimeError(message);
+}
+
+class $Instance {
+  constructor() {
+    this.$fields = new Map();
+  }
+}
+
+function $truthy(value) {
+  return value !== null && value !== false;
+}
+
+function $isClass(value) {
+  return typeof value === "function" && value.prototype instanceof $Instance;
+}
+
+// Tags a function with the name it prints with, or null for an anonymous one.
+function $fn(name, fn) {
+  fn.$name = name;
+  return fn;
+}
+
+function $native(name, fn) {
+  fn.$name = name;
+  fn.$native = true;
+  return fn;
+}
+
+// Names a class and its methods, which print and are called by those names.
+function $class(name, cls) {
+  cls.$name = name;
+  cls.prototype.$class = cls;
+  for (const method of Object.getOwnPropertyNames(cls.prototype)) {
+    const value = cls.prototype[method];
+    if (typeof value === "function" && value !== cls) value.$name = method;
+  }
+  return cls;
+}
+
+function $superclass(value) {
+  if (!$isClass(value)) throw $error("Superclass must be a class.");
+  return value;
+}
+
+// Looks `name` up among the methods of `proto` and the classes it inherits from.
+function $findMethod(proto, name) {
+  for (let p = proto; p !== $Instance.prototype; p = Object.getPrototypeOf(p)) {
+    // Every prototype has a `constructor`, but it's only a method if Lox defined one.
+    if (Object.hasOwn(p, name) && p[name] !== p.$class) return p[name];
+  }
+  return undefined;
+}
+
+function $bind(method, instance) {
+  const bound = method.bind(instance);
+  bound.$name = method.$name;
+  return bound;
+}
+
+function $arity(callee) {
+  if ($isClass(callee)) {
+    const init = $findMethod(callee.prototype, "init");
+    return init ? init.length : 0;
+  }
+  return callee.length;
+}
+
+function $call(callee, ...args) {
+  if (typeof callee !== "function") throw $error("Can only call functions and classes.");
+  const arity = $arity(callee);
+  if (args.length !== arity) {
+    throw $error(`Expected ${arity} arguments but got ${args.length}.`);
+  }
+  if ($isClass(callee)) {
+    const instance = new callee();
+    const init = $findMethod(callee.prototype, "init");
+    if (init) init.apply(instance, args);
+    return instance;
+  }
+  const result = callee(...args);
+  return result === undefined ? null : result;
+}
+
+function $decorate(decorator, value) {
+  if (typeof decorator !== "function" || $arity(decorator) !== 1) {
+    throw $error("Decorator must be a callable taking one argument.");
+  }
+  return $call(decorator, value);
+}
+
+function $property(instance, name) {
+  if (instance.$fields.has(name)) return instance.$fields.get(name);
+  const method = $findMethod(Object.getPrototypeOf(instance), name);
+  return method ? $bind(method, instance) : undefined;
+}
+
+function $get(object, name) {
+  if (!(object instanceof $Instance)) throw $error("Only instances have properties.");
+  const value = $property(object, name);
+  if (value === undefined) throw $error(`Undefined property '${name}'.`);
+  return value;
+}
+
+function $expectInstance(object) {
+  if (!(object instanceof $Instance)) throw $error("Only instances have fields.");
+  return object;
+}
+
+function $set(instance, name, value) {
+  instance.$fields.set(name, value);
+  return value;
+}
+
+function $super(superclass, instance, name) {
+  const method = $findMethod(superclass.prototype, name);
+  if (!method) throw $error(`Undefined property '${name}'.`);
+  return $bind(method, instance);
+}
+
+function $listIndex(list, index) {
+  if (typeof index !== "number" || !Number.isInteger(index)) {
+    throw $error("List index must be an integer.");
+  }
+  if (index < 0 || index >= list.length) throw $error("List index out of range.");
+  return index;
+}
+
+function $expectList(object) {
+  if (!Array.isArray(object)) throw $error("Only lists can be indexed.");
+  return object;
+}
+
+function $index(object, index) {
+  const list = $expectList(object);
+  return list[$listIndex(list, index)];
+}
+
+// The list and index an element is assigned through, checked before the value is evaluated.
+function $indexTarget(object, index) {
+  return [$expectList(object), index];
+}
+
+function $setIndex([list, index], value) {
+  list[$listIndex(list, index)] = value;
+  return value;
+}
+
+function $destructure(value, count) {
+  if (!Array.isArray(value)) throw $error("Can only destructure lists.");
+  if (value.length !== count) {
+    throw $error(`Expected ${count} values to destructure but got ${value.length}.`);
+  }
+  return value;
+}
+
+function $iteratorMethod(iterator, name) {
+  const method = $property(iterator, name);
+  if (typeof method !== "function" || $arity(method) !== 0) {
+    throw $error(`Iterator must implement '${name}()' taking no arguments.`);
+  }
+  return $call(method);
+}
+
+// The values a for-in loop or list comprehension visits: a list's elements, or what an
+// instance's iterator returns from `next()` until `done()`.
+function* $iterate(iterable) {
+  if (Array.isArray(iterable)) {
+    for (let i = 0; i < iterable.length; i++) yield iterable[i];
+    return;
+  }
+  if (!(iterable instanceof $Instance)) {
+    throw $error(
+      "Can only iterate over lists and instances implementing the iterator protocol.",
+    );
+  }
+  let iterator = iterable;
+  if ($property(iterable, "iterate") !== undefined) {
+    iterator = $iteratorMethod(iterable, "iterate");
+    if (!(iterator instanceof $Instance)) throw $error("'iterate()' must return an instance.");
+  }
+  while (!$truthy($iteratorMethod(iterator, "done"))) {
+    yield $iteratorMethod(iterator, "next");
+  }
+}
+
+function $or(left, right) {
+  return $truthy(left) ? left : right();
+}
+
+function $and(left, right) {
+  return $truthy(left) ? right() : left;
+}
+
+function $numbers(left, right) {
+  if (typeof left !== "number" || typeof right !== "number") {
+    throw $error("Operands must be numbers.");
+  }
+}
+
+function $add(left, right) {
+  if (typeof left === "number" && typeof right === "number") return left + right;
+  if (typeof left === "string" || typeof right === "string") return $str(left) + $str(right);
+  throw $error("Operands must be two numbers or one must be a string.");
+}
+
+function $sub(left, right) {
+  $numbers(left, right);
+  return left - right;
+}
+
+function $mul(left, right) {
+  $numbers(left, right);
+  return left * right;
+}
+
+function $div(left, right) {
+  $numbers(left, right);
+  if (right === 0) throw $error("Cannot divide by zero.");
+  return left / right;
+}
+
+function $gt(left, right) {
+  $numbers(left, right);
+  return left > right;
+}
+
+function $ge(left, right) {
+  $numbers(left, right);
+  return left >= right;
+}
+
+function $lt(left, right) {
+  $numbers(left, right);
+  return left < right;
+}
+
+function $le(left, right) {
+  $numbers(left, right);
+  return left <= right;
+}
+
+function $neg(value) {
+  if (typeof value !== "number") throw $error("Operand must be a number.");
+  return -value;
+}
+
+// Lists are equal when their elements are; everything else compares as `===` does.
+function $eq(left, right) {
+  if (Array.isArray(left) && Array.isArray(right)) {
+    return (
+      left === right ||
+      (left.length === right.length && left.every((element, i) => $eq(element, right[i])))
+    );
+  }
+  return left === right;
+}
+
+// Formats a number as Lox does: never in exponent notation.
+function $number(value) {
+  if (value === Infinity) return "inf";
+  if (value === -Infinity) return "-inf";
+  if (Object.is(value, -0)) return "-0";
+  const text = String(value);
+  const match = /^(-?)(\d)(?:\.(\d+))?e([+-]\d+)$/.exec(text);
+  if (!match) return text;
+  const [, sign, first, rest = "", exponent] = match;
+  const digits = first + rest;
+  const point = 1 + Number(exponent);
+  if (point <= 0) return `${sign}0.${"0".repeat(-point)}${digits}`;
+  if (point >= digits.length) return sign + digits + "0".repeat(point - digits.length);
+  return `${sign}${digits.slice(0, point)}.${digits.slice(point)}`;
+}
+
+function $str(value) {
+  if (value === null) return "nil";
+  if (typeof value === "number") return $number(value);
+  if (typeof value === "string" || typeof value === "boolean") return String(value);
+  if (Array.isArray(value)) return `[${value.map($str).join(", ")}]`;
+  if ($isClass(value)) return value.$name;
+  if (typeof value === "function") {
+    if (value.$native) return "<native fn>";
+    return value.$name === null || value.$name === undefined ? "<fn>" : `<fn ${value.$name}>`;
+  }
+  if (value instanceof $Instance) return `${value.$class.$name} instance`;
+  return String(value);
+}
+
+function $print(value) {
+  console.log($str(value));
+}
+
+function $string(value, position, name) {
+  if (typeof value !== "string") throw $error(`${name}() expects a string as argument ${position}.`);
+  return value;
+}
+
+function $natural(value, position, name) {
+  if (typeof value !== "number" || value < 0 || !Number.isInteger(value)) {
+    throw $error(`${name}() expects a non-negative integer as argument ${position}.`);
+  }
+  return value;
+}
+
+const clock = $native("clock", () => Date.now() / 1000);
+const clockMillis = $native("clockMillis", () => Date.now());
+const now = $native("now", () => performance.now() / 1000);
+const type = $native("type", (value) => {
+  if (value === null) return "nil";
+  if (Array.isArray(value)) return "list";
+  if ($isClass(value)) return "class";
+  if (value instanceof $Instance) return value.$class.$name;
+  return typeof value;
+});
+const arityOf = $native("arityOf", (value) => {
+  if (typeof value !== "function") throw $error("arityOf() expects a function or class.");
+  return $arity(value);
+});
+const nameOf = $native("nameOf", (value) => {
+  if (typeof value !== "function") throw $error("nameOf() expects a function or class.");
+  return value.$name ?? null;
+});
+const eprint = $native("eprint", (value) => {
+  console.error($str(value));
+  return null;
+});
+const len = $native("len", (value) => {
+  if (typeof value === "string") return [...value].length;
+  if (Array.isArray(value)) return value.length;
+  throw $error("len() expects a string or list.");
+});
+const substr = $native("substr", (string, start, end) => {
+  const chars = [...$string(string, 1, "substr")];
+  $natural(start, 2, "substr");
+  $natural(end, 3, "substr");
+  if (start > end || end > chars.length) throw $error("substr() range out of bounds.");
+  return chars.slice(start, end).join("");
+});
+const indexOf = $native("indexOf", (string, needle) => {
+  const index = $string(string, 1, "indexOf").indexOf($string(needle, 2, "indexOf"));
+  return index < 0 ? -1 : [...string.slice(0, index)].length;
+});
+const split = $native("split", (string, separator) => {
+  $string(string, 1, "split");
+  $string(separator, 2, "split");
+  return separator === "" ? [...string] : string.split(separator);
+});
+const toUpper = $native("toUpper", (string) => $string(string, 1, "toUpper").toUpperCase());
+const toLower = $native("toLower", (string) => $string(string, 1, "toLower").toLowerCase());
+const trim = $native("trim", (string) => $string(string, 1, "trim").trim());
+const replace = $native("replace", (string, from, to) => {
+  $string(string, 1, "replace");
+  $string(from, 2, "replace");
+  $string(to, 3, "replace");
+  if (from === "") throw $error("replace() expects a non-empty pattern.");
+  return string.split(from).join(to);
+});
+const startsWith = $native("startsWith", (string, prefix) =>
+  $string(string, 1, "startsWith").startsWith($string(prefix, 2, "startsWith")),
+);
+const endsWith = $native("endsWith", (string, suffix) =>
+  $string(string, 1, "endsWith").endsWith($string(suffix, 2, "endsWith")),
+);
+const approxEqual = $native("approxEqual", (a, b, epsilon) => {
+  if (typeof a !== "number" || typeof b !== "number" || typeof epsilon !== "number" || epsilon < 0) {
+    throw $error("approxEqual() expects two numbers and a non-negative epsilon.");
+  }
+  return a === b || Math.abs(a - b) <= epsilon;
+});
+const random = $native("random", () => Math.random());
+const randomInt = $native("randomInt", (lo, hi) => {
+  if (!Number.isInteger(lo) || !Number.isInteger(hi)) {
+    throw $error("randomInt() expects two integers.");
+  }
+  if (lo > hi) throw $error("randomInt() expects lo to be at most hi.");
+  return lo + Math.floor(Math.random() * (hi - lo + 1));
+});
+const args = [];
+
+// Runs a translated program, reporting runtime errors as the interpreter would.
+function $run(program) {
+  try {
+    program();
+  } catch (error) {
+    if (error instanceof $RuntimeError) {
+      console.error(error.message);
+    } else if (error instanceof RangeError) {
+      console.error("Stack overflow.");
+    } else {
+      throw error;
+    }
+  }
+}
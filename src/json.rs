@@ -0,0 +1,284 @@
+use crate::diagnostic::{column_at, json_string};
+use std::{fmt, iter::Peekable, str::CharIndices};
+
+/// A parsed JSON value. Objects keep their members in order.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Parses `text`, which must hold exactly one value, possibly surrounded by whitespace.
+    pub(crate) fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = JsonParser {
+            text,
+            chars: text.char_indices().peekable(),
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        match parser.chars.peek() {
+            Some(&(offset, _)) => Err(parser.error_at(offset, "Expected the end of the input.")),
+            None => Ok(value),
+        }
+    }
+
+    /// The member `name`, if this is an object that has one.
+    pub(crate) fn get(&self, name: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(member, _)| member == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Writes the value indented two spaces per level, starting `depth` levels in.
+    fn write(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        let indent = "  ".repeat(depth + 1);
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{value}"),
+            // Like `JSON.stringify`, since JSON has no infinities or NaN.
+            Json::Number(value) if !value.is_finite() => write!(f, "null"),
+            Json::Number(value) => write!(f, "{value}"),
+            Json::String(value) => write!(f, "{}", json_string(value)),
+            Json::Array(elements) if elements.is_empty() => write!(f, "[]"),
+            Json::Array(elements) => {
+                writeln!(f, "[")?;
+                for (idx, element) in elements.iter().enumerate() {
+                    write!(f, "{indent}")?;
+                    element.write(f, depth + 1)?;
+                    writeln!(f, "{}", if idx + 1 < elements.len() { "," } else { "" })?;
+                }
+                write!(f, "{}]", "  ".repeat(depth))
+            }
+            Json::Object(members) if members.is_empty() => write!(f, "{{}}"),
+            Json::Object(members) => {
+                writeln!(f, "{{")?;
+                for (idx, (name, value)) in members.iter().enumerate() {
+                    write!(f, "{indent}{}: ", json_string(name))?;
+                    value.write(f, depth + 1)?;
+                    writeln!(f, "{}", if idx + 1 < members.len() { "," } else { "" })?;
+                }
+                write!(f, "{}}}", "  ".repeat(depth))
+            }
+        }
+    }
+}
+
+/// Pretty-printed, with each array element and object member on its own line.
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+struct JsonParser<'a> {
+    text: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl JsonParser<'_> {
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        let Some(&(offset, c)) = self.chars.peek() else {
+            return Err(self.error_at(self.text.len(), "Expected a value."));
+        };
+        match c {
+            '{' => self.object(),
+            '[' => self.array(),
+            '"' => self.string().map(Json::String),
+            '-' | '0'..='9' => self.number(),
+            _ => {
+                let rest = &self.text[offset..];
+                let (value, len) = if rest.starts_with("null") {
+                    (Json::Null, 4)
+                } else if rest.starts_with("true") {
+                    (Json::Bool(true), 4)
+                } else if rest.starts_with("false") {
+                    (Json::Bool(false), 5)
+                } else {
+                    return Err(self.error_at(offset, "Expected a value."));
+                };
+                for _ in 0..len {
+                    self.chars.next();
+                }
+                Ok(value)
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.chars.next();
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.eat('}') {
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if !matches!(self.chars.peek(), Some((_, '"'))) {
+                return Err(self.error_here("Expected a member name."));
+            }
+            let name = self.string()?;
+            self.skip_whitespace();
+            if !self.eat(':') {
+                return Err(self.error_here("Expected ':' after a member name."));
+            }
+            members.push((name, self.value()?));
+            self.skip_whitespace();
+            if self.eat('}') {
+                return Ok(Json::Object(members));
+            }
+            if !self.eat(',') {
+                return Err(self.error_here("Expected ',' or '}' after a member."));
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.chars.next();
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.eat(']') {
+            return Ok(Json::Array(elements));
+        }
+        loop {
+            elements.push(self.value()?);
+            self.skip_whitespace();
+            if self.eat(']') {
+                return Ok(Json::Array(elements));
+            }
+            if !self.eat(',') {
+                return Err(self.error_here("Expected ',' or ']' after an element."));
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let (start, _) = self
+            .chars
+            .next()
+            .expect("Expect a string to start with '\"'.");
+        let mut value = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(value),
+                Some((offset, '\\')) => {
+                    let escaped = match self.chars.next() {
+                        Some((_, '"')) => '"',
+                        Some((_, '\\')) => '\\',
+                        Some((_, '/')) => '/',
+                        Some((_, 'b')) => '\u{8}',
+                        Some((_, 'f')) => '\u{c}',
+                        Some((_, 'n')) => '\n',
+                        Some((_, 'r')) => '\r',
+                        Some((_, 't')) => '\t',
+                        Some((_, 'u')) => self.unicode_escape(offset)?,
+                        _ => return Err(self.error_at(offset, "Invalid escape sequence.")),
+                    };
+                    value.push(escaped);
+                }
+                Some((offset, c)) if c.is_control() => {
+                    return Err(self.error_at(offset, "Unescaped control character in string."))
+                }
+                Some((_, c)) => value.push(c),
+                None => return Err(self.error_at(start, "Unterminated string.")),
+            }
+        }
+    }
+
+    /// The character a `\u` escape starting at `offset` stands for, reading a second escape
+    /// for the low half of a surrogate pair.
+    fn unicode_escape(&mut self, offset: usize) -> Result<char, String> {
+        let high = self.hex_digits(offset)?;
+        let code = match high {
+            0xD800..=0xDBFF => {
+                if !(self.eat('\\') && self.eat('u')) {
+                    return Err(self.error_at(offset, "Unpaired surrogate in escape."));
+                }
+                let low = self.hex_digits(offset)?;
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    return Err(self.error_at(offset, "Unpaired surrogate in escape."));
+                }
+                0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+            }
+            _ => high,
+        };
+        char::from_u32(code).ok_or_else(|| self.error_at(offset, "Unpaired surrogate in escape."))
+    }
+
+    fn hex_digits(&mut self, offset: usize) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.chars.next().and_then(|(_, c)| c.to_digit(16));
+            let Some(digit) = digit else {
+                return Err(self.error_at(offset, "Expected four hex digits after '\\u'."));
+            };
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let (start, _) = *self
+            .chars
+            .peek()
+            .expect("Expect a number to have a first digit.");
+        let mut end = start;
+        while let Some(&(offset, c)) = self.chars.peek() {
+            if !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E') {
+                break;
+            }
+            end = offset + 1;
+            self.chars.next();
+        }
+        let text = &self.text[start..end];
+        // Rust accepts forms JSON doesn't, such as "+1", ".5" and "1.".
+        let digits = text.strip_prefix('-').unwrap_or(text);
+        let leading_zero =
+            digits.starts_with('0') && digits[1..].starts_with(|c: char| c.is_ascii_digit());
+        let mantissa = digits.split(['e', 'E']).next().unwrap_or(digits);
+        let valid = digits.starts_with(|c: char| c.is_ascii_digit())
+            && !leading_zero
+            && !mantissa.ends_with('.');
+        match text.parse() {
+            Ok(value) if valid => Ok(Json::Number(value)),
+            _ => Err(self.error_at(start, &format!("Invalid number '{text}'."))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .chars
+            .next_if(|(_, c)| matches!(c, ' ' | '\t' | '\n' | '\r'))
+            .is_some()
+        {}
+    }
+
+    /// Consumes the next character if it's `expected`.
+    fn eat(&mut self, expected: char) -> bool {
+        self.chars.next_if(|&(_, c)| c == expected).is_some()
+    }
+
+    fn error_here(&mut self, message: &str) -> String {
+        let offset = self
+            .chars
+            .peek()
+            .map_or(self.text.len(), |&(offset, _)| offset);
+        self.error_at(offset, message)
+    }
+
+    /// `message`, prefixed with the line and column of the byte at `offset`.
+    fn error_at(&self, offset: usize, message: &str) -> String {
+        let line = self.text[..offset].matches('\n').count() + 1;
+        let column = column_at(self.text, offset);
+        format!("[line {line}:{column}] {message}")
+    }
+}
@@ -1,20 +1,63 @@
+pub mod ast_json;
+pub mod ast_printer;
+pub mod cancellation;
+pub mod cli;
+pub mod clock;
+mod compiler;
+pub mod conversions;
+pub mod coverage;
+mod cycle_collector;
+pub mod debugger;
+pub mod diagnostic;
+pub mod doc;
 mod environment;
+pub mod error_code;
+pub mod error_reporter;
 mod expr;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod formatter;
+pub mod heap;
+pub mod highlight;
+pub mod hooks;
 pub mod interpreter;
+pub mod interpreter_thread;
+pub mod javascript;
+mod json;
+pub mod lint;
+mod live_count;
 mod lox_callable;
 mod lox_class;
-mod lox_exception;
+pub mod lox_exception;
+pub mod lox_foreign;
 mod lox_function;
-mod lox_instance;
-mod lox_object;
-mod native_function;
+pub mod lox_instance;
+pub mod lox_object;
+mod lox_string;
+mod memoized_function;
+pub mod minify;
+pub mod native_function;
+mod natives;
+pub mod optimizer;
 pub mod parser;
+pub mod profiler;
+mod random;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod repl;
+pub mod report;
 pub mod resolver;
+pub mod run;
 pub mod scanner;
+#[cfg(feature = "serde")]
+mod serialization;
+pub mod span;
 mod stmt;
+pub mod test_runner;
 mod token;
 mod token_type;
+mod vm;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-pub fn report(line: usize, loc: &str, message: &str) {
-    eprintln!("[line {line}] Error {loc}: {message}");
-}
+pub use parser::parse_str;
+pub use run::{run_ast_json, run_source, LoxError};
@@ -0,0 +1,360 @@
+use crate::{
+    diagnostic::{column_at, has_errors, Diagnostic, SourceSpan},
+    error_code::ErrorCode,
+    expr::{
+        Assign, Binary, Call, Closure, Expr, ExprVisitor, Get, Grouping, Index, List,
+        ListComprehension, Literal, Logical, Set, SetIndex, Super, Ternary, This, Unary, Variable,
+    },
+    interpreter::Interpreter,
+    lox_object::LoxLiteral,
+    parser::Parser,
+    resolver::Resolver,
+    scanner::Scanner,
+    span::Span,
+    stmt::{
+        Block, Break, Class, Destructure, Expression, For, ForIn, Function, If, Print, Return,
+        Stmt, StmtVisitor, Var, While,
+    },
+    token::Token,
+    token_type::TokenType,
+};
+use std::{collections::HashSet, rc::Rc};
+
+/// Every problem found in `source` without running it: scan, parse and resolution errors, the
+/// resolver's warnings such as unused locals, and the linter's own warnings for shadowed
+/// variables, constant conditions, empty blocks and self-assignments. The lints only run on
+/// scripts free of errors. Diagnostics come back in the order they appear in the source.
+pub fn lint(source: &str) -> Vec<Diagnostic> {
+    let mut scanner = Scanner::new(source);
+    scanner.scan_tokens();
+    let mut diagnostics = std::mem::take(&mut scanner.diagnostics);
+
+    let mut parser = Parser::new(scanner.tokens);
+    let parse_result = parser.parse();
+    diagnostics.append(&mut parser.diagnostics);
+    let Ok(statements) = parse_result else {
+        return diagnostics;
+    };
+    let mut interpreter = Interpreter::new();
+    let mut resolver = Resolver::new(&mut interpreter);
+    resolver.resolve_statements(&statements);
+    diagnostics.append(&mut resolver.diagnostics);
+    if has_errors(&diagnostics) {
+        return diagnostics;
+    }
+
+    let mut linter = Linter {
+        source,
+        scopes: vec![HashSet::new()],
+        diagnostics,
+    };
+    linter.visit_statements(&statements);
+    let mut diagnostics = linter.diagnostics;
+    diagnostics.sort_by_key(|diagnostic| {
+        let offset = diagnostic.span.map(|span| span.offset);
+        (diagnostic.line, offset)
+    });
+    diagnostics
+}
+
+struct Linter<'a> {
+    source: &'a str,
+    /// Names declared in each scope, globals first.
+    scopes: Vec<HashSet<Rc<str>>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter<'_> {
+    fn visit_statements(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            statement.accept(self);
+        }
+    }
+
+    fn warn(&mut self, code: ErrorCode, token: &Token, message: &str) {
+        self.diagnostics
+            .push(Diagnostic::at_token(code, token, "", message));
+    }
+
+    fn warn_at(&mut self, code: ErrorCode, span: Span, message: &str) {
+        let span_in_source = SourceSpan {
+            offset: span.start,
+            len: span.len(),
+            column: column_at(self.source, span.start),
+        };
+        self.diagnostics.push(Diagnostic::new(
+            code,
+            span.line,
+            Some(span_in_source),
+            "",
+            message,
+        ));
+    }
+
+    /// Records `name` in the innermost scope, warning if it hides a variable from an enclosing
+    /// one.
+    fn declare(&mut self, name: &Token) {
+        let enclosing = &self.scopes[..self.scopes.len() - 1];
+        if let Some(depth) = enclosing
+            .iter()
+            .rposition(|scope| scope.contains(&name.lexeme))
+        {
+            let shadowed = match depth {
+                0 => "global",
+                _ => "variable in an enclosing scope",
+            };
+            let message = format!("'{}' shadows a {shadowed}.", name.lexeme);
+            self.warn(ErrorCode::SHADOWED_VARIABLE, name, &message);
+        }
+        let current = self.scopes.last_mut();
+        let current = current.expect("Expect the global scope to always be open.");
+        current.insert(Rc::clone(&name.lexeme));
+    }
+
+    fn check_condition(&mut self, condition: &Expr, loop_condition: bool) {
+        let Some(value) = constant_truthiness(condition) else {
+            return;
+        };
+        // `while (true)` is how loops that end with `break` are written.
+        if loop_condition && value && matches!(condition, Expr::Literal(_)) {
+            return;
+        }
+        let message = format!("Condition is always {value}.");
+        self.warn_at(ErrorCode::CONSTANT_CONDITION, condition.span(), &message);
+    }
+
+    fn visit_function(&mut self, closure: &Closure) {
+        self.scopes.push(HashSet::new());
+        for param in closure.params.iter() {
+            self.declare(param);
+        }
+        self.visit_statements(&closure.body);
+        self.scopes.pop();
+    }
+}
+
+/// Whether `expr` is always truthy or always falsey, if that's evident without running it.
+fn constant_truthiness(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::Literal(literal) => Some(!matches!(
+            literal.value,
+            LoxLiteral::Nil | LoxLiteral::Boolean(false)
+        )),
+        Expr::Grouping(grouping) => constant_truthiness(&grouping.expression),
+        Expr::Unary(unary) if unary.operator.token_type == TokenType::Bang => {
+            constant_truthiness(&unary.right).map(|value| !value)
+        }
+        _ => None,
+    }
+}
+
+/// Whether `a` and `b` name the same variable or are both `this`.
+fn same_object(a: &Expr, b: &Expr) -> bool {
+    match (a, b) {
+        (Expr::Variable(a), Expr::Variable(b)) => a.name.lexeme == b.name.lexeme,
+        (Expr::This(_), Expr::This(_)) => true,
+        _ => false,
+    }
+}
+
+impl ExprVisitor<()> for Linter<'_> {
+    fn visit_binary_expr(&mut self, expr: &Binary) {
+        expr.left.accept(self);
+        expr.right.accept(self);
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Grouping) {
+        expr.expression.accept(self);
+    }
+
+    fn visit_literal_expr(&mut self, _expr: &Literal) {}
+
+    fn visit_unary_expr(&mut self, expr: &Unary) {
+        expr.right.accept(self);
+    }
+
+    fn visit_ternary_expr(&mut self, expr: &Ternary) {
+        self.check_condition(&expr.condition, false);
+        expr.condition.accept(self);
+        expr.left.accept(self);
+        expr.right.accept(self);
+    }
+
+    fn visit_variable_expr(&mut self, _expr: &Variable) {}
+
+    fn visit_assign_expr(&mut self, expr: &Assign) {
+        if let Expr::Variable(ref value) = *expr.value {
+            if value.name.lexeme == expr.name.lexeme {
+                let message = format!("'{}' is assigned to itself.", expr.name.lexeme);
+                self.warn(ErrorCode::SELF_ASSIGNMENT, &expr.name, &message);
+            }
+        }
+        expr.value.accept(self);
+    }
+
+    fn visit_logical_expr(&mut self, expr: &Logical) {
+        expr.left.accept(self);
+        expr.right.accept(self);
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) {
+        expr.callee.accept(self);
+        for argument in expr.arguments.iter() {
+            argument.accept(self);
+        }
+    }
+
+    fn visit_closure_expr(&mut self, expr: &Rc<Closure>) {
+        self.visit_function(expr);
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) {
+        expr.object.accept(self);
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) {
+        if let Expr::Get(ref value) = *expr.value {
+            if value.name.lexeme == expr.name.lexeme && same_object(&value.object, &expr.object) {
+                let message = format!("Property '{}' is assigned to itself.", expr.name.lexeme);
+                self.warn(ErrorCode::SELF_ASSIGNMENT, &expr.name, &message);
+            }
+        }
+        expr.object.accept(self);
+        expr.value.accept(self);
+    }
+
+    fn visit_this_expr(&mut self, _expr: &This) {}
+
+    fn visit_super_expr(&mut self, _expr: &Super) {}
+
+    fn visit_list_expr(&mut self, expr: &List) {
+        for element in expr.elements.iter() {
+            element.accept(self);
+        }
+    }
+
+    fn visit_list_comprehension_expr(&mut self, expr: &ListComprehension) {
+        expr.iterable.accept(self);
+        self.scopes.push(HashSet::new());
+        self.declare(&expr.name);
+        if let Some(ref condition) = expr.condition {
+            self.check_condition(condition, false);
+            condition.accept(self);
+        }
+        expr.element.accept(self);
+        self.scopes.pop();
+    }
+
+    fn visit_index_expr(&mut self, expr: &Index) {
+        expr.object.accept(self);
+        expr.index.accept(self);
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &SetIndex) {
+        expr.object.accept(self);
+        expr.index.accept(self);
+        expr.value.accept(self);
+    }
+}
+
+impl StmtVisitor<()> for Linter<'_> {
+    fn visit_expression_stmt(&mut self, stmt: &Expression) {
+        stmt.expression.accept(self);
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Print) {
+        stmt.expression.accept(self);
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Var) {
+        if let Some(ref initializer) = stmt.initializer {
+            initializer.accept(self);
+        }
+        self.declare(&stmt.name);
+    }
+
+    fn visit_destructure_stmt(&mut self, stmt: &Destructure) {
+        stmt.initializer.accept(self);
+        for name in stmt.names.iter() {
+            self.declare(name);
+        }
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &Block) {
+        if stmt.statements.is_empty() {
+            self.warn_at(ErrorCode::EMPTY_BLOCK, stmt.span, "Block is empty.");
+        }
+        self.scopes.push(HashSet::new());
+        self.visit_statements(&stmt.statements);
+        self.scopes.pop();
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &If) {
+        self.check_condition(&stmt.condition, false);
+        stmt.condition.accept(self);
+        stmt.then_branch.accept(self);
+        if let Some(ref else_branch) = stmt.else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &While) {
+        self.check_condition(&stmt.condition, true);
+        stmt.condition.accept(self);
+        stmt.body.accept(self);
+    }
+
+    fn visit_for_stmt(&mut self, stmt: &For) {
+        self.scopes.push(HashSet::new());
+        if let Some(ref initializer) = stmt.initializer {
+            initializer.accept(self);
+        }
+        if let Some(ref condition) = stmt.condition {
+            self.check_condition(condition, true);
+            condition.accept(self);
+        }
+        stmt.body.accept(self);
+        if let Some(ref increment) = stmt.increment {
+            increment.accept(self);
+        }
+        self.scopes.pop();
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) {
+        stmt.iterable.accept(self);
+        self.scopes.push(HashSet::new());
+        self.declare(&stmt.name);
+        stmt.body.accept(self);
+        self.scopes.pop();
+    }
+
+    fn visit_break_stmt(&mut self, _stmt: &Break) {}
+
+    fn visit_function_stmt(&mut self, stmt: &Function) {
+        for decorator in stmt.decorators.iter() {
+            decorator.accept(self);
+        }
+        self.declare(&stmt.name);
+        self.visit_function(&stmt.closure);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Return) {
+        stmt.value.accept(self);
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &Class) {
+        if let Some(ref superclass) = stmt.superclass {
+            superclass.accept(self);
+        }
+        self.declare(&stmt.name);
+        // Methods are looked up as properties, so their names can't shadow anything.
+        for method in stmt.methods.iter() {
+            if let Stmt::Function(ref method) = method {
+                for decorator in method.decorators.iter() {
+                    decorator.accept(self);
+                }
+                self.visit_function(&method.closure);
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::cell::Cell;
+
+/// Kinds of runtime values whose live instances are tracked for `stats()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiveKind {
+    Environment,
+    Instance,
+}
+
+thread_local! {
+    static LIVE_ENVIRONMENTS: Cell<usize> = const { Cell::new(0) };
+    static LIVE_INSTANCES: Cell<usize> = const { Cell::new(0) };
+}
+
+fn with_counter<T>(kind: LiveKind, f: impl FnOnce(&Cell<usize>) -> T) -> T {
+    match kind {
+        LiveKind::Environment => LIVE_ENVIRONMENTS.with(f),
+        LiveKind::Instance => LIVE_INSTANCES.with(f),
+    }
+}
+
+/// Number of values of `kind` that currently exist on this thread.
+pub fn live(kind: LiveKind) -> usize {
+    with_counter(kind, Cell::get)
+}
+
+/// Held as a field by tracked values; counts one live value for as long as it exists.
+#[derive(Debug, PartialEq)]
+pub struct LiveCount(LiveKind);
+
+impl LiveCount {
+    pub fn new(kind: LiveKind) -> Self {
+        with_counter(kind, |count| count.set(count.get() + 1));
+        LiveCount(kind)
+    }
+}
+
+impl Clone for LiveCount {
+    fn clone(&self) -> Self {
+        LiveCount::new(self.0)
+    }
+}
+
+impl Drop for LiveCount {
+    fn drop(&mut self) {
+        with_counter(self.0, |count| count.set(count.get() - 1));
+    }
+}
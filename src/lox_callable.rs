@@ -1,44 +1,60 @@
 use crate::{
     interpreter::Interpreter, lox_class::LoxClass, lox_exception::LoxException,
-    lox_function::LoxFunction, lox_object::LoxObject, native_function::NativeFunction,
+    lox_function::LoxFunction, lox_object::LoxObject, memoized_function::MemoizedFunction,
+    native_function::NativeFunction, span::Span,
 };
 use std::{fmt, rc::Rc};
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum LoxCallable<'src> {
-    Function(Rc<LoxFunction<'src>>),
-    NativeFun(Rc<NativeFunction<'src>>),
-    Class(Rc<LoxClass<'src>>),
+pub enum LoxCallable {
+    Function(Rc<LoxFunction>),
+    NativeFun(Rc<NativeFunction>),
+    Class(Rc<LoxClass>),
+    Memoized(Rc<MemoizedFunction>),
 }
 
-impl<'src> LoxCallable<'src> {
+impl LoxCallable {
     pub fn arity(&self) -> usize {
         match self {
             LoxCallable::Function(function) => function.arity(),
             LoxCallable::NativeFun(native_fun) => native_fun.arity(),
             LoxCallable::Class(class) => class.arity(),
+            LoxCallable::Memoized(memoized) => memoized.arity(),
+        }
+    }
+
+    /// The callable's name, or `None` for anonymous functions.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            LoxCallable::Function(function) => function.name(),
+            LoxCallable::NativeFun(native_fun) => Some(native_fun.name()),
+            LoxCallable::Class(class) => Some(&class.name),
+            LoxCallable::Memoized(memoized) => memoized.name(),
         }
     }
 
     pub fn call(
         &self,
-        interpreter: &mut Interpreter<'src>,
-        arguments: Vec<LoxObject<'src>>,
-    ) -> Result<LoxObject<'src>, LoxException<'src>> {
+        interpreter: &mut Interpreter,
+        arguments: Vec<LoxObject>,
+        span: Span,
+    ) -> Result<LoxObject, LoxException> {
         match self {
             LoxCallable::Function(function) => function.call(interpreter, arguments),
-            LoxCallable::NativeFun(native_fun) => native_fun.call(interpreter, arguments),
+            LoxCallable::NativeFun(native_fun) => native_fun.call(interpreter, arguments, span),
             LoxCallable::Class(class) => class.call(interpreter, arguments),
+            LoxCallable::Memoized(memoized) => memoized.call(interpreter, arguments, span),
         }
     }
 }
 
-impl<'src> fmt::Display for LoxCallable<'src> {
+impl fmt::Display for LoxCallable {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             LoxCallable::Function(function) => write!(f, "{function}"),
             LoxCallable::NativeFun(native_fun) => write!(f, "{native_fun}"),
             LoxCallable::Class(class) => write!(f, "{class}"),
+            LoxCallable::Memoized(memoized) => write!(f, "{memoized}"),
         }
     }
 }
@@ -5,21 +5,33 @@ use crate::{
 use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct LoxClass<'src> {
-    pub name: &'src str,
-    pub superclass: Option<Rc<LoxClass<'src>>>,
-    pub methods: HashMap<&'src str, LoxFunction<'src>>,
+pub struct LoxClass {
+    pub name: Rc<str>,
+    pub superclass: Option<Rc<LoxClass>>,
+    /// Every method by name, including the inherited ones this class doesn't override, so
+    /// looking one up never walks the superclass chain.
+    pub methods: HashMap<Rc<str>, LoxFunction>,
+    /// The doc comment of the class's declaration.
+    pub doc: Option<Rc<str>>,
 }
-impl<'src> LoxClass<'src> {
+impl LoxClass {
+    /// A class declaring the `declared` methods, which are added to those it inherits from
+    /// `superclass`.
     pub fn new(
-        name: &'src str,
-        superclass: Option<Rc<LoxClass<'src>>>,
-        methods: HashMap<&'src str, LoxFunction<'src>>,
+        name: Rc<str>,
+        superclass: Option<Rc<LoxClass>>,
+        declared: HashMap<Rc<str>, LoxFunction>,
     ) -> Self {
+        let mut methods = match superclass {
+            Some(ref superclass) => superclass.methods.clone(),
+            None => HashMap::new(),
+        };
+        methods.extend(declared);
         LoxClass {
             name,
             superclass,
             methods,
+            doc: None,
         }
     }
 
@@ -31,11 +43,12 @@ impl<'src> LoxClass<'src> {
     }
 
     pub fn call(
-        &self,
-        interpreter: &mut Interpreter<'src>,
-        arguments: Vec<LoxObject<'src>>,
-    ) -> Result<LoxObject<'src>, LoxException<'src>> {
-        let instance = Rc::new(RefCell::new(LoxInstance::new(self.clone())));
+        self: &Rc<Self>,
+        interpreter: &mut Interpreter,
+        arguments: Vec<LoxObject>,
+    ) -> Result<LoxObject, LoxException> {
+        interpreter.record_construction(Rc::clone(&self.name));
+        let instance = Rc::new(RefCell::new(LoxInstance::new(Rc::clone(self))));
         if let Some(initializer) = self.find_method("init") {
             initializer
                 .bind(Rc::clone(&instance))
@@ -44,19 +57,12 @@ impl<'src> LoxClass<'src> {
         Ok(LoxObject::Instance(instance))
     }
 
-    pub fn find_method(&self, name: &str) -> Option<&LoxFunction<'src>> {
-        if self.methods.contains_key(name) {
-            return self.methods.get(name);
-        }
-
-        match self.superclass {
-            Some(ref class) => class.find_method(name),
-            None => None,
-        }
+    pub fn find_method(&self, name: &str) -> Option<&LoxFunction> {
+        self.methods.get(name)
     }
 }
 
-impl<'src> fmt::Display for LoxClass<'src> {
+impl fmt::Display for LoxClass {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.name)
     }
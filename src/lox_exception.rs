@@ -1,36 +1,144 @@
-use crate::lox_object::LoxObject;
+use crate::{
+    diagnostic::{caret_marker, column_at, Diagnostic, SourceSpan},
+    error_code::ErrorCode,
+    lox_object::LoxObject,
+    span::Span,
+};
 use std::{error::Error, fmt};
 
 #[derive(Debug, Clone)]
-pub enum LoxException<'src> {
+pub enum LoxException {
     RuntimeError(RuntimeError),
-    Return(LoxObject<'src>),
+    Return(LoxObject),
+    /// The run was stopped through a [`CancellationToken`].
+    ///
+    /// [`CancellationToken`]: crate::cancellation::CancellationToken
+    Cancelled,
 }
 
-impl fmt::Display for LoxException<'_> {
+impl LoxException {
+    /// Renders a runtime error with [`RuntimeError::render`], quoting `source`.
+    pub fn render(&self, source: &str) -> String {
+        match self {
+            LoxException::RuntimeError(error) => error.render(source),
+            LoxException::Return(_) | LoxException::Cancelled => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for LoxException {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             LoxException::RuntimeError(error) => write!(f, "{error}"),
             LoxException::Return(value) => write!(f, "{value}"),
+            LoxException::Cancelled => write!(f, "Cancelled."),
         }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct RuntimeError {
-    pub line: usize,
+    pub code: ErrorCode,
+    pub span: Span,
     pub message: String,
+    /// The calls in progress when the error was raised, innermost first.
+    pub trace: Vec<CallFrame>,
 }
 
 impl RuntimeError {
-    pub fn new(line: usize, message: String) -> Self {
-        RuntimeError { line, message }
+    pub fn new(code: ErrorCode, span: Span, message: String) -> Self {
+        RuntimeError {
+            code,
+            span,
+            message,
+            trace: Vec::new(),
+        }
+    }
+
+    /// The error as printed after running a script: like its `Display` form, with the line of
+    /// `source` it occurred on quoted beneath the message and carets under the expression that
+    /// failed.
+    pub fn render(&self, source: &str) -> String {
+        let mut out = self.summary();
+        // Errors raised outside any parsed code have no span to quote.
+        let marker = match self.span.line {
+            0 => None,
+            _ => caret_marker(source, self.span.start, self.span.len()),
+        };
+        if let Some((text, carets)) = marker {
+            out.push_str(&format!("\n    {text}\n    {carets}"));
+        }
+        self.push_trace(&mut out);
+        out
+    }
+
+    /// The error as a [`Diagnostic`] pointing into `source`, for reporting alongside compile
+    /// errors. The backtrace is left out.
+    pub fn diagnostic(&self, source: &str) -> Diagnostic {
+        let span = match self.span.line {
+            0 => None,
+            _ => source.get(..self.span.start).map(|_| SourceSpan {
+                offset: self.span.start,
+                len: self.span.len(),
+                column: column_at(source, self.span.start),
+            }),
+        };
+        Diagnostic::new(self.code, self.span.line, span, "", &self.message)
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "[line {}] RuntimeError[{}]: {}",
+            self.span.line, self.code, self.message
+        )
+    }
+
+    /// Appends the backtrace, one call per line.
+    fn push_trace(&self, out: &mut String) {
+        // Deep recursion leaves thousands of frames, so elide the middle of long traces.
+        if self.trace.len() <= 2 * TRACE_ENDS {
+            for frame in self.trace.iter() {
+                out.push_str(&format!("\n  {frame}"));
+            }
+            return;
+        }
+        for frame in self.trace[..TRACE_ENDS].iter() {
+            out.push_str(&format!("\n  {frame}"));
+        }
+        let elided = self.trace.len() - 2 * TRACE_ENDS;
+        out.push_str(&format!("\n  ... {elided} more calls ..."));
+        for frame in self.trace[self.trace.len() - TRACE_ENDS..].iter() {
+            out.push_str(&format!("\n  {frame}"));
+        }
+    }
+}
+
+/// How many frames to print from each end of a long backtrace.
+const TRACE_ENDS: usize = 10;
+
+/// A call in progress, as recorded in a [`RuntimeError`]'s backtrace.
+#[derive(Debug, Clone)]
+pub struct CallFrame {
+    /// The callee's name, or `None` for an anonymous function.
+    pub name: Option<String>,
+    /// Line of the call expression.
+    pub line: usize,
+}
+
+impl fmt::Display for CallFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name {
+            Some(ref name) => write!(f, "in {name}(), called from line {}", self.line),
+            None => write!(f, "in anonymous function, called from line {}", self.line),
+        }
     }
 }
 
 impl fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "[line {}] RuntimeError: {}", self.line, self.message)
+        let mut out = self.summary();
+        self.push_trace(&mut out);
+        write!(f, "{out}")
     }
 }
 
@@ -0,0 +1,92 @@
+use crate::{
+    error_code::ErrorCode,
+    interpreter::Interpreter,
+    lox_callable::LoxCallable,
+    lox_exception::{LoxException, RuntimeError},
+    lox_object::LoxObject,
+    native_function::NativeFunction,
+    span::Span,
+    token::Token,
+};
+use std::{any::Any, collections::HashMap, fmt, rc::Rc};
+
+/// An opaque Rust value a host program hands to scripts. Scripts can pass it around and call
+/// its methods, and native functions can get the Rust value back with
+/// [`LoxForeign::downcast_ref`].
+pub struct LoxForeign {
+    /// Name of the value's type, shown when it's printed and returned by `type()`.
+    name: Rc<str>,
+    value: Box<dyn Any>,
+    methods: HashMap<Rc<str>, Rc<NativeFunction>>,
+}
+
+impl LoxForeign {
+    /// Wraps `value` as a foreign value of the type `name`, with no methods.
+    pub fn new(name: &str, value: impl Any) -> Self {
+        LoxForeign {
+            name: Rc::from(name),
+            value: Box::new(value),
+            methods: HashMap::new(),
+        }
+    }
+
+    /// Adds a method scripts can call with `arity` arguments. `function` is given the foreign
+    /// value itself as its first argument, followed by the script's arguments.
+    pub fn with_method(
+        mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&mut Interpreter, Vec<LoxObject>, Span) -> Result<LoxObject, LoxException>
+            + 'static,
+    ) -> Self {
+        let method = NativeFunction::named(name, arity, function);
+        self.methods.insert(Rc::from(name), Rc::new(method));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The wrapped value, if it's a `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+
+    /// The method `name` bound to `foreign`, so calling it passes `foreign` first.
+    pub fn get(foreign: &Rc<LoxForeign>, name: &Token) -> Result<LoxObject, LoxException> {
+        let Some(method) = foreign.methods.get(&name.lexeme) else {
+            return Err(LoxException::RuntimeError(RuntimeError::new(
+                ErrorCode::UNDEFINED_PROPERTY,
+                name.span(),
+                format!("Undefined property '{}'.", name.lexeme),
+            )));
+        };
+        let receiver = Rc::clone(foreign);
+        let unbound = Rc::clone(method);
+        let bound = NativeFunction::new(
+            Rc::clone(&name.lexeme),
+            move |interpreter, mut arguments, span| {
+                arguments.insert(0, LoxObject::Foreign(Rc::clone(&receiver)));
+                unbound.call(interpreter, arguments, span)
+            },
+            method.arity(),
+            String::from("<native fn>"),
+        );
+        Ok(LoxObject::Callable(LoxCallable::NativeFun(Rc::new(bound))))
+    }
+}
+
+impl fmt::Debug for LoxForeign {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoxForeign")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for LoxForeign {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<foreign {}>", self.name)
+    }
+}
@@ -1,4 +1,6 @@
 use crate::{
+    compiler::Chunk,
+    cycle_collector,
     environment::Environment,
     expr::Closure,
     interpreter::Interpreter,
@@ -8,83 +10,152 @@ use crate::{
 };
 use std::{cell::RefCell, fmt, rc::Rc};
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct LoxFunction<'src> {
-    declaration: Closure<'src>,
-    context: Rc<RefCell<Environment<'src>>>,
+#[derive(Debug, Clone)]
+pub struct LoxFunction {
+    /// Shared with the syntax tree, so defining and binding functions doesn't copy their bodies.
+    declaration: Rc<Closure>,
+    context: Rc<RefCell<Environment>>,
     arity: usize,
-    name: Option<&'src str>,
-    repr: String,
+    name: Option<Rc<str>>,
     is_initializer: bool,
+    unbound: Option<Box<LoxFunction>>,
+    /// The body compiled to bytecode, when running on the VM backend.
+    chunk: Option<Rc<Chunk>>,
 }
 
-impl<'src> LoxFunction<'src> {
+impl LoxFunction {
     pub fn new(
-        declaration: &Closure<'src>,
-        context: Rc<RefCell<Environment<'src>>>,
-        name: Option<&'src str>,
+        declaration: &Rc<Closure>,
+        context: Rc<RefCell<Environment>>,
+        name: Option<Rc<str>>,
         is_initializer: bool,
     ) -> Self {
+        // The function may be stored in its own context, or in a value the context reaches.
+        cycle_collector::track_environment(&context);
         let arity = declaration.params.len();
-        let repr = match name {
-            Some(ref lexeme) => format!("<fn {}>", lexeme),
-            None => String::from("<fn>"),
-        };
         LoxFunction {
-            declaration: declaration.clone(),
+            declaration: Rc::clone(declaration),
             context,
             arity,
             name,
-            repr,
             is_initializer,
+            unbound: None,
+            chunk: None,
         }
     }
 
+    /// Runs the function's body from `chunk` rather than walking its syntax tree.
+    pub fn with_chunk(mut self, chunk: Rc<Chunk>) -> Self {
+        self.chunk = Some(chunk);
+        self
+    }
+
     pub fn arity(&self) -> usize {
         self.arity
     }
 
+    /// The declared name, or `None` for anonymous functions.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
     pub fn call(
         &self,
-        interpreter: &mut Interpreter<'src>,
-        arguments: Vec<LoxObject<'src>>,
-    ) -> Result<LoxObject<'src>, LoxException<'src>> {
-        let environment = Rc::new(RefCell::new(Environment::new(Some(Rc::clone(
-            &self.context,
-        )))));
-        for (idx, value) in arguments.into_iter().enumerate() {
-            environment
-                .borrow_mut()
-                .define(self.declaration.params[idx].lexeme, value);
-        }
-
-        match interpreter.execute_block(&self.declaration.body, environment) {
-            Ok(_) if self.is_initializer => Ok(self.context.borrow().get_at(0, "this")),
+        interpreter: &mut Interpreter,
+        arguments: Vec<LoxObject>,
+    ) -> Result<LoxObject, LoxException> {
+        // A bound method's context holds only `this`, in slot 0.
+        let result = match self.chunk {
+            Some(ref chunk) if chunk.params_on_stack => {
+                interpreter.run(chunk, Rc::clone(&self.context), arguments)
+            }
+            Some(ref chunk) => interpreter.run(chunk, self.environment(arguments), Vec::new()),
+            None => interpreter.execute_block(&self.declaration.body, self.environment(arguments)),
+        };
+        match result {
+            Ok(_) if self.is_initializer => Ok(self.context.borrow().get_at(0, 0)),
             Ok(_) => Ok(LoxObject::Literal(LoxLiteral::Nil)),
             Err(exception) => match exception {
-                LoxException::RuntimeError(_) => Err(exception),
+                LoxException::RuntimeError(_) | LoxException::Cancelled => Err(exception),
                 LoxException::Return(_) if self.is_initializer => {
-                    Ok(self.context.borrow().get_at(0, "this"))
+                    Ok(self.context.borrow().get_at(0, 0))
                 }
                 LoxException::Return(value) => Ok(value),
             },
         }
     }
 
-    pub fn bind(&self, instance: Rc<RefCell<LoxInstance<'src>>>) -> LoxFunction<'src> {
+    /// A new environment inside the function's context, with the parameters bound to
+    /// `arguments`.
+    fn environment(&self, arguments: Vec<LoxObject>) -> Rc<RefCell<Environment>> {
         let mut environment = Environment::new(Some(Rc::clone(&self.context)));
+        for (param, value) in self.declaration.params.iter().zip(arguments) {
+            environment.define(Rc::clone(&param.lexeme), value);
+        }
+        Rc::new(RefCell::new(environment))
+    }
+
+    /// Binds `this` to `instance`. Binding an already bound function replaces its receiver.
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction {
+        let unbound = self.unbind().unwrap_or(self);
+        let mut environment = Environment::new(Some(Rc::clone(&unbound.context)));
         environment.define("this", LoxObject::Instance(instance));
-        LoxFunction::new(
-            &self.declaration,
-            Rc::new(RefCell::new(environment)),
-            self.name,
-            self.is_initializer,
-        )
+        // Not tracked: a cycle through `this` also passes through the instance or the context.
+        LoxFunction {
+            declaration: Rc::clone(&unbound.declaration),
+            context: Rc::new(RefCell::new(environment)),
+            arity: unbound.arity,
+            name: unbound.name.clone(),
+            is_initializer: unbound.is_initializer,
+            unbound: Some(Box::new(unbound.clone())),
+            chunk: unbound.chunk.clone(),
+        }
+    }
+
+    pub fn declaration(&self) -> &Rc<Closure> {
+        &self.declaration
+    }
+
+    /// The doc comment of the function's declaration.
+    pub fn doc(&self) -> Option<&str> {
+        self.declaration.doc.as_deref()
+    }
+
+    /// The environment the function closes over.
+    pub fn context(&self) -> &Rc<RefCell<Environment>> {
+        &self.context
+    }
+
+    /// Returns the function this one was bound from, if it is bound.
+    pub fn unbind(&self) -> Option<&LoxFunction> {
+        self.unbound.as_deref()
+    }
+}
+
+/// Functions are the same if the same declaration made them in the same environment. Bound
+/// methods are also compared by their receiver, so `a.m == a.m` even though each access binds
+/// anew.
+impl PartialEq for LoxFunction {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.unbind(), other.unbind()) {
+            (Some(left), Some(right)) => {
+                left == right
+                    && self.context.borrow().get_at(0, 0) == other.context.borrow().get_at(0, 0)
+            }
+            (None, None) => {
+                Rc::ptr_eq(&self.declaration, &other.declaration)
+                    && Rc::ptr_eq(&self.context, &other.context)
+            }
+            _ => false,
+        }
     }
 }
 
-impl<'src> fmt::Display for LoxFunction<'src> {
+impl fmt::Display for LoxFunction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.repr)
+        match self.name {
+            Some(ref name) => write!(f, "<fn {name}>"),
+            None => write!(f, "<fn>"),
+        }
     }
 }
@@ -1,4 +1,7 @@
 use crate::{
+    conversions::ConversionError,
+    error_code::ErrorCode,
+    live_count::{LiveCount, LiveKind},
     lox_callable::LoxCallable,
     lox_class::LoxClass,
     lox_exception::{LoxException, RuntimeError},
@@ -8,45 +11,93 @@ use crate::{
 use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct LoxInstance<'src> {
-    klass: LoxClass<'src>,
-    fields: HashMap<&'src str, LoxObject<'src>>,
+pub struct LoxInstance {
+    klass: Rc<LoxClass>,
+    fields: HashMap<Rc<str>, LoxObject>,
+    live: LiveCount,
 }
-impl<'src> LoxInstance<'src> {
-    pub fn new(klass: LoxClass<'src>) -> Self {
+impl LoxInstance {
+    pub fn new(klass: Rc<LoxClass>) -> Self {
         LoxInstance {
             klass,
             fields: HashMap::new(),
+            live: LiveCount::new(LiveKind::Instance),
         }
     }
 
+    pub fn class_name(&self) -> &str {
+        &self.klass.name
+    }
+
+    pub fn class(&self) -> &Rc<LoxClass> {
+        &self.klass
+    }
+
     pub fn get(
         &self,
-        name: &Token<'src>,
-        instance: Rc<RefCell<LoxInstance<'src>>>,
-    ) -> Result<LoxObject<'src>, LoxException<'src>> {
-        if self.fields.contains_key(name.lexeme) {
-            return Ok(self.fields.get(name.lexeme).unwrap().clone());
-        }
-
-        match self.klass.find_method(name.lexeme) {
-            Some(method) => Ok(LoxObject::Callable(LoxCallable::Function(Rc::new(
-                method.bind(instance),
-            )))),
+        name: &Token,
+        instance: Rc<RefCell<LoxInstance>>,
+    ) -> Result<LoxObject, LoxException> {
+        match self.get_property(&name.lexeme, instance) {
+            Some(value) => Ok(value),
             None => Err(LoxException::RuntimeError(RuntimeError::new(
-                name.line,
+                ErrorCode::UNDEFINED_PROPERTY,
+                name.span(),
                 format!("Undefined property '{}'.", name.lexeme),
             ))),
         }
     }
 
-    pub fn set(&mut self, name: &Token<'src>, value: LoxObject<'src>) -> LoxObject<'src> {
-        self.fields.insert(name.lexeme, value.clone());
+    pub fn get_property(
+        &self,
+        name: &str,
+        instance: Rc<RefCell<LoxInstance>>,
+    ) -> Option<LoxObject> {
+        if let Some(value) = self.fields.get(name) {
+            return Some(value.clone());
+        }
+
+        self.klass.find_method(name).map(|method| {
+            LoxObject::Callable(LoxCallable::Function(Rc::new(method.bind(instance))))
+        })
+    }
+
+    pub fn set(&mut self, name: &Token, value: LoxObject) -> LoxObject {
+        self.set_field(Rc::clone(&name.lexeme), value.clone());
         value
     }
+
+    pub fn set_field(&mut self, name: impl Into<Rc<str>>, value: LoxObject) {
+        self.fields.insert(name.into(), value);
+    }
+
+    /// The field `name` converted to a `T`, for reading instances from Rust. Methods aren't
+    /// fields, so they aren't found.
+    pub fn field<T>(&self, name: &str) -> Result<T, ConversionError>
+    where
+        T: TryFrom<LoxObject, Error = ConversionError>,
+    {
+        match self.fields.get(name) {
+            Some(value) => T::try_from(value.clone()),
+            None => Err(ConversionError::MissingField(String::from(name))),
+        }
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = (&Rc<str>, &LoxObject)> {
+        self.fields.iter()
+    }
+
+    pub fn clear_fields(&mut self) {
+        self.fields.clear();
+    }
+
+    /// A new instance of the same class with no fields set.
+    pub fn empty_copy(&self) -> LoxInstance {
+        LoxInstance::new(Rc::clone(&self.klass))
+    }
 }
 
-impl<'src> fmt::Display for LoxInstance<'src> {
+impl fmt::Display for LoxInstance {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{} instance", self.klass.name)
     }
@@ -1,14 +1,42 @@
-use crate::{lox_callable::LoxCallable, lox_instance::LoxInstance};
-use std::{cell::RefCell, fmt, rc::Rc};
+use crate::{
+    lox_callable::LoxCallable, lox_foreign::LoxForeign, lox_instance::LoxInstance,
+    lox_string::LoxString,
+};
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    fmt::{self, Write},
+    rc::Rc,
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum LoxLiteral {
     Number(f64),
-    String(Rc<String>),
+    String(Rc<LoxString>),
     Boolean(bool),
     Nil,
 }
 
+/// Hashable identity of a literal; numbers compare by their exact bits.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LiteralKey {
+    Number(u64),
+    String(Rc<LoxString>),
+    Boolean(bool),
+    Nil,
+}
+
+impl LoxLiteral {
+    pub fn key(&self) -> LiteralKey {
+        match self {
+            LoxLiteral::Number(val) => LiteralKey::Number(val.to_bits()),
+            LoxLiteral::String(val) => LiteralKey::String(Rc::clone(val)),
+            LoxLiteral::Boolean(val) => LiteralKey::Boolean(*val),
+            LoxLiteral::Nil => LiteralKey::Nil,
+        }
+    }
+}
+
 impl fmt::Display for LoxLiteral {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -20,19 +48,154 @@ impl fmt::Display for LoxLiteral {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum LoxObject<'src> {
+#[derive(Debug, Clone)]
+pub enum LoxObject {
     Literal(LoxLiteral),
-    Callable(LoxCallable<'src>),
-    Instance(Rc<RefCell<LoxInstance<'src>>>),
+    Callable(LoxCallable),
+    Instance(Rc<RefCell<LoxInstance>>),
+    List(Rc<RefCell<Vec<LoxObject>>>),
+    Foreign(Rc<LoxForeign>),
+}
+
+impl PartialEq for LoxObject {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other, &mut HashSet::new())
+    }
 }
 
-impl fmt::Display for LoxObject<'_> {
+impl LoxObject {
+    /// `comparing` holds the pairs of lists currently being compared. Meeting a pair again
+    /// means both sides loop back the same way, so it counts as equal instead of recursing
+    /// forever.
+    fn equals(&self, other: &Self, comparing: &mut HashSet<(*const (), *const ())>) -> bool {
+        match (self, other) {
+            (LoxObject::Literal(left), LoxObject::Literal(right)) => left == right,
+            (LoxObject::Callable(left), LoxObject::Callable(right)) => left == right,
+            // Instances are only equal to themselves, so cyclic instances compare safely.
+            (LoxObject::Instance(left), LoxObject::Instance(right)) => Rc::ptr_eq(left, right),
+            (LoxObject::List(left), LoxObject::List(right)) => {
+                let key = (
+                    Rc::as_ptr(left) as *const (),
+                    Rc::as_ptr(right) as *const (),
+                );
+                if Rc::ptr_eq(left, right) || comparing.contains(&key) {
+                    return true;
+                }
+                let (left, right) = (left.borrow(), right.borrow());
+                if left.len() != right.len() {
+                    return false;
+                }
+                comparing.insert(key);
+                let equal = left
+                    .iter()
+                    .zip(right.iter())
+                    .all(|(left, right)| left.equals(right, comparing));
+                comparing.remove(&key);
+                equal
+            }
+            (LoxObject::Foreign(left), LoxObject::Foreign(right)) => Rc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
+
+    /// Renders lists and instances across multiple lines, one element or field per line,
+    /// with nested values indented. Other values render as with `Display`.
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write_pretty(&mut out, 0, &mut Vec::new())
+            .expect("Writing to a String can't fail.");
+        out
+    }
+
+    /// `open` holds the lists and instances currently being written, so cycles print
+    /// as `<cycle>` instead of recursing forever.
+    fn write_pretty(
+        &self,
+        out: &mut String,
+        indent: usize,
+        open: &mut Vec<*const ()>,
+    ) -> fmt::Result {
+        let outer = "  ".repeat(indent);
+        let pad = "  ".repeat(indent + 1);
+        match self {
+            LoxObject::Literal(LoxLiteral::String(val)) if indent > 0 => write!(out, "\"{val}\""),
+            LoxObject::List(list) => {
+                let key = Rc::as_ptr(list) as *const ();
+                if open.contains(&key) {
+                    return write!(out, "<cycle>");
+                }
+                let list = list.borrow();
+                if list.is_empty() {
+                    return write!(out, "[]");
+                }
+                open.push(key);
+                writeln!(out, "[")?;
+                for element in list.iter() {
+                    write!(out, "{pad}")?;
+                    element.write_pretty(out, indent + 1, open)?;
+                    writeln!(out, ",")?;
+                }
+                open.pop();
+                write!(out, "{outer}]")
+            }
+            LoxObject::Instance(instance) => {
+                let key = Rc::as_ptr(instance) as *const ();
+                if open.contains(&key) {
+                    return write!(out, "<cycle>");
+                }
+                let instance = instance.borrow();
+                let mut fields: Vec<_> = instance.fields().collect();
+                if fields.is_empty() {
+                    return write!(out, "{} {{}}", instance.class_name());
+                }
+                // Field storage is unordered, so sort for stable output.
+                fields.sort_by(|a, b| a.0.cmp(b.0));
+                open.push(key);
+                writeln!(out, "{} {{", instance.class_name())?;
+                for (name, value) in fields {
+                    write!(out, "{pad}{name}: ")?;
+                    value.write_pretty(out, indent + 1, open)?;
+                    writeln!(out, ",")?;
+                }
+                open.pop();
+                write!(out, "{outer}}}")
+            }
+            _ => write!(out, "{self}"),
+        }
+    }
+}
+
+impl fmt::Display for LoxObject {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_display(f, &mut Vec::new())
+    }
+}
+
+impl LoxObject {
+    /// `open` holds the lists currently being written, so a list inside itself prints as
+    /// `[...]` instead of recursing forever.
+    fn write_display(&self, f: &mut fmt::Formatter<'_>, open: &mut Vec<*const ()>) -> fmt::Result {
         match self {
             LoxObject::Literal(literal) => write!(f, "{literal}"),
             LoxObject::Callable(function) => write!(f, "{function}"),
             LoxObject::Instance(instance) => write!(f, "{}", instance.borrow()),
+            LoxObject::Foreign(foreign) => write!(f, "{foreign}"),
+            LoxObject::List(list) => {
+                let key = Rc::as_ptr(list) as *const ();
+                if open.contains(&key) {
+                    return write!(f, "[...]");
+                }
+                open.push(key);
+                write!(f, "[")?;
+                for (idx, element) in list.borrow().iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.write_display(f, open)?;
+                }
+                open.pop();
+                write!(f, "]")
+            }
         }
     }
 }
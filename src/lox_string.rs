@@ -0,0 +1,186 @@
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    hash::{Hash, Hasher},
+    mem,
+    rc::Rc,
+};
+
+/// Concatenations at most this long are copied right away, since keeping their pieces apart
+/// would cost more than it saves.
+const MIN_ROPE_LEN: usize = 64;
+
+/// Longest string, in bytes, that concatenation may build. Doubling a rope allocates nothing,
+/// so without a cap a loop could describe a string far larger than memory, only to fail when
+/// its contents are first needed.
+pub const MAX_LEN: usize = 1 << 30;
+
+/// A Lox string. Concatenating long strings records the pieces rather than copying them, and
+/// the pieces are joined into one buffer the first time the contents are needed, so a loop
+/// that keeps appending to a string copies it once instead of on every iteration.
+pub struct LoxString {
+    repr: RefCell<Repr>,
+    /// Length in bytes.
+    len: usize,
+    /// Length in characters, counted the first time it's asked for. Concatenations add up
+    /// their pieces' counts, so a rope never has to be joined to be counted.
+    chars: Cell<Option<usize>>,
+}
+
+enum Repr {
+    Flat(Rc<str>),
+    Concat(Rc<LoxString>, Rc<LoxString>),
+}
+
+impl LoxString {
+    pub fn new(value: impl Into<Rc<str>>) -> Self {
+        let value = value.into();
+        LoxString {
+            len: value.len(),
+            chars: Cell::new(None),
+            repr: RefCell::new(Repr::Flat(value)),
+        }
+    }
+
+    /// `left` followed by `right`, or `None` if the result would be longer than [`MAX_LEN`].
+    pub fn concat(left: &Rc<LoxString>, right: &Rc<LoxString>) -> Option<Self> {
+        let len = left.len + right.len;
+        if len > MAX_LEN {
+            return None;
+        }
+        if len <= MIN_ROPE_LEN {
+            let mut value = String::with_capacity(len);
+            value.push_str(&left.flat());
+            value.push_str(&right.flat());
+            return Some(LoxString::new(value));
+        }
+        Some(LoxString {
+            repr: RefCell::new(Repr::Concat(Rc::clone(left), Rc::clone(right))),
+            len,
+            chars: Cell::new(Some(left.char_count() + right.char_count())),
+        })
+    }
+
+    /// The number of characters, without joining the pieces of a concatenation.
+    pub fn char_count(&self) -> usize {
+        if let Some(chars) = self.chars.get() {
+            return chars;
+        }
+        let chars = self.flat().chars().count();
+        self.chars.set(Some(chars));
+        chars
+    }
+
+    /// The contents, joining the pieces first if they haven't been yet.
+    pub fn flat(&self) -> Rc<str> {
+        let mut pending = match *self.repr.borrow() {
+            Repr::Flat(ref value) => return Rc::clone(value),
+            Repr::Concat(ref left, ref right) => vec![Rc::clone(right), Rc::clone(left)],
+        };
+        // Walk the pieces with an explicit stack, since a string built by appending in a loop
+        // nests one concatenation per iteration.
+        let mut value = String::with_capacity(self.len);
+        while let Some(piece) = pending.pop() {
+            match *piece.repr.borrow() {
+                Repr::Flat(ref flat) => value.push_str(flat),
+                Repr::Concat(ref left, ref right) => {
+                    pending.push(Rc::clone(right));
+                    pending.push(Rc::clone(left));
+                }
+            }
+        }
+        let value: Rc<str> = value.into();
+        *self.repr.borrow_mut() = Repr::Flat(Rc::clone(&value));
+        value
+    }
+
+    /// Moves the pieces of a concatenation to `pieces`, leaving this string empty.
+    fn take_pieces(&mut self, pieces: &mut Vec<Rc<LoxString>>) {
+        if let Repr::Concat(..) = self.repr.get_mut() {
+            let empty = Repr::Flat(EMPTY.with(Rc::clone));
+            if let Repr::Concat(left, right) = mem::replace(self.repr.get_mut(), empty) {
+                pieces.push(left);
+                pieces.push(right);
+            }
+        }
+    }
+}
+
+thread_local! {
+    static EMPTY: Rc<str> = Rc::from("");
+}
+
+impl Drop for LoxString {
+    /// Takes nested concatenations apart one at a time, since dropping them recursively could
+    /// overflow the stack.
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        self.take_pieces(&mut pending);
+        while let Some(piece) = pending.pop() {
+            if let Ok(mut piece) = Rc::try_unwrap(piece) {
+                piece.take_pieces(&mut pending);
+            }
+        }
+    }
+}
+
+impl From<String> for LoxString {
+    fn from(value: String) -> Self {
+        LoxString::new(value)
+    }
+}
+
+impl From<&str> for LoxString {
+    fn from(value: &str) -> Self {
+        LoxString::new(value)
+    }
+}
+
+impl PartialEq for LoxString {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.flat() == other.flat()
+    }
+}
+
+impl Eq for LoxString {}
+
+impl Hash for LoxString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.flat().hash(state);
+    }
+}
+
+impl fmt::Display for LoxString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.flat())
+    }
+}
+
+impl fmt::Debug for LoxString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.flat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concat_fails_past_the_maximum_length() {
+        let mut string = Rc::new(LoxString::new("x".repeat(70)));
+        for _ in 0..usize::BITS {
+            match LoxString::concat(&string, &string) {
+                Some(doubled) => string = Rc::new(doubled),
+                None => {
+                    assert!(string.len <= MAX_LEN && string.len * 2 > MAX_LEN);
+                    // Counting must not join the pieces, which would allocate all of it.
+                    assert_eq!(string.char_count(), string.len);
+                    assert!(matches!(*string.repr.borrow(), Repr::Concat(..)));
+                    return;
+                }
+            }
+        }
+        panic!("doubling never reached the maximum length");
+    }
+}
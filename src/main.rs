@@ -1,86 +1,732 @@
 use lox_treewalk::{
-    interpreter::Interpreter, parser::Parser, resolver::Resolver, scanner::Scanner,
+    ast_json::ast_to_json,
+    ast_printer::print_ast,
+    cli::{self, Command, Options, DEFAULT_MAX_CALL_DEPTH},
+    coverage::CoverageFormat,
+    debugger::Debugger,
+    diagnostic::{self, emit, is_fatal},
+    doc::document,
+    error_reporter::{ConsoleReporter, SharedReporter},
+    formatter::format,
+    heap::CountingAllocator,
+    highlight::{highlight, HighlightFormat},
+    interpreter::Interpreter,
+    interpreter_thread::STACK_SIZE,
+    javascript::transpile,
+    lint::lint,
+    minify::minify,
+    optimizer::eliminate_dead_code,
+    parser::Parser,
+    repl::ReplHelper,
+    report::report,
+    resolver::{Resolver, ResolverState},
+    run_ast_json,
+    scanner::Scanner,
+    test_runner::run_tests,
+    LoxError,
 };
+use rustyline::{error::ReadlineError, history::DefaultHistory, Editor};
 use std::{
+    cell::RefCell,
     env,
     error::Error,
     fs,
-    io::{self, Write},
+    io::{self, BufRead, IsTerminal, Read},
+    mem,
+    path::Path,
+    rc::Rc,
+    slice,
+    sync::{atomic::Ordering, Arc},
+    thread,
+    time::{Duration, Instant},
 };
 
-fn main() -> Result<(), Box<dyn Error>> {
-    if let Some(_) = env::args().nth(2) {
-        println!("Usage: lox_treewalk [script]");
-        std::process::exit(64);
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn main() {
+    let runner = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run_command)
+        .expect("failed to spawn interpreter thread");
+    if let Err(panic) = runner.join() {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+fn run_command() {
+    let options = match cli::parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("Error: {error}");
+            eprint!("{}", cli::usage());
+            std::process::exit(64);
+        }
+    };
+    if options.help {
+        print!("{}", cli::usage());
+        return;
+    }
+    if options.version {
+        println!("lox_treewalk {}", env!("CARGO_PKG_VERSION"));
+        return;
+    }
+
+    diagnostic::set_color(!options.no_color && io::stderr().is_terminal());
+    for &code in options.allow.iter() {
+        diagnostic::allow(code);
     }
-    let res = match env::args().nth(1) {
-        Some(file_path) => run_file(&file_path),
-        None => run_prompt(),
+    for &code in options.deny.iter() {
+        diagnostic::deny(code);
+    }
+    diagnostic::deny_warnings(options.deny_warnings);
+    diagnostic::set_format(options.diagnostics);
+    let file = match options.command {
+        Command::Run(ref path)
+        | Command::Check(ref path)
+        | Command::Fmt(ref path)
+        | Command::Minify(ref path)
+        | Command::Js(ref path)
+        | Command::Ast(ref path)
+        | Command::Tokens(ref path)
+        | Command::Highlight(ref path)
+        | Command::Report(ref path)
+        | Command::Doc(ref path)
+        | Command::Lint(ref path)
+        | Command::Debug(ref path) => Some(path.as_str()).filter(|&path| path != "-"),
+        Command::Prompt | Command::Eval(_) | Command::Test(_) => None,
+    };
+    diagnostic::set_file(file);
+
+    let res = match options.command {
+        Command::Prompt => run_prompt(options.max_line_length, options.init.as_deref()),
+        Command::Run(ref file_path) if options.watch => watch_file(file_path, &options),
+        Command::Run(ref file_path) => run_file(file_path, &options),
+        Command::Eval(ref code) => run_script(code, &options),
+        Command::Check(ref file_path) => check_file(file_path),
+        Command::Fmt(ref file_path) => fmt_file(file_path),
+        Command::Minify(ref file_path) => minify_file(file_path, options.rename_locals),
+        Command::Js(ref file_path) => js_file(file_path),
+        Command::Ast(ref file_path) => ast_file(file_path, options.json),
+        Command::Tokens(ref file_path) => tokens_file(file_path),
+        Command::Highlight(ref file_path) => {
+            let format = match options.html {
+                true => HighlightFormat::Html,
+                false => HighlightFormat::Ansi,
+            };
+            highlight_file(file_path, format)
+        }
+        Command::Report(ref file_path) => report_file(file_path),
+        Command::Doc(ref file_path) => doc_file(file_path),
+        Command::Lint(ref file_path) => lint_file(file_path),
+        Command::Debug(ref file_path) => debug_file(file_path, &options),
+        Command::Test(ref path) => test_path(path, &options),
     };
     if let Err(error) = res {
         eprintln!("Error: {error}");
     }
+}
+
+pub fn run_file(file_path: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    let contents = read_source(file_path)?;
+    run_script(&contents, options)
+}
+
+/// Runs `source` as a whole script, exiting with its status code if it fails.
+pub fn run_script(source: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    let exit_code = execute_script(source, options);
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
     Ok(())
 }
 
-pub fn run_file(file_path: &str) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(file_path)?;
-    let mut interpreter = Interpreter::new();
-    let exit_code = run(&contents, &mut interpreter);
+/// Runs the script under the step debugger, exiting with its status code if it fails or the
+/// user quits.
+pub fn debug_file(file_path: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    if file_path == "-" {
+        return Err(
+            "The debugger reads commands from stdin, so it can't read the script from there."
+                .into(),
+        );
+    }
+    let contents = read_source(file_path)?;
+    let mut interpreter = Interpreter::builder()
+        .script_args(options.script_args.clone())
+        .max_call_depth(options.max_call_depth)
+        .build();
+    let debugger = Debugger::new(&contents, file_path, interpreter.cancellation_token());
+    interpreter.set_hooks(debugger);
+    let exit_code = run_phases(
+        &contents,
+        &mut interpreter,
+        &mut ResolverState::default(),
+        false,
+        &mut PhaseTimes::default(),
+    );
     if exit_code != 0 {
         std::process::exit(exit_code);
     }
     Ok(())
 }
 
-pub fn run_prompt() -> Result<(), Box<dyn Error>> {
-    let mut buffer = String::new();
-    let mut interpreter = Interpreter::new();
+/// Re-runs the script in a fresh interpreter every time the file is modified, polling its
+/// modification time.
+pub fn watch_file(file_path: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    if file_path == "-" {
+        return Err("Can't watch stdin.".into());
+    }
+    let mut last_modified = None;
     loop {
-        print!("> ");
-        io::stdout().flush()?;
-        match io::stdin().read_line(&mut buffer) {
-            Ok(n) => {
-                if n == 1 {
-                    break;
+        // Editors may briefly remove the file while saving, so a failed lookup isn't fatal.
+        let modified = fs::metadata(file_path).and_then(|metadata| metadata.modified());
+        if let Ok(modified) = modified {
+            if last_modified != Some(modified) {
+                last_modified = Some(modified);
+                match fs::read_to_string(file_path) {
+                    Ok(contents) => {
+                        execute_script(&contents, options);
+                    }
+                    Err(error) => eprintln!("Error: {error}"),
                 }
-                run(buffer.leak(), &mut interpreter);
-                buffer = String::new();
+                eprintln!("[watch] Waiting for changes to {file_path}...");
+            }
+        }
+        thread::sleep(Duration::from_millis(250));
+    }
+}
+
+/// Runs `source` in a fresh interpreter configured by `options`, returning the exit code.
+fn execute_script(source: &str, options: &Options) -> i32 {
+    let mut interpreter = Interpreter::builder()
+        .script_args(options.script_args.clone())
+        .trace(options.trace)
+        .trace_env(options.trace_env)
+        .backend(options.backend)
+        .max_call_depth(options.max_call_depth)
+        .max_ops(options.max_ops)
+        .max_memory(options.max_memory)
+        .profiling(options.profile)
+        .coverage(options.coverage.is_some())
+        .build();
+    let mut phases = PhaseTimes::default();
+    let exit_code = match options.from_json {
+        true => run_syntax_tree(source, &mut interpreter, &mut phases),
+        false => run_phases(
+            source,
+            &mut interpreter,
+            &mut ResolverState::default(),
+            false,
+            &mut phases,
+        ),
+    };
+    if options.stats {
+        print_stats(&interpreter);
+    }
+    if options.bench {
+        print_bench(&phases, &interpreter);
+    }
+    if options.profile {
+        print_profile(&interpreter);
+    }
+    if let (Some(format), Some(coverage)) = (options.coverage, interpreter.coverage()) {
+        let path = match options.command {
+            Command::Run(ref path) => path.as_str(),
+            _ => "-",
+        };
+        eprint!("{}", coverage.report(source, path, format));
+    }
+    exit_code
+}
+
+fn print_bench(phases: &PhaseTimes, interpreter: &Interpreter) {
+    let total = phases.parse + phases.resolve + phases.interpret;
+    eprintln!("== bench ==");
+    eprintln!("parse:      {:?}", phases.parse);
+    eprintln!("resolve:    {:?}", phases.resolve);
+    eprintln!("interpret:  {:?}", phases.interpret);
+    eprintln!("total:      {total:?}");
+    eprintln!("statements: {}", interpreter.statements_executed());
+    eprintln!("calls:      {}", interpreter.calls_made());
+}
+
+fn print_profile(interpreter: &Interpreter) {
+    let entries = interpreter.profile();
+    let width = entries
+        .iter()
+        .map(|entry| entry.name.len())
+        .chain(["function".len()])
+        .max()
+        .unwrap_or(0);
+    eprintln!("== profile ==");
+    eprintln!(
+        "{:width$}  {:>10}  {:>12}  {:>12}",
+        "function", "calls", "total ms", "self ms"
+    );
+    for entry in entries {
+        eprintln!(
+            "{:width$}  {:>10}  {:>12.3}  {:>12.3}",
+            entry.name,
+            entry.calls,
+            entry.total.as_secs_f64() * 1000.0,
+            entry.self_time.as_secs_f64() * 1000.0
+        );
+    }
+}
+
+fn print_stats(interpreter: &Interpreter) {
+    eprintln!("== stats ==");
+    eprintln!("instances created:");
+    for (class_name, count) in interpreter.construction_counts() {
+        eprintln!("  {class_name}: {count}");
+    }
+}
+
+/// Prints each token's line, type, lexeme and literal value, one per line.
+pub fn tokens_file(file_path: &str) -> Result<(), Box<dyn Error>> {
+    let contents = read_source(file_path)?;
+    let mut scanner = Scanner::new(&contents);
+    scanner.scan_tokens();
+    emit(&contents, &scanner.diagnostics);
+    for token in scanner.tokens.iter() {
+        println!("{}", token.describe());
+    }
+    if scanner.had_error() {
+        std::process::exit(65);
+    }
+    Ok(())
+}
+
+/// Reports every scan, parse and resolution error in the script without running it, exiting
+/// with status 65 if there were any.
+pub fn check_file(file_path: &str) -> Result<(), Box<dyn Error>> {
+    let contents = read_source(file_path)?;
+    let mut scanner = Scanner::new(&contents);
+    scanner.scan_tokens();
+    let mut diagnostics = scanner.diagnostics;
+
+    let mut parser = Parser::new(scanner.tokens);
+    let parse_result = parser.parse();
+    diagnostics.append(&mut parser.diagnostics);
+    // Still resolve after scan errors, so their diagnostics are reported together.
+    if let Ok(statements) = parse_result {
+        let mut interpreter = Interpreter::new();
+        let mut resolver = Resolver::new(&mut interpreter);
+        resolver.resolve_statements(&statements);
+        diagnostics.append(&mut resolver.diagnostics);
+    }
+    emit(&contents, &diagnostics);
+    if is_fatal(&diagnostics) {
+        std::process::exit(65);
+    }
+    Ok(())
+}
+
+/// Reports every error and warning `lint` finds in the script without running it, exiting with
+/// status 65 if any are errors, including warnings denied with `--deny`.
+pub fn lint_file(file_path: &str) -> Result<(), Box<dyn Error>> {
+    let contents = read_source(file_path)?;
+    let diagnostics = lint(&contents);
+    emit(&contents, &diagnostics);
+    if is_fatal(&diagnostics) {
+        std::process::exit(65);
+    }
+    Ok(())
+}
+
+/// Runs the test scripts under `path`, printing how each failing one differed from its
+/// expectations and exiting with status 1 if any failed.
+pub fn test_path(path: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    let summary = run_tests(Path::new(path), options.coverage.is_some())?;
+    for failure in summary.failures.iter() {
+        println!("FAIL {}", failure.path.display());
+        for line in failure.diff.iter() {
+            println!("  {line}");
+        }
+    }
+    println!(
+        "{} passed, {} failed.",
+        summary.passed,
+        summary.failures.len()
+    );
+    if let Some(format) = options.coverage {
+        for script in summary.coverage.iter() {
+            let path = script.path.display().to_string();
+            if format == CoverageFormat::Annotated {
+                eprintln!("== {path} ==");
+            }
+            eprint!("{}", script.lines.report(&script.source, &path, format));
+        }
+    }
+    if !summary.failures.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+pub fn fmt_file(file_path: &str) -> Result<(), Box<dyn Error>> {
+    let contents = read_source(file_path)?;
+    match format(&contents) {
+        Some(formatted) => print!("{formatted}"),
+        None => std::process::exit(65),
+    }
+    Ok(())
+}
+
+pub fn minify_file(file_path: &str, rename_locals: bool) -> Result<(), Box<dyn Error>> {
+    let contents = read_source(file_path)?;
+    match minify(&contents, rename_locals) {
+        Some(minified) => print!("{minified}"),
+        None => std::process::exit(65),
+    }
+    Ok(())
+}
+
+pub fn js_file(file_path: &str) -> Result<(), Box<dyn Error>> {
+    let contents = read_source(file_path)?;
+    match transpile(&contents) {
+        Some(javascript) => print!("{javascript}"),
+        None => std::process::exit(65),
+    }
+    Ok(())
+}
+
+pub fn ast_file(file_path: &str, json: bool) -> Result<(), Box<dyn Error>> {
+    let contents = read_source(file_path)?;
+    let ast = match json {
+        true => ast_to_json(&contents),
+        false => print_ast(&contents),
+    };
+    match ast {
+        Some(ast) => print!("{ast}"),
+        None => std::process::exit(65),
+    }
+    Ok(())
+}
+
+pub fn highlight_file(file_path: &str, format: HighlightFormat) -> Result<(), Box<dyn Error>> {
+    let contents = read_source(file_path)?;
+    print!("{}", highlight(&contents, format));
+    Ok(())
+}
+
+pub fn doc_file(file_path: &str) -> Result<(), Box<dyn Error>> {
+    let contents = read_source(file_path)?;
+    match document(&contents) {
+        Some(doc) => print!("{doc}"),
+        None => std::process::exit(65),
+    }
+    Ok(())
+}
+
+pub fn report_file(file_path: &str) -> Result<(), Box<dyn Error>> {
+    let contents = read_source(file_path)?;
+    match report(&contents) {
+        Some(report) => print!("{report}"),
+        None => std::process::exit(65),
+    }
+    Ok(())
+}
+
+pub fn run_prompt(max_line_length: usize, init: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        return run_piped(max_line_length);
+    }
+
+    let mut interpreter = Interpreter::builder()
+        .max_call_depth(DEFAULT_MAX_CALL_DEPTH)
+        .build();
+    // While a line is being edited the terminal is in raw mode and rustyline sees Ctrl-C
+    // itself, so this only fires during evaluation.
+    let interrupted = interpreter.interrupt_flag();
+    let handler_flag = Arc::clone(&interrupted);
+    ctrlc::set_handler(move || handler_flag.store(true, Ordering::Relaxed))?;
+    // Shared by every line so each resolves against the same top level.
+    let mut resolver_state = ResolverState::default();
+    run_prelude(init, &mut interpreter, &mut resolver_state)?;
+    // Globals as of the last `:save`, for `:restore` to roll back to.
+    let mut saved = None;
+    let mut editor = Editor::<ReplHelper, DefaultHistory>::new()?;
+    let mut helper = ReplHelper::new();
+    helper.set_globals(interpreter.global_names());
+    helper.set_locals(&resolver_state);
+    editor.set_helper(Some(helper));
+    loop {
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            // Ctrl-C discards the current line.
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(ReadlineError::Io(error)) if error.kind() == io::ErrorKind::InvalidData => {
+                eprintln!("Error: Input is not valid UTF-8.");
+                continue;
             }
             Err(error) => {
                 eprintln!("Error: {error}");
                 break;
             }
+        };
+        if line.len() > max_line_length {
+            eprintln!("Error: Line exceeds the maximum length of {max_line_length} bytes.");
+            continue;
+        }
+        if line.trim().is_empty() {
+            break;
+        }
+        editor.add_history_entry(line.as_str())?;
+        // Drop any Ctrl-C that arrived after the previous evaluation finished.
+        interrupted.store(false, Ordering::Relaxed);
+        match line.strip_prefix(":time") {
+            Some(source) if source.trim().is_empty() => {
+                eprintln!("Usage: :time <statement or expression>");
+            }
+            Some(source) if source.starts_with(char::is_whitespace) => {
+                time(source, &mut interpreter, &mut resolver_state);
+            }
+            _ if line.trim() == ":scopes" || line.starts_with(":scopes ") => {
+                scopes(&line[":scopes".len()..], &mut interpreter, &resolver_state);
+            }
+            _ if line.trim() == ":save" => saved = Some(interpreter.snapshot()),
+            _ if line.trim() == ":restore" => match saved {
+                Some(ref snapshot) => interpreter.restore(snapshot),
+                None => eprintln!("Error: No globals saved; save them with :save first."),
+            },
+            _ => {
+                run(&line, &mut interpreter, &mut resolver_state, true);
+            }
+        }
+
+        if let Some(helper) = editor.helper_mut() {
+            helper.set_globals(interpreter.global_names());
+            helper.set_locals(&resolver_state);
         }
     }
     Ok(())
 }
 
-fn run<'src>(source: &'src str, interpreter: &mut Interpreter<'src>) -> i32 {
+/// Runs the prelude at `init`, or `~/.loxrc` if it exists, so its definitions are available
+/// from the first prompt. Errors in the prelude are reported without ending the session.
+fn run_prelude(
+    init: Option<&str>,
+    interpreter: &mut Interpreter,
+    resolver_state: &mut ResolverState,
+) -> Result<(), Box<dyn Error>> {
+    let contents = match init {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|error| format!("Couldn't read init file '{path}': {error}"))?,
+        None => {
+            let Some(home) = env::var_os("HOME") else {
+                return Ok(());
+            };
+            match fs::read_to_string(Path::new(&home).join(".loxrc")) {
+                Ok(contents) => contents,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+                Err(error) => return Err(format!("Couldn't read ~/.loxrc: {error}").into()),
+            }
+        }
+    };
+    run(&contents, interpreter, resolver_state, false);
+    Ok(())
+}
+
+/// Runs `source` in the REPL, then reports how long it took and how many statements ran.
+fn time(source: &str, interpreter: &mut Interpreter, resolver_state: &mut ResolverState) {
+    let statements_before = interpreter.statements_executed();
+    let start = Instant::now();
+    run(source, interpreter, resolver_state, true);
+    let elapsed = start.elapsed();
+    let statements = interpreter.statements_executed() - statements_before;
+    let plural = if statements == 1 { "" } else { "s" };
+    println!("Time: {elapsed:?}, {statements} statement{plural} executed");
+}
+
+/// Prints the local scopes open in the REPL, then resolves `source` against them without
+/// running it and prints where each variable it references resolves to.
+fn scopes(source: &str, interpreter: &mut Interpreter, resolver_state: &ResolverState) {
+    let open_scopes = resolver_state.scopes();
+    if open_scopes.is_empty() {
+        println!("No local scopes are open; names not declared in the input are globals.");
+    }
+    for (idx, scope) in open_scopes.iter().enumerate() {
+        let variables: Vec<String> = scope
+            .iter()
+            .map(|variable| match variable.defined {
+                true => format!("{} (slot {})", variable.name, variable.slot),
+                false => format!("{} (slot {}, declared)", variable.name, variable.slot),
+            })
+            .collect();
+        println!("Scope {idx}: {}", variables.join(", "));
+    }
+    if source.trim().is_empty() {
+        return;
+    }
+
+    let reporter: SharedReporter = Rc::new(RefCell::new(ConsoleReporter::new(source)));
     let mut scanner = Scanner::new(source);
+    scanner.set_reporter(Rc::clone(&reporter));
+    scanner.scan_tokens();
+    let mut parser = Parser::new(scanner.tokens);
+    parser.set_reporter(Rc::clone(&reporter));
+    parser.set_repl_mode(true);
+    let parse_result = parser.parse();
+    let statements = match parse_result {
+        Ok(statements) if !is_fatal(&scanner.diagnostics) => statements,
+        _ => return,
+    };
+    let mut resolver = Resolver::with_state(interpreter, resolver_state.clone());
+    resolver.set_reporter(reporter);
+    resolver.resolve_statements(&statements);
+    for resolution in resolver.resolutions.iter() {
+        println!("{}", resolution.describe());
+    }
+}
+
+/// Runs everything piped into stdin as a single script, without printing prompts. As in the
+/// REPL, lines that aren't valid UTF-8 or are longer than `max_line_length` are reported and
+/// skipped; each leaves a blank line behind so the rest keep their line numbers.
+fn run_piped(max_line_length: usize) -> Result<(), Box<dyn Error>> {
+    let mut contents = String::new();
+    for (index, line) in io::stdin().lock().split(b'\n').enumerate() {
+        let line = line?;
+        let line_number = index + 1;
+        if line.len() > max_line_length {
+            eprintln!(
+                "Error: Line {line_number} exceeds the maximum length of {max_line_length} bytes."
+            );
+        } else {
+            match std::str::from_utf8(&line) {
+                Ok(line) => contents.push_str(line),
+                Err(_) => eprintln!("Error: Line {line_number} is not valid UTF-8."),
+            }
+        }
+        contents.push('\n');
+    }
+
+    let mut interpreter = Interpreter::builder()
+        .max_call_depth(DEFAULT_MAX_CALL_DEPTH)
+        .build();
+    let exit_code = run(
+        &contents,
+        &mut interpreter,
+        &mut ResolverState::default(),
+        false,
+    );
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
+    Ok(())
+}
+
+/// Reads the script at `file_path`, or standard input if the path is `-`.
+fn read_source(file_path: &str) -> Result<String, Box<dyn Error>> {
+    if file_path == "-" {
+        return read_stdin();
+    }
+    Ok(fs::read_to_string(file_path)?)
+}
+
+fn read_stdin() -> Result<String, Box<dyn Error>> {
+    let mut bytes = Vec::new();
+    io::stdin().read_to_end(&mut bytes)?;
+    Ok(String::from_utf8(bytes).map_err(|_| "Input is not valid UTF-8.")?)
+}
+
+/// Runs `source`, returning the process exit code. Resolution resumes from and updates
+/// `resolver_state`. `repl` echoes a trailing bare expression.
+fn run(
+    source: &str,
+    interpreter: &mut Interpreter,
+    resolver_state: &mut ResolverState,
+    repl: bool,
+) -> i32 {
+    run_phases(
+        source,
+        interpreter,
+        resolver_state,
+        repl,
+        &mut PhaseTimes::default(),
+    )
+}
+
+/// Time spent in each phase of [`run`]. Scanning counts as part of parsing.
+#[derive(Default)]
+struct PhaseTimes {
+    parse: Duration,
+    resolve: Duration,
+    interpret: Duration,
+}
+
+/// Runs a syntax tree written by `ast --json`, returning the exit code.
+fn run_syntax_tree(json: &str, interpreter: &mut Interpreter, phases: &mut PhaseTimes) -> i32 {
+    interpreter.set_reporter(Rc::new(RefCell::new(ConsoleReporter::new(""))));
+    let start = Instant::now();
+    let result = run_ast_json(json, interpreter);
+    phases.interpret = start.elapsed();
+    let Err(errors) = result else {
+        return 0;
+    };
+    let mut exit_code = 65;
+    for error in errors.iter() {
+        match error {
+            // There's no source to quote, so errors only give their position.
+            LoxError::Static(diagnostic) => emit("", slice::from_ref(diagnostic)),
+            LoxError::InvalidSyntaxTree(_) => eprintln!("{error}"),
+            // The reporter has printed runtime errors already.
+            LoxError::Runtime(_) | LoxError::Cancelled => exit_code = 70,
+        }
+    }
+    exit_code
+}
+
+/// [`run`], recording how long each phase took in `phases`.
+fn run_phases(
+    source: &str,
+    interpreter: &mut Interpreter,
+    resolver_state: &mut ResolverState,
+    repl: bool,
+    phases: &mut PhaseTimes,
+) -> i32 {
+    let mut console = ConsoleReporter::new(source);
+    // Functions defined by earlier REPL input report lines of that input, not this one,
+    // so only quote the source for whole scripts.
+    console.set_quote_runtime_errors(!repl);
+    let reporter: SharedReporter = Rc::new(RefCell::new(console));
+
+    let start = Instant::now();
+    let mut scanner = Scanner::new(source);
+    scanner.set_reporter(Rc::clone(&reporter));
     scanner.scan_tokens();
 
     let mut parser = Parser::new(scanner.tokens);
+    parser.set_reporter(Rc::clone(&reporter));
+    parser.set_repl_mode(repl);
     let parse_result = parser.parse();
+    phases.parse = start.elapsed();
 
-    if parse_result.is_err() || scanner.had_error {
+    if parse_result.is_err() || is_fatal(&scanner.diagnostics) || is_fatal(&parser.diagnostics) {
         return 65;
     }
-    let statements = parse_result.unwrap();
+    let mut statements = parse_result.unwrap();
 
-    let mut resolver = Resolver::new(interpreter);
+    let start = Instant::now();
+    let mut resolver = Resolver::with_state(interpreter, mem::take(resolver_state));
+    resolver.set_reporter(Rc::clone(&reporter));
     resolver.resolve_statements(&statements);
-    if resolver.had_error {
+    let diagnostics = mem::take(&mut resolver.diagnostics);
+    *resolver_state = resolver.into_state();
+    phases.resolve = start.elapsed();
+    if is_fatal(&diagnostics) {
         return 65;
     }
+    eliminate_dead_code(&mut statements);
 
-    match interpreter.interpret(&statements) {
-        Ok(()) => (),
-        Err(error) => {
-            println!("{error}");
-            return 70;
-        }
+    let start = Instant::now();
+    interpreter.set_reporter(reporter);
+    let result = interpreter.interpret(&statements);
+    phases.interpret = start.elapsed();
+    match result {
+        Ok(()) => 0,
+        Err(_) => 70,
     }
-
-    0
 }
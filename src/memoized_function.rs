@@ -0,0 +1,85 @@
+use crate::{
+    interpreter::Interpreter,
+    lox_callable::LoxCallable,
+    lox_exception::LoxException,
+    lox_object::{LiteralKey, LoxLiteral, LoxObject},
+    span::Span,
+};
+use std::{cell::RefCell, collections::HashMap, fmt};
+
+/// Cache key for a single argument, or `None` if the argument isn't a literal.
+fn memo_key(object: &LoxObject) -> Option<LiteralKey> {
+    match object {
+        // Normalize -0 so it shares a cache entry with 0, matching Lox equality.
+        LoxObject::Literal(LoxLiteral::Number(val)) if *val == 0.0 => {
+            Some(LoxLiteral::Number(0.0).key())
+        }
+        LoxObject::Literal(literal) => Some(literal.key()),
+        _ => None,
+    }
+}
+
+/// Wraps a callable with a cache of results keyed by its arguments. Calls with any
+/// non-literal argument bypass the cache.
+#[derive(Debug)]
+pub struct MemoizedFunction {
+    function: LoxCallable,
+    cache: RefCell<HashMap<Vec<LiteralKey>, LoxObject>>,
+}
+
+impl MemoizedFunction {
+    pub fn new(function: LoxCallable) -> Self {
+        MemoizedFunction {
+            function,
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.function.arity()
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.function.name()
+    }
+
+    pub fn function(&self) -> &LoxCallable {
+        &self.function
+    }
+
+    pub fn cache(&self) -> &RefCell<HashMap<Vec<LiteralKey>, LoxObject>> {
+        &self.cache
+    }
+
+    pub fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<LoxObject>,
+        span: Span,
+    ) -> Result<LoxObject, LoxException> {
+        let key: Option<Vec<LiteralKey>> = arguments.iter().map(memo_key).collect();
+        let Some(key) = key else {
+            return self.function.call(interpreter, arguments, span);
+        };
+        if let Some(value) = self.cache.borrow().get(&key) {
+            return Ok(value.clone());
+        }
+
+        // The cache must not stay borrowed here, since recursive calls come back through it.
+        let value = self.function.call(interpreter, arguments, span)?;
+        self.cache.borrow_mut().insert(key, value.clone());
+        Ok(value)
+    }
+}
+
+impl PartialEq for MemoizedFunction {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Display for MemoizedFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.function)
+    }
+}
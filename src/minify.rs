@@ -0,0 +1,137 @@
+use crate::{
+    diagnostic::{emit, has_errors},
+    interpreter::Interpreter,
+    parser::Parser,
+    resolver::{Resolution, Resolver},
+    scanner::{Scanner, KEYWORDS},
+    token::Token,
+    token_type::TokenType,
+};
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+};
+
+/// Reprints `source` on a single line, without comments and with only the spaces needed to
+/// keep adjacent tokens apart. With `rename_locals`, local variables and parameters also get
+/// the shortest names not otherwise used in the script, the most referenced first; globals,
+/// properties and the names of functions and classes, which show when they're printed, are
+/// kept. Returns `None` if the source has scan, parse or (when renaming) resolution errors,
+/// which are reported as usual.
+pub fn minify(source: &str, rename_locals: bool) -> Option<String> {
+    let mut scanner = Scanner::new(source);
+    scanner.scan_tokens();
+    emit(source, &scanner.diagnostics);
+    let tokens = scanner.tokens;
+    let mut parser = Parser::new(tokens.clone());
+    let parse_result = parser.parse();
+    emit(source, &parser.diagnostics);
+    let statements = parse_result.ok()?;
+    if has_errors(&scanner.diagnostics) {
+        return None;
+    }
+
+    let mut renames = HashMap::new();
+    if rename_locals {
+        let mut interpreter = Interpreter::new();
+        let mut resolver = Resolver::new(&mut interpreter);
+        resolver.resolve_statements(&statements);
+        if resolver.had_error() {
+            emit(source, &resolver.diagnostics);
+            return None;
+        }
+        renames = local_renames(&tokens, &resolver.resolutions);
+    }
+
+    let mut out = String::new();
+    for token in tokens.iter() {
+        if token.token_type == TokenType::Eof {
+            continue;
+        }
+        let text = match renames.get(&(token.line, token.column)) {
+            Some(name) => name.as_str(),
+            None => &token.lexeme,
+        };
+        if needs_space(&out, text) {
+            out.push(' ');
+        }
+        out.push_str(text);
+    }
+    out.push('\n');
+    Some(out)
+}
+
+/// New names for renamed locals, keyed by the line and column of each token to rename: the
+/// declaration and every reference to it.
+fn local_renames(tokens: &[Token], resolutions: &[Resolution]) -> HashMap<(usize, usize), String> {
+    let named: HashSet<(usize, usize)> = tokens
+        .windows(2)
+        .filter(|pair| matches!(pair[0].token_type, TokenType::Fun | TokenType::Class))
+        .map(|pair| (pair[1].line, pair[1].column))
+        .collect();
+    let mut uses: HashMap<(usize, usize), Vec<(usize, usize)>> = HashMap::new();
+    for resolution in resolutions {
+        if let Some(declared_at) = resolution.declared_at {
+            if !named.contains(&declared_at) {
+                let reference = (resolution.line, resolution.column);
+                uses.entry(declared_at).or_default().push(reference);
+            }
+        }
+    }
+    let mut locals: Vec<_> = uses.into_iter().collect();
+    locals.sort_by_key(|(declared_at, references)| (Reverse(references.len()), *declared_at));
+
+    // Every local gets a name of its own, unused anywhere else in the script, so none can
+    // capture a reference meant for another variable.
+    let taken: HashSet<&str> = tokens
+        .iter()
+        .filter(|token| token.token_type == TokenType::Identifier)
+        .map(|token| &*token.lexeme)
+        .chain(KEYWORDS.iter().map(|&(keyword, _)| keyword))
+        .collect();
+    let mut names = (0..)
+        .map(short_name)
+        .filter(|name| !taken.contains(name.as_str()));
+    let mut renames = HashMap::new();
+    for (declared_at, references) in locals {
+        let Some(name) = names.next() else {
+            break;
+        };
+        for position in references {
+            renames.insert(position, name.clone());
+        }
+        renames.insert(declared_at, name);
+    }
+    renames
+}
+
+/// The `n`th identifier in order of length: `a` to `z`, `A` to `Z`, then `aa`, `ab` and so on.
+fn short_name(mut n: usize) -> String {
+    const LETTERS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    let mut name = Vec::new();
+    loop {
+        name.push(LETTERS[n % LETTERS.len()]);
+        n /= LETTERS.len();
+        if n == 0 {
+            break;
+        }
+        n -= 1;
+    }
+    name.reverse();
+    String::from_utf8_lossy(&name).into_owned()
+}
+
+/// Whether `next` must be separated from the text before it to scan as the same tokens: two
+/// words would run together, and some pairs of symbols would become an operator or comment.
+fn needs_space(before: &str, next: &str) -> bool {
+    let (Some(last), Some(first)) = (before.chars().next_back(), next.chars().next()) else {
+        return false;
+    };
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    match (last, first) {
+        _ if is_word(last) && is_word(first) => true,
+        ('/', '/' | '*') | ('*', '/') => true,
+        ('=' | '!' | '<' | '>', '=') => true,
+        _ => false,
+    }
+}
@@ -1,39 +1,85 @@
-use crate::{interpreter::Interpreter, lox_exception::LoxException, lox_object::LoxObject};
-use std::fmt;
+use crate::{
+    interpreter::Interpreter, lox_exception::LoxException, lox_object::LoxObject, span::Span,
+};
+use std::{fmt, rc::Rc};
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct NativeFunction<'src> {
-    function: fn(&mut Interpreter<'src>, Vec<LoxObject<'src>>) -> LoxObject<'src>,
+/// The Rust code behind a native function. It's given the call's arguments, already checked
+/// against the function's arity, and the span of the call for reporting errors.
+pub type NativeFn =
+    dyn Fn(&mut Interpreter, Vec<LoxObject>, Span) -> Result<LoxObject, LoxException>;
+
+#[derive(Clone)]
+pub struct NativeFunction {
+    name: Rc<str>,
+    function: Rc<NativeFn>,
     arity: usize,
     repr: String,
 }
-impl<'src> NativeFunction<'src> {
+impl NativeFunction {
     pub fn new(
-        function: fn(&mut Interpreter<'src>, Vec<LoxObject<'src>>) -> LoxObject<'src>,
+        name: Rc<str>,
+        function: impl Fn(&mut Interpreter, Vec<LoxObject>, Span) -> Result<LoxObject, LoxException>
+            + 'static,
         arity: usize,
         repr: String,
     ) -> Self {
         NativeFunction {
-            function,
+            name,
+            function: Rc::new(function),
             arity,
             repr,
         }
     }
 
+    /// A native function `name` taking `arity` arguments that prints as `<native fn>`, as
+    /// built-in natives do.
+    pub fn named(
+        name: &str,
+        arity: usize,
+        function: impl Fn(&mut Interpreter, Vec<LoxObject>, Span) -> Result<LoxObject, LoxException>
+            + 'static,
+    ) -> Self {
+        NativeFunction::new(Rc::from(name), function, arity, String::from("<native fn>"))
+    }
+
     pub fn arity(&self) -> usize {
         self.arity
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn call(
         &self,
-        interpreter: &mut Interpreter<'src>,
-        arguments: Vec<LoxObject<'src>>,
-    ) -> Result<LoxObject<'src>, LoxException<'src>> {
-        Ok((self.function)(interpreter, arguments))
+        interpreter: &mut Interpreter,
+        arguments: Vec<LoxObject>,
+        span: Span,
+    ) -> Result<LoxObject, LoxException> {
+        (self.function)(interpreter, arguments, span)
+    }
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.function, &other.function)
+            && self.name == other.name
+            && self.arity == other.arity
+            && self.repr == other.repr
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .field("repr", &self.repr)
+            .finish_non_exhaustive()
     }
 }
 
-impl<'src> fmt::Display for NativeFunction<'src> {
+impl fmt::Display for NativeFunction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.repr)
     }
@@ -0,0 +1,634 @@
+use crate::{
+    cycle_collector, doc,
+    environment::Environment,
+    error_code::ErrorCode,
+    interpreter::Interpreter,
+    live_count::{live, LiveKind},
+    lox_callable::LoxCallable,
+    lox_class::LoxClass,
+    lox_exception::{LoxException, RuntimeError},
+    lox_instance::LoxInstance,
+    lox_object::{LoxLiteral, LoxObject},
+    lox_string::LoxString,
+    memoized_function::MemoizedFunction,
+    native_function::NativeFunction,
+    span::Span,
+};
+use std::{cell::RefCell, collections::HashMap, env, rc::Rc, time::Duration};
+
+type NativeFnPtr = fn(&mut Interpreter, Vec<LoxObject>, Span) -> Result<LoxObject, LoxException>;
+
+/// Every built-in native function, with its arity.
+const NATIVES: &[(&str, usize, NativeFnPtr)] = &[
+    ("clock", 0, clock),
+    ("clockMillis", 0, clock_millis),
+    ("now", 0, now),
+    ("sleep", 1, sleep),
+    ("newCount", 1, new_count),
+    ("stats", 0, stats),
+    ("type", 1, type_of),
+    ("arityOf", 1, arity_of),
+    ("nameOf", 1, name_of),
+    ("help", 1, help),
+    ("clone", 1, clone),
+    ("approxEqual", 3, approx_equal),
+    ("eprint", 1, eprint),
+    ("readLine", 0, read_line),
+    ("getenv", 1, getenv),
+    ("setenv", 2, setenv),
+    ("input", 1, input),
+    ("bind", 2, bind),
+    ("unbind", 1, unbind),
+    ("memoize", 1, memoize),
+    ("len", 1, len),
+    ("substr", 3, substr),
+    ("indexOf", 2, index_of),
+    ("split", 2, split),
+    ("toUpper", 1, to_upper),
+    ("toLower", 1, to_lower),
+    ("trim", 1, trim),
+    ("replace", 3, replace),
+    ("startsWith", 2, starts_with),
+    ("endsWith", 2, ends_with),
+    ("random", 0, random),
+    ("randomInt", 2, random_int),
+    ("seedRandom", 1, seed_random),
+];
+
+/// Defines each native function in [`NATIVES`] whose name `enabled` accepts.
+pub fn define_natives(globals: &mut Environment, enabled: impl Fn(&str) -> bool) {
+    for &(name, arity, function) in NATIVES {
+        if enabled(name) {
+            define(globals, name, arity, function);
+        }
+    }
+}
+
+fn define(globals: &mut Environment, name: &str, arity: usize, function: NativeFnPtr) {
+    let native = NativeFunction::named(name, arity, function);
+    globals.define(
+        name,
+        LoxObject::Callable(LoxCallable::NativeFun(Rc::new(native))),
+    );
+}
+
+fn native_error(span: Span, message: &str) -> LoxException {
+    LoxException::RuntimeError(RuntimeError::new(
+        ErrorCode::NATIVE_FUNCTION,
+        span,
+        String::from(message),
+    ))
+}
+
+fn string_arg(
+    arguments: &[LoxObject],
+    idx: usize,
+    name: &str,
+    span: Span,
+) -> Result<Rc<str>, LoxException> {
+    match &arguments[idx] {
+        LoxObject::Literal(LoxLiteral::String(val)) => Ok(val.flat()),
+        _ => Err(native_error(
+            span,
+            &format!("{name}() expects a string as argument {}.", idx + 1),
+        )),
+    }
+}
+
+fn index_arg(
+    arguments: &[LoxObject],
+    idx: usize,
+    name: &str,
+    span: Span,
+) -> Result<usize, LoxException> {
+    match &arguments[idx] {
+        LoxObject::Literal(LoxLiteral::Number(val)) if *val >= 0.0 && val.fract() == 0.0 => {
+            Ok(*val as usize)
+        }
+        _ => Err(native_error(
+            span,
+            &format!(
+                "{name}() expects a non-negative integer as argument {}.",
+                idx + 1
+            ),
+        )),
+    }
+}
+
+fn string_object(val: String) -> LoxObject {
+    LoxObject::Literal(LoxLiteral::String(Rc::new(LoxString::from(val))))
+}
+
+fn clock(
+    interpreter: &mut Interpreter,
+    _: Vec<LoxObject>,
+    _: Span,
+) -> Result<LoxObject, LoxException> {
+    Ok(LoxObject::Literal(LoxLiteral::Number(
+        interpreter.clock().since_epoch().as_secs_f64(),
+    )))
+}
+
+/// Milliseconds since the Unix epoch.
+fn clock_millis(
+    interpreter: &mut Interpreter,
+    _: Vec<LoxObject>,
+    _: Span,
+) -> Result<LoxObject, LoxException> {
+    Ok(LoxObject::Literal(LoxLiteral::Number(
+        interpreter.clock().since_epoch().as_millis() as f64,
+    )))
+}
+
+/// Seconds from a monotonic clock, unaffected by system clock adjustments. Only
+/// differences between two calls are meaningful.
+fn now(
+    interpreter: &mut Interpreter,
+    _: Vec<LoxObject>,
+    _: Span,
+) -> Result<LoxObject, LoxException> {
+    Ok(LoxObject::Literal(LoxLiteral::Number(
+        interpreter.clock().monotonic().as_secs_f64(),
+    )))
+}
+
+fn new_count(
+    interpreter: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    match &arguments[0] {
+        LoxObject::Callable(LoxCallable::Class(class)) => Ok(LoxObject::Literal(
+            LoxLiteral::Number(interpreter.construction_count(&class.name) as f64),
+        )),
+        _ => Err(native_error(span, "newCount() expects a class.")),
+    }
+}
+
+fn bind(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    match (&arguments[0], &arguments[1]) {
+        (LoxObject::Callable(LoxCallable::Function(function)), LoxObject::Instance(instance)) => {
+            Ok(LoxObject::Callable(LoxCallable::Function(Rc::new(
+                function.bind(Rc::clone(instance)),
+            ))))
+        }
+        (LoxObject::Callable(LoxCallable::Function(_)), _) => Err(native_error(
+            span,
+            "bind() expects an instance as its second argument.",
+        )),
+        _ => Err(native_error(
+            span,
+            "bind() expects a function as its first argument.",
+        )),
+    }
+}
+
+fn unbind(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    match &arguments[0] {
+        LoxObject::Callable(LoxCallable::Function(function)) => match function.unbind() {
+            Some(unbound) => Ok(LoxObject::Callable(LoxCallable::Function(Rc::new(
+                unbound.clone(),
+            )))),
+            None => Err(native_error(span, "unbind() expects a bound method.")),
+        },
+        _ => Err(native_error(span, "unbind() expects a function.")),
+    }
+}
+
+fn random(
+    interpreter: &mut Interpreter,
+    _: Vec<LoxObject>,
+    _: Span,
+) -> Result<LoxObject, LoxException> {
+    Ok(LoxObject::Literal(LoxLiteral::Number(
+        interpreter.random().next_float(),
+    )))
+}
+
+fn random_int(
+    interpreter: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    match (&arguments[0], &arguments[1]) {
+        (
+            LoxObject::Literal(LoxLiteral::Number(lo)),
+            LoxObject::Literal(LoxLiteral::Number(hi)),
+        ) if lo.fract() == 0.0 && hi.fract() == 0.0 => {
+            if lo > hi {
+                return Err(native_error(
+                    span,
+                    "randomInt() expects lo to be at most hi.",
+                ));
+            }
+            let value = interpreter.random().next_int(*lo as i64, *hi as i64);
+            Ok(LoxObject::Literal(LoxLiteral::Number(value as f64)))
+        }
+        _ => Err(native_error(span, "randomInt() expects two integers.")),
+    }
+}
+
+fn seed_random(
+    interpreter: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    match &arguments[0] {
+        LoxObject::Literal(LoxLiteral::Number(seed)) if seed.fract() == 0.0 => {
+            interpreter.seed_random(*seed as i64 as u64);
+            Ok(LoxObject::Literal(LoxLiteral::Nil))
+        }
+        _ => Err(native_error(span, "seedRandom() expects an integer.")),
+    }
+}
+
+fn memoize(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    match &arguments[0] {
+        LoxObject::Callable(callable) => Ok(LoxObject::Callable(LoxCallable::Memoized(Rc::new(
+            MemoizedFunction::new(callable.clone()),
+        )))),
+        _ => Err(native_error(span, "memoize() expects a function.")),
+    }
+}
+
+fn len(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    let length = match &arguments[0] {
+        LoxObject::Literal(LoxLiteral::String(val)) => val.char_count(),
+        LoxObject::List(list) => list.borrow().len(),
+        _ => return Err(native_error(span, "len() expects a string or list.")),
+    };
+    Ok(LoxObject::Literal(LoxLiteral::Number(length as f64)))
+}
+
+/// `substr(s, start, end)` returns the characters of `s` in `[start, end)`.
+fn substr(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    let string = string_arg(&arguments, 0, "substr", span)?;
+    let start = index_arg(&arguments, 1, "substr", span)?;
+    let end = index_arg(&arguments, 2, "substr", span)?;
+    if start > end || end > string.chars().count() {
+        return Err(native_error(span, "substr() range out of bounds."));
+    }
+    Ok(string_object(
+        string.chars().skip(start).take(end - start).collect(),
+    ))
+}
+
+/// Returns the character index of the first occurrence of the needle, or -1.
+fn index_of(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    let string = string_arg(&arguments, 0, "indexOf", span)?;
+    let needle = string_arg(&arguments, 1, "indexOf", span)?;
+    let index = match string.find(&*needle) {
+        Some(byte_idx) => string[..byte_idx].chars().count() as f64,
+        None => -1.0,
+    };
+    Ok(LoxObject::Literal(LoxLiteral::Number(index)))
+}
+
+/// Splits on every occurrence of the separator; an empty separator splits into characters.
+fn split(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    let string = string_arg(&arguments, 0, "split", span)?;
+    let separator = string_arg(&arguments, 1, "split", span)?;
+    let parts: Vec<LoxObject> = match separator.is_empty() {
+        true => string
+            .chars()
+            .map(|c| string_object(c.to_string()))
+            .collect(),
+        false => string
+            .split(&*separator)
+            .map(|part| string_object(part.to_string()))
+            .collect(),
+    };
+    Ok(LoxObject::List(Rc::new(RefCell::new(parts))))
+}
+
+fn to_upper(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    let string = string_arg(&arguments, 0, "toUpper", span)?;
+    Ok(string_object(string.to_uppercase()))
+}
+
+fn to_lower(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    let string = string_arg(&arguments, 0, "toLower", span)?;
+    Ok(string_object(string.to_lowercase()))
+}
+
+fn trim(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    let string = string_arg(&arguments, 0, "trim", span)?;
+    Ok(string_object(string.trim().to_string()))
+}
+
+/// Replaces every occurrence of `from` with `to`.
+fn replace(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    let string = string_arg(&arguments, 0, "replace", span)?;
+    let from = string_arg(&arguments, 1, "replace", span)?;
+    let to = string_arg(&arguments, 2, "replace", span)?;
+    if from.is_empty() {
+        return Err(native_error(span, "replace() expects a non-empty pattern."));
+    }
+    Ok(string_object(string.replace(&*from, &to)))
+}
+
+fn starts_with(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    let string = string_arg(&arguments, 0, "startsWith", span)?;
+    let prefix = string_arg(&arguments, 1, "startsWith", span)?;
+    Ok(LoxObject::Literal(LoxLiteral::Boolean(
+        string.starts_with(&*prefix),
+    )))
+}
+
+fn ends_with(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    let string = string_arg(&arguments, 0, "endsWith", span)?;
+    let suffix = string_arg(&arguments, 1, "endsWith", span)?;
+    Ok(LoxObject::Literal(LoxLiteral::Boolean(
+        string.ends_with(&*suffix),
+    )))
+}
+
+/// Whether `a` and `b` differ by at most `epsilon`.
+fn approx_equal(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    match (&arguments[0], &arguments[1], &arguments[2]) {
+        (
+            LoxObject::Literal(LoxLiteral::Number(a)),
+            LoxObject::Literal(LoxLiteral::Number(b)),
+            LoxObject::Literal(LoxLiteral::Number(epsilon)),
+        ) if *epsilon >= 0.0 => Ok(LoxObject::Literal(LoxLiteral::Boolean(
+            a == b || (a - b).abs() <= *epsilon,
+        ))),
+        _ => Err(native_error(
+            span,
+            "approxEqual() expects two numbers and a non-negative epsilon.",
+        )),
+    }
+}
+
+/// Reads a line from the interpreter's input, stdin by default, without its line ending, or
+/// `nil` at end of input. The read blocks until it returns, even if the run is cancelled.
+fn read_line(
+    interpreter: &mut Interpreter,
+    _: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    let mut line = String::new();
+    match interpreter.read_line(&mut line) {
+        Ok(0) => Ok(LoxObject::Literal(LoxLiteral::Nil)),
+        Ok(_) => {
+            let len = line.trim_end_matches(['\r', '\n']).len();
+            line.truncate(len);
+            Ok(string_object(line))
+        }
+        Err(error) => Err(native_error(span, &format!("readLine() failed: {error}"))),
+    }
+}
+
+/// Prints `prompt` without a trailing newline, then reads a line like `readLine()`.
+fn input(
+    interpreter: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    interpreter.write_prompt(&arguments[0].to_string(), span)?;
+    read_line(interpreter, Vec::new(), span)
+}
+
+/// Pauses execution for the given number of milliseconds.
+fn sleep(
+    interpreter: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    match &arguments[0] {
+        LoxObject::Literal(LoxLiteral::Number(ms)) if ms.is_finite() && *ms >= 0.0 => {
+            let duration = Duration::try_from_secs_f64(ms / 1000.0)
+                .map_err(|_| native_error(span, "sleep() duration is too long."))?;
+            interpreter.sleep(duration, span)?;
+            Ok(LoxObject::Literal(LoxLiteral::Nil))
+        }
+        _ => Err(native_error(
+            span,
+            "sleep() expects a non-negative number of milliseconds.",
+        )),
+    }
+}
+
+/// Returns the value of an environment variable, or `nil` if it is unset or not valid UTF-8.
+fn getenv(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    let name = string_arg(&arguments, 0, "getenv", span)?;
+    match env::var(&*name) {
+        Ok(value) => Ok(string_object(value)),
+        Err(_) => Ok(LoxObject::Literal(LoxLiteral::Nil)),
+    }
+}
+
+fn setenv(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    let name = string_arg(&arguments, 0, "setenv", span)?;
+    let value = string_arg(&arguments, 1, "setenv", span)?;
+    if name.is_empty() || name.contains(['=', '\0']) || value.contains('\0') {
+        return Err(native_error(span, "setenv() got an invalid name or value."));
+    }
+    env::set_var(&*name, &*value);
+    Ok(LoxObject::Literal(LoxLiteral::Nil))
+}
+
+/// Like the `print` statement, but writes to stderr.
+fn eprint(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    _: Span,
+) -> Result<LoxObject, LoxException> {
+    eprintln!("{}", arguments[0]);
+    Ok(LoxObject::Literal(LoxLiteral::Nil))
+}
+
+/// Returns the name of a value's type, or its class name for instances.
+fn type_of(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    _: Span,
+) -> Result<LoxObject, LoxException> {
+    let name = match &arguments[0] {
+        LoxObject::Literal(LoxLiteral::Number(_)) => "number",
+        LoxObject::Literal(LoxLiteral::String(_)) => "string",
+        LoxObject::Literal(LoxLiteral::Boolean(_)) => "boolean",
+        LoxObject::Literal(LoxLiteral::Nil) => "nil",
+        LoxObject::Callable(LoxCallable::Class(_)) => "class",
+        LoxObject::Callable(_) => "function",
+        LoxObject::List(_) => "list",
+        LoxObject::Instance(instance) => {
+            return Ok(string_object(instance.borrow().class_name().to_string()))
+        }
+        LoxObject::Foreign(foreign) => return Ok(string_object(foreign.name().to_string())),
+    };
+    Ok(string_object(name.to_string()))
+}
+
+/// Deep copies lists and instances. Functions and classes are shared with the original, and
+/// cycles in the original are reproduced in the copy.
+fn clone(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    _: Span,
+) -> Result<LoxObject, LoxException> {
+    Ok(deep_clone(&arguments[0], &mut HashMap::new()))
+}
+
+/// `copies` maps each list or instance already copied, by address, to its copy.
+fn deep_clone(value: &LoxObject, copies: &mut HashMap<*const (), LoxObject>) -> LoxObject {
+    match value {
+        LoxObject::List(list) => {
+            let key = Rc::as_ptr(list) as *const ();
+            if let Some(copy) = copies.get(&key) {
+                return copy.clone();
+            }
+            let copy = Rc::new(RefCell::new(Vec::with_capacity(list.borrow().len())));
+            copies.insert(key, LoxObject::List(Rc::clone(&copy)));
+            cycle_collector::track_list(&copy);
+            for element in list.borrow().iter() {
+                let element = deep_clone(element, copies);
+                copy.borrow_mut().push(element);
+            }
+            LoxObject::List(copy)
+        }
+        LoxObject::Instance(instance) => {
+            let key = Rc::as_ptr(instance) as *const ();
+            if let Some(copy) = copies.get(&key) {
+                return copy.clone();
+            }
+            let copy: Rc<RefCell<LoxInstance>> =
+                Rc::new(RefCell::new(instance.borrow().empty_copy()));
+            copies.insert(key, LoxObject::Instance(Rc::clone(&copy)));
+            cycle_collector::track_instance(&copy);
+            for (name, field) in instance.borrow().fields() {
+                let field = deep_clone(field, copies);
+                copy.borrow_mut().set_field(Rc::clone(name), field);
+            }
+            LoxObject::Instance(copy)
+        }
+        _ => value.clone(),
+    }
+}
+
+/// Returns a `Stats` instance with `environments`, `instances` and `globals` fields counting
+/// the live environments, live instances, and defined globals.
+fn stats(
+    interpreter: &mut Interpreter,
+    _: Vec<LoxObject>,
+    _: Span,
+) -> Result<LoxObject, LoxException> {
+    // Counted before the Stats instance itself is created.
+    let counts = [
+        ("environments", live(LiveKind::Environment)),
+        ("instances", live(LiveKind::Instance)),
+        ("globals", interpreter.globals.borrow().len()),
+    ];
+    let mut instance = LoxInstance::new(Rc::new(LoxClass::new(
+        Rc::from("Stats"),
+        None,
+        HashMap::new(),
+    )));
+    for (name, count) in counts {
+        instance.set_field(name, LoxObject::Literal(LoxLiteral::Number(count as f64)));
+    }
+    Ok(LoxObject::Instance(Rc::new(RefCell::new(instance))))
+}
+
+fn arity_of(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    match &arguments[0] {
+        LoxObject::Callable(callable) => Ok(LoxObject::Literal(LoxLiteral::Number(
+            callable.arity() as f64,
+        ))),
+        _ => Err(native_error(span, "arityOf() expects a function or class.")),
+    }
+}
+
+/// Returns a callable's name, or `nil` for anonymous functions.
+fn name_of(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    match &arguments[0] {
+        LoxObject::Callable(callable) => match callable.name() {
+            Some(name) => Ok(string_object(name.to_string())),
+            None => Ok(LoxObject::Literal(LoxLiteral::Nil)),
+        },
+        _ => Err(native_error(span, "nameOf() expects a function or class.")),
+    }
+}
+
+fn help(
+    _: &mut Interpreter,
+    arguments: Vec<LoxObject>,
+    span: Span,
+) -> Result<LoxObject, LoxException> {
+    match &arguments[0] {
+        LoxObject::Callable(callable) => Ok(string_object(doc::help(callable))),
+        _ => Err(native_error(span, "help() expects a function or class.")),
+    }
+}
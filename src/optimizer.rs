@@ -0,0 +1,439 @@
+use crate::{
+    expr::{
+        Assign, Binary, Call, Closure, Expr, ExprVisitor, Get, Grouping, Index, List,
+        ListComprehension, Literal, Logical, Set, SetIndex, Super, Ternary, This, Unary, Variable,
+    },
+    lox_object::LoxLiteral,
+    stmt::{
+        Block, Break, Class, Destructure, Expression, For, ForIn, Function, If, Print, Return,
+        Stmt, StmtVisitor, Var, While,
+    },
+};
+use std::{collections::HashSet, mem, rc::Rc};
+
+/// Removes code that can never run or whose result is never used: branches of `if` and
+/// `while` statements whose condition is a literal that rules them out, statements following
+/// a `return` or `break`, and local functions that are never referred to.
+///
+/// Runs on resolved statements, so that code removed here still gets its errors and warnings
+/// reported. Nothing removed changes the slot of a local variable that's still used.
+pub fn eliminate_dead_code(statements: &mut Vec<Stmt>) {
+    prune(statements, false);
+}
+
+/// Simplifies each of `statements`, dropping any after one that always leaves the list early.
+/// Unused functions are dropped too if the statements are `local` rather than globals.
+fn prune(statements: &mut Vec<Stmt>, local: bool) {
+    let mut pruned = Vec::with_capacity(statements.len());
+    for statement in mem::take(statements) {
+        if let Some(statement) = simplify(statement) {
+            let terminates = terminates(&statement);
+            pruned.push(statement);
+            if terminates {
+                break;
+            }
+        }
+    }
+    if local {
+        remove_unused_functions(&mut pruned);
+    }
+    *statements = pruned;
+}
+
+/// `stmt` with its dead branches removed, or `None` if none of it can run.
+fn simplify(stmt: Stmt) -> Option<Stmt> {
+    match stmt {
+        Stmt::If(mut if_stmt) => match truthiness(&if_stmt.condition) {
+            Some(true) => simplify(*if_stmt.then_branch),
+            Some(false) => if_stmt.else_branch.and_then(|branch| simplify(*branch)),
+            None => {
+                visit_expr(&mut if_stmt.condition);
+                if_stmt.then_branch = Box::new(simplify_branch(*if_stmt.then_branch));
+                if_stmt.else_branch = if_stmt
+                    .else_branch
+                    .and_then(|branch| simplify(*branch))
+                    .map(Box::new);
+                Some(Stmt::If(if_stmt))
+            }
+        },
+        Stmt::While(mut while_stmt) => match truthiness(&while_stmt.condition) {
+            Some(false) => None,
+            _ => {
+                visit_expr(&mut while_stmt.condition);
+                while_stmt.body = Box::new(simplify_branch(*while_stmt.body));
+                Some(Stmt::While(while_stmt))
+            }
+        },
+        Stmt::For(mut for_stmt) => {
+            let condition = for_stmt.condition.as_ref().and_then(truthiness);
+            if condition == Some(false) {
+                // Only the initializer runs, in a scope of its own.
+                let span = for_stmt.span;
+                return for_stmt.initializer.and_then(|initializer| {
+                    simplify(Stmt::Block(Block::new(vec![*initializer], span)))
+                });
+            }
+            if condition == Some(true) {
+                for_stmt.condition = None;
+            }
+            for_stmt.initializer = for_stmt
+                .initializer
+                .and_then(|initializer| simplify(*initializer))
+                .map(Box::new);
+            for expr in [&mut for_stmt.condition, &mut for_stmt.increment]
+                .into_iter()
+                .flatten()
+            {
+                visit_expr(expr);
+            }
+            for_stmt.body = Box::new(simplify_branch(*for_stmt.body));
+            Some(Stmt::For(for_stmt))
+        }
+        Stmt::ForIn(mut for_in) => {
+            visit_expr(&mut for_in.iterable);
+            for_in.body = Box::new(simplify_branch(*for_in.body));
+            Some(Stmt::ForIn(for_in))
+        }
+        Stmt::Block(mut block) => {
+            prune(&mut block.statements, true);
+            Some(Stmt::Block(block))
+        }
+        Stmt::Function(mut function) => {
+            for decorator in function.decorators.iter_mut() {
+                visit_expr(decorator);
+            }
+            prune_closure(&mut function.closure);
+            Some(Stmt::Function(function))
+        }
+        Stmt::Class(mut class) => {
+            if let Some(ref mut superclass) = class.superclass {
+                visit_expr(superclass);
+            }
+            for method in class.methods.iter_mut() {
+                if let Stmt::Function(function) = method {
+                    prune_closure(&mut function.closure);
+                }
+            }
+            Some(Stmt::Class(class))
+        }
+        Stmt::Expression(mut expression) => {
+            visit_expr(&mut expression.expression);
+            Some(Stmt::Expression(expression))
+        }
+        Stmt::Print(mut print) => {
+            visit_expr(&mut print.expression);
+            Some(Stmt::Print(print))
+        }
+        Stmt::Var(mut var) => {
+            if let Some(ref mut initializer) = var.initializer {
+                visit_expr(initializer);
+            }
+            Some(Stmt::Var(var))
+        }
+        Stmt::Destructure(mut destructure) => {
+            visit_expr(&mut destructure.initializer);
+            Some(Stmt::Destructure(destructure))
+        }
+        Stmt::Return(mut return_stmt) => {
+            visit_expr(&mut return_stmt.value);
+            Some(Stmt::Return(return_stmt))
+        }
+        Stmt::Break(_) => Some(stmt),
+    }
+}
+
+/// A statement that must stay in place, such as a loop body, simplified. It's replaced by an
+/// empty block if none of it can run.
+fn simplify_branch(stmt: Stmt) -> Stmt {
+    let span = stmt.span();
+    simplify(stmt).unwrap_or_else(|| Stmt::Block(Block::new(Vec::new(), span)))
+}
+
+fn prune_closure(closure: &mut Rc<Closure>) {
+    prune(&mut Rc::make_mut(closure).body, true);
+}
+
+/// Whether `expr` is always truthy or always falsey, if it's a literal.
+fn truthiness(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::Grouping(grouping) => truthiness(&grouping.expression),
+        Expr::Literal(literal) => Some(!matches!(
+            literal.value,
+            LoxLiteral::Nil | LoxLiteral::Boolean(false)
+        )),
+        _ => None,
+    }
+}
+
+/// Whether `stmt` always leaves the statement list it's in, skipping whatever follows.
+fn terminates(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Return(_) | Stmt::Break(_) => true,
+        Stmt::Block(block) => block.statements.last().is_some_and(terminates),
+        Stmt::If(if_stmt) => match if_stmt.else_branch {
+            Some(ref else_branch) => terminates(&if_stmt.then_branch) && terminates(else_branch),
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+/// Drops functions declared in `statements` that none of the statements after them refer to.
+/// A function followed by other declarations becomes an uninitialized variable instead, so the
+/// slots the resolver gave those declarations stay the same. Decorated functions are kept,
+/// since applying a decorator may have effects.
+fn remove_unused_functions(statements: &mut Vec<Stmt>) {
+    let mut reads = Reads::default();
+    let mut declared_after = false;
+    // Working backwards lets a function only used by an unused one be removed as well.
+    for idx in (0..statements.len()).rev() {
+        let unused = match statements[idx] {
+            Stmt::Function(ref function) => {
+                function.decorators.is_empty() && !reads.names.contains(&function.name.lexeme)
+            }
+            _ => false,
+        };
+        if !unused {
+            statements[idx].accept(&mut reads);
+            declared_after |= matches!(
+                statements[idx],
+                Stmt::Var(_) | Stmt::Destructure(_) | Stmt::Function(_) | Stmt::Class(_)
+            );
+            continue;
+        }
+        match declared_after {
+            true => {
+                let Stmt::Function(ref function) = statements[idx] else {
+                    unreachable!()
+                };
+                let placeholder = Var::new(function.name.clone(), None, function.span);
+                statements[idx] = Stmt::Var(placeholder);
+            }
+            false => {
+                statements.remove(idx);
+            }
+        }
+    }
+}
+
+/// Simplifies the bodies of functions written as expressions within `expr`.
+fn visit_expr(expr: &mut Expr) {
+    match expr {
+        Expr::Binary(binary) => {
+            visit_expr(&mut binary.left);
+            visit_expr(&mut binary.right);
+        }
+        Expr::Grouping(grouping) => visit_expr(&mut grouping.expression),
+        Expr::Unary(unary) => visit_expr(&mut unary.right),
+        Expr::Ternary(ternary) => {
+            visit_expr(&mut ternary.condition);
+            visit_expr(&mut ternary.left);
+            visit_expr(&mut ternary.right);
+        }
+        Expr::Assign(assign) => visit_expr(&mut assign.value),
+        Expr::Logical(logical) => {
+            visit_expr(&mut logical.left);
+            visit_expr(&mut logical.right);
+        }
+        Expr::Call(call) => {
+            visit_expr(&mut call.callee);
+            call.arguments.iter_mut().for_each(visit_expr);
+        }
+        Expr::Closure(closure) => prune_closure(closure),
+        Expr::Get(get) => visit_expr(&mut get.object),
+        Expr::Set(set) => {
+            visit_expr(&mut set.object);
+            visit_expr(&mut set.value);
+        }
+        Expr::List(list) => list.elements.iter_mut().for_each(visit_expr),
+        Expr::ListComprehension(comprehension) => {
+            visit_expr(&mut comprehension.element);
+            visit_expr(&mut comprehension.iterable);
+            if let Some(ref mut condition) = comprehension.condition {
+                visit_expr(condition);
+            }
+        }
+        Expr::Index(index) => {
+            visit_expr(&mut index.object);
+            visit_expr(&mut index.index);
+        }
+        Expr::SetIndex(set_index) => {
+            visit_expr(&mut set_index.object);
+            visit_expr(&mut set_index.index);
+            visit_expr(&mut set_index.value);
+        }
+        Expr::Literal(_) | Expr::Variable(_) | Expr::This(_) | Expr::Super(_) => {}
+    }
+}
+
+/// Every variable name the visited code reads or assigns, whichever variable it resolves to.
+#[derive(Default)]
+struct Reads {
+    names: HashSet<Rc<str>>,
+}
+
+impl Reads {
+    fn visit_statements(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            statement.accept(self);
+        }
+    }
+}
+
+impl ExprVisitor<()> for Reads {
+    fn visit_binary_expr(&mut self, expr: &Binary) {
+        expr.left.accept(self);
+        expr.right.accept(self);
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Grouping) {
+        expr.expression.accept(self);
+    }
+
+    fn visit_literal_expr(&mut self, _: &Literal) {}
+
+    fn visit_unary_expr(&mut self, expr: &Unary) {
+        expr.right.accept(self);
+    }
+
+    fn visit_ternary_expr(&mut self, expr: &Ternary) {
+        expr.condition.accept(self);
+        expr.left.accept(self);
+        expr.right.accept(self);
+    }
+
+    fn visit_variable_expr(&mut self, expr: &Variable) {
+        self.names.insert(Rc::clone(&expr.name.lexeme));
+    }
+
+    fn visit_assign_expr(&mut self, expr: &Assign) {
+        self.names.insert(Rc::clone(&expr.name.lexeme));
+        expr.value.accept(self);
+    }
+
+    fn visit_logical_expr(&mut self, expr: &Logical) {
+        expr.left.accept(self);
+        expr.right.accept(self);
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) {
+        expr.callee.accept(self);
+        for argument in expr.arguments.iter() {
+            argument.accept(self);
+        }
+    }
+
+    fn visit_closure_expr(&mut self, expr: &Rc<Closure>) {
+        self.visit_statements(&expr.body);
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) {
+        expr.object.accept(self);
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) {
+        expr.object.accept(self);
+        expr.value.accept(self);
+    }
+
+    fn visit_this_expr(&mut self, _: &This) {}
+
+    fn visit_super_expr(&mut self, _: &Super) {}
+
+    fn visit_list_expr(&mut self, expr: &List) {
+        for element in expr.elements.iter() {
+            element.accept(self);
+        }
+    }
+
+    fn visit_list_comprehension_expr(&mut self, expr: &ListComprehension) {
+        expr.element.accept(self);
+        expr.iterable.accept(self);
+        if let Some(ref condition) = expr.condition {
+            condition.accept(self);
+        }
+    }
+
+    fn visit_index_expr(&mut self, expr: &Index) {
+        expr.object.accept(self);
+        expr.index.accept(self);
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &SetIndex) {
+        expr.object.accept(self);
+        expr.index.accept(self);
+        expr.value.accept(self);
+    }
+}
+
+impl StmtVisitor<()> for Reads {
+    fn visit_expression_stmt(&mut self, stmt: &Expression) {
+        stmt.expression.accept(self);
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Print) {
+        stmt.expression.accept(self);
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Var) {
+        if let Some(ref initializer) = stmt.initializer {
+            initializer.accept(self);
+        }
+    }
+
+    fn visit_destructure_stmt(&mut self, stmt: &Destructure) {
+        stmt.initializer.accept(self);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &Block) {
+        self.visit_statements(&stmt.statements);
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &If) {
+        stmt.condition.accept(self);
+        stmt.then_branch.accept(self);
+        if let Some(ref else_branch) = stmt.else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &While) {
+        stmt.condition.accept(self);
+        stmt.body.accept(self);
+    }
+
+    fn visit_for_stmt(&mut self, stmt: &For) {
+        if let Some(ref initializer) = stmt.initializer {
+            initializer.accept(self);
+        }
+        for expr in [&stmt.condition, &stmt.increment].into_iter().flatten() {
+            expr.accept(self);
+        }
+        stmt.body.accept(self);
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) {
+        stmt.iterable.accept(self);
+        stmt.body.accept(self);
+    }
+
+    fn visit_break_stmt(&mut self, _: &Break) {}
+
+    fn visit_function_stmt(&mut self, stmt: &Function) {
+        for decorator in stmt.decorators.iter() {
+            decorator.accept(self);
+        }
+        self.visit_statements(&stmt.closure.body);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Return) {
+        stmt.value.accept(self);
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &Class) {
+        if let Some(ref superclass) = stmt.superclass {
+            superclass.accept(self);
+        }
+        self.visit_statements(&stmt.methods);
+    }
+}
@@ -1,40 +1,262 @@
 use crate::{
+    ast_json::statements_to_json,
+    diagnostic::{has_errors, Diagnostic},
+    error_code::ErrorCode,
+    error_reporter::SharedReporter,
     expr::{
-        Assign, Binary, Call, Closure, Expr, Get, Grouping, Literal, Logical, Set, Super, Ternary,
-        This, Unary, Variable,
+        Assign, Binary, Call, Closure, Expr, Get, Grouping, Index, List, ListComprehension,
+        Literal, Logical, Set, SetIndex, Super, Ternary, This, Unary, Variable,
     },
     lox_object::LoxLiteral,
-    report,
-    stmt::{Block, Class, Expression, Function, If, Print, Return, Stmt, Var, While},
+    scanner::Scanner,
+    span::Span,
+    stmt::{
+        Block, Break, Class, Destructure, Expression, For, ForIn, Function, If, Print, Return,
+        Stmt, Var, While,
+    },
     token::Token,
     token_type::TokenType,
 };
-use std::{iter::Peekable, vec::IntoIter};
+use std::{iter::Peekable, mem, rc::Rc, vec::IntoIter};
+
+/// How deeply statements and expressions may nest before parsing gives up, so that
+/// adversarial input such as thousands of `(` can't overflow the stack. Each link of a chain
+/// such as `a + b + c` or `a.b[c]()` nests the tree one level deeper too, so links count
+/// against it as well.
+pub const MAX_NESTING_DEPTH: usize = 128;
+
+/// Keywords that start a statement, where recovery from an error can resume.
+const STATEMENT_KEYWORDS: [TokenType; 8] = [
+    TokenType::Class,
+    TokenType::Fun,
+    TokenType::Var,
+    TokenType::For,
+    TokenType::If,
+    TokenType::While,
+    TokenType::Print,
+    TokenType::Return,
+];
 
 #[derive(Debug)]
 pub struct LoxParseError;
 
-pub struct Parser<'src> {
-    token_iter: Peekable<IntoIter<Token<'src>>>,
-    had_error: bool,
+/// A script that scanned and parsed without errors, returned by [`parse_str`].
+#[derive(Debug, Clone)]
+pub struct ParsedSource {
+    pub(crate) statements: Vec<Stmt>,
+    /// The warnings found parsing the script.
+    pub warnings: Vec<Diagnostic>,
+}
+
+impl ParsedSource {
+    /// The number of top-level statements.
+    pub fn len(&self) -> usize {
+        self.statements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.statements.is_empty()
+    }
+
+    /// The syntax tree as JSON, in the format [`ast_to_json`] writes.
+    ///
+    /// [`ast_to_json`]: crate::ast_json::ast_to_json
+    pub fn to_json(&self) -> String {
+        statements_to_json(&self.statements).to_string()
+    }
+}
+
+/// Scans and parses `source` without reporting or running anything, returning every
+/// diagnostic found if there are errors. This never panics, whatever `source` holds, so it can
+/// be given fuzzer input: nesting deeper than [`MAX_NESTING_DEPTH`] is an error rather than a
+/// stack overflow.
+pub fn parse_str(source: &str) -> Result<ParsedSource, Vec<Diagnostic>> {
+    let mut scanner = Scanner::new(source);
+    scanner.scan_tokens();
+    let mut diagnostics = std::mem::take(&mut scanner.diagnostics);
+
+    let mut parser = Parser::new(scanner.tokens);
+    let parse_result = parser.parse();
+    diagnostics.append(&mut parser.diagnostics);
+    match parse_result {
+        Ok(statements) if !has_errors(&diagnostics) => Ok(ParsedSource {
+            statements,
+            warnings: diagnostics,
+        }),
+        _ => Err(diagnostics),
+    }
+}
+
+pub struct Parser {
+    token_iter: Peekable<IntoIter<Token>>,
+    /// The token at the end of the input, returned once `token_iter` runs out.
+    eof: Token,
+    /// Errors found so far. Parsing fails if there are any.
+    pub diagnostics: Vec<Diagnostic>,
+    reporter: Option<SharedReporter>,
     loop_level: u32,
+    previous_span: Span,
+    /// Parentheses, brackets and braces opened by the tokens consumed so far and not yet closed.
+    nesting: usize,
+    /// Statements and expressions currently being parsed inside one another.
+    depth: usize,
+    /// The deepest level the chain being parsed reaches so far, counting its operands and the
+    /// links of the chains inside them.
+    deepest: usize,
+    repl_mode: bool,
 }
 
-impl<'src> Parser<'src> {
-    pub fn new(tokens: Vec<Token<'src>>) -> Self {
+impl Parser {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        let eof = match tokens.last() {
+            Some(last) if last.token_type == TokenType::Eof => last.clone(),
+            // The scanner always ends with Eof, but parsing mustn't depend on it.
+            last => {
+                let last = last.cloned().unwrap_or_default();
+                Token::new(
+                    TokenType::Eof,
+                    "",
+                    None,
+                    last.line,
+                    last.end(),
+                    last.column,
+                    0,
+                )
+            }
+        };
         Parser {
             token_iter: tokens.into_iter().peekable(),
-            had_error: false,
+            eof,
+            diagnostics: Vec::new(),
+            reporter: None,
             loop_level: 0,
+            previous_span: Span::default(),
+            nesting: 0,
+            depth: 0,
+            deepest: 0,
+            repl_mode: false,
         }
     }
 
-    fn parse_error(&mut self, line: usize, loc: &str, message: &str) {
-        self.had_error = true;
-        report(line, loc, message);
+    /// In REPL mode, an expression ending the input without a semicolon is parsed as a
+    /// print statement so its value is echoed.
+    pub fn set_repl_mode(&mut self, repl_mode: bool) {
+        self.repl_mode = repl_mode;
     }
 
-    pub fn parse(&mut self) -> Result<Vec<Stmt<'src>>, LoxParseError> {
+    /// Parses the tokens as a single expression with nothing following it.
+    pub fn parse_expression(&mut self) -> Result<Expr, LoxParseError> {
+        let expr = self.expression()?;
+        if !self.is_at_end() {
+            let next_token = self.peek().clone();
+            self.parse_error(
+                ErrorCode::TRAILING_TOKENS,
+                &next_token,
+                &format!("at '{}'", next_token.lexeme),
+                "Expect end of expression.",
+            );
+            return Err(LoxParseError);
+        }
+        match has_errors(&self.diagnostics) {
+            true => Err(LoxParseError),
+            false => Ok(expr),
+        }
+    }
+
+    /// Sets the reporter that each error and warning is passed to as soon as it's found.
+    pub fn set_reporter(&mut self, reporter: SharedReporter) {
+        self.reporter = Some(reporter);
+    }
+
+    fn record(&mut self, diagnostic: Diagnostic) {
+        if let Some(ref reporter) = self.reporter {
+            reporter.borrow_mut().report(&diagnostic);
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn parse_error(&mut self, code: ErrorCode, token: &Token, loc: &str, message: &str) {
+        self.record(Diagnostic::at_token(code, token, loc, message));
+    }
+
+    /// Runs `parse` one level deeper, failing instead if that is more than
+    /// [`MAX_NESTING_DEPTH`] levels.
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, LoxParseError>,
+    ) -> Result<T, LoxParseError> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(self.too_deeply_nested());
+        }
+        self.depth += 1;
+        self.deepest = self.deepest.max(self.depth);
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Parses a left-associative chain, such as `a + b - c` or `a.b[c]()`, with `parse`. Each
+    /// link, counted with [`Parser::link`], puts everything before it one level deeper in the
+    /// tree, so the links count against [`MAX_NESTING_DEPTH`] on top of the deepest level the
+    /// chain's operands reach.
+    fn chain(
+        &mut self,
+        parse: impl FnOnce(&mut Self, &mut usize) -> Result<Expr, LoxParseError>,
+    ) -> Result<Expr, LoxParseError> {
+        let outer_deepest = mem::replace(&mut self.deepest, self.depth);
+        let mut links = 0;
+        let result = parse(self, &mut links);
+        let deepest = self.deepest + links;
+        self.deepest = outer_deepest.max(deepest);
+        match result {
+            Ok(_) if deepest > MAX_NESTING_DEPTH => Err(self.too_deeply_nested()),
+            result => result,
+        }
+    }
+
+    /// Counts another link of the chain being parsed, failing before it's built if the chain
+    /// already nests as deeply as allowed.
+    fn link(&mut self, links: &mut usize) -> Result<(), LoxParseError> {
+        if self.deepest + *links >= MAX_NESTING_DEPTH {
+            return Err(self.too_deeply_nested());
+        }
+        *links += 1;
+        Ok(())
+    }
+
+    fn too_deeply_nested(&mut self) -> LoxParseError {
+        let token = self.peek().clone();
+        self.parse_error(
+            ErrorCode::TOO_DEEPLY_NESTED,
+            &token,
+            &format!("at '{}'", token.lexeme),
+            "Too much nesting.",
+        );
+        LoxParseError
+    }
+
+    /// Records a warning, which doesn't stop the tokens from parsing.
+    fn warn(&mut self, code: ErrorCode, token: &Token, message: &str) {
+        self.record(Diagnostic::at_token(code, token, "", message));
+    }
+
+    /// Parses the parenthesized condition of an `if` or `while`, which has already had its `(`
+    /// consumed.
+    fn condition(&mut self, message: &str) -> Result<Expr, LoxParseError> {
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, message)?;
+        // Extra parentheses mark the assignment as intended.
+        if let Expr::Assign(ref assign) = condition {
+            self.warn(
+                ErrorCode::ASSIGNMENT_AS_CONDITION,
+                &assign.name,
+                "Assignment used as a condition; did you mean '=='?",
+            );
+        }
+        Ok(condition)
+    }
+
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, LoxParseError> {
         let mut statements = Vec::new();
         while !self.is_at_end() {
             if let Some(stmt) = self.declaration() {
@@ -42,13 +264,15 @@ impl<'src> Parser<'src> {
             }
         }
 
-        match self.had_error {
+        match has_errors(&self.diagnostics) {
             true => Err(LoxParseError),
             false => Ok(statements),
         }
     }
 
-    fn declaration(&mut self) -> Option<Stmt<'src>> {
+    fn declaration(&mut self) -> Option<Stmt> {
+        // Doc comments attach to the keyword, or decorator, starting a declaration.
+        let doc = self.peek().doc.clone();
         let res = match self.peek_token_type() {
             TokenType::Var => {
                 // Consume the Var token.
@@ -59,51 +283,114 @@ impl<'src> Parser<'src> {
                 // Consume the Fun token.
                 self.advance();
                 match self.check(&TokenType::Identifier) {
-                    true => self.function("function"),
+                    true => self.function("function", Vec::new(), doc),
                     false => self.closure_statement(),
                 }
             }
+            TokenType::At => self.decorated_function(doc),
             TokenType::Class => {
                 // Consume the Class token.
                 self.advance();
-                self.class_declaration()
+                self.class_declaration(doc)
             }
             _ => self.statement(),
         };
         match res {
             Ok(stmt) => Some(stmt),
+            // The error has already been recorded where it was found.
             Err(_) => {
-                self.had_error = true;
                 self.synchronize();
                 None
             }
         }
     }
 
-    fn class_declaration(&mut self) -> Result<Stmt<'src>, LoxParseError> {
+    fn class_declaration(&mut self, doc: Option<Rc<str>>) -> Result<Stmt, LoxParseError> {
+        let start = self.previous_span;
         let name = self.consume(TokenType::Identifier, "Expect class name.")?;
 
         let mut superclass = None;
-        if let Some(_) = self.match_token_type(&[TokenType::Less]) {
+        if self.match_token_type(&[TokenType::Less]).is_some() {
             let superclass_name = self.consume(TokenType::Identifier, "Expect superclass name.")?;
             superclass = Some(Box::new(Expr::Variable(Variable::new(superclass_name))));
         }
 
         self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
 
+        let nesting = self.nesting;
         let mut methods = Vec::new();
+        let mut brace_consumed = false;
         while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
-            methods.push(self.function("method")?);
+            let method_doc = self.peek().doc.clone();
+            match self.function("method", Vec::new(), method_doc) {
+                Ok(method) => methods.push(method),
+                // Carry on with the next method rather than the statement after the class.
+                Err(_)
+                    if self.skip_to(
+                        nesting,
+                        &[TokenType::Identifier, TokenType::RightBrace],
+                        &STATEMENT_KEYWORDS,
+                    ) => {}
+                // A statement where a method should start means the class's closing brace went
+                // by uncounted, such as after a method header that left a parenthesis open.
+                // End the class there, so the statement is parsed rather than skipped.
+                Err(_) if STATEMENT_KEYWORDS.contains(&self.peek_token_type()) => {
+                    brace_consumed = true;
+                    break;
+                }
+                Err(error) => return Err(error),
+            }
         }
 
-        self.consume(TokenType::RightBrace, "Except '}' after class body.")?;
+        if !brace_consumed {
+            self.consume(TokenType::RightBrace, "Except '}' after class body.")?;
+        }
 
-        Ok(Stmt::Class(Class::new(name, superclass, methods)))
+        Ok(Stmt::Class(Class::new(
+            name,
+            superclass,
+            methods,
+            doc,
+            self.span_from(start),
+        )))
     }
 
-    fn var_declaration(&mut self) -> Result<Stmt<'src>, LoxParseError> {
+    fn var_declaration(&mut self) -> Result<Stmt, LoxParseError> {
+        let start = self.previous_span;
+        if self.match_token_type(&[TokenType::LeftParen]).is_some() {
+            return self.destructure_declaration(start);
+        }
         let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
+        self.finish_var_declaration(start, name)
+    }
 
+    fn destructure_declaration(&mut self, start: Span) -> Result<Stmt, LoxParseError> {
+        let mut names = vec![self.consume(TokenType::Identifier, "Expect variable name.")?];
+        while self.match_token_type(&[TokenType::Comma]).is_some() {
+            names.push(self.consume(TokenType::Identifier, "Expect variable name.")?);
+        }
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after destructured variable names.",
+        )?;
+        self.consume(
+            TokenType::Equal,
+            "Expect '=' after destructured variable names.",
+        )?;
+        let initializer = self.expression()?;
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
+
+        Ok(Stmt::Destructure(Destructure::new(
+            names,
+            initializer,
+            self.span_from(start),
+        )))
+    }
+
+    fn finish_var_declaration(&mut self, start: Span, name: Token) -> Result<Stmt, LoxParseError> {
         let mut initializer = None;
         if self.check(&TokenType::Equal) {
             // Consume the Equal token.
@@ -115,17 +402,45 @@ impl<'src> Parser<'src> {
             TokenType::Semicolon,
             "Expect ';' after variable declaration.",
         )?;
-        Ok(Stmt::Var(Var::new(name, initializer)))
+        Ok(Stmt::Var(Var::new(
+            name,
+            initializer,
+            self.span_from(start),
+        )))
     }
 
-    fn function(&mut self, kind: &str) -> Result<Stmt<'src>, LoxParseError> {
-        let name = self.consume(TokenType::Identifier, &format!("Expect {kind} name."))?;
-        let closure = self.closure(kind)?;
+    fn decorated_function(&mut self, doc: Option<Rc<str>>) -> Result<Stmt, LoxParseError> {
+        let mut decorators = Vec::new();
+        while self.match_token_type(&[TokenType::At]).is_some() {
+            decorators.push(self.call()?);
+        }
+        self.consume(
+            TokenType::Fun,
+            "Expect function declaration after decorator.",
+        )?;
+        self.function("function", decorators, doc)
+    }
 
-        Ok(Stmt::Function(Function::new(name, closure)))
+    fn function(
+        &mut self,
+        kind: &str,
+        decorators: Vec<Expr>,
+        doc: Option<Rc<str>>,
+    ) -> Result<Stmt, LoxParseError> {
+        let start = self.peek_span();
+        let name = self.consume(TokenType::Identifier, &format!("Expect {kind} name."))?;
+        let closure = self.closure(kind, doc)?;
+
+        Ok(Stmt::Function(Function::new(
+            name,
+            closure,
+            decorators,
+            self.span_from(start),
+        )))
     }
 
-    fn closure(&mut self, kind: &str) -> Result<Closure<'src>, LoxParseError> {
+    fn closure(&mut self, kind: &str, doc: Option<Rc<str>>) -> Result<Rc<Closure>, LoxParseError> {
+        let start = self.peek_span();
         self.consume(
             TokenType::LeftParen,
             &format!("Expect '(' after {kind} start."),
@@ -133,11 +448,11 @@ impl<'src> Parser<'src> {
         let mut params = Vec::new();
         if !self.check(&TokenType::RightParen) {
             params.push(self.consume(TokenType::Identifier, "Expect parameter name.")?);
-            while self.check(&TokenType::Comma) {
-                let comma_token = self.advance().unwrap();
+            while let Some(comma_token) = self.match_token_type(&[TokenType::Comma]) {
                 if params.len() >= 255 {
                     self.parse_error(
-                        comma_token.line,
+                        ErrorCode::TOO_MANY_PARAMETERS,
+                        &comma_token,
                         &format!("at '{}'", comma_token.lexeme),
                         "Can't have more than 255 parameters",
                     );
@@ -152,124 +467,168 @@ impl<'src> Parser<'src> {
             &format!("Expect '{{' before {kind} body."),
         )?;
 
-        let body = self.block()?;
+        let body = self.nested(Self::block)?;
 
-        Ok(Closure::new(params, body))
+        Ok(Rc::new(Closure::new(
+            params,
+            body,
+            doc,
+            self.span_from(start),
+        )))
     }
 
-    fn statement(&mut self) -> Result<Stmt<'src>, LoxParseError> {
-        let token_types = [
-            TokenType::Print,
-            TokenType::LeftBrace,
-            TokenType::If,
-            TokenType::While,
-            TokenType::For,
-            TokenType::Break,
-            TokenType::Return,
-        ];
-        if let Some(statement_token) = self.match_token_type(&token_types) {
-            match statement_token.token_type {
-                TokenType::Print => self.print_statement(),
-                TokenType::LeftBrace => Ok(Stmt::Block(Block::new(self.block()?))),
-                TokenType::If => self.if_statement(),
-                TokenType::While => self.while_statement(),
-                TokenType::For => self.for_statement(),
-                TokenType::Break => self.break_statement(),
-                TokenType::Return => self.return_statement(statement_token),
-                _ => unreachable!("Above match_token_type guarentees that no other token types are possible here."),
+    fn statement(&mut self) -> Result<Stmt, LoxParseError> {
+        let statement_token = self.peek().clone();
+        let parse: fn(&mut Self) -> Result<Stmt, LoxParseError> = match statement_token.token_type {
+            TokenType::Print => Self::print_statement,
+            TokenType::If => Self::if_statement,
+            TokenType::While => Self::while_statement,
+            TokenType::For => Self::for_statement,
+            TokenType::Break => Self::break_statement,
+            TokenType::LeftBrace => {
+                // Consume the LeftBrace token.
+                self.advance();
+                let statements = self.nested(Self::block)?;
+                return Ok(Stmt::Block(Block::new(
+                    statements,
+                    self.span_from(statement_token.span()),
+                )));
             }
-        } else {
-            self.expression_statement()
-        }
+            TokenType::Return => {
+                // Consume the Return token.
+                self.advance();
+                return self.return_statement(statement_token);
+            }
+            _ => return self.expression_statement(),
+        };
+        // Consume the keyword starting the statement.
+        self.advance();
+        parse(self)
     }
 
-    fn for_statement(&mut self) -> Result<Stmt<'src>, LoxParseError> {
+    fn for_statement(&mut self) -> Result<Stmt, LoxParseError> {
+        let start = self.previous_span;
         self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
 
-        let initializer_option = if self.check(&TokenType::Semicolon) {
+        let initializer = if self.check(&TokenType::Semicolon) {
             // Consume Semicolon token.
             self.advance();
             None
         } else if self.check(&TokenType::Var) {
             // Consume Var token.
             self.advance();
-            Some(self.var_declaration()?)
+            let var_start = self.previous_span;
+            let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
+            if self.match_token_type(&[TokenType::In]).is_some() {
+                return self.for_in_statement(start, name);
+            }
+            Some(self.finish_var_declaration(var_start, name)?)
         } else {
             Some(self.expression_statement()?)
         };
 
         let condition = match self.check(&TokenType::Semicolon) {
-            true => Expr::Literal(Literal::new(LoxLiteral::Boolean(true))),
-            false => self.expression()?,
+            true => None,
+            false => Some(self.expression()?),
         };
         self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
 
-        let increment_option = match self.check(&TokenType::RightParen) {
+        let increment = match self.check(&TokenType::RightParen) {
             true => None,
             false => Some(self.expression()?),
         };
         self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
 
         self.loop_level += 1;
-        let mut body = self.statement()?;
+        let body = self.nested(Self::statement)?;
         self.loop_level -= 1;
 
-        if let Some(increment) = increment_option {
-            body = Stmt::Block(Block::new(vec![
-                body,
-                Stmt::Expression(Expression::new(increment)),
-            ]));
-        }
+        Ok(Stmt::For(For::new(
+            initializer.map(Box::new),
+            condition,
+            increment,
+            Box::new(body),
+            self.span_from(start),
+        )))
+    }
 
-        body = Stmt::While(While::new(condition, Box::new(body)));
+    fn for_in_statement(&mut self, start: Span, name: Token) -> Result<Stmt, LoxParseError> {
+        let iterable = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after for-in clause.")?;
 
-        if let Some(initializer) = initializer_option {
-            body = Stmt::Block(Block::new(vec![initializer, body]));
-        }
+        self.loop_level += 1;
+        let body = Box::new(self.nested(Self::statement)?);
+        self.loop_level -= 1;
 
-        Ok(body)
+        Ok(Stmt::ForIn(ForIn::new(
+            name,
+            iterable,
+            body,
+            self.span_from(start),
+        )))
     }
 
-    fn while_statement(&mut self) -> Result<Stmt<'src>, LoxParseError> {
+    fn while_statement(&mut self) -> Result<Stmt, LoxParseError> {
+        let start = self.previous_span;
         self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
-        let condition = self.expression()?;
-        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+        let condition = self.condition("Expect ')' after condition.")?;
 
         self.loop_level += 1;
-        let body = Box::new(self.statement()?);
+        let body = Box::new(self.nested(Self::statement)?);
         self.loop_level -= 1;
 
-        Ok(Stmt::While(While::new(condition, body)))
+        Ok(Stmt::While(While::new(
+            condition,
+            body,
+            self.span_from(start),
+        )))
     }
 
-    fn break_statement(&mut self) -> Result<Stmt<'src>, LoxParseError> {
+    fn break_statement(&mut self) -> Result<Stmt, LoxParseError> {
+        let start = self.previous_span;
         let stmt_end = self.consume(TokenType::Semicolon, "Expect ';' after 'break' statement.")?;
         if self.loop_level == 0 {
             self.parse_error(
-                stmt_end.line,
+                ErrorCode::BREAK_OUTSIDE_LOOP,
+                &stmt_end,
                 "at 'break;'",
                 "A 'break;' cannot appear outside of any enclosing loop.",
             );
         }
-        Ok(Stmt::Break)
+        Ok(Stmt::Break(Break::new(self.span_from(start))))
     }
 
-    fn print_statement(&mut self) -> Result<Stmt<'src>, LoxParseError> {
+    fn print_statement(&mut self) -> Result<Stmt, LoxParseError> {
+        let start = self.previous_span;
         let value = self.expression()?;
         self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
-        Ok(Stmt::Print(Print::new(value)))
+        Ok(Stmt::Print(Print::new(value, self.span_from(start))))
     }
 
-    fn return_statement(&mut self, keyword: Token<'src>) -> Result<Stmt<'src>, LoxParseError> {
-        let mut value = Expr::Literal(Literal::new(LoxLiteral::Nil));
+    fn return_statement(&mut self, keyword: Token) -> Result<Stmt, LoxParseError> {
+        let start = keyword.span();
+        let mut value = Expr::Literal(Literal::new(LoxLiteral::Nil, start));
         if !self.check(&TokenType::Semicolon) {
-            value = self.expression()?;
+            value = self.assignment()?;
+            // Returning several comma separated values returns them together as a list.
+            if self.check(&TokenType::Comma) {
+                let mut values = vec![value];
+                while self.match_token_type(&[TokenType::Comma]).is_some() {
+                    values.push(self.assignment()?);
+                }
+                let span = values[0].span().to(self.previous_span);
+                value = Expr::List(List::new(values, span));
+            }
         }
         self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
-        Ok(Stmt::Return(Return::new(keyword, value)))
+        Ok(Stmt::Return(Return::new(
+            keyword,
+            value,
+            self.span_from(start),
+        )))
     }
 
-    fn block(&mut self) -> Result<Vec<Stmt<'src>>, LoxParseError> {
+    fn block(&mut self) -> Result<Vec<Stmt>, LoxParseError> {
         let mut statements = Vec::new();
 
         while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
@@ -282,56 +641,72 @@ impl<'src> Parser<'src> {
         Ok(statements)
     }
 
-    fn if_statement(&mut self) -> Result<Stmt<'src>, LoxParseError> {
+    fn if_statement(&mut self) -> Result<Stmt, LoxParseError> {
+        let start = self.previous_span;
         self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
-        let condition = self.expression()?;
-        self.consume(TokenType::RightParen, "Expect ')' after 'if' condition.")?;
+        let condition = self.condition("Expect ')' after 'if' condition.")?;
 
-        let then_branch = Box::new(self.statement()?);
+        let then_branch = Box::new(self.nested(Self::statement)?);
         let else_branch = match self.check(&TokenType::Else) {
             true => {
                 // Consume the Else token.
                 self.advance();
-                Some(Box::new(self.statement()?))
+                Some(Box::new(self.nested(Self::statement)?))
             }
             false => None,
         };
-        Ok(Stmt::If(If::new(condition, then_branch, else_branch)))
+        Ok(Stmt::If(If::new(
+            condition,
+            then_branch,
+            else_branch,
+            self.span_from(start),
+        )))
     }
 
-    fn expression_statement(&mut self) -> Result<Stmt<'src>, LoxParseError> {
+    fn expression_statement(&mut self) -> Result<Stmt, LoxParseError> {
         let expr = self.expression()?;
+        if self.repl_mode && self.is_at_end() {
+            let span = self.span_from(expr.span());
+            return Ok(Stmt::Print(Print::echo(expr, span)));
+        }
         self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
-        Ok(Stmt::Expression(Expression::new(expr)))
+        let span = self.span_from(expr.span());
+        Ok(Stmt::Expression(Expression::new(expr, span)))
     }
 
-    fn closure_statement(&mut self) -> Result<Stmt<'src>, LoxParseError> {
-        let closure = Expr::Closure(self.closure("closure")?);
+    fn closure_statement(&mut self) -> Result<Stmt, LoxParseError> {
+        let start = self.previous_span;
+        let closure = Expr::Closure(self.closure("closure", None)?);
         self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
-        Ok(Stmt::Expression(Expression::new(closure)))
+        Ok(Stmt::Expression(Expression::new(
+            closure,
+            self.span_from(start),
+        )))
     }
 
-    fn expression(&mut self) -> Result<Expr<'src>, LoxParseError> {
-        self.comma()
+    fn expression(&mut self) -> Result<Expr, LoxParseError> {
+        self.nested(Self::comma)
     }
 
-    fn comma(&mut self) -> Result<Expr<'src>, LoxParseError> {
-        let mut expr = self.assignment()?;
+    fn comma(&mut self) -> Result<Expr, LoxParseError> {
+        self.chain(|parser, links| {
+            let mut expr = parser.assignment()?;
 
-        while let Some(operator) = self.match_token_type(&[TokenType::Comma]) {
-            let right = self.assignment()?;
-            expr = Expr::Binary(Binary::new(Box::new(expr), operator, Box::new(right)));
-        }
+            while let Some(operator) = parser.match_token_type(&[TokenType::Comma]) {
+                parser.link(links)?;
+                let right = parser.assignment()?;
+                expr = Expr::Binary(Binary::new(Box::new(expr), operator, Box::new(right)));
+            }
 
-        Ok(expr)
+            Ok(expr)
+        })
     }
 
-    fn assignment(&mut self) -> Result<Expr<'src>, LoxParseError> {
+    fn assignment(&mut self) -> Result<Expr, LoxParseError> {
         let mut expr = self.closure_expression()?;
 
-        if self.check(&TokenType::Equal) {
-            let equals = self.advance().unwrap();
-            let value = Box::new(self.assignment()?);
+        if let Some(equals) = self.match_token_type(&[TokenType::Equal]) {
+            let value = Box::new(self.nested(Self::assignment)?);
             match expr {
                 Expr::Variable(variable) => {
                     expr = Expr::Assign(Assign::new(variable.name, value));
@@ -339,8 +714,12 @@ impl<'src> Parser<'src> {
                 Expr::Get(get) => {
                     expr = Expr::Set(Set::new(get.object, get.name, value));
                 }
+                Expr::Index(index) => {
+                    expr = Expr::SetIndex(SetIndex::new(index.object, index.index, value));
+                }
                 _ => self.parse_error(
-                    equals.line,
+                    ErrorCode::INVALID_ASSIGNMENT_TARGET,
+                    &equals,
                     &format!("at '{}'", equals.lexeme),
                     "Invalid assignment target.",
                 ),
@@ -350,278 +729,342 @@ impl<'src> Parser<'src> {
         Ok(expr)
     }
 
-    fn closure_expression(&mut self) -> Result<Expr<'src>, LoxParseError> {
+    fn closure_expression(&mut self) -> Result<Expr, LoxParseError> {
         if self.check(&TokenType::Fun) {
             // Consume the Fun token
             self.advance();
-            Ok(Expr::Closure(self.closure("closure")?))
+            Ok(Expr::Closure(self.closure("closure", None)?))
         } else {
             self.ternary()
         }
     }
 
-    fn ternary(&mut self) -> Result<Expr<'src>, LoxParseError> {
+    fn ternary(&mut self) -> Result<Expr, LoxParseError> {
         let mut expr = self.or()?;
 
         if self.check(&TokenType::QuestionMark) {
             // Consume the QuestionMark Token.
             self.advance();
-            let left = Box::new(self.ternary()?);
+            let left = Box::new(self.nested(Self::ternary)?);
             self.consume(
                 TokenType::Colon,
                 "Expect ':' to separate two expressions after '?'",
             )?;
-            let right = Box::new(self.ternary()?);
+            let right = Box::new(self.nested(Self::ternary)?);
             expr = Expr::Ternary(Ternary::new(Box::new(expr), left, right));
         }
 
         Ok(expr)
     }
 
-    fn or(&mut self) -> Result<Expr<'src>, LoxParseError> {
-        let mut expr = self.and()?;
+    fn or(&mut self) -> Result<Expr, LoxParseError> {
+        self.chain(|parser, links| {
+            let mut expr = parser.and()?;
 
-        while let Some(operator) = self.match_token_type(&[TokenType::Or]) {
-            let right = Box::new(self.and()?);
-            expr = Expr::Logical(Logical::new(Box::new(expr), operator, right));
-        }
+            while let Some(operator) = parser.match_token_type(&[TokenType::Or]) {
+                parser.link(links)?;
+                let right = Box::new(parser.and()?);
+                expr = Expr::Logical(Logical::new(Box::new(expr), operator, right));
+            }
 
-        Ok(expr)
+            Ok(expr)
+        })
     }
 
-    fn and(&mut self) -> Result<Expr<'src>, LoxParseError> {
-        let mut expr = self.equality()?;
+    fn and(&mut self) -> Result<Expr, LoxParseError> {
+        self.chain(|parser, links| {
+            let mut expr = parser.equality()?;
 
-        while let Some(operator) = self.match_token_type(&[TokenType::And]) {
-            let right = Box::new(self.equality()?);
-            expr = Expr::Logical(Logical::new(Box::new(expr), operator, right));
-        }
+            while let Some(operator) = parser.match_token_type(&[TokenType::And]) {
+                parser.link(links)?;
+                let right = Box::new(parser.equality()?);
+                expr = Expr::Logical(Logical::new(Box::new(expr), operator, right));
+            }
 
-        Ok(expr)
+            Ok(expr)
+        })
     }
 
-    fn equality(&mut self) -> Result<Expr<'src>, LoxParseError> {
-        let mut expr = self.comparison()?;
-
+    fn equality(&mut self) -> Result<Expr, LoxParseError> {
         let token_types = [TokenType::BangEqual, TokenType::EqualEqual];
-        while let Some(operator) = self.match_token_type(&token_types) {
-            let right = Box::new(self.comparison()?);
-            expr = Expr::Binary(Binary::new(Box::new(expr), operator, right));
-        }
-
-        Ok(expr)
+        self.binary(&token_types, Self::comparison)
     }
 
-    fn comparison(&mut self) -> Result<Expr<'src>, LoxParseError> {
-        let mut expr = self.term()?;
-
+    fn comparison(&mut self) -> Result<Expr, LoxParseError> {
         let token_types = [
             TokenType::Greater,
             TokenType::GreaterEqual,
             TokenType::Less,
             TokenType::LessEqual,
         ];
-        while let Some(operator) = self.match_token_type(&token_types) {
-            let right = Box::new(self.term()?);
-            expr = Expr::Binary(Binary::new(Box::new(expr), operator, right));
-        }
-
-        Ok(expr)
+        self.binary(&token_types, Self::term)
     }
 
-    fn term(&mut self) -> Result<Expr<'src>, LoxParseError> {
-        let mut expr = self.factor()?;
-
-        let token_types = [TokenType::Minus, TokenType::Plus];
-        while let Some(operator) = self.match_token_type(&token_types) {
-            let right = Box::new(self.factor()?);
-            expr = Expr::Binary(Binary::new(Box::new(expr), operator, right));
-        }
-
-        Ok(expr)
+    fn term(&mut self) -> Result<Expr, LoxParseError> {
+        self.binary(&[TokenType::Minus, TokenType::Plus], Self::factor)
     }
 
-    fn factor(&mut self) -> Result<Expr<'src>, LoxParseError> {
-        let mut expr = self.binary_operator_error()?;
+    fn factor(&mut self) -> Result<Expr, LoxParseError> {
+        self.binary(
+            &[TokenType::Slash, TokenType::Star],
+            Self::binary_operator_error,
+        )
+    }
 
-        let token_types = [TokenType::Slash, TokenType::Star];
-        while let Some(operator) = self.match_token_type(&token_types) {
-            let right = Box::new(self.binary_operator_error()?);
-            expr = Expr::Binary(Binary::new(Box::new(expr), operator, right));
-        }
+    /// Parses a chain of `operand`s joined by any of the binary `token_types`.
+    fn binary(
+        &mut self,
+        token_types: &[TokenType],
+        operand: fn(&mut Self) -> Result<Expr, LoxParseError>,
+    ) -> Result<Expr, LoxParseError> {
+        self.chain(|parser, links| {
+            let mut expr = operand(parser)?;
+
+            while let Some(operator) = parser.match_token_type(token_types) {
+                parser.link(links)?;
+                let right = Box::new(operand(parser)?);
+                expr = Expr::Binary(Binary::new(Box::new(expr), operator, right));
+            }
 
-        Ok(expr)
+            Ok(expr)
+        })
     }
 
-    fn binary_operator_error(&mut self) -> Result<Expr<'src>, LoxParseError> {
-        let token_types = [
-            TokenType::Comma,
-            TokenType::BangEqual,
-            TokenType::EqualEqual,
-            TokenType::Greater,
-            TokenType::GreaterEqual,
-            TokenType::Less,
-            TokenType::LessEqual,
-            TokenType::Plus,
-            TokenType::Slash,
-            TokenType::Star,
-        ];
-        if let Some(operator) = self.match_token_type(&token_types) {
-            // Consume expression after invalid binary operator.
-            let _ = match operator.token_type {
-                TokenType::Comma => self.equality(),
-                TokenType::BangEqual | TokenType::EqualEqual => self.comparison(),
-                TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => self.term(),
-                TokenType::Plus => self.factor(),
-                TokenType::Slash | TokenType::Star => self.unary(),
-                _ => unreachable!("Above match_token_type guarentees that no other token types are possible here."),
-            };
-            self.parse_error(
-                operator.line,
-                &format!("at '{}'", operator.lexeme),
-                "Invalid use of binary operator, must be preceded by an expression.",
-            );
-            Err(LoxParseError)
-        } else {
-            self.unary()
-        }
+    fn binary_operator_error(&mut self) -> Result<Expr, LoxParseError> {
+        let operator = self.peek().clone();
+        let right_operand: fn(&mut Self) -> Result<Expr, LoxParseError> = match operator.token_type
+        {
+            TokenType::Comma => Self::equality,
+            TokenType::BangEqual | TokenType::EqualEqual => Self::comparison,
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => Self::term,
+            TokenType::Plus => Self::factor,
+            TokenType::Slash | TokenType::Star => Self::unary,
+            _ => return self.unary(),
+        };
+        // Consume the invalid binary operator and the expression after it.
+        self.advance();
+        let _ = self.nested(right_operand);
+        self.parse_error(
+            ErrorCode::MISSING_LEFT_OPERAND,
+            &operator,
+            &format!("at '{}'", operator.lexeme),
+            "Invalid use of binary operator, must be preceded by an expression.",
+        );
+        Err(LoxParseError)
     }
 
-    fn unary(&mut self) -> Result<Expr<'src>, LoxParseError> {
+    fn unary(&mut self) -> Result<Expr, LoxParseError> {
         let token_types = [TokenType::Bang, TokenType::Minus];
         if let Some(operator) = self.match_token_type(&token_types) {
-            let right = Box::new(self.unary()?);
+            let right = Box::new(self.nested(Self::unary)?);
             return Ok(Expr::Unary(Unary::new(operator, right)));
         }
         self.call()
     }
 
-    fn call(&mut self) -> Result<Expr<'src>, LoxParseError> {
-        let mut expr = self.primary()?;
-
-        loop {
-            match self.peek_token_type() {
-                TokenType::LeftParen => {
-                    // Consume LeftParen token.
-                    self.advance();
-                    expr = self.finish_call(expr)?;
-                }
-                TokenType::Dot => {
-                    // Consume Dot token.
-                    self.advance();
-                    let name =
-                        self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
-                    expr = Expr::Get(Get::new(Box::new(expr), name));
+    fn call(&mut self) -> Result<Expr, LoxParseError> {
+        self.chain(|parser, links| {
+            let mut expr = parser.primary()?;
+
+            loop {
+                match parser.peek_token_type() {
+                    TokenType::LeftParen => {
+                        parser.link(links)?;
+                        // Consume LeftParen token.
+                        parser.advance();
+                        expr = parser.finish_call(expr)?;
+                    }
+                    TokenType::Dot => {
+                        parser.link(links)?;
+                        // Consume Dot token.
+                        parser.advance();
+                        let name = parser
+                            .consume(TokenType::Identifier, "Expect property name after '.'.")?;
+                        expr = Expr::Get(Get::new(Box::new(expr), name));
+                    }
+                    TokenType::LeftBracket => {
+                        parser.link(links)?;
+                        // Consume LeftBracket token.
+                        parser.advance();
+                        let index = Box::new(parser.expression()?);
+                        parser.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+                        let span = parser.span_from(expr.span());
+                        expr = Expr::Index(Index::new(Box::new(expr), index, span));
+                    }
+                    _ => break,
                 }
-                _ => break,
             }
-        }
 
-        Ok(expr)
+            Ok(expr)
+        })
     }
 
-    fn finish_call(&mut self, callee: Expr<'src>) -> Result<Expr<'src>, LoxParseError> {
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, LoxParseError> {
+        let nesting = self.nesting;
         let mut arguments = Vec::new();
 
         if !self.check(&TokenType::RightParen) {
-            arguments.push(self.assignment()?);
-            while self.check(&TokenType::Comma) {
-                let comma_token = self.advance().unwrap();
+            self.argument(nesting, &mut arguments)?;
+            while let Some(comma_token) = self.match_token_type(&[TokenType::Comma]) {
                 if arguments.len() >= 255 {
                     self.parse_error(
-                        comma_token.line,
+                        ErrorCode::TOO_MANY_ARGUMENTS,
+                        &comma_token,
                         &format!("at '{}'", comma_token.lexeme),
                         "Can't have more than 255 arguments",
                     );
                 }
-                arguments.push(self.assignment()?);
+                self.argument(nesting, &mut arguments)?;
             }
         }
 
-        let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+        let paren = match self.consume(TokenType::RightParen, "Expect ')' after arguments.") {
+            Ok(paren) => paren,
+            // Skipping to the ')' means this can't fail again.
+            Err(_) if self.skip_to(nesting, &[TokenType::RightParen], &[TokenType::Semicolon]) => {
+                self.consume(TokenType::RightParen, "Expect ')' after arguments.")?
+            }
+            Err(error) => return Err(error),
+        };
 
         Ok(Expr::Call(Call::new(Box::new(callee), paren, arguments)))
     }
 
-    fn primary(&mut self) -> Result<Expr<'src>, LoxParseError> {
-        let literal_token_types = [
-            TokenType::False,
-            TokenType::True,
-            TokenType::Nil,
-            TokenType::Number,
-            TokenType::String,
-        ];
-        if let Some(token) = self.match_token_type(&literal_token_types) {
-            return Ok(Expr::Literal(Literal::new(token.literal.unwrap())));
-        }
-
-        let other_primary_token_types = [
-            TokenType::Identifier,
-            TokenType::This,
-            TokenType::LeftParen,
-            TokenType::Super,
-        ];
-        if let Some(token) = self.match_token_type(&other_primary_token_types) {
-            let expr = match token.token_type {
-                TokenType::Identifier => Expr::Variable(Variable::new(token)),
-                TokenType::This => Expr::This(This::new(token)),
-                TokenType::LeftParen => {
-                    let expr = Box::new(self.expression()?);
-                    self.consume(TokenType::RightParen, "Expect ')' after expression")?;
-                    Expr::Grouping(Grouping::new(expr))
-                }
-                TokenType::Super => {
-                    self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
-                    let method =
-                        self.consume(TokenType::Identifier, "Expect superclass method name.")?;
-                    Expr::Super(Super::new(token, method))
+    /// Parses an argument of a call whose parentheses are at `nesting`. A malformed argument is
+    /// skipped so the ones after it are still checked, unless the argument list doesn't end.
+    fn argument(&mut self, nesting: usize, arguments: &mut Vec<Expr>) -> Result<(), LoxParseError> {
+        match self.nested(Self::assignment) {
+            Ok(argument) => arguments.push(argument),
+            Err(error) => {
+                let stops = [TokenType::Comma, TokenType::RightParen];
+                if !self.skip_to(nesting, &stops, &[TokenType::Semicolon]) {
+                    return Err(error);
                 }
-                _ => unreachable!(),
-            };
+            }
+        }
+        Ok(())
+    }
 
-            Ok(expr)
-        } else {
-            // Will always be Some variant from peek since we never consume the last Eof token.
-            let next_token = self.token_iter.peek().unwrap();
-            let next_token_line = next_token.line;
-            let next_token_lexeme = next_token.lexeme;
+    fn primary(&mut self) -> Result<Expr, LoxParseError> {
+        let token = self.peek().clone();
+        let is_primary = matches!(
+            token.token_type,
+            TokenType::False
+                | TokenType::True
+                | TokenType::Nil
+                | TokenType::Number
+                | TokenType::String
+                | TokenType::Identifier
+                | TokenType::This
+                | TokenType::LeftParen
+                | TokenType::Super
+                | TokenType::LeftBracket
+        );
+        if !is_primary {
             self.parse_error(
-                next_token_line,
-                &format!("at '{}'", next_token_lexeme),
+                ErrorCode::EXPECTED_EXPRESSION,
+                &token,
+                &format!("at '{}'", token.lexeme),
                 "Failed to match a valid expression.",
             );
+            return Err(LoxParseError);
+        }
+        // Consume the token starting the expression.
+        self.advance();
+
+        let expr = match token.token_type {
+            TokenType::Identifier => Expr::Variable(Variable::new(token)),
+            TokenType::This => Expr::This(This::new(token)),
+            TokenType::LeftParen => {
+                let expr = Box::new(self.expression()?);
+                self.consume(TokenType::RightParen, "Expect ')' after expression")?;
+                Expr::Grouping(Grouping::new(expr, self.span_from(token.span())))
+            }
+            TokenType::Super => {
+                self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+                let method =
+                    self.consume(TokenType::Identifier, "Expect superclass method name.")?;
+                Expr::Super(Super::new(token, method))
+            }
+            TokenType::LeftBracket => self.nested(|parser| parser.list(token.span()))?,
+            // The scanner gives every literal token its value.
+            _ => {
+                let span = token.span();
+                Expr::Literal(Literal::new(token.literal.unwrap_or(LoxLiteral::Nil), span))
+            }
+        };
+
+        Ok(expr)
+    }
+
+    fn list(&mut self, start: Span) -> Result<Expr, LoxParseError> {
+        if self.match_token_type(&[TokenType::RightBracket]).is_some() {
+            return Ok(Expr::List(List::new(Vec::new(), self.span_from(start))));
+        }
 
-            Err(LoxParseError)
+        let first = self.assignment()?;
+        if self.match_token_type(&[TokenType::For]).is_some() {
+            return self.list_comprehension(start, first);
         }
+
+        let mut elements = vec![first];
+        while self.match_token_type(&[TokenType::Comma]).is_some() {
+            if self.check(&TokenType::RightBracket) {
+                break;
+            }
+            elements.push(self.assignment()?);
+        }
+        self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+
+        Ok(Expr::List(List::new(elements, self.span_from(start))))
     }
 
-    fn consume(
-        &mut self,
-        token_type: TokenType,
-        message: &str,
-    ) -> Result<Token<'src>, LoxParseError> {
-        match self.check(&token_type) {
-            // Will always be Some variant in true arm of this match.
-            true => Ok(self.advance().unwrap()),
-            false => {
-                // Will always be Some variant from peek since we never consume the last Eof token.
-                let next_token = self.token_iter.peek().unwrap();
-                let next_token_line = next_token.line;
-                let next_token_lexeme = next_token.lexeme;
-                match next_token.token_type {
-                    TokenType::Eof => self.parse_error(next_token_line, "at end", message),
-                    _ => self.parse_error(
-                        next_token_line,
-                        &format!("at '{}'", next_token_lexeme),
-                        message,
-                    ),
-                }
-                Err(LoxParseError)
+    fn list_comprehension(&mut self, start: Span, element: Expr) -> Result<Expr, LoxParseError> {
+        let name = self.consume(TokenType::Identifier, "Expect variable name after 'for'.")?;
+        self.consume(TokenType::In, "Expect 'in' after comprehension variable.")?;
+        let iterable = Box::new(self.assignment()?);
+
+        let condition = match self.match_token_type(&[TokenType::If]) {
+            Some(_) => Some(Box::new(self.assignment()?)),
+            None => None,
+        };
+        self.consume(
+            TokenType::RightBracket,
+            "Expect ']' after list comprehension.",
+        )?;
+
+        Ok(Expr::ListComprehension(ListComprehension::new(
+            Box::new(element),
+            name,
+            iterable,
+            condition,
+            self.span_from(start),
+        )))
+    }
+
+    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<Token, LoxParseError> {
+        if let Some(token) = self.match_token_type(&[token_type]) {
+            return Ok(token);
+        }
+        let next_token = self.peek().clone();
+        match next_token.token_type {
+            TokenType::Eof => {
+                self.parse_error(ErrorCode::EXPECTED_TOKEN, &next_token, "at end", message)
             }
+            _ => self.parse_error(
+                ErrorCode::EXPECTED_TOKEN,
+                &next_token,
+                &format!("at '{}'", next_token.lexeme),
+                message,
+            ),
         }
+        Err(LoxParseError)
     }
 
-    fn match_token_type(&mut self, token_types: &[TokenType]) -> Option<Token<'src>> {
+    fn match_token_type(&mut self, token_types: &[TokenType]) -> Option<Token> {
         for token_type in token_types {
             if self.check(token_type) {
                 return self.advance();
@@ -637,22 +1080,75 @@ impl<'src> Parser<'src> {
         }
     }
 
-    fn advance(&mut self) -> Option<Token<'src>> {
+    fn advance(&mut self) -> Option<Token> {
         match self.is_at_end() {
             true => None,
-            false => self.token_iter.next(),
+            false => {
+                let token = self.token_iter.next();
+                if let Some(ref token) = token {
+                    self.previous_span = token.span();
+                    match token.token_type {
+                        TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace => {
+                            self.nesting += 1
+                        }
+                        TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace => {
+                            self.nesting = self.nesting.saturating_sub(1)
+                        }
+                        _ => (),
+                    }
+                }
+                token
+            }
         }
     }
 
+    /// The next token, which is Eof at the end of the input.
+    fn peek(&mut self) -> &Token {
+        self.token_iter.peek().unwrap_or(&self.eof)
+    }
+
+    fn peek_span(&mut self) -> Span {
+        self.peek().span()
+    }
+
+    /// Span from `start` through the most recently consumed token.
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous_span)
+    }
+
     fn is_at_end(&mut self) -> bool {
         self.peek_token_type() == TokenType::Eof
     }
 
     fn peek_token_type(&mut self) -> TokenType {
-        self.token_iter
-            .peek()
-            .expect("Parser should never be able to consume Eof token and reach end of iteration.")
-            .token_type
+        self.peek().token_type
+    }
+
+    /// Recovers from an error inside a delimited list, such as a call's arguments, whose opening
+    /// delimiter left the parser at `nesting`. Skips tokens until the next of `stops` at that
+    /// nesting, and returns whether one was found. Gives up, leaving the rest to
+    /// [`Parser::synchronize`], at the end of the list, at the end of the input or at any of
+    /// `give_up` found at that nesting.
+    fn skip_to(&mut self, nesting: usize, stops: &[TokenType], give_up: &[TokenType]) -> bool {
+        loop {
+            let token_type = self.peek_token_type();
+            if self.nesting == nesting {
+                if stops.contains(&token_type) {
+                    return true;
+                }
+                let closes_list = matches!(
+                    token_type,
+                    TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace
+                );
+                if closes_list || give_up.contains(&token_type) {
+                    return false;
+                }
+            }
+            if token_type == TokenType::Eof {
+                return false;
+            }
+            self.advance();
+        }
     }
 
     fn synchronize(&mut self) {
@@ -661,19 +1157,81 @@ impl<'src> Parser<'src> {
                 break;
             }
 
-            match self.peek_token_type() {
-                TokenType::Class
-                | TokenType::Fun
-                | TokenType::Var
-                | TokenType::For
-                | TokenType::If
-                | TokenType::While
-                | TokenType::Print
-                | TokenType::Return => {
-                    break;
-                }
-                _ => (),
+            if STATEMENT_KEYWORDS.contains(&self.peek_token_type()) {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn too_deeply_nested(source: &str) -> bool {
+        match parse_str(source) {
+            Ok(_) => false,
+            Err(diagnostics) => diagnostics
+                .iter()
+                .any(|diagnostic| diagnostic.code == ErrorCode::TOO_DEEPLY_NESTED),
+        }
+    }
+
+    #[test]
+    fn long_chains_are_too_deeply_nested() {
+        let sources = [
+            format!("{}1;", "1+".repeat(300_000)),
+            format!("f{};", "()".repeat(300_000)),
+            format!("a{};", ".b".repeat(300_000)),
+            format!("a{};", "[0]".repeat(300_000)),
+            format!("{}1;", "1 and ".repeat(300_000)),
+        ];
+        for source in sources.iter() {
+            assert!(too_deeply_nested(source), "{}", &source[..20]);
+        }
+    }
+
+    #[test]
+    fn chains_count_on_top_of_their_operands() {
+        let nest = |levels: usize| {
+            let mut source = String::from("1");
+            for _ in 0..levels {
+                source = format!("({source}{})", "+1".repeat(60));
             }
+            source + ";"
+        };
+        assert!(!too_deeply_nested(&nest(2)));
+        assert!(too_deeply_nested(&nest(3)));
+        assert!(!too_deeply_nested(&format!("1{};", "+1".repeat(100))));
+    }
+
+    #[test]
+    fn recovering_from_errors_takes_linear_time() {
+        for unit in ["1 ? 1 : ", "if (1) print 1; else "] {
+            let start = std::time::Instant::now();
+            assert!(parse_str(&unit.repeat(32_000)).is_err());
+            assert!(
+                start.elapsed().as_secs() < 10,
+                "{unit:?} took {:?}",
+                start.elapsed()
+            );
         }
     }
+
+    #[test]
+    fn class_recovery_stops_at_the_statement_after_the_class() {
+        let source = "class A { m( { } n() { return 1; } } print 2 +; var ok = 1;";
+        let diagnostics = parse_str(source).unwrap_err();
+        let messages: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| format!("{} {}", diagnostic.location, diagnostic.message))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "at '{' Expect parameter name.",
+                "at ';' Failed to match a valid expression.",
+            ]
+        );
+    }
 }
@@ -0,0 +1,102 @@
+use crate::lox_callable::LoxCallable;
+use std::{
+    collections::HashMap,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+/// Calls and time spent in one function, as reported by `--profile`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileEntry {
+    /// The function's name, with the line it's declared on for functions written in Lox.
+    pub name: String,
+    pub calls: usize,
+    /// Time from entering the function to returning from it, counting calls it makes. Time in
+    /// recursive calls is only counted once, by the outermost call.
+    pub total: Duration,
+    /// Time spent in the function's own body, not counting calls it makes.
+    pub self_time: Duration,
+    /// Calls to the function in progress.
+    active: usize,
+}
+
+/// Collects call counts and times for each function the interpreter calls.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    entries: HashMap<usize, ProfileEntry>,
+    /// Time spent in the calls made by each call in progress, innermost last.
+    callee_time: Vec<Duration>,
+}
+
+impl Profiler {
+    /// Starts timing a call to `callable`, returning when it started.
+    pub fn enter(&mut self, callable: &LoxCallable) -> Instant {
+        let entry = self
+            .entries
+            .entry(key(callable))
+            .or_insert_with(|| ProfileEntry {
+                name: label(callable),
+                calls: 0,
+                total: Duration::ZERO,
+                self_time: Duration::ZERO,
+                active: 0,
+            });
+        entry.active += 1;
+        self.callee_time.push(Duration::ZERO);
+        Instant::now()
+    }
+
+    /// Finishes timing the call to `callable` that [`Profiler::enter`] said started at `start`.
+    pub fn exit(&mut self, callable: &LoxCallable, start: Instant) {
+        let elapsed = start.elapsed();
+        let callee_time = self.callee_time.pop().unwrap_or_default();
+        if let Some(caller_time) = self.callee_time.last_mut() {
+            *caller_time += elapsed;
+        }
+        let entry = self
+            .entries
+            .get_mut(&key(callable))
+            .expect("Expect a call to be entered before it exits.");
+        entry.calls += 1;
+        entry.self_time += elapsed.saturating_sub(callee_time);
+        entry.active -= 1;
+        if entry.active == 0 {
+            entry.total += elapsed;
+        }
+    }
+
+    /// Every function called so far, the one with the most self time first.
+    pub fn report(&self) -> Vec<ProfileEntry> {
+        let mut entries: Vec<ProfileEntry> = self.entries.values().cloned().collect();
+        entries.sort_by(|a, b| {
+            b.self_time
+                .cmp(&a.self_time)
+                .then(b.calls.cmp(&a.calls))
+                .then(a.name.cmp(&b.name))
+        });
+        entries
+    }
+}
+
+/// Identifies the function behind `callable`. Bound methods are new functions each time
+/// they're looked up, so functions written in Lox are told apart by their declaration.
+fn key(callable: &LoxCallable) -> usize {
+    match callable {
+        LoxCallable::Function(function) => Rc::as_ptr(function.declaration()) as *const () as usize,
+        LoxCallable::NativeFun(native_fun) => Rc::as_ptr(native_fun) as *const () as usize,
+        LoxCallable::Class(class) => Rc::as_ptr(class) as *const () as usize,
+        LoxCallable::Memoized(memoized) => Rc::as_ptr(memoized) as *const () as usize,
+    }
+}
+
+fn label(callable: &LoxCallable) -> String {
+    match callable {
+        LoxCallable::Function(function) => {
+            let line = function.declaration().span.line;
+            format!("{} (line {line})", function.name().unwrap_or("<fn>"))
+        }
+        LoxCallable::NativeFun(native_fun) => format!("{} (native)", native_fun.name()),
+        LoxCallable::Class(class) => format!("{} (class)", class.name),
+        LoxCallable::Memoized(memoized) => format!("{} (memoized)", label(memoized.function())),
+    }
+}
@@ -0,0 +1,82 @@
+use crate::{resolver::ResolverState, scanner::KEYWORDS};
+use rustyline::{
+    completion::Completer, highlight::Highlighter, hint::Hinter, validate::Validator, Context,
+    Helper,
+};
+use std::collections::BTreeSet;
+
+/// Line editor support for the interactive prompt.
+#[derive(Debug, Default)]
+pub struct ReplHelper {
+    globals: Vec<String>,
+    locals: Vec<String>,
+}
+
+impl ReplHelper {
+    pub fn new() -> Self {
+        ReplHelper::default()
+    }
+
+    /// Refreshes the global names offered for completion, e.g. after each evaluated line.
+    pub fn set_globals(&mut self, names: Vec<String>) {
+        self.globals = names;
+    }
+
+    /// Refreshes the local names offered for completion from the scopes the REPL's resolver
+    /// state still has open.
+    pub fn set_locals(&mut self, state: &ResolverState) {
+        self.locals = state
+            .scopes()
+            .into_iter()
+            .flatten()
+            .map(|variable| variable.name)
+            .collect();
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    /// Completes keywords, globals, and locals in the scopes the resolver has open.
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let before = &line[..pos];
+        let start = before
+            .char_indices()
+            .rev()
+            .take_while(|&(_, c)| is_identifier_char(c))
+            .last()
+            .map_or(pos, |(idx, _)| idx);
+        let prefix = &before[start..];
+        if prefix.is_empty() || prefix.starts_with(|c: char| c.is_ascii_digit()) {
+            return Ok((pos, Vec::new()));
+        }
+
+        let candidates: BTreeSet<&str> = KEYWORDS
+            .iter()
+            .map(|(keyword, _)| *keyword)
+            .chain(self.globals.iter().map(String::as_str))
+            .chain(self.locals.iter().map(String::as_str))
+            .filter(|name| name.starts_with(prefix) && *name != prefix)
+            .collect();
+        Ok((start, candidates.into_iter().map(String::from).collect()))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
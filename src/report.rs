@@ -0,0 +1,345 @@
+use crate::{
+    diagnostic::{emit, has_errors},
+    expr::{
+        Assign, Binary, Call, Closure, ExprVisitor, Get, Grouping, Index, List, ListComprehension,
+        Literal, Logical, Set, SetIndex, Super, Ternary, This, Unary, Variable,
+    },
+    interpreter::Interpreter,
+    parser::Parser,
+    resolver::{ResolvedTo, Resolver},
+    scanner::Scanner,
+    stmt::{
+        Block, Break, Class, Destructure, Expression, For, ForIn, Function, If, Print, Return,
+        Stmt, StmtVisitor, Var, While,
+    },
+};
+use std::{collections::HashSet, fmt, rc::Rc};
+
+/// Aggregate metrics about a script, gathered without executing it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub statements: usize,
+    pub functions: usize,
+    pub classes: usize,
+    pub max_depth: usize,
+    /// Name, length in lines, and starting line of the longest function.
+    pub longest_function: Option<(String, usize, usize)>,
+    /// Declared names that are never read, with the line they were declared on.
+    pub unused_names: Vec<(String, usize)>,
+    pub todo_comments: usize,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "statements:        {}", self.statements)?;
+        writeln!(f, "functions:         {}", self.functions)?;
+        writeln!(f, "classes:           {}", self.classes)?;
+        writeln!(f, "max nesting depth: {}", self.max_depth)?;
+        match self.longest_function {
+            Some((ref name, lines, line)) => {
+                writeln!(f, "longest function:  {name} ({lines} lines, line {line})")?
+            }
+            None => writeln!(f, "longest function:  none")?,
+        }
+        writeln!(f, "TODO comments:     {}", self.todo_comments)?;
+        match self.unused_names.is_empty() {
+            true => writeln!(f, "unused names:      none"),
+            false => {
+                writeln!(f, "unused names:")?;
+                for (name, line) in self.unused_names.iter() {
+                    writeln!(f, "  {name} (line {line})")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Builds a [`Report`] for `source`, or `None` if it fails to scan or parse. Errors are
+/// reported the same way as when running the script.
+pub fn report(source: &str) -> Option<Report> {
+    let mut scanner = Scanner::new(source);
+    scanner.scan_tokens();
+
+    // Comments never become tokens, so look for TODOs in the gaps between them.
+    let mut todo_comments = 0;
+    let mut position = 0;
+    for token in scanner.tokens.iter() {
+        todo_comments += source[position..token.start].matches("TODO").count();
+        position = token.start + token.lexeme.len();
+    }
+    todo_comments += source[position..].matches("TODO").count();
+
+    emit(source, &scanner.diagnostics);
+    let mut parser = Parser::new(scanner.tokens);
+    let parse_result = parser.parse();
+    emit(source, &parser.diagnostics);
+    let statements = parse_result.ok()?;
+    if has_errors(&scanner.diagnostics) {
+        return None;
+    }
+
+    let mut metrics = Metrics::new();
+    metrics.visit_statements(&statements);
+
+    let mut report = metrics.report;
+    report.unused_names = unused_names(&statements);
+    report.todo_comments = todo_comments;
+    Some(report)
+}
+
+/// Declared names that are never read, found by resolving `statements` on an interpreter of
+/// their own, with the line each was declared on.
+fn unused_names(statements: &Vec<Stmt>) -> Vec<(String, usize)> {
+    let mut interpreter = Interpreter::new();
+    let mut resolver = Resolver::new(&mut interpreter);
+    resolver.resolve_statements(statements);
+
+    let mut read_locals = HashSet::new();
+    let mut read_globals = HashSet::new();
+    for resolution in resolver.resolutions.iter().filter(|r| !r.assigned) {
+        match resolution.resolved_to {
+            ResolvedTo::Local { .. } => read_locals.extend(resolution.declared_at),
+            ResolvedTo::Global { slot } => {
+                read_globals.insert(slot);
+            }
+        }
+    }
+    let mut unused: Vec<(String, usize)> = resolver
+        .declarations
+        .iter()
+        // A leading underscore marks a name as intentionally unused.
+        .filter(|declaration| !declaration.name.starts_with('_'))
+        .filter(|declaration| match declaration.declared_as {
+            ResolvedTo::Local { .. } => {
+                !read_locals.contains(&(declaration.line, declaration.column))
+            }
+            ResolvedTo::Global { slot } => !read_globals.contains(&slot),
+        })
+        .map(|declaration| (declaration.name.clone(), declaration.line))
+        .collect();
+    unused.sort_by_key(|(_, line)| *line);
+    unused
+}
+
+/// Counts statements, functions and classes, and how deeply they nest. Which names go unused
+/// is left to [`unused_names`].
+struct Metrics {
+    report: Report,
+    depth: usize,
+    current_class: Option<Rc<str>>,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        Metrics {
+            report: Report::default(),
+            depth: 0,
+            current_class: None,
+        }
+    }
+
+    fn visit_statements(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            statement.accept(self);
+        }
+    }
+
+    fn nested(&mut self, body: impl FnOnce(&mut Self)) {
+        self.depth += 1;
+        self.report.max_depth = self.report.max_depth.max(self.depth);
+        body(self);
+        self.depth -= 1;
+    }
+
+    fn visit_function(&mut self, name: String, closure: &Closure) {
+        self.report.functions += 1;
+        let lines = closure.span.end_line - closure.span.line + 1;
+        let is_longest = match self.report.longest_function {
+            Some((_, longest, _)) => lines > longest,
+            None => true,
+        };
+        if is_longest {
+            self.report.longest_function = Some((name, lines, closure.span.line));
+        }
+
+        self.nested(|metrics| metrics.visit_statements(&closure.body));
+    }
+}
+
+impl ExprVisitor<()> for Metrics {
+    fn visit_binary_expr(&mut self, expr: &Binary) {
+        expr.left.accept(self);
+        expr.right.accept(self);
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Grouping) {
+        expr.expression.accept(self);
+    }
+
+    fn visit_literal_expr(&mut self, _: &Literal) {}
+
+    fn visit_unary_expr(&mut self, expr: &Unary) {
+        expr.right.accept(self);
+    }
+
+    fn visit_ternary_expr(&mut self, expr: &Ternary) {
+        expr.condition.accept(self);
+        expr.left.accept(self);
+        expr.right.accept(self);
+    }
+
+    fn visit_variable_expr(&mut self, _: &Variable) {}
+
+    fn visit_assign_expr(&mut self, expr: &Assign) {
+        expr.value.accept(self);
+    }
+
+    fn visit_logical_expr(&mut self, expr: &Logical) {
+        expr.left.accept(self);
+        expr.right.accept(self);
+    }
+
+    fn visit_call_expr(&mut self, expr: &Call) {
+        expr.callee.accept(self);
+        for argument in expr.arguments.iter() {
+            argument.accept(self);
+        }
+    }
+
+    fn visit_get_expr(&mut self, expr: &Get) {
+        expr.object.accept(self);
+    }
+
+    fn visit_set_expr(&mut self, expr: &Set) {
+        expr.object.accept(self);
+        expr.value.accept(self);
+    }
+
+    fn visit_this_expr(&mut self, _: &This) {}
+
+    fn visit_super_expr(&mut self, _: &Super) {}
+
+    fn visit_closure_expr(&mut self, expr: &Rc<Closure>) {
+        self.visit_function(String::from("<fn>"), expr);
+    }
+
+    fn visit_list_expr(&mut self, expr: &List) {
+        for element in expr.elements.iter() {
+            element.accept(self);
+        }
+    }
+
+    fn visit_list_comprehension_expr(&mut self, expr: &ListComprehension) {
+        expr.iterable.accept(self);
+        if let Some(ref condition) = expr.condition {
+            condition.accept(self);
+        }
+        expr.element.accept(self);
+    }
+
+    fn visit_index_expr(&mut self, expr: &Index) {
+        expr.object.accept(self);
+        expr.index.accept(self);
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &SetIndex) {
+        expr.object.accept(self);
+        expr.index.accept(self);
+        expr.value.accept(self);
+    }
+}
+
+impl StmtVisitor<()> for Metrics {
+    fn visit_expression_stmt(&mut self, stmt: &Expression) {
+        self.report.statements += 1;
+        stmt.expression.accept(self);
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Print) {
+        self.report.statements += 1;
+        stmt.expression.accept(self);
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Var) {
+        self.report.statements += 1;
+        if let Some(ref initializer) = stmt.initializer {
+            initializer.accept(self);
+        }
+    }
+
+    fn visit_destructure_stmt(&mut self, stmt: &Destructure) {
+        self.report.statements += 1;
+        stmt.initializer.accept(self);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &Block) {
+        self.report.statements += 1;
+        self.nested(|metrics| metrics.visit_statements(&stmt.statements));
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &If) {
+        self.report.statements += 1;
+        stmt.condition.accept(self);
+        stmt.then_branch.accept(self);
+        if let Some(ref else_branch) = stmt.else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &While) {
+        self.report.statements += 1;
+        stmt.condition.accept(self);
+        stmt.body.accept(self);
+    }
+
+    fn visit_for_stmt(&mut self, stmt: &For) {
+        self.report.statements += 1;
+        if let Some(ref initializer) = stmt.initializer {
+            initializer.accept(self);
+        }
+        for expr in [&stmt.condition, &stmt.increment].into_iter().flatten() {
+            expr.accept(self);
+        }
+        stmt.body.accept(self);
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) {
+        self.report.statements += 1;
+        stmt.iterable.accept(self);
+        stmt.body.accept(self);
+    }
+
+    fn visit_break_stmt(&mut self, _: &Break) {
+        self.report.statements += 1;
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Function) {
+        self.report.statements += 1;
+        for decorator in stmt.decorators.iter() {
+            decorator.accept(self);
+        }
+        let name = match &self.current_class {
+            Some(class_name) => format!("{class_name}.{}", stmt.name.lexeme),
+            None => stmt.name.lexeme.to_string(),
+        };
+        self.visit_function(name, &stmt.closure);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Return) {
+        self.report.statements += 1;
+        stmt.value.accept(self);
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &Class) {
+        self.report.statements += 1;
+        self.report.classes += 1;
+        if let Some(ref superclass) = stmt.superclass {
+            superclass.accept(self);
+        }
+
+        let enclosing_class = self.current_class.replace(Rc::clone(&stmt.name.lexeme));
+        self.nested(|metrics| metrics.visit_statements(&stmt.methods));
+        self.current_class = enclosing_class;
+    }
+}
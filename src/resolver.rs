@@ -1,15 +1,51 @@
 use crate::{
+    diagnostic::{has_errors, Diagnostic},
+    error_code::ErrorCode,
+    error_reporter::SharedReporter,
     expr::{
-        Assign, Binary, Call, Closure, Expr, ExprVisitor, Get, Grouping, Literal, Logical, Set,
-        Super, Ternary, This, Unary, Variable,
+        Assign, Binary, Call, Closure, Expr, ExprVisitor, Get, Grouping, Index, List,
+        ListComprehension, Literal, Logical, Set, SetIndex, Super, Ternary, This, Unary, Variable,
     },
     interpreter::Interpreter,
     lox_object::LoxLiteral,
-    report,
-    stmt::{Block, Class, Expression, Function, If, Print, Return, Stmt, StmtVisitor, Var, While},
+    stmt::{
+        Block, Break, Class, Destructure, Expression, For, ForIn, Function, If, Print, Return,
+        Stmt, StmtVisitor, Var, While,
+    },
     token::Token,
+    token_type::TokenType,
 };
-use std::collections::HashMap;
+use std::{collections::HashMap, rc::Rc};
+
+/// Whether `expr` is arithmetic likely to produce an inexact float: a division, or
+/// arithmetic involving a fractional number literal.
+fn is_float_computation(expr: &Expr) -> bool {
+    fn is_fractional(expr: &Expr) -> bool {
+        match expr {
+            Expr::Literal(literal) => {
+                matches!(literal.value, LoxLiteral::Number(val) if val.fract() != 0.0)
+            }
+            Expr::Grouping(grouping) => is_fractional(&grouping.expression),
+            Expr::Unary(unary) => is_fractional(&unary.right),
+            Expr::Binary(binary) => is_arithmetic(binary),
+            _ => false,
+        }
+    }
+    fn is_arithmetic(binary: &Binary) -> bool {
+        match binary.operator.token_type {
+            TokenType::Slash => true,
+            TokenType::Plus | TokenType::Minus | TokenType::Star => {
+                is_fractional(&binary.left) || is_fractional(&binary.right)
+            }
+            _ => false,
+        }
+    }
+    match expr {
+        Expr::Grouping(grouping) => is_float_computation(&grouping.expression),
+        Expr::Binary(binary) => is_arithmetic(binary),
+        _ => false,
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum FunctionType {
@@ -26,30 +62,235 @@ enum ClassType {
     Subclass,
 }
 
-pub struct Resolver<'interpreter, 'src> {
-    interpreter: &'interpreter mut Interpreter<'src>,
-    scopes: Vec<HashMap<&'src str, bool>>,
+/// What the resolver knows about a local variable in scope.
+#[derive(Debug, Clone)]
+struct Local {
+    /// Where the variable is stored in its scope's environment at runtime. Slots are numbered
+    /// in declaration order, which is the order the interpreter defines variables in.
+    slot: usize,
+    /// Whether the initializer has finished, so the variable can be read.
+    defined: bool,
+    read: bool,
+    /// Where the variable was declared, if it should be warned about when never read.
+    unread_warning: Option<Token>,
+    /// Line and column of the name that declared the variable, if it appears in the source.
+    declared_at: Option<(usize, usize)>,
+}
+
+impl Local {
+    /// A variable the resolver introduces itself, such as `this`.
+    fn defined(slot: usize) -> Self {
+        Local {
+            slot,
+            defined: true,
+            read: true,
+            unread_warning: None,
+            declared_at: None,
+        }
+    }
+}
+
+/// A local variable in one of the resolver's scopes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScopeVariable {
+    pub name: String,
+    /// Where the variable is stored in its scope's environment at runtime.
+    pub slot: usize,
+    /// Whether its initializer has finished; a variable only declared can't be read yet.
+    pub defined: bool,
+    pub read: bool,
+}
+
+/// Where a variable reference resolved to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolvedTo {
+    /// The local in `slot` of the scope `depth` scopes out from the reference.
+    Local { depth: usize, slot: usize },
+    /// The global in `slot`, which needn't be defined yet.
+    Global { slot: usize },
+}
+
+/// A variable reference, including `this` and `super`, and where the resolver bound it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    pub name: String,
+    pub line: usize,
+    pub column: usize,
+    /// Whether the reference assigns to the variable rather than reading it.
+    pub assigned: bool,
+    pub resolved_to: ResolvedTo,
+    /// Whether the variable was defined when referenced. Globals always count as defined,
+    /// since they are looked up when the reference runs.
+    pub defined: bool,
+    /// Line and column of the local's declaration, telling apart locals that share a name.
+    /// `None` for globals, `this` and `super`.
+    pub declared_at: Option<(usize, usize)>,
+}
+
+/// A variable declaration, and where the resolver placed the variable. Reads of it are the
+/// [`Resolution`]s resolved to the same place, which for locals also share `declared_at`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Declaration {
+    pub name: String,
+    pub line: usize,
+    pub column: usize,
+    /// For locals, the depth is always 0: the scope the declaration is in.
+    pub declared_as: ResolvedTo,
+}
+
+impl Resolution {
+    /// The reference's position and name, then where it resolved.
+    pub fn describe(&self) -> String {
+        let access = if self.assigned { "assign" } else { "read" };
+        let resolved_to = match self.resolved_to {
+            ResolvedTo::Local { depth, slot } => format!("local depth {depth} slot {slot}"),
+            ResolvedTo::Global { slot } => format!("global slot {slot}"),
+        };
+        let state = if self.defined {
+            ""
+        } else {
+            " (declared, not yet defined)"
+        };
+        format!(
+            "{:>9}  {access:<6} {:<15} {resolved_to}{state}",
+            format!("{}:{}", self.line, self.column),
+            self.name
+        )
+    }
+}
+
+/// The variables in each of `scopes`, outermost first, each scope in slot order.
+fn scope_variables(scopes: &[HashMap<Rc<str>, Local>]) -> Vec<Vec<ScopeVariable>> {
+    scopes
+        .iter()
+        .map(|scope| {
+            let mut variables: Vec<ScopeVariable> = scope
+                .iter()
+                .map(|(name, local)| ScopeVariable {
+                    name: name.to_string(),
+                    slot: local.slot,
+                    defined: local.defined,
+                    read: local.read,
+                })
+                .collect();
+            variables.sort_by_key(|variable| variable.slot);
+            variables
+        })
+        .collect()
+}
+
+/// Resolver state carried between passes, so input resolved in pieces (such as REPL lines)
+/// sees the same top level as a single script would.
+#[derive(Debug, Clone, Default)]
+pub struct ResolverState {
+    scopes: Vec<HashMap<Rc<str>, Local>>,
+}
+
+impl ResolverState {
+    /// The local scopes still open, outermost first. Empty at the top level, whose
+    /// variables are globals.
+    pub fn scopes(&self) -> Vec<Vec<ScopeVariable>> {
+        scope_variables(&self.scopes)
+    }
+}
+
+pub struct Resolver<'interpreter> {
+    interpreter: &'interpreter mut Interpreter,
+    scopes: Vec<HashMap<Rc<str>, Local>>,
     current_function: FunctionType,
     current_class: ClassType,
-    pub had_error: bool,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Every variable reference resolved so far, in the order they were resolved.
+    pub resolutions: Vec<Resolution>,
+    /// Every variable declared so far, in the order they were declared.
+    pub declarations: Vec<Declaration>,
+    reporter: Option<SharedReporter>,
 }
-impl<'interpreter, 'src> Resolver<'interpreter, 'src> {
-    pub fn new(interpreter: &'interpreter mut Interpreter<'src>) -> Self {
+impl<'interpreter> Resolver<'interpreter> {
+    pub fn new(interpreter: &'interpreter mut Interpreter) -> Self {
+        Resolver::with_state(interpreter, ResolverState::default())
+    }
+
+    /// Resumes resolving from `state`, as returned by [`Resolver::into_state`] after an
+    /// earlier pass with the same interpreter.
+    pub fn with_state(interpreter: &'interpreter mut Interpreter, state: ResolverState) -> Self {
         Resolver {
             interpreter,
-            scopes: Vec::new(),
+            scopes: state.scopes,
             current_function: FunctionType::None,
             current_class: ClassType::None,
-            had_error: false,
+            diagnostics: Vec::new(),
+            resolutions: Vec::new(),
+            declarations: Vec::new(),
+            reporter: None,
+        }
+    }
+
+    pub fn into_state(self) -> ResolverState {
+        ResolverState {
+            scopes: self.scopes,
+        }
+    }
+
+    pub fn had_error(&self) -> bool {
+        has_errors(&self.diagnostics)
+    }
+
+    /// The local scopes open at this point of resolution, outermost first.
+    pub fn scopes(&self) -> Vec<Vec<ScopeVariable>> {
+        scope_variables(&self.scopes)
+    }
+
+    fn record_declaration(&mut self, name: &Token, declared_as: ResolvedTo) {
+        self.declarations.push(Declaration {
+            name: name.lexeme.to_string(),
+            line: name.line,
+            column: name.column,
+            declared_as,
+        });
+    }
+
+    fn record_resolution(
+        &mut self,
+        name: &Token,
+        read: bool,
+        resolved_to: ResolvedTo,
+        defined: bool,
+        declared_at: Option<(usize, usize)>,
+    ) {
+        self.resolutions.push(Resolution {
+            name: name.lexeme.to_string(),
+            line: name.line,
+            column: name.column,
+            assigned: !read,
+            resolved_to,
+            defined,
+            declared_at,
+        });
+    }
+
+    /// Sets the reporter that each error and warning is passed to as soon as it's found.
+    pub fn set_reporter(&mut self, reporter: SharedReporter) {
+        self.reporter = Some(reporter);
+    }
+
+    fn record(&mut self, diagnostic: Diagnostic) {
+        if let Some(ref reporter) = self.reporter {
+            reporter.borrow_mut().report(&diagnostic);
         }
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Records a warning, which doesn't stop the script from running.
+    fn warn(&mut self, code: ErrorCode, token: &Token, message: &str) {
+        self.record(Diagnostic::at_token(code, token, "", message));
     }
 
-    fn resolver_error(&mut self, line: usize, loc: &str, message: &str) {
-        self.had_error = true;
-        report(line, loc, message);
+    fn resolver_error(&mut self, code: ErrorCode, token: &Token, loc: &str, message: &str) {
+        self.record(Diagnostic::at_token(code, token, loc, message));
     }
 
-    fn get_cur_scope(&mut self) -> &mut HashMap<&'src str, bool> {
+    fn get_cur_scope(&mut self) -> &mut HashMap<Rc<str>, Local> {
         let cur_scope_idx = self.scopes.len() - 1;
         &mut self.scopes[cur_scope_idx]
     }
@@ -59,47 +300,125 @@ impl<'interpreter, 'src> Resolver<'interpreter, 'src> {
     }
 
     fn end_scope(&mut self) {
-        self.scopes.pop();
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+        let mut unread: Vec<Token> = scope
+            .into_values()
+            .filter(|local| !local.read)
+            .filter_map(|local| local.unread_warning)
+            .collect();
+        unread.sort_by_key(|name| name.start);
+        for name in unread {
+            self.warn(
+                ErrorCode::UNUSED_LOCAL,
+                &name,
+                &format!("Local variable '{}' is never read.", name.lexeme),
+            );
+        }
     }
 
-    fn declare(&mut self, name: &Token<'src>) {
+    fn declare(&mut self, name: &Token) {
         if self.scopes.is_empty() {
+            let slot = self
+                .interpreter
+                .globals
+                .borrow_mut()
+                .global_slot(&name.lexeme);
+            self.record_declaration(name, ResolvedTo::Global { slot });
             return;
         }
 
         let scope = self.get_cur_scope();
-        let already_declared = scope.contains_key(name.lexeme);
-
-        scope.insert(name.lexeme, false);
+        let already_declared = scope.contains_key(&name.lexeme);
+
+        let slot = scope.len();
+        scope.insert(
+            Rc::clone(&name.lexeme),
+            Local {
+                slot,
+                defined: false,
+                read: false,
+                unread_warning: None,
+                declared_at: Some((name.line, name.column)),
+            },
+        );
+
+        self.record_declaration(name, ResolvedTo::Local { depth: 0, slot });
 
         if already_declared {
             self.resolver_error(
-                name.line,
+                ErrorCode::ALREADY_DECLARED,
+                name,
                 &format!("at '{}'", &name.lexeme),
                 "Already a variable with this name in this scope.",
             );
         }
     }
 
-    fn define(&mut self, name: &Token<'src>) {
-        if self.scopes.is_empty() {
-            return;
+    /// Declares and defines a variable the interpreter binds itself, such as `this`.
+    fn define_implicit(&mut self, name: &str) {
+        let scope = self.get_cur_scope();
+        let slot = scope.len();
+        scope.insert(Rc::from(name), Local::defined(slot));
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(local) = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.get_mut(&name.lexeme))
+        {
+            local.defined = true;
         }
+    }
 
-        self.get_cur_scope().insert(name.lexeme, true);
+    /// Declares a local variable that gets a warning if it's never read. Names starting with
+    /// `_` are exempt, marking variables that are unused on purpose.
+    fn declare_checked(&mut self, name: &Token) {
+        self.declare(name);
+        if name.lexeme.starts_with('_') {
+            return;
+        }
+        if let Some(local) = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.get_mut(&name.lexeme))
+        {
+            local.unread_warning = Some(name.clone());
+        }
     }
 
-    fn resolve_local(&mut self, name: &Token<'src>) {
-        for idx in (0..self.scopes.len()).rev() {
-            if self.scopes[idx].contains_key(name.lexeme) {
-                self.interpreter
-                    .resolve(name.clone(), self.scopes.len() - 1 - idx);
-                return;
+    /// Finds which scope and slot `name` refers to, counting it as a read of the variable if
+    /// `read`. Returns `None` for globals.
+    fn resolve_local(&mut self, name: &Token, read: bool) -> Option<(usize, usize)> {
+        let depth = self.scopes.len();
+        for idx in (0..depth).rev() {
+            if let Some(local) = self.scopes[idx].get_mut(&name.lexeme) {
+                local.read |= read;
+                let (slot, defined, declared_at) = (local.slot, local.defined, local.declared_at);
+                let depth = depth - 1 - idx;
+                let resolved_to = ResolvedTo::Local { depth, slot };
+                self.record_resolution(name, read, resolved_to, defined, declared_at);
+                return Some((depth, slot));
             }
         }
+        None
+    }
+
+    /// Slot of the global `name`, which it gets before being defined if need be. `read` is
+    /// as for [`Resolver::resolve_local`].
+    fn resolve_global(&mut self, name: &Token, read: bool) -> usize {
+        let slot = self
+            .interpreter
+            .globals
+            .borrow_mut()
+            .global_slot(&name.lexeme);
+        self.record_resolution(name, read, ResolvedTo::Global { slot }, true, None);
+        slot
     }
 
-    fn resolve_function(&mut self, closure: &Closure<'src>, function_type: FunctionType) {
+    fn resolve_function(&mut self, closure: &Closure, function_type: FunctionType) {
         let enclosing_function = self.current_function;
         self.current_function = function_type;
 
@@ -114,138 +433,220 @@ impl<'interpreter, 'src> Resolver<'interpreter, 'src> {
         self.current_function = enclosing_function;
     }
 
-    pub fn resolve_statements(&mut self, statements: &Vec<Stmt<'src>>) {
+    /// Resolves an expression evaluated as if inside a method, with `this` in scope.
+    pub fn resolve_instance_expr(&mut self, expr: &Expr) {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+        self.begin_scope();
+        self.define_implicit("this");
+        self.resolve_expr(expr);
+        self.end_scope();
+        self.current_class = enclosing_class;
+    }
+
+    pub fn resolve_statements(&mut self, statements: &Vec<Stmt>) {
         for statement in statements {
             self.resolve_stmt(statement);
         }
     }
 
-    fn resolve_stmt(&mut self, stmt: &Stmt<'src>) {
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
         stmt.accept(self);
     }
 
-    fn resolve_expr(&mut self, expr: &Expr<'src>) {
+    fn resolve_expr(&mut self, expr: &Expr) {
         expr.accept(self);
     }
 }
 
-impl<'interpreter, 'src> ExprVisitor<'src, ()> for Resolver<'interpreter, 'src> {
-    fn visit_binary_expr(&mut self, expr: &Binary<'src>) {
+impl<'interpreter> ExprVisitor<()> for Resolver<'interpreter> {
+    fn visit_binary_expr(&mut self, expr: &Binary) {
         self.resolve_expr(&expr.left);
         self.resolve_expr(&expr.right);
+
+        let is_equality = matches!(
+            expr.operator.token_type,
+            TokenType::EqualEqual | TokenType::BangEqual
+        );
+        if is_equality && (is_float_computation(&expr.left) || is_float_computation(&expr.right)) {
+            let message = format!(
+                "Comparing computed floats with '{}' may be inexact; consider approxEqual().",
+                expr.operator.lexeme
+            );
+            self.warn(ErrorCode::FLOAT_EQUALITY, &expr.operator, &message);
+        }
     }
 
-    fn visit_grouping_expr(&mut self, expr: &Grouping<'src>) {
+    fn visit_grouping_expr(&mut self, expr: &Grouping) {
         self.resolve_expr(&expr.expression);
     }
 
-    fn visit_literal_expr(&mut self, _: &Literal) {}
+    fn visit_literal_expr(&mut self, expr: &Literal) {
+        expr.constant
+            .set(self.interpreter.add_constant(&expr.value));
+    }
 
-    fn visit_unary_expr(&mut self, expr: &Unary<'src>) {
+    fn visit_unary_expr(&mut self, expr: &Unary) {
         self.resolve_expr(&expr.right);
     }
 
-    fn visit_ternary_expr(&mut self, expr: &Ternary<'src>) {
+    fn visit_ternary_expr(&mut self, expr: &Ternary) {
         self.resolve_expr(&expr.condition);
         self.resolve_expr(&expr.left);
         self.resolve_expr(&expr.right);
     }
 
-    fn visit_variable_expr(&mut self, expr: &Variable<'src>) {
-        if !self.scopes.is_empty() && self.get_cur_scope().get(&expr.name.lexeme) == Some(&false) {
+    fn visit_variable_expr(&mut self, expr: &Variable) {
+        let declared_here = self
+            .scopes
+            .last()
+            .and_then(|scope| scope.get(&expr.name.lexeme));
+        if declared_here.is_some_and(|local| !local.defined) {
             self.resolver_error(
-                expr.name.line,
+                ErrorCode::READ_IN_OWN_INITIALIZER,
+                &expr.name,
                 &format!("at '{}'", &expr.name.lexeme),
                 "Can't read local variable in its own initializer.",
             );
         }
 
-        self.resolve_local(&expr.name);
+        let local = self.resolve_local(&expr.name, true);
+        expr.local.set(local);
+        if local.is_none() {
+            expr.global.set(Some(self.resolve_global(&expr.name, true)));
+        }
     }
 
-    fn visit_assign_expr(&mut self, expr: &Assign<'src>) {
+    fn visit_assign_expr(&mut self, expr: &Assign) {
         self.resolve_expr(&expr.value);
-        self.resolve_local(&expr.name);
+        let local = self.resolve_local(&expr.name, false);
+        expr.local.set(local);
+        if local.is_none() {
+            expr.global
+                .set(Some(self.resolve_global(&expr.name, false)));
+        }
     }
 
-    fn visit_logical_expr(&mut self, expr: &Logical<'src>) {
+    fn visit_logical_expr(&mut self, expr: &Logical) {
         self.resolve_expr(&expr.left);
         self.resolve_expr(&expr.right);
     }
 
-    fn visit_call_expr(&mut self, expr: &Call<'src>) {
+    fn visit_call_expr(&mut self, expr: &Call) {
         self.resolve_expr(&expr.callee);
         for argument in expr.arguments.iter() {
             self.resolve_expr(argument);
         }
     }
 
-    fn visit_get_expr(&mut self, expr: &Get<'src>) {
+    fn visit_get_expr(&mut self, expr: &Get) {
         self.resolve_expr(&expr.object);
     }
 
-    fn visit_set_expr(&mut self, expr: &Set<'src>) -> () {
+    fn visit_set_expr(&mut self, expr: &Set) {
         self.resolve_expr(&expr.value);
         self.resolve_expr(&expr.object);
     }
 
-    fn visit_this_expr(&mut self, expr: &This<'src>) {
+    fn visit_this_expr(&mut self, expr: &This) {
         if self.current_class == ClassType::None {
             self.resolver_error(
-                expr.keyword.line,
+                ErrorCode::THIS_OUTSIDE_CLASS,
+                &expr.keyword,
                 "at 'this'",
                 "Can't use 'this' outside of a class.",
             );
         }
 
-        self.resolve_local(&expr.keyword);
+        expr.local.set(self.resolve_local(&expr.keyword, true));
     }
 
-    fn visit_super_expr(&mut self, expr: &Super<'src>) {
+    fn visit_super_expr(&mut self, expr: &Super) {
         match self.current_class {
             ClassType::None => self.resolver_error(
-                expr.keyword.line,
+                ErrorCode::SUPER_OUTSIDE_CLASS,
+                &expr.keyword,
                 "at 'super'",
                 "Can't use 'super' outside of a class.",
             ),
             ClassType::Class => self.resolver_error(
-                expr.keyword.line,
+                ErrorCode::SUPER_WITHOUT_SUPERCLASS,
+                &expr.keyword,
                 "at 'super'",
                 "Can't use 'super' in a class with no superclass.",
             ),
-            ClassType::Subclass => self.resolve_local(&expr.keyword),
+            ClassType::Subclass => expr.local.set(self.resolve_local(&expr.keyword, true)),
         }
     }
 
-    fn visit_closure_expr(&mut self, expr: &Closure<'src>) {
+    fn visit_closure_expr(&mut self, expr: &Rc<Closure>) {
         self.resolve_function(expr, FunctionType::Function);
     }
+
+    fn visit_list_expr(&mut self, expr: &List) {
+        for element in expr.elements.iter() {
+            self.resolve_expr(element);
+        }
+    }
+
+    fn visit_list_comprehension_expr(&mut self, expr: &ListComprehension) {
+        self.resolve_expr(&expr.iterable);
+        self.begin_scope();
+        self.declare(&expr.name);
+        self.define(&expr.name);
+        if let Some(ref condition) = expr.condition {
+            self.resolve_expr(condition);
+        }
+        self.resolve_expr(&expr.element);
+        self.end_scope();
+    }
+
+    fn visit_index_expr(&mut self, expr: &Index) {
+        self.resolve_expr(&expr.object);
+        self.resolve_expr(&expr.index);
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &SetIndex) {
+        self.resolve_expr(&expr.value);
+        self.resolve_expr(&expr.object);
+        self.resolve_expr(&expr.index);
+    }
 }
 
-impl<'interpreter, 'src> StmtVisitor<'src, ()> for Resolver<'interpreter, 'src> {
-    fn visit_expression_stmt(&mut self, stmt: &Expression<'src>) {
+impl<'interpreter> StmtVisitor<()> for Resolver<'interpreter> {
+    fn visit_expression_stmt(&mut self, stmt: &Expression) {
         self.resolve_expr(&stmt.expression);
     }
 
-    fn visit_print_stmt(&mut self, stmt: &Print<'src>) {
+    fn visit_print_stmt(&mut self, stmt: &Print) {
         self.resolve_expr(&stmt.expression);
     }
 
-    fn visit_var_stmt(&mut self, stmt: &Var<'src>) {
-        self.declare(&stmt.name);
+    fn visit_var_stmt(&mut self, stmt: &Var) {
+        self.declare_checked(&stmt.name);
         if let Some(ref initializer) = stmt.initializer {
             self.resolve_expr(initializer);
         }
         self.define(&stmt.name);
     }
 
-    fn visit_block_stmt(&mut self, stmt: &Block<'src>) {
+    fn visit_destructure_stmt(&mut self, stmt: &Destructure) {
+        for name in stmt.names.iter() {
+            self.declare_checked(name);
+        }
+        self.resolve_expr(&stmt.initializer);
+        for name in stmt.names.iter() {
+            self.define(name);
+        }
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &Block) {
         self.begin_scope();
         self.resolve_statements(&stmt.statements);
         self.end_scope();
     }
 
-    fn visit_if_stmt(&mut self, stmt: &If<'src>) {
+    fn visit_if_stmt(&mut self, stmt: &If) {
         self.resolve_expr(&stmt.condition);
         self.resolve_stmt(&stmt.then_branch);
         if let Some(ref else_stmt) = stmt.else_branch {
@@ -253,24 +654,57 @@ impl<'interpreter, 'src> StmtVisitor<'src, ()> for Resolver<'interpreter, 'src>
         }
     }
 
-    fn visit_while_stmt(&mut self, stmt: &While<'src>) {
+    fn visit_while_stmt(&mut self, stmt: &While) {
         self.resolve_expr(&stmt.condition);
         self.resolve_stmt(&stmt.body);
     }
 
-    fn visit_break_stmt(&mut self) {}
+    fn visit_for_stmt(&mut self, stmt: &For) {
+        // The initializer gets a scope of its own only if there is one, matching the
+        // environment the interpreter creates for it.
+        if let Some(ref initializer) = stmt.initializer {
+            self.begin_scope();
+            self.resolve_stmt(initializer);
+        }
+        if let Some(ref condition) = stmt.condition {
+            self.resolve_expr(condition);
+        }
+        self.resolve_stmt(&stmt.body);
+        if let Some(ref increment) = stmt.increment {
+            self.resolve_expr(increment);
+        }
+        if stmt.initializer.is_some() {
+            self.end_scope();
+        }
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) {
+        self.resolve_expr(&stmt.iterable);
+        self.begin_scope();
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+        self.resolve_stmt(&stmt.body);
+        self.end_scope();
+    }
+
+    fn visit_break_stmt(&mut self, _: &Break) {}
 
-    fn visit_function_stmt(&mut self, stmt: &Function<'src>) {
+    fn visit_function_stmt(&mut self, stmt: &Function) {
+        // Decorators are evaluated before the function's name is bound.
+        for decorator in stmt.decorators.iter() {
+            self.resolve_expr(decorator);
+        }
         self.declare(&stmt.name);
         self.define(&stmt.name);
 
         self.resolve_function(&stmt.closure, FunctionType::Function);
     }
 
-    fn visit_return_stmt(&mut self, stmt: &Return<'src>) {
+    fn visit_return_stmt(&mut self, stmt: &Return) {
         if self.current_function == FunctionType::None {
             self.resolver_error(
-                stmt.keyword.line,
+                ErrorCode::RETURN_FROM_TOP_LEVEL,
+                &stmt.keyword,
                 "at 'return'",
                 "Can't return from top-level code.",
             );
@@ -279,7 +713,8 @@ impl<'interpreter, 'src> StmtVisitor<'src, ()> for Resolver<'interpreter, 'src>
             Expr::Literal(literal) if literal.value == LoxLiteral::Nil => (),
             _ if self.current_function == FunctionType::Initializer => {
                 self.resolver_error(
-                    stmt.keyword.line,
+                    ErrorCode::RETURN_VALUE_FROM_INITIALIZER,
+                    &stmt.keyword,
                     "at 'return",
                     "Can't return a value from an initializer.",
                 );
@@ -290,7 +725,7 @@ impl<'interpreter, 'src> StmtVisitor<'src, ()> for Resolver<'interpreter, 'src>
         }
     }
 
-    fn visit_class_stmt(&mut self, stmt: &Class<'src>) {
+    fn visit_class_stmt(&mut self, stmt: &Class) {
         let enclosing_class = self.current_class;
         self.current_class = ClassType::Class;
 
@@ -301,7 +736,8 @@ impl<'interpreter, 'src> StmtVisitor<'src, ()> for Resolver<'interpreter, 'src>
             if let Expr::Variable(superclass_var) = superclass.as_ref() {
                 if stmt.name.lexeme == superclass_var.name.lexeme {
                     self.resolver_error(
-                        superclass_var.name.line,
+                        ErrorCode::INHERITS_FROM_ITSELF,
+                        &superclass_var.name,
                         &format!("at '{}'", superclass_var.name.lexeme),
                         "A class can't inherit from itself",
                     );
@@ -310,15 +746,15 @@ impl<'interpreter, 'src> StmtVisitor<'src, ()> for Resolver<'interpreter, 'src>
             self.current_class = ClassType::Subclass;
             self.resolve_expr(superclass);
             self.begin_scope();
-            self.get_cur_scope().insert("super", true);
+            self.define_implicit("super");
         }
 
         self.begin_scope();
-        self.get_cur_scope().insert("this", true);
+        self.define_implicit("this");
 
         for method in stmt.methods.iter() {
             if let Stmt::Function(function) = method {
-                let declaration = match function.name.lexeme == "init" {
+                let declaration = match &*function.name.lexeme == "init" {
                     true => FunctionType::Initializer,
                     false => FunctionType::Method,
                 };
@@ -0,0 +1,124 @@
+use crate::{
+    ast_json::statements_from_json,
+    diagnostic::{is_fatal, Diagnostic},
+    interpreter::Interpreter,
+    lox_exception::{LoxException, RuntimeError},
+    optimizer::eliminate_dead_code,
+    parser::Parser,
+    resolver::Resolver,
+    scanner::Scanner,
+    stmt::Stmt,
+};
+use std::{
+    cell::RefCell,
+    error::Error,
+    fmt,
+    io::{self, Write},
+    rc::Rc,
+};
+
+/// An error that stopped [`run_source`] from running a script to the end.
+#[derive(Debug, Clone)]
+pub enum LoxError {
+    /// A problem found scanning, parsing or resolving the script, before any of it ran.
+    Static(Diagnostic),
+    /// The error that stopped the script while it was running.
+    Runtime(RuntimeError),
+    /// The script was stopped through the interpreter's cancellation token.
+    Cancelled,
+    /// JSON given to [`run_ast_json`] that isn't a syntax tree the parser could have produced.
+    InvalidSyntaxTree(String),
+}
+
+impl LoxError {
+    /// The error as the command line prints it, quoting the line of `source` it occurred on.
+    pub fn render(&self, source: &str) -> String {
+        match self {
+            LoxError::Static(diagnostic) => diagnostic.render(source, false).trim_end().to_owned(),
+            LoxError::Runtime(error) => error.render(source),
+            LoxError::Cancelled | LoxError::InvalidSyntaxTree(_) => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoxError::Static(diagnostic) => write!(f, "{diagnostic}"),
+            LoxError::Runtime(error) => write!(f, "{error}"),
+            LoxError::Cancelled => write!(f, "Cancelled."),
+            LoxError::InvalidSyntaxTree(message) => write!(f, "Invalid syntax tree: {message}"),
+        }
+    }
+}
+
+impl Error for LoxError {}
+
+/// Scans, parses, resolves and runs `source` in `interpreter`, printing nothing but what the
+/// script prints. Globals defined by earlier calls with the same interpreter stay defined.
+///
+/// If scanning, parsing or resolving finds errors, nothing runs and every problem found is
+/// returned, warnings included. Otherwise the script runs, and a runtime error or cancellation
+/// stopping it is returned on its own.
+pub fn run_source(source: &str, interpreter: &mut Interpreter) -> Result<(), Vec<LoxError>> {
+    let mut scanner = Scanner::new(source);
+    scanner.scan_tokens();
+    let mut diagnostics = std::mem::take(&mut scanner.diagnostics);
+
+    let mut parser = Parser::new(scanner.tokens);
+    let parse_result = parser.parse();
+    diagnostics.append(&mut parser.diagnostics);
+    let statements = match parse_result {
+        Ok(statements) if !is_fatal(&diagnostics) => statements,
+        _ => return Err(diagnostics.into_iter().map(LoxError::Static).collect()),
+    };
+    run_statements(statements, diagnostics, interpreter)
+}
+
+/// Like [`run_source`], but runs a syntax tree written as JSON by `ast --json` (see
+/// [`ast_to_json`]) instead of parsing source code. The tree is resolved afresh, so tools may
+/// rewrite it before handing it back.
+///
+/// [`ast_to_json`]: crate::ast_json::ast_to_json
+pub fn run_ast_json(json: &str, interpreter: &mut Interpreter) -> Result<(), Vec<LoxError>> {
+    match statements_from_json(json) {
+        Ok(statements) => run_statements(statements, Vec::new(), interpreter),
+        Err(message) => Err(vec![LoxError::InvalidSyntaxTree(message)]),
+    }
+}
+
+/// Resolves and runs parsed `statements`, adding to the `diagnostics` found parsing them.
+fn run_statements(
+    mut statements: Vec<Stmt>,
+    mut diagnostics: Vec<Diagnostic>,
+    interpreter: &mut Interpreter,
+) -> Result<(), Vec<LoxError>> {
+    let mut resolver = Resolver::new(interpreter);
+    resolver.resolve_statements(&statements);
+    diagnostics.append(&mut resolver.diagnostics);
+    if is_fatal(&diagnostics) {
+        return Err(diagnostics.into_iter().map(LoxError::Static).collect());
+    }
+    eliminate_dead_code(&mut statements);
+
+    match interpreter.interpret(&statements) {
+        Err(LoxException::RuntimeError(error)) => Err(vec![LoxError::Runtime(error)]),
+        Err(LoxException::Cancelled) => Err(vec![LoxError::Cancelled]),
+        _ => Ok(()),
+    }
+}
+
+/// An output sink whose contents can still be read once the interpreter writing to it is done.
+#[derive(Clone, Default)]
+pub(crate) struct SharedBuffer(pub(crate) Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
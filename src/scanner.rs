@@ -1,51 +1,87 @@
-use crate::{lox_object::LoxLiteral, report, token::Token, token_type::TokenType};
-use std::{collections::HashMap, iter::Peekable, rc::Rc, str::Chars};
+use crate::{
+    diagnostic::{column_at, has_errors, Diagnostic, SourceSpan},
+    error_code::ErrorCode,
+    error_reporter::SharedReporter,
+    lox_object::LoxLiteral,
+    lox_string::LoxString,
+    token::Token,
+    token_type::TokenType,
+};
+use std::{
+    collections::HashMap,
+    iter::Peekable,
+    rc::Rc,
+    str::Chars,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Token ids identify resolved variables, so they must stay unique across every scan in
+/// the process, not just within one source (REPL lines, evaluated expressions).
+static NEXT_TOKEN_ID: AtomicUsize = AtomicUsize::new(0);
+
+fn next_token_id() -> usize {
+    NEXT_TOKEN_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+pub const KEYWORDS: [(&str, TokenType); 18] = [
+    ("and", TokenType::And),
+    ("class", TokenType::Class),
+    ("else", TokenType::Else),
+    ("false", TokenType::False),
+    ("for", TokenType::For),
+    ("fun", TokenType::Fun),
+    ("if", TokenType::If),
+    ("nil", TokenType::Nil),
+    ("or", TokenType::Or),
+    ("print", TokenType::Print),
+    ("return", TokenType::Return),
+    ("super", TokenType::Super),
+    ("this", TokenType::This),
+    ("true", TokenType::True),
+    ("var", TokenType::Var),
+    ("while", TokenType::While),
+    ("break", TokenType::Break),
+    ("in", TokenType::In),
+];
 
 pub struct Scanner<'src> {
-    pub tokens: Vec<Token<'src>>,
-    pub had_error: bool,
+    pub tokens: Vec<Token>,
+    pub diagnostics: Vec<Diagnostic>,
+    reporter: Option<SharedReporter>,
     source: &'src str,
     source_iter: Peekable<Chars<'src>>,
     start: usize,
     current: usize,
     line: usize,
+    /// Byte offset where the line being scanned starts.
+    line_start: usize,
+    /// The offset and column of the last column worked out, which later ones on the same line
+    /// count on from.
+    column_mark: (usize, usize),
     keywords: HashMap<&'static str, TokenType>,
-    next_token_id: usize,
+    /// The lines of the `///` comments since the last token, for the next token's `doc`.
+    doc_lines: Vec<&'src str>,
 }
 
 impl<'src> Scanner<'src> {
     pub fn new(source: &'src str) -> Self {
-        let mut keywords = HashMap::with_capacity(16);
-        keywords.insert("and", TokenType::And);
-        keywords.insert("class", TokenType::Class);
-        keywords.insert("else", TokenType::Else);
-        keywords.insert("false", TokenType::False);
-        keywords.insert("for", TokenType::For);
-        keywords.insert("fun", TokenType::Fun);
-        keywords.insert("if", TokenType::If);
-        keywords.insert("nil", TokenType::Nil);
-        keywords.insert("or", TokenType::Or);
-        keywords.insert("print", TokenType::Print);
-        keywords.insert("return", TokenType::Return);
-        keywords.insert("super", TokenType::Super);
-        keywords.insert("this", TokenType::This);
-        keywords.insert("true", TokenType::True);
-        keywords.insert("var", TokenType::Var);
-        keywords.insert("while", TokenType::While);
-        keywords.insert("break", TokenType::Break);
+        let keywords = HashMap::from(KEYWORDS);
 
         let source_iter: Peekable<Chars<'_>> = source.chars().peekable();
 
         Scanner {
             tokens: Vec::new(),
-            had_error: false,
+            diagnostics: Vec::new(),
+            reporter: None,
             source,
             source_iter,
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            column_mark: (0, 1),
             keywords,
-            next_token_id: 0,
+            doc_lines: Vec::new(),
         }
     }
 
@@ -55,14 +91,36 @@ impl<'src> Scanner<'src> {
             self.scan_token();
         }
 
+        // Place Eof just after the last non-whitespace character, so errors "at end" point there.
+        let end = self.source.trim_end().len();
+        let trailing_lines = self.source[end..].matches('\n').count();
+        let column = self.column(end);
         self.tokens.push(Token::new(
             TokenType::Eof,
             "",
             None,
-            self.line,
-            self.next_token_id,
+            self.line.saturating_sub(trailing_lines),
+            end,
+            column,
+            next_token_id(),
         ));
-        self.next_token_id += 1;
+    }
+
+    /// One-based column of the character at byte `offset`. Tokens are scanned in order, so
+    /// this counts on from the last column worked out, or from the start of the line, rather
+    /// than rescanning the line for every token.
+    fn column(&mut self, offset: usize) -> usize {
+        let (mark, mark_column) = match self.column_mark {
+            (mark, column) if mark >= self.line_start && mark <= offset => (mark, column),
+            _ => (self.line_start, 1),
+        };
+        let column = match offset >= mark {
+            true => mark_column + self.source[mark..offset].chars().count(),
+            // Only a lexeme spanning lines, such as a string, starts before the current line.
+            false => column_at(self.source, offset),
+        };
+        self.column_mark = (offset, column);
+        column
     }
 
     fn is_at_end(&self) -> bool {
@@ -73,6 +131,10 @@ impl<'src> Scanner<'src> {
         match self.source_iter.next() {
             Some(return_char) => {
                 self.current += return_char.len_utf8();
+                if return_char == '\n' {
+                    self.line += 1;
+                    self.line_start = self.current;
+                }
                 return_char
             }
             None => '\0',
@@ -86,6 +148,8 @@ impl<'src> Scanner<'src> {
             ')' => self.add_token(TokenType::RightParen, None),
             '{' => self.add_token(TokenType::LeftBrace, None),
             '}' => self.add_token(TokenType::RightBrace, None),
+            '[' => self.add_token(TokenType::LeftBracket, None),
+            ']' => self.add_token(TokenType::RightBracket, None),
             ',' => self.add_token(TokenType::Comma, None),
             '.' => self.add_token(TokenType::Dot, None),
             '-' => self.add_token(TokenType::Minus, None),
@@ -94,6 +158,7 @@ impl<'src> Scanner<'src> {
             ':' => self.add_token(TokenType::Colon, None),
             '?' => self.add_token(TokenType::QuestionMark, None),
             '*' => self.add_token(TokenType::Star, None),
+            '@' => self.add_token(TokenType::At, None),
             '!' => match self.match_char('=') {
                 true => self.add_token(TokenType::BangEqual, None),
                 false => self.add_token(TokenType::Bang, None),
@@ -117,25 +182,30 @@ impl<'src> Scanner<'src> {
                     false => self.add_token(TokenType::Slash, None),
                 },
             },
-            ' ' | '\r' | '\t' => (),
-            '\n' => self.line += 1,
+            ' ' | '\r' | '\t' | '\n' => (),
             '"' => self.string(),
             '0'..='9' => self.number(),
             _ if c.is_alphabetic() || c == '_' => self.identifier(),
-            _ => self.error(self.line, "Unexpected character."),
+            _ => self.error(ErrorCode::UNEXPECTED_CHARACTER, "Unexpected character."),
         }
     }
 
     fn add_token(&mut self, token_type: TokenType, literal: Option<LoxLiteral>) {
         let text = &self.source[self.start..self.current];
-        self.tokens.push(Token::new(
+        let mut token = Token::new(
             token_type,
             text,
             literal,
             self.line,
-            self.next_token_id,
-        ));
-        self.next_token_id += 1;
+            self.start,
+            self.column(self.start),
+            next_token_id(),
+        );
+        if !self.doc_lines.is_empty() {
+            token.doc = Some(Rc::from(self.doc_lines.join("\n")));
+            self.doc_lines.clear();
+        }
+        self.tokens.push(token);
     }
 
     fn match_char(&mut self, expected: char) -> bool {
@@ -153,13 +223,10 @@ impl<'src> Scanner<'src> {
             if peek_char == '"' {
                 break;
             }
-            if peek_char == '\n' {
-                self.line += 1;
-            }
             self.advance();
         }
         if self.is_at_end() {
-            self.error(self.line, "Unterminated string.");
+            self.error(ErrorCode::UNTERMINATED_STRING, "Unterminated string.");
             return;
         }
 
@@ -168,7 +235,10 @@ impl<'src> Scanner<'src> {
 
         // Time the surrounding quotes.
         let value = self.source[self.start + 1..self.current - 1].to_string();
-        self.add_token(TokenType::String, Some(LoxLiteral::String(Rc::new(value))));
+        self.add_token(
+            TokenType::String,
+            Some(LoxLiteral::String(Rc::new(LoxString::from(value)))),
+        );
     }
 
     fn number(&mut self) {
@@ -196,18 +266,10 @@ impl<'src> Scanner<'src> {
             }
         }
 
-        self.add_token(
-            TokenType::Number,
-            Some(LoxLiteral::Number(
-                self.source[self.start..self.current]
-                    .parse()
-                    .expect(&format!(
-                        "Failed to parse number literal '{}' on line {}",
-                        &self.source[self.start..self.current],
-                        self.line
-                    )),
-            )),
-        );
+        match self.source[self.start..self.current].parse() {
+            Ok(value) => self.add_token(TokenType::Number, Some(LoxLiteral::Number(value))),
+            Err(_) => self.error(ErrorCode::INVALID_NUMBER, "Invalid number literal."),
+        }
     }
 
     fn identifier(&mut self) {
@@ -239,6 +301,17 @@ impl<'src> Scanner<'src> {
             }
             self.advance();
         }
+
+        // Only `///` starts a doc comment; `////` and beyond are ordinary comments, as are
+        // `//` comments, which also detach any doc comment before them.
+        let comment = &self.source[self.start..self.current];
+        match comment.strip_prefix("///") {
+            Some(text) if !text.starts_with('/') => {
+                let text = text.strip_prefix(' ').unwrap_or(text);
+                self.doc_lines.push(text.trim_end());
+            }
+            _ => self.doc_lines.clear(),
+        }
     }
 
     fn block_comments(&mut self) {
@@ -258,15 +331,36 @@ impl<'src> Scanner<'src> {
                 // Consume the '*'.
                 self.advance();
             }
-            if current_char == '\n' {
-                self.line += 1;
-            }
             current_char = self.advance();
         }
     }
 
-    fn error(&mut self, line: usize, message: &str) {
-        self.had_error = true;
-        report(line, "", message);
+    pub fn had_error(&self) -> bool {
+        has_errors(&self.diagnostics)
+    }
+
+    /// Sets the reporter that each error is passed to as soon as it's found.
+    pub fn set_reporter(&mut self, reporter: SharedReporter) {
+        self.reporter = Some(reporter);
+    }
+
+    fn record(&mut self, diagnostic: Diagnostic) {
+        if let Some(ref reporter) = self.reporter {
+            reporter.borrow_mut().report(&diagnostic);
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Records an error at the lexeme being scanned.
+    fn error(&mut self, code: ErrorCode, message: &str) {
+        // Unterminated strings span lines, so report the line the lexeme starts on.
+        let line = self.line - self.source[self.start..self.current].matches('\n').count();
+        let column = self.column(self.start);
+        let span = SourceSpan {
+            offset: self.start,
+            len: self.current - self.start,
+            column,
+        };
+        self.record(Diagnostic::new(code, line, Some(span), "", message));
     }
 }
@@ -0,0 +1,184 @@
+use crate::{
+    lox_callable::LoxCallable,
+    lox_class::LoxClass,
+    lox_instance::LoxInstance,
+    lox_object::{LoxLiteral, LoxObject},
+    lox_string::LoxString,
+};
+use serde::{
+    de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor},
+    ser::{self, Serialize, SerializeMap, SerializeSeq, Serializer},
+};
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+/// Name of the class that deserialized maps become instances of.
+const MAP_CLASS: &str = "Object";
+
+impl Serialize for LoxLiteral {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            LoxLiteral::Number(val) => serializer.serialize_f64(*val),
+            LoxLiteral::String(val) => serializer.serialize_str(&val.flat()),
+            LoxLiteral::Boolean(val) => serializer.serialize_bool(*val),
+            LoxLiteral::Nil => serializer.serialize_unit(),
+        }
+    }
+}
+
+/// Lists serialize as sequences and instances as maps from field names to values. Functions and
+/// classes can't be serialized, and neither can lists or instances that contain themselves;
+/// trying raises an error.
+impl Serialize for LoxObject {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Nested {
+            object: self,
+            open: &RefCell::new(Vec::new()),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// A value being serialized, along with the lists and instances it's nested in, so cycles are
+/// reported rather than recursed into forever.
+struct Nested<'a> {
+    object: &'a LoxObject,
+    open: &'a RefCell<Vec<*const ()>>,
+}
+
+impl Nested<'_> {
+    fn nested<'b>(&'b self, object: &'b LoxObject) -> Nested<'b> {
+        Nested {
+            object,
+            open: self.open,
+        }
+    }
+
+    /// Marks the value at `key` as open while `serialize` runs.
+    fn enter<T, E: ser::Error>(
+        &self,
+        key: *const (),
+        serialize: impl FnOnce() -> Result<T, E>,
+    ) -> Result<T, E> {
+        if self.open.borrow().contains(&key) {
+            return Err(E::custom(
+                "A list or instance that contains itself can't be serialized.",
+            ));
+        }
+        self.open.borrow_mut().push(key);
+        let result = serialize();
+        self.open.borrow_mut().pop();
+        result
+    }
+}
+
+impl Serialize for Nested<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.object {
+            LoxObject::Literal(literal) => literal.serialize(serializer),
+            LoxObject::List(list) => self.enter(Rc::as_ptr(list) as *const (), || {
+                let list = list.borrow();
+                let mut seq = serializer.serialize_seq(Some(list.len()))?;
+                for element in list.iter() {
+                    seq.serialize_element(&self.nested(element))?;
+                }
+                seq.end()
+            }),
+            LoxObject::Instance(instance) => self.enter(Rc::as_ptr(instance) as *const (), || {
+                let instance = instance.borrow();
+                // Field storage is unordered, so sort for stable output.
+                let mut fields: Vec<_> = instance.fields().collect();
+                fields.sort_by(|a, b| a.0.cmp(b.0));
+                let mut map = serializer.serialize_map(Some(fields.len()))?;
+                for (name, value) in fields {
+                    map.serialize_entry(&**name, &self.nested(value))?;
+                }
+                map.end()
+            }),
+            LoxObject::Callable(LoxCallable::Class(_)) => {
+                Err(ser::Error::custom("Classes can't be serialized."))
+            }
+            LoxObject::Callable(_) => Err(ser::Error::custom("Functions can't be serialized.")),
+            LoxObject::Foreign(_) => Err(ser::Error::custom("Foreign values can't be serialized.")),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LoxLiteral {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match deserializer.deserialize_any(ObjectVisitor)? {
+            LoxObject::Literal(literal) => Ok(literal),
+            _ => Err(de::Error::custom(
+                "Expected a number, string, boolean or nil.",
+            )),
+        }
+    }
+}
+
+/// Sequences deserialize as lists and maps as instances of a class named `Object` with no
+/// methods.
+impl<'de> Deserialize<'de> for LoxObject {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ObjectVisitor)
+    }
+}
+
+struct ObjectVisitor;
+
+impl<'de> Visitor<'de> for ObjectVisitor {
+    type Value = LoxObject;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a number, string, boolean, nil, sequence or map")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<LoxObject, E> {
+        Ok(LoxObject::Literal(LoxLiteral::Boolean(value)))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<LoxObject, E> {
+        self.visit_f64(value as f64)
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<LoxObject, E> {
+        self.visit_f64(value as f64)
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<LoxObject, E> {
+        Ok(LoxObject::Literal(LoxLiteral::Number(value)))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<LoxObject, E> {
+        Ok(LoxObject::Literal(LoxLiteral::String(Rc::new(
+            LoxString::from(value),
+        ))))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<LoxObject, E> {
+        Ok(LoxObject::Literal(LoxLiteral::Nil))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<LoxObject, E> {
+        self.visit_unit()
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<LoxObject, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<LoxObject, A::Error> {
+        let mut elements = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(element) = seq.next_element()? {
+            elements.push(element);
+        }
+        Ok(LoxObject::List(Rc::new(RefCell::new(elements))))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<LoxObject, A::Error> {
+        let class = LoxClass::new(Rc::from(MAP_CLASS), None, HashMap::new());
+        let mut instance = LoxInstance::new(Rc::new(class));
+        while let Some((name, value)) = map.next_entry::<String, LoxObject>()? {
+            instance.set_field(name, value);
+        }
+        Ok(LoxObject::Instance(Rc::new(RefCell::new(instance))))
+    }
+}
@@ -0,0 +1,41 @@
+/// The stretch of source a token or syntax tree node was parsed from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub line: usize,
+    pub end_line: usize,
+    /// Byte offset of the first character within the source.
+    pub start: usize,
+    /// Byte offset just past the last character.
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, end_line: usize, start: usize, end: usize) -> Self {
+        Span {
+            line,
+            end_line,
+            start,
+            end,
+        }
+    }
+
+    /// Returns the smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            line: self.line.min(other.line),
+            end_line: self.end_line.max(other.end_line),
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+
+    /// Length of the spanned text in bytes.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Whether the span covers no text, as at the end of the source.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
@@ -1,168 +1,315 @@
 use crate::{
     expr::{Closure, Expr},
+    span::Span,
     token::Token,
 };
+use std::rc::Rc;
 
-pub trait StmtVisitor<'src, T> {
-    fn visit_expression_stmt(&mut self, stmt: &Expression<'src>) -> T;
-    fn visit_print_stmt(&mut self, stmt: &Print<'src>) -> T;
-    fn visit_var_stmt(&mut self, stmt: &Var<'src>) -> T;
-    fn visit_block_stmt(&mut self, stmt: &Block<'src>) -> T;
-    fn visit_if_stmt(&mut self, stmt: &If<'src>) -> T;
-    fn visit_while_stmt(&mut self, stmt: &While<'src>) -> T;
-    fn visit_break_stmt(&mut self) -> T;
-    fn visit_function_stmt(&mut self, stmt: &Function<'src>) -> T;
-    fn visit_return_stmt(&mut self, stmt: &Return<'src>) -> T;
-    fn visit_class_stmt(&mut self, stmt: &Class<'src>) -> T;
+pub trait StmtVisitor<T> {
+    fn visit_expression_stmt(&mut self, stmt: &Expression) -> T;
+    fn visit_print_stmt(&mut self, stmt: &Print) -> T;
+    fn visit_var_stmt(&mut self, stmt: &Var) -> T;
+    fn visit_destructure_stmt(&mut self, stmt: &Destructure) -> T;
+    fn visit_block_stmt(&mut self, stmt: &Block) -> T;
+    fn visit_if_stmt(&mut self, stmt: &If) -> T;
+    fn visit_while_stmt(&mut self, stmt: &While) -> T;
+    fn visit_for_stmt(&mut self, stmt: &For) -> T;
+    fn visit_for_in_stmt(&mut self, stmt: &ForIn) -> T;
+    fn visit_break_stmt(&mut self, stmt: &Break) -> T;
+    fn visit_function_stmt(&mut self, stmt: &Function) -> T;
+    fn visit_return_stmt(&mut self, stmt: &Return) -> T;
+    fn visit_class_stmt(&mut self, stmt: &Class) -> T;
 }
 
 #[derive(Debug, Clone)]
-pub enum Stmt<'src> {
-    Expression(Expression<'src>),
-    Print(Print<'src>),
-    Var(Var<'src>),
-    Block(Block<'src>),
-    If(If<'src>),
-    While(While<'src>),
-    Break,
-    Function(Function<'src>),
-    Return(Return<'src>),
-    Class(Class<'src>),
-}
-
-impl<'src> Stmt<'src> {
-    pub fn accept<T>(&self, visitor: &mut dyn StmtVisitor<'src, T>) -> T {
+pub enum Stmt {
+    Expression(Expression),
+    Print(Print),
+    Var(Var),
+    Destructure(Destructure),
+    Block(Block),
+    If(If),
+    While(While),
+    For(For),
+    ForIn(ForIn),
+    Break(Break),
+    Function(Function),
+    Return(Return),
+    Class(Class),
+}
+
+impl Stmt {
+    pub fn accept<T>(&self, visitor: &mut dyn StmtVisitor<T>) -> T {
         match self {
             Stmt::Expression(expression) => visitor.visit_expression_stmt(expression),
             Stmt::Print(print) => visitor.visit_print_stmt(print),
             Stmt::Var(var) => visitor.visit_var_stmt(var),
+            Stmt::Destructure(destructure) => visitor.visit_destructure_stmt(destructure),
             Stmt::Block(block) => visitor.visit_block_stmt(block),
             Stmt::If(if_stmt) => visitor.visit_if_stmt(if_stmt),
             Stmt::While(while_stmt) => visitor.visit_while_stmt(while_stmt),
-            Stmt::Break => visitor.visit_break_stmt(),
+            Stmt::For(for_stmt) => visitor.visit_for_stmt(for_stmt),
+            Stmt::ForIn(for_in) => visitor.visit_for_in_stmt(for_in),
+            Stmt::Break(break_stmt) => visitor.visit_break_stmt(break_stmt),
             Stmt::Function(function) => visitor.visit_function_stmt(function),
             Stmt::Return(return_stmt) => visitor.visit_return_stmt(return_stmt),
             Stmt::Class(class) => visitor.visit_class_stmt(class),
         }
     }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Expression(expression) => expression.span,
+            Stmt::Print(print) => print.span,
+            Stmt::Var(var) => var.span,
+            Stmt::Destructure(destructure) => destructure.span,
+            Stmt::Block(block) => block.span,
+            Stmt::If(if_stmt) => if_stmt.span,
+            Stmt::While(while_stmt) => while_stmt.span,
+            Stmt::For(for_stmt) => for_stmt.span,
+            Stmt::ForIn(for_in) => for_in.span,
+            Stmt::Break(break_stmt) => break_stmt.span,
+            Stmt::Function(function) => function.span,
+            Stmt::Return(return_stmt) => return_stmt.span,
+            Stmt::Class(class) => class.span,
+        }
+    }
 }
 
 // Statement Types
 #[derive(Debug, Clone)]
-pub struct Expression<'src> {
-    pub expression: Expr<'src>,
+pub struct Expression {
+    pub expression: Expr,
+    pub span: Span,
+}
+impl Expression {
+    pub fn new(expression: Expr, span: Span) -> Self {
+        Expression { expression, span }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Print {
+    pub expression: Expr,
+    pub span: Span,
+    /// Set for values echoed by the REPL, which are pretty-printed.
+    pub echo: bool,
 }
-impl<'src> Expression<'src> {
-    pub fn new(expression: Expr<'src>) -> Self {
-        Expression { expression }
+impl Print {
+    pub fn new(expression: Expr, span: Span) -> Self {
+        Print {
+            expression,
+            span,
+            echo: false,
+        }
+    }
+
+    pub fn echo(expression: Expr, span: Span) -> Self {
+        Print {
+            expression,
+            span,
+            echo: true,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Print<'src> {
-    pub expression: Expr<'src>,
+pub struct Var {
+    pub name: Token,
+    pub initializer: Option<Expr>,
+    pub span: Span,
 }
-impl<'src> Print<'src> {
-    pub fn new(expression: Expr<'src>) -> Self {
-        Print { expression }
+impl Var {
+    pub fn new(name: Token, initializer: Option<Expr>, span: Span) -> Self {
+        Var {
+            name,
+            initializer,
+            span,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Var<'src> {
-    pub name: Token<'src>,
-    pub initializer: Option<Expr<'src>>,
+pub struct Destructure {
+    pub names: Vec<Token>,
+    pub initializer: Expr,
+    pub span: Span,
 }
-impl<'src> Var<'src> {
-    pub fn new(name: Token<'src>, initializer: Option<Expr<'src>>) -> Self {
-        Var { name, initializer }
+impl Destructure {
+    pub fn new(names: Vec<Token>, initializer: Expr, span: Span) -> Self {
+        Destructure {
+            names,
+            initializer,
+            span,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Block<'src> {
-    pub statements: Vec<Stmt<'src>>,
+pub struct Block {
+    pub statements: Vec<Stmt>,
+    pub span: Span,
 }
-impl<'src> Block<'src> {
-    pub fn new(statements: Vec<Stmt<'src>>) -> Self {
-        Block { statements }
+impl Block {
+    pub fn new(statements: Vec<Stmt>, span: Span) -> Self {
+        Block { statements, span }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct If<'src> {
-    pub condition: Expr<'src>,
-    pub then_branch: Box<Stmt<'src>>,
-    pub else_branch: Option<Box<Stmt<'src>>>,
+pub struct If {
+    pub condition: Expr,
+    pub then_branch: Box<Stmt>,
+    pub else_branch: Option<Box<Stmt>>,
+    pub span: Span,
 }
-impl<'src> If<'src> {
+impl If {
     pub fn new(
-        condition: Expr<'src>,
-        then_branch: Box<Stmt<'src>>,
-        else_branch: Option<Box<Stmt<'src>>>,
+        condition: Expr,
+        then_branch: Box<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+        span: Span,
     ) -> Self {
         If {
             condition,
             then_branch,
             else_branch,
+            span,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct While {
+    pub condition: Expr,
+    pub body: Box<Stmt>,
+    pub span: Span,
+}
+impl While {
+    pub fn new(condition: Expr, body: Box<Stmt>, span: Span) -> Self {
+        While {
+            condition,
+            body,
+            span,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct For {
+    pub initializer: Option<Box<Stmt>>,
+    pub condition: Option<Expr>,
+    pub increment: Option<Expr>,
+    pub body: Box<Stmt>,
+    pub span: Span,
+}
+impl For {
+    pub fn new(
+        initializer: Option<Box<Stmt>>,
+        condition: Option<Expr>,
+        increment: Option<Expr>,
+        body: Box<Stmt>,
+        span: Span,
+    ) -> Self {
+        For {
+            initializer,
+            condition,
+            increment,
+            body,
+            span,
         }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct While<'src> {
-    pub condition: Expr<'src>,
-    pub body: Box<Stmt<'src>>,
+pub struct ForIn {
+    pub name: Token,
+    pub iterable: Expr,
+    pub body: Box<Stmt>,
+    pub span: Span,
 }
-impl<'src> While<'src> {
-    pub fn new(condition: Expr<'src>, body: Box<Stmt<'src>>) -> Self {
-        While { condition, body }
+impl ForIn {
+    pub fn new(name: Token, iterable: Expr, body: Box<Stmt>, span: Span) -> Self {
+        ForIn {
+            name,
+            iterable,
+            body,
+            span,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Function<'src> {
-    pub name: Token<'src>,
-    pub closure: Closure<'src>,
+pub struct Break {
+    pub span: Span,
 }
-impl<'src> Function<'src> {
-    pub fn new(name: Token<'src>, closure: Closure<'src>) -> Self {
-        Function { name, closure }
+impl Break {
+    pub fn new(span: Span) -> Self {
+        Break { span }
     }
 }
-impl<'src> PartialEq for Function<'src> {
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: Token,
+    pub closure: Rc<Closure>,
+    pub decorators: Vec<Expr>,
+    pub span: Span,
+}
+impl Function {
+    pub fn new(name: Token, closure: Rc<Closure>, decorators: Vec<Expr>, span: Span) -> Self {
+        Function {
+            name,
+            closure,
+            decorators,
+            span,
+        }
+    }
+}
+impl PartialEq for Function {
     fn eq(&self, other: &Self) -> bool {
         self.name == other.name
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Return<'src> {
-    pub keyword: Token<'src>,
-    pub value: Expr<'src>,
+pub struct Return {
+    pub keyword: Token,
+    pub value: Expr,
+    pub span: Span,
 }
-impl<'src> Return<'src> {
-    pub fn new(keyword: Token<'src>, value: Expr<'src>) -> Self {
-        Return { keyword, value }
+impl Return {
+    pub fn new(keyword: Token, value: Expr, span: Span) -> Self {
+        Return {
+            keyword,
+            value,
+            span,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Class<'src> {
-    pub name: Token<'src>,
-    pub superclass: Option<Box<Expr<'src>>>,
-    pub methods: Vec<Stmt<'src>>,
+pub struct Class {
+    pub name: Token,
+    pub superclass: Option<Box<Expr>>,
+    pub methods: Vec<Stmt>,
+    pub doc: Option<Rc<str>>,
+    pub span: Span,
 }
-impl<'src> Class<'src> {
+impl Class {
     pub fn new(
-        name: Token<'src>,
-        superclass: Option<Box<Expr<'src>>>,
-        methods: Vec<Stmt<'src>>,
+        name: Token,
+        superclass: Option<Box<Expr>>,
+        methods: Vec<Stmt>,
+        doc: Option<Rc<str>>,
+        span: Span,
     ) -> Self {
         Class {
             name,
             superclass,
             methods,
+            doc,
+            span,
         }
     }
 }
@@ -0,0 +1,205 @@
+use crate::{
+    coverage::LineCoverage,
+    diagnostic::Severity,
+    interpreter::Interpreter,
+    run::{run_source, LoxError, SharedBuffer},
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// What a test script's comments say it should print and which errors it should raise.
+#[derive(Debug, Default)]
+struct Expectations {
+    /// Each expected line of output and the line of the comment expecting it.
+    output: Vec<(String, usize)>,
+    /// Each expected error, as `[line N] ...`.
+    errors: Vec<String>,
+}
+
+impl Expectations {
+    fn parse(source: &str) -> Self {
+        let mut expectations = Expectations::default();
+        for (idx, line) in source.lines().enumerate() {
+            let line_number = idx + 1;
+            let Some((_, comment)) = line.split_once("//") else {
+                continue;
+            };
+            let comment = comment.trim_start();
+            if let Some(text) = comment.strip_prefix("expect: ") {
+                expectations.output.push((text.to_owned(), line_number));
+            } else if let Some(message) = comment.strip_prefix("expect runtime error: ") {
+                let error = format!("[line {line_number}] {message}");
+                expectations.errors.push(error);
+            } else if comment.starts_with("Error") {
+                expectations
+                    .errors
+                    .push(format!("[line {line_number}] {comment}"));
+            } else if let Some(error) = annotated_error(comment) {
+                expectations.errors.push(error);
+            }
+        }
+        expectations
+    }
+}
+
+/// The error a `[line N] Error ...` or `[java line N] Error ...` comment expects.
+fn annotated_error(comment: &str) -> Option<String> {
+    let rest = comment.strip_prefix('[')?;
+    let rest = rest.strip_prefix("java ").unwrap_or(rest);
+    let (line, error) = rest.strip_prefix("line ")?.split_once("] ")?;
+    let line: usize = line.parse().ok()?;
+    error
+        .starts_with("Error")
+        .then(|| format!("[line {line}] {error}"))
+}
+
+/// A test script that didn't do what its comments expect.
+#[derive(Debug, Clone)]
+pub struct TestFailure {
+    pub path: PathBuf,
+    /// How the script went wrong: expected lines prefixed with `-`, actual ones with `+`.
+    pub diff: Vec<String>,
+}
+
+/// The lines a test script ran.
+#[derive(Debug, Clone)]
+pub struct ScriptCoverage {
+    pub path: PathBuf,
+    pub source: String,
+    pub lines: LineCoverage,
+}
+
+/// How running a directory of test scripts went.
+#[derive(Debug, Clone, Default)]
+pub struct TestSummary {
+    pub passed: usize,
+    pub failures: Vec<TestFailure>,
+    /// The lines each script ran, passing or not, if coverage was asked for.
+    pub coverage: Vec<ScriptCoverage>,
+}
+
+/// Runs every `.lox` file under `path`, in subdirectories too, or just `path` if it's a file.
+/// Each runs in a fresh interpreter with empty input, and passes if it prints the output and
+/// raises the errors its comments expect (see [`run_test`]). With `coverage`, the summary
+/// also has the lines each script ran.
+pub fn run_tests(path: &Path, coverage: bool) -> io::Result<TestSummary> {
+    let mut paths = Vec::new();
+    match fs::metadata(path)?.is_dir() {
+        true => collect_scripts(path, &mut paths)?,
+        false => paths.push(path.to_path_buf()),
+    }
+    let mut summary = TestSummary::default();
+    for path in paths {
+        let source = fs::read_to_string(&path)?;
+        let (diff, lines) = run_script(&source, coverage);
+        if let Some(lines) = lines {
+            summary.coverage.push(ScriptCoverage {
+                path: path.clone(),
+                source,
+                lines,
+            });
+        }
+        match diff.is_empty() {
+            true => summary.passed += 1,
+            false => summary.failures.push(TestFailure { path, diff }),
+        }
+    }
+    Ok(summary)
+}
+
+/// Adds the `.lox` files under `dir` to `paths`, in order of their names.
+fn collect_scripts(dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            collect_scripts(&entry, paths)?;
+        } else if entry
+            .extension()
+            .is_some_and(|extension| extension == "lox")
+        {
+            paths.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// Runs `source` and compares what it did with what its comments expect, returning the
+/// differences. An empty diff means the test passed. The comments follow the Crafting
+/// Interpreters test suite:
+///
+/// - `// expect: <text>` for each line the script prints, in order.
+/// - `// expect runtime error: <message>` for the runtime error raised on that line.
+/// - `// Error at 'x': <message>`, or `// [line N] Error ...` for one reported on another
+///   line, for each scan, parse or resolution error. Annotations for a specific
+///   implementation, such as `[c line N]`, are ignored unless meant for jlox (`[java line N]`).
+pub fn run_test(source: &str) -> Vec<String> {
+    run_script(source, false).0
+}
+
+/// Like [`run_test`], also returning the lines the script ran if `coverage` is set.
+fn run_script(source: &str, coverage: bool) -> (Vec<String>, Option<LineCoverage>) {
+    let expectations = Expectations::parse(source);
+    let output = SharedBuffer::default();
+    let mut interpreter = Interpreter::builder()
+        .output(output.clone())
+        .input(io::empty())
+        .coverage(coverage)
+        .build();
+    let mut errors = Vec::new();
+    if let Err(lox_errors) = run_source(source, &mut interpreter) {
+        for error in lox_errors {
+            match error {
+                LoxError::Static(diagnostic) if diagnostic.severity == Severity::Error => {
+                    let location = match diagnostic.location.is_empty() {
+                        true => String::new(),
+                        false => format!(" {}", diagnostic.location),
+                    };
+                    errors.push(format!(
+                        "[line {}] Error{location}: {}",
+                        diagnostic.line, diagnostic.message
+                    ));
+                }
+                LoxError::Static(_) => (),
+                LoxError::Runtime(error) => {
+                    errors.push(format!("[line {}] {}", error.span.line, error.message));
+                }
+                error => errors.push(error.to_string()),
+            }
+        }
+    }
+    let lines = interpreter.coverage().cloned();
+    drop(interpreter);
+    let output = String::from_utf8_lossy(&output.0.borrow()).into_owned();
+
+    let mut diff = Vec::new();
+    let actual: Vec<&str> = output.lines().collect();
+    for idx in 0..expectations.output.len().max(actual.len()) {
+        match (expectations.output.get(idx), actual.get(idx)) {
+            (Some((expected, _)), Some(actual)) if expected == actual => (),
+            (expected, actual) => {
+                if let Some((expected, line)) = expected {
+                    diff.push(format!("- {expected} (expected on line {line})"));
+                }
+                if let Some(actual) = actual {
+                    diff.push(format!("+ {actual}"));
+                }
+            }
+        }
+    }
+    for expected in expectations.errors.iter() {
+        if !errors.contains(expected) {
+            diff.push(format!("- {expected}"));
+        }
+    }
+    for actual in errors.iter() {
+        if !expectations.errors.contains(actual) {
+            diff.push(format!("+ {actual}"));
+        }
+    }
+    (diff, lines)
+}
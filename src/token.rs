@@ -1,37 +1,72 @@
-use crate::{lox_object::LoxLiteral, token_type::TokenType};
+use crate::{lox_object::LoxLiteral, span::Span, token_type::TokenType};
 use std::{
     fmt,
     hash::{Hash, Hasher},
+    rc::Rc,
 };
 
 #[derive(Debug, Clone, Default)]
-pub struct Token<'src> {
+pub struct Token {
     pub token_type: TokenType,
-    pub lexeme: &'src str,
+    pub lexeme: Rc<str>,
     pub literal: Option<LoxLiteral>,
     pub line: usize,
+    /// Byte offset of the lexeme within the source.
+    pub start: usize,
+    /// One-based position of the lexeme's first character within its line.
+    pub column: usize,
+    /// The `///` comment lines just before the token, without the slashes, if there were any.
+    pub doc: Option<Rc<str>>,
     token_id: usize,
 }
 
-impl<'src> Token<'src> {
+impl Token {
     pub fn new(
         token_type: TokenType,
-        lexeme: &'src str,
+        lexeme: &str,
         literal: Option<LoxLiteral>,
         line: usize,
+        start: usize,
+        column: usize,
         token_id: usize,
     ) -> Self {
         Token {
             token_type,
-            lexeme,
+            lexeme: Rc::from(lexeme),
             literal,
             line,
+            start,
+            column,
+            doc: None,
             token_id,
         }
     }
+
+    /// Byte offset just past the lexeme.
+    pub fn end(&self) -> usize {
+        self.start + self.lexeme.len()
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.line, self.line, self.start, self.end())
+    }
+
+    /// A column-aligned line and type, followed by the lexeme and literal value (`-` if none).
+    pub fn describe(&self) -> String {
+        let literal = match self.literal {
+            Some(LoxLiteral::String(ref val)) => format!("{val:?}"),
+            Some(ref literal) => literal.to_string(),
+            None => String::from("-"),
+        };
+        let token_type = format!("{:?}", self.token_type);
+        format!(
+            "{:>4}  {token_type:<13} {:<15} {literal}",
+            self.line, self.lexeme
+        )
+    }
 }
 
-impl fmt::Display for Token<'_> {
+impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
@@ -41,15 +76,15 @@ impl fmt::Display for Token<'_> {
     }
 }
 
-impl PartialEq for Token<'_> {
+impl PartialEq for Token {
     fn eq(&self, other: &Self) -> bool {
         self.token_id == other.token_id
     }
 }
 
-impl Eq for Token<'_> {}
+impl Eq for Token {}
 
-impl Hash for Token<'_> {
+impl Hash for Token {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.token_id.hash(state);
     }
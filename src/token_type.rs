@@ -1,10 +1,12 @@
-#[derive(Debug, Clone, PartialEq, Eq, Copy, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Hash, Default)]
 pub enum TokenType {
     // Single-character tokens.
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
@@ -14,6 +16,7 @@ pub enum TokenType {
     QuestionMark,
     Slash,
     Star,
+    At,
     // One or two character tokens.
     Bang,
     BangEqual,
@@ -35,6 +38,7 @@ pub enum TokenType {
     Fun,
     For,
     If,
+    #[default]
     Nil,
     Or,
     Print,
@@ -45,12 +49,86 @@ pub enum TokenType {
     Var,
     While,
     Break,
+    In,
 
     Eof,
 }
 
-impl Default for TokenType {
-    fn default() -> Self {
-        TokenType::Nil
+/// Coarse classification of tokens, used by tooling such as the syntax highlighter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenCategory {
+    Keyword,
+    Identifier,
+    String,
+    Number,
+    Literal,
+    Operator,
+    Punctuation,
+    Eof,
+}
+
+impl TokenCategory {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenCategory::Keyword => "keyword",
+            TokenCategory::Identifier => "identifier",
+            TokenCategory::String => "string",
+            TokenCategory::Number => "number",
+            TokenCategory::Literal => "literal",
+            TokenCategory::Operator => "operator",
+            TokenCategory::Punctuation => "punctuation",
+            TokenCategory::Eof => "eof",
+        }
+    }
+}
+
+impl TokenType {
+    pub fn category(&self) -> TokenCategory {
+        match self {
+            TokenType::LeftParen
+            | TokenType::RightParen
+            | TokenType::LeftBrace
+            | TokenType::RightBrace
+            | TokenType::LeftBracket
+            | TokenType::RightBracket
+            | TokenType::Comma
+            | TokenType::Dot
+            | TokenType::Semicolon
+            | TokenType::At => TokenCategory::Punctuation,
+            TokenType::Minus
+            | TokenType::Plus
+            | TokenType::Colon
+            | TokenType::QuestionMark
+            | TokenType::Slash
+            | TokenType::Star
+            | TokenType::Bang
+            | TokenType::BangEqual
+            | TokenType::Equal
+            | TokenType::EqualEqual
+            | TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => TokenCategory::Operator,
+            TokenType::Identifier => TokenCategory::Identifier,
+            TokenType::String => TokenCategory::String,
+            TokenType::Number => TokenCategory::Number,
+            TokenType::False | TokenType::True | TokenType::Nil => TokenCategory::Literal,
+            TokenType::And
+            | TokenType::Class
+            | TokenType::Else
+            | TokenType::Fun
+            | TokenType::For
+            | TokenType::If
+            | TokenType::Or
+            | TokenType::Print
+            | TokenType::Return
+            | TokenType::Super
+            | TokenType::This
+            | TokenType::Var
+            | TokenType::While
+            | TokenType::Break
+            | TokenType::In => TokenCategory::Keyword,
+            TokenType::Eof => TokenCategory::Eof,
+        }
     }
 }
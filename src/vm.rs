@@ -0,0 +1,257 @@
+use crate::{
+    compiler::{Chunk, Op},
+    cycle_collector,
+    environment::Environment,
+    interpreter::{Interpreter, LoxIterator},
+    lox_callable::LoxCallable,
+    lox_exception::LoxException,
+    lox_function::LoxFunction,
+    lox_object::LoxObject,
+};
+use std::{cell::RefCell, rc::Rc};
+
+impl Interpreter {
+    /// Runs `chunk` in `environment` with `stack` as its first stack slots, restoring the
+    /// current environment afterwards. A `return` ends the chunk with
+    /// [`LoxException::Return`], as it does a block in the tree-walker.
+    pub(crate) fn run(
+        &mut self,
+        chunk: &Chunk,
+        environment: Rc<RefCell<Environment>>,
+        stack: Vec<LoxObject>,
+    ) -> Result<(), LoxException> {
+        let previous_env = std::mem::replace(&mut self.environment, environment);
+        let result = self.run_code(&chunk.code, stack);
+        self.environment = previous_env;
+        result
+    }
+
+    fn run_code(&mut self, code: &[Op], mut stack: Vec<LoxObject>) -> Result<(), LoxException> {
+        let mut iterators: Vec<LoxIterator> = Vec::new();
+        let mut pc = 0;
+        while let Some(op) = code.get(pc) {
+            pc += 1;
+            match op {
+                Op::Constant(value) => stack.push(value.clone()),
+                Op::Pop => {
+                    stack.pop();
+                }
+                Op::GetSlot(slot) => stack.push(stack[*slot].clone()),
+                Op::SetSlot { slot, name } => {
+                    let value = peek(&stack).clone();
+                    if let Some(name) = name {
+                        self.assigned(name, &value);
+                    }
+                    stack[*slot] = value;
+                }
+                Op::Truncate(len) => stack.truncate(*len),
+                Op::GetLocal { depth, slot } => {
+                    let value = self.environment.borrow().get_at(*depth, *slot);
+                    stack.push(value);
+                }
+                Op::SetLocal { depth, slot, name } => {
+                    let value = peek(&stack).clone();
+                    let value = self
+                        .environment
+                        .borrow_mut()
+                        .assign_at(*depth, *slot, value);
+                    if let Some(name) = name {
+                        self.assigned(name, &value);
+                    }
+                }
+                Op::GetGlobal { name, slot } => {
+                    let value = match slot {
+                        Some(slot) => self.globals.borrow().get_global(*slot),
+                        None => self.globals.borrow().get(name).ok(),
+                    };
+                    let value =
+                        value.ok_or_else(|| self.environment.borrow().undefined_variable(name))?;
+                    stack.push(value);
+                }
+                Op::SetGlobal { name, slot } => {
+                    let value = peek(&stack).clone();
+                    let result = match slot {
+                        Some(slot) => self.globals.borrow_mut().assign_global(*slot, value),
+                        None => self.globals.borrow_mut().assign(name, value).ok(),
+                    };
+                    let value =
+                        result.ok_or_else(|| self.environment.borrow().undefined_variable(name))?;
+                    self.assigned(name, &value);
+                }
+                Op::Define(name) => {
+                    let value = pop(&mut stack);
+                    self.environment.borrow_mut().define(Rc::clone(name), value);
+                }
+                Op::Binary {
+                    operator,
+                    span,
+                    right,
+                } => {
+                    let right_value = pop(&mut stack);
+                    let left_value = pop(&mut stack);
+                    stack.push(self.binary(*operator, left_value, right_value, *span, *right)?);
+                }
+                Op::Unary { operator, span } => {
+                    let right = pop(&mut stack);
+                    stack.push(self.unary(*operator, right, *span)?);
+                }
+                Op::Jump(target) => pc = *target,
+                Op::JumpIfFalse(target) => {
+                    if !self.is_truthy(peek(&stack)) {
+                        pc = *target;
+                    }
+                }
+                Op::JumpIfTrue(target) => {
+                    if self.is_truthy(peek(&stack)) {
+                        pc = *target;
+                    }
+                }
+                Op::Statement(span) => self.begin_statement(*span)?,
+                Op::Return => return Err(LoxException::Return(pop(&mut stack))),
+                Op::Print { echo, span } => {
+                    let value = pop(&mut stack);
+                    self.print(&value, *echo, *span)?;
+                }
+                Op::Call { argc, span, callee } => {
+                    let arguments = stack.split_off(stack.len() - argc);
+                    let callee_value = pop(&mut stack);
+                    stack.push(self.call_value(callee_value, arguments, *span, *callee)?);
+                }
+                Op::Function(proto) => {
+                    let function = LoxFunction::new(
+                        &proto.declaration,
+                        Rc::clone(&self.environment),
+                        proto.name.clone(),
+                        false,
+                    )
+                    .with_chunk(Rc::clone(&proto.chunk));
+                    stack.push(LoxObject::Callable(LoxCallable::Function(Rc::new(
+                        function,
+                    ))));
+                }
+                Op::Decorate(span) => {
+                    let value = pop(&mut stack);
+                    let decorator = pop(&mut stack);
+                    stack.push(self.decorate(decorator, value, *span)?);
+                }
+                Op::Class(proto) => {
+                    let superclass = match proto.superclass {
+                        Some(span) => {
+                            let value = pop(&mut stack);
+                            Some(self.superclass(value, span)?)
+                        }
+                        None => None,
+                    };
+                    let methods = proto
+                        .methods
+                        .iter()
+                        .map(|(name, declaration, chunk)| (name, declaration, Some(chunk)));
+                    stack.push(self.class(&proto.name, proto.doc.as_ref(), superclass, methods));
+                }
+                Op::PushScope => {
+                    self.environment = Rc::new(RefCell::new(Environment::new(Some(Rc::clone(
+                        &self.environment,
+                    )))));
+                }
+                Op::PopScope => {
+                    let enclosing = self.environment.borrow().enclosing.clone();
+                    self.environment =
+                        enclosing.expect("Expect scopes pushed by the chunk to be popped.");
+                }
+                Op::GetProperty { name, object } => {
+                    let value = pop(&mut stack);
+                    stack.push(self.get_property(value, name, *object)?);
+                }
+                Op::ExpectInstance(span) => {
+                    self.expect_instance(peek(&stack).clone(), *span)?;
+                }
+                Op::SetProperty(name) => {
+                    let value = pop(&mut stack);
+                    let instance = match pop(&mut stack) {
+                        LoxObject::Instance(instance) => instance,
+                        _ => unreachable!("Expect ExpectInstance to have checked the object."),
+                    };
+                    cycle_collector::track_instance(&instance);
+                    let value = instance.borrow_mut().set(name, value);
+                    stack.push(value);
+                }
+                Op::Super {
+                    depth,
+                    slot,
+                    method,
+                    span,
+                } => {
+                    let superclass = self.environment.borrow().get_at(*depth, *slot);
+                    // `this` is alone in the environment just inside the one holding `super`.
+                    let object = self.environment.borrow().get_at(depth - 1, 0);
+                    stack.push(self.super_method(superclass, object, method, *span)?);
+                }
+                Op::List(count) => {
+                    let elements = stack.split_off(stack.len() - count);
+                    stack.push(LoxObject::List(Rc::new(RefCell::new(elements))));
+                }
+                Op::Append => {
+                    let value = pop(&mut stack);
+                    match peek(&stack) {
+                        LoxObject::List(list) => list.borrow_mut().push(value),
+                        _ => unreachable!("Expect comprehensions to append to their list."),
+                    }
+                }
+                Op::Index { object, index } => {
+                    let index_value = pop(&mut stack);
+                    let object_value = pop(&mut stack);
+                    stack.push(self.index(object_value, index_value, *object, *index)?);
+                }
+                Op::ExpectList(span) => {
+                    self.expect_list(stack[stack.len() - 2].clone(), *span)?;
+                }
+                Op::SetIndex { index } => {
+                    let value = pop(&mut stack);
+                    let index_value = pop(&mut stack);
+                    let list = match pop(&mut stack) {
+                        LoxObject::List(list) => list,
+                        _ => unreachable!("Expect ExpectList to have checked the object."),
+                    };
+                    stack.push(self.set_index(list, index_value, value, *index)?);
+                }
+                Op::Destructure { names, span } => {
+                    let value = pop(&mut stack);
+                    let values = self.destructure(value, names.len(), *span)?;
+                    let mut environment = self.environment.borrow_mut();
+                    for (name, value) in names.iter().zip(values) {
+                        environment.define(Rc::clone(name), value);
+                    }
+                }
+                Op::Unpack { count, span } => {
+                    let value = pop(&mut stack);
+                    stack.extend(self.destructure(value, *count, *span)?);
+                }
+                Op::IterStart(span) => {
+                    let iterable = pop(&mut stack);
+                    iterators.push(self.iterator_for(iterable, *span)?);
+                }
+                Op::IterNext { exit, span } => {
+                    let iterator = iterators
+                        .last_mut()
+                        .expect("Expect IterNext inside a loop.");
+                    match self.next_value(iterator, *span)? {
+                        Some(value) => stack.push(value),
+                        None => pc = *exit,
+                    }
+                }
+                Op::IterEnd => {
+                    iterators.pop();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn pop(stack: &mut Vec<LoxObject>) -> LoxObject {
+    stack.pop().expect("Expect a value on the stack.")
+}
+
+fn peek(stack: &[LoxObject]) -> &LoxObject {
+    stack.last().expect("Expect a value on the stack.")
+}
@@ -0,0 +1,59 @@
+use crate::{
+    clock::Clock,
+    interpreter::Interpreter,
+    run::{self, SharedBuffer},
+};
+use std::time::Duration;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = Date, js_name = now)]
+    fn date_now() -> f64;
+
+    #[wasm_bindgen(js_namespace = performance, js_name = now)]
+    fn performance_now() -> f64;
+}
+
+/// The browser's clocks, `Date.now()` and `performance.now()`. A page can't block, so
+/// `sleep()` waits by spinning.
+pub struct BrowserClock;
+
+impl Clock for BrowserClock {
+    fn since_epoch(&self) -> Duration {
+        Duration::from_secs_f64(date_now() / 1000.0)
+    }
+
+    fn monotonic(&self) -> Duration {
+        Duration::from_secs_f64(performance_now() / 1000.0)
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let end = self.monotonic() + duration;
+        while self.monotonic() < end {}
+    }
+}
+
+/// What running a script with [`run_source`] produced.
+#[wasm_bindgen(getter_with_clone)]
+pub struct PlaygroundRun {
+    /// Everything the script printed.
+    pub output: String,
+    /// Each error that stopped the script, rendered as the command line prints it.
+    pub errors: Vec<String>,
+}
+
+/// Runs `source` in a fresh interpreter, collecting what it prints rather than writing it to
+/// stdout, which browsers don't have.
+#[wasm_bindgen(js_name = runSource)]
+pub fn run_source(source: &str) -> PlaygroundRun {
+    let output = SharedBuffer::default();
+    let mut interpreter = Interpreter::builder().output(output.clone()).build();
+    let errors = match run::run_source(source, &mut interpreter) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors.iter().map(|error| error.render(source)).collect(),
+    };
+    drop(interpreter);
+    let output = String::from_utf8_lossy(&output.0.borrow()).into_owned();
+    PlaygroundRun { output, errors }
+}